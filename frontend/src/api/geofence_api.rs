@@ -0,0 +1,34 @@
+use reqwasm::http;
+
+use super::types::{ErrorResponse, Geofence, ListGeofences};
+use crate::app::API_ROOT;
+
+/// cf `server/src/api_geofence.rs`
+/// returns: the geofences of the room, and the ones of its active route
+pub async fn api_get_room_geofences(
+    auth_token: &str,
+    room_id: i64,
+) -> Result<Vec<Geofence>, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms/{room_id}/geofences"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<ListGeofences>().await;
+    match res_json {
+        Ok(data) => Ok(data.geofences),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
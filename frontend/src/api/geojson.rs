@@ -0,0 +1,63 @@
+use serde_json::Value;
+
+/// Extract all the lines from any `GeoJSON` object, eg:
+/// - the `GeometryCollection` of `MultiLineString` produced by `geozero` from a .gpx (cf `server/src/route_gpx.rs`)
+/// - the `FeatureCollection` of `LineString`/`Point` returned by `server/src/api_track.rs`
+///
+/// Unknown or malformed parts are skipped; so this never fails.
+/// NOTE: `GeoJSON` coordinates are [lng, lat] but this returns (lat, lng) for leaflet
+pub(crate) fn geojson_to_lines(geojson: &Value) -> Vec<Vec<(f64, f64)>> {
+    let mut lines = vec![];
+    collect_lines(geojson, &mut lines);
+    lines
+}
+
+fn collect_lines(geojson: &Value, lines: &mut Vec<Vec<(f64, f64)>>) {
+    let coordinates = &geojson["coordinates"];
+    match geojson["type"].as_str() {
+        Some("FeatureCollection") => {
+            for feature in as_slice(&geojson["features"]) {
+                collect_lines(feature, lines);
+            }
+        }
+        Some("Feature") => collect_lines(&geojson["geometry"], lines),
+        Some("GeometryCollection") => {
+            for geometry in as_slice(&geojson["geometries"]) {
+                collect_lines(geometry, lines);
+            }
+        }
+        Some("Point") => lines.extend(to_line(std::slice::from_ref(coordinates))),
+        Some("LineString" | "MultiPoint") => lines.extend(to_line(as_slice(coordinates))),
+        Some("MultiLineString" | "Polygon") => {
+            lines.extend(
+                as_slice(coordinates)
+                    .iter()
+                    .filter_map(|line| to_line(as_slice(line))),
+            );
+        }
+        Some("MultiPolygon") => {
+            for polygon in as_slice(coordinates) {
+                lines.extend(
+                    as_slice(polygon)
+                        .iter()
+                        .filter_map(|line| to_line(as_slice(line))),
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+fn as_slice(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// returns: None if there is no valid point in this line
+fn to_line(coordinates: &[Value]) -> Option<Vec<(f64, f64)>> {
+    let line: Vec<(f64, f64)> = coordinates
+        .iter()
+        .filter_map(|coordinate| Some((coordinate[1].as_f64()?, coordinate[0].as_f64()?)))
+        .collect();
+
+    (!line.is_empty()).then_some(line)
+}
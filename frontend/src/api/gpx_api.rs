@@ -0,0 +1,40 @@
+use reqwasm::http;
+use serde_json::Value;
+
+use super::geojson::geojson_to_lines;
+use super::types::ErrorResponse;
+use crate::app::API_ROOT;
+
+/// cf `get_room_route` in `server/src/api_route.rs`
+///
+/// returns: the lines of the active route of the room; or None if there is none
+pub async fn api_get_room_route(
+    auth_token: &str,
+    room_id: i64,
+) -> Result<Option<Vec<Vec<(f64, f64)>>>, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms/{room_id}/route"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() == 404 {
+        return Ok(None);
+    }
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<Value>().await;
+    match res_json {
+        Ok(data) => Ok(Some(geojson_to_lines(&data))),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
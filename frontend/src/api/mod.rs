@@ -1,2 +1,9 @@
+pub(crate) mod geofence_api;
+pub(crate) mod geojson;
+pub(crate) mod gpx_api;
+pub(crate) mod poi_api;
+pub(crate) mod room_api;
+pub(crate) mod route_api;
+pub(crate) mod track_api;
 pub(crate) mod types;
 pub(crate) mod user_api;
@@ -0,0 +1,31 @@
+use reqwasm::http;
+
+use super::types::{ErrorResponse, ListPois, Poi};
+use crate::app::API_ROOT;
+
+/// cf `server/src/api_poi.rs`
+/// returns: the points of interest of the active route of the room; empty if none
+pub async fn api_get_room_pois(auth_token: &str, room_id: i64) -> Result<Vec<Poi>, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms/{room_id}/pois"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<ListPois>().await;
+    match res_json {
+        Ok(data) => Ok(data.pois),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
@@ -0,0 +1,223 @@
+use reqwasm::http;
+
+use super::types::{ErrorResponse, ListRooms, Privacy, Room, RoomLead, RoomPresence};
+use crate::app::API_ROOT;
+
+/// cf `server/src/api_room.rs`
+pub async fn api_list_rooms(auth_token: &str) -> Result<ListRooms, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<ListRooms>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
+
+/// cf `server/src/api_room.rs`
+pub async fn api_create_room(auth_token: &str, name: &str) -> Result<Room, String> {
+    let response = http::Request::post(&format!("{API_ROOT}/api/rooms"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .body(serde_json::json!({ "name": name }).to_string())
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<Room>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
+
+/// cf `server/src/api_room.rs`
+/// `action` is either "join" or "leave"
+async fn api_room_membership(auth_token: &str, room_id: i64, action: &str) -> Result<(), String> {
+    let response = http::Request::post(&format!("{API_ROOT}/api/rooms/{room_id}/{action}"))
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    Ok(())
+}
+
+pub async fn api_join_room(auth_token: &str, room_id: i64) -> Result<(), String> {
+    api_room_membership(auth_token, room_id, "join").await
+}
+
+pub async fn api_leave_room(auth_token: &str, room_id: i64) -> Result<(), String> {
+    api_room_membership(auth_token, room_id, "leave").await
+}
+
+/// cf `server/src/api_room.rs`
+/// The changes are then received on the "geolocation" websocket, cf `WsMessage::Presence`
+pub async fn api_get_room_presence(auth_token: &str, room_id: i64) -> Result<RoomPresence, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms/{room_id}/presence"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<RoomPresence>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
+
+/// cf `server/src/api_room.rs`
+pub async fn api_get_privacy(auth_token: &str, room_id: i64) -> Result<Privacy, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms/{room_id}/privacy"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<Privacy>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
+
+/// cf `server/src/api_room.rs`
+/// The others are then notified on the "geolocation" websocket, cf `WsMessage::Sharing`
+pub async fn api_set_privacy(
+    auth_token: &str,
+    room_id: i64,
+    privacy: Privacy,
+) -> Result<(), String> {
+    let response = http::Request::put(&format!("{API_ROOT}/api/rooms/{room_id}/privacy"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .body(serde_json::to_string(&privacy).map_err(|err| err.to_string())?)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    Ok(())
+}
+
+/// cf `server/src/api_room.rs`
+pub async fn api_get_lead(auth_token: &str, room_id: i64) -> Result<RoomLead, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/rooms/{room_id}/lead"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<RoomLead>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
+
+/// cf `server/src/api_room.rs`; MUST be called by an organiser of the room
+/// The room is then notified on the "geolocation" websocket, cf `WsMessage::Lead`
+pub async fn api_set_lead(
+    auth_token: &str,
+    room_id: i64,
+    leader: Option<&str>,
+    sweeper: Option<&str>,
+) -> Result<(), String> {
+    let response = http::Request::put(&format!("{API_ROOT}/api/rooms/{room_id}/lead"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .body(serde_json::json!({ "leader": leader, "sweeper": sweeper }).to_string())
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use reqwasm::http;
+
+use super::types::{ErrorResponse, ListRoutes, RouteProfile};
+use crate::app::API_ROOT;
+
+/// cf `server/src/api_route.rs`
+pub async fn api_list_routes(auth_token: &str) -> Result<ListRoutes, String> {
+    let response = http::Request::get(&format!("{API_ROOT}/api/routes"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<ListRoutes>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
+
+/// cf `server/src/api_route.rs`
+/// `route_id`: None to clear the active route of the room
+pub async fn api_set_room_route(
+    auth_token: &str,
+    room_id: i64,
+    route_id: Option<i64>,
+) -> Result<(), String> {
+    let response = http::Request::post(&format!("{API_ROOT}/api/rooms/{room_id}/route"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .body(serde_json::json!({ "route_id": route_id }).to_string())
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    Ok(())
+}
+
+/// cf `server/src/api_route.rs`
+pub async fn api_delete_route(auth_token: &str, route_id: i64) -> Result<(), String> {
+    let response = http::Request::delete(&format!("{API_ROOT}/api/routes/{route_id}"))
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    Ok(())
+}
+
+/// cf `server/src/api_route.rs`
+/// `max_points`: the profile is downsampled to at most that many points, eg the width of the chart
+pub async fn api_get_route_profile(
+    auth_token: &str,
+    route_id: i64,
+    max_points: usize,
+) -> Result<RouteProfile, String> {
+    let response = http::Request::get(&format!(
+        "{API_ROOT}/api/routes/{route_id}/profile?max_points={max_points}"
+    ))
+    .header("Content-Type", "application/json")
+    .header("Authorization", &format!("Bearer {auth_token}",))
+    .credentials(http::RequestCredentials::Include)
+    .send()
+    .await
+    .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<RouteProfile>().await;
+    match res_json {
+        Ok(data) => Ok(data),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
@@ -0,0 +1,50 @@
+use reqwasm::http;
+use serde_json::Value;
+
+use super::geojson::geojson_to_lines;
+use super::types::ErrorResponse;
+use crate::app::API_ROOT;
+
+/// cf `server/src/api_track.rs`
+///
+/// params:
+/// - `from`/`to`: optional bounds, in milliseconds since UNIX epoch
+///
+/// returns: one line of (lat, lng) per room
+pub async fn api_get_user_track(
+    auth_token: &str,
+    username: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<Vec<Vec<(f64, f64)>>, String> {
+    let mut url = format!("{API_ROOT}/api/users/{username}/track?");
+    if let Some(from) = from {
+        url.push_str(&format!("from={from}&"));
+    }
+    if let Some(to) = to {
+        url.push_str(&format!("to={to}"));
+    }
+
+    let response = http::Request::get(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", &format!("Bearer {auth_token}",))
+        .credentials(http::RequestCredentials::Include)
+        .send()
+        .await
+        .map_err(|_| "Failed to make request".to_string())?;
+
+    if response.status() != 200 {
+        let error_response = response.json::<ErrorResponse>().await;
+        return if let Ok(error_response) = error_response {
+            Err(error_response.message)
+        } else {
+            Err(format!("API error: {}", response.status()))
+        };
+    }
+
+    let res_json = response.json::<Value>().await;
+    match res_json {
+        Ok(data) => Ok(geojson_to_lines(&data)),
+        Err(_) => Err("Failed to parse response".to_string()),
+    }
+}
@@ -9,14 +9,23 @@ pub struct User {
     // pub id: String,
     // pub name: String,
     // pub email: String,
-    // pub role: String,
     // pub photo: String,
     // pub verified: bool,
     // pub createdAt: DateTime<Utc>,
     // pub updatedAt: DateTime<Utc>,
     pub(crate) username: String,
     // pub(crate) password_hash: String,
-    pub(crate) is_super_user: bool,
+    /// "viewer", "member", "organiser" or "admin"; cf `server/src/role.rs`
+    /// NOTE: default for the users persisted before the roles were introduced
+    #[serde(default)]
+    pub(crate) role: String,
+}
+
+impl User {
+    /// cf `RequireRole<Organiser>` in `server/src/role.rs`
+    pub(crate) fn is_organiser(&self) -> bool {
+        self.role == "organiser" || self.role == "admin"
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -30,11 +39,15 @@ pub struct UserResponse {
     pub data: UserData,
 }
 
-/// MUST match `struct AuthBody` in `server/src/auth_jwt.rs`
+/// MUST match `struct AuthBody` in `server/src/api_authorize_jwt.rs`
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserLoginResponse {
     pub access_token: String,
     pub token_type: String,
+    /// cf `api_refresh_token`
+    pub refresh_token: String,
+    /// lifetime of `access_token`, in seconds
+    pub expires_in: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -48,3 +61,149 @@ pub struct ErrorResponse {
 pub(crate) struct ListUsers {
     pub(crate) users: Vec<User>,
 }
+
+/// SHOULD match `server/src/room.rs`
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct Room {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) created_by: String,
+    #[serde(default)]
+    pub(crate) active_route_id: Option<i64>,
+}
+
+/// SHOULD roughly match `server/src/api_room.rs`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ListRooms {
+    pub(crate) rooms: Vec<Room>,
+}
+
+/// SHOULD match `server/src/presence.rs`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct MemberPresence {
+    pub(crate) username: String,
+    pub(crate) status: protocol::PresenceStatus,
+    /// None if they never connected since the server started
+    #[allow(dead_code)]
+    pub(crate) last_seen: Option<i64>,
+    pub(crate) sharing: protocol::SharingMode,
+    /// in meters: their distance to the route if they are off route
+    pub(crate) off_route: Option<f64>,
+}
+
+/// SHOULD roughly match `server/src/api_room.rs`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RoomPresence {
+    pub(crate) users: Vec<MemberPresence>,
+}
+
+/// SHOULD match `RoomLead` in `server/src/api_room.rs`
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub(crate) struct RoomLead {
+    pub(crate) leader: Option<String>,
+    pub(crate) sweeper: Option<String>,
+    /// in meters: how far ahead of the sweeper the leader is along the route; None if unknown
+    pub(crate) gap: Option<f64>,
+    /// cf `WsMessage::GroupSpread`
+    pub(crate) is_spread: bool,
+}
+
+/// SHOULD match `server/src/privacy.rs`
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+pub(crate) struct Privacy {
+    pub(crate) mode: protocol::SharingMode,
+    /// in meters: the positions are rounded to a grid of that size; None for the exact positions
+    pub(crate) precision: Option<u32>,
+}
+
+/// SHOULD match `server/src/route.rs` and `server/src/route_stats.rs`
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct Route {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) uploaded_by: String,
+    pub(crate) uploaded_at: i64,
+    /// in meters
+    pub(crate) distance: f64,
+    /// in meters; None if the uploaded file has no elevation
+    pub(crate) elevation_gain: Option<f64>,
+    pub(crate) elevation_loss: Option<f64>,
+    pub(crate) min_elevation: Option<f64>,
+    pub(crate) max_elevation: Option<f64>,
+    pub(crate) bbox: [f64; 4],
+    pub(crate) point_count: i64,
+    /// in milliseconds; None if the uploaded file has no timestamps
+    pub(crate) duration: Option<i64>,
+}
+
+/// SHOULD roughly match `server/src/api_route.rs`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ListRoutes {
+    pub(crate) routes: Vec<Route>,
+}
+
+/// SHOULD match `server/src/geofence.rs`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum GeofenceShape {
+    Circle {
+        lat: f64,
+        lng: f64,
+        /// in meters
+        radius: f64,
+    },
+    Polygon {
+        /// (lat, lng)
+        points: Vec<(f64, f64)>,
+    },
+}
+
+/// SHOULD roughly match `server/src/geofence.rs`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub(crate) struct Geofence {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) shape: GeofenceShape,
+}
+
+/// SHOULD roughly match `server/src/api_geofence.rs`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ListGeofences {
+    pub(crate) geofences: Vec<Geofence>,
+}
+
+/// A point of interest of the route, eg water or food; SHOULD roughly match `server/src/poi.rs`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub(crate) struct Poi {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    /// eg "Drinking Water"
+    pub(crate) symbol: Option<String>,
+    pub(crate) lat: f64,
+    pub(crate) lng: f64,
+}
+
+/// SHOULD roughly match `server/src/api_poi.rs`
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ListPois {
+    pub(crate) pois: Vec<Poi>,
+}
+
+/// SHOULD match `server/src/route_profile.rs`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub(crate) struct ProfilePoint {
+    /// in meters, from the start of the route
+    pub(crate) distance: f64,
+    /// in meters
+    pub(crate) elevation: f64,
+}
+
+/// The elevation profile of a route; SHOULD match `server/src/route_profile.rs`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub(crate) struct RouteProfile {
+    /// in meters
+    pub(crate) length: f64,
+    /// ordered by distance; empty if the route has no elevation
+    pub(crate) points: Vec<ProfilePoint>,
+}
@@ -1,10 +1,12 @@
 /// `https://github.com/wpcodevo/rust-yew-signup-signin/blob/62e9186ba1ede01b6d13eeeac036bbd56a131e1e/src/components/header.rs`
 ///
 use crate::{
+    api::{types::User, user_api::api_logout_user},
     router::Route,
-    store::{set_auth_user, set_page_loading, set_show_alert, PersistentStore, Store},
+    store::{set_auth_user, set_page_loading, set_room_id, set_show_alert, PersistentStore, Store},
 };
 use wasm_bindgen_futures::spawn_local;
+use web_sys::console;
 use yew::prelude::*;
 use yew_router::prelude::*;
 use yewdux::prelude::*;
@@ -25,23 +27,16 @@ pub fn header_component() -> Html {
             let dispatch = store_dispatch.clone();
             let dispatch2 = store_dispatch2.clone();
             let _navigator = cloned_navigator.clone();
+            let token = dispatch2.get().token.clone().unwrap_or_default();
             spawn_local(async move {
                 set_page_loading(true, &dispatch);
-                // let res = api_logout_user().await;
-                // match res {
-                //     Ok(_) => {
-                //         set_page_loading(false, dispatch.clone());
-                //         set_auth_user(None, dispatch2.clone());
-                //         set_show_alert("Logged out successfully".to_string(), dispatch);
-                //         navigator.push(&router::Route::LoginPage);
-                //     }
-                //     Err(e) => {
-                //         set_show_alert(e.to_string(), dispatch.clone());
-                //         set_page_loading(false, dispatch);
-                //     }
-                // };
+                // NOTE: even if the server is unreachable we still forget the tokens locally
+                if let Err(e) = api_logout_user(&token).await {
+                    console::warn_1(&format!("api_logout_user error: {e:?}",).into());
+                }
                 set_page_loading(false, &dispatch);
-                set_auth_user(None, None, &dispatch2);
+                set_auth_user(None, None, None, &dispatch2);
+                set_room_id(None, &dispatch2);
                 set_show_alert("Logged out successfully".to_string(), &dispatch);
             });
         })
@@ -60,6 +55,14 @@ pub fn header_component() -> Html {
             <li>
               <Link<Route> to={Route::UsersComponent} classes="text-ct-dark-600">{"Users"}</Link<Route>>
             </li>
+            <li>
+              <Link<Route> to={Route::RoomsComponent} classes="text-ct-dark-600">{"Rooms"}</Link<Route>>
+            </li>
+            if user.as_ref().is_some_and(User::is_organiser) {
+              <li>
+                <Link<Route> to={Route::RoutesComponent} classes="text-ct-dark-600">{"Routes"}</Link<Route>>
+              </li>
+            }
             if user.is_some() {
                <>
                   // TODO ?
@@ -79,8 +82,7 @@ pub fn header_component() -> Html {
             } else {
               <>
                 <li>
-                  // TODO
-                  // <Link<Route> to={Route::RegisterPage} classes="text-ct-dark-600">{"SignUp"}</Link<Route>>
+                  <Link<Route> to={Route::RegisterPage} classes="text-ct-dark-600">{"SignUp"}</Link<Route>>
                 </li>
                 <li>
                   <Link<Route> to={Route::LoginPage} classes="text-ct-dark-600">{"Login"}</Link<Route>>
@@ -1,12 +1,25 @@
+use wasm_bindgen_futures::spawn_local;
+use web_sys::console;
 use yew::prelude::*;
+use yew_hooks::use_interval;
 use yewdux::prelude::*;
 
+use crate::api::user_api::api_refresh_token;
 use crate::components::header::Header;
+use crate::pages::leaderboard_component::LeaderboardComponent;
 use crate::pages::login_page::LoginPage;
 use crate::pages::map_component::MapComponent;
+use crate::pages::privacy_component::PrivacyComponent;
+use crate::pages::profile_component::ProfileComponent;
+use crate::pages::rooms_component::RoomsComponent;
+use crate::pages::track_component::TrackComponent;
 use crate::pages::websocket_chat_component::WebSocketChatComponent;
 use crate::pages::websocket_geoloc_component::WebSocketGeoLocComponent;
-use crate::store::PersistentStore;
+use crate::store::{set_auth_user, set_tokens, PersistentStore};
+
+/// The access tokens are short-lived(cf `--access-token-ttl` on the server, 15 minutes by default)
+/// so we renew them well before they expire
+const TOKEN_REFRESH_INTERVAL_MS: u32 = 5 * 60 * 1000;
 
 // /// https://github.com/yewstack/yew/blob/d0419a278dc126af4556c9afae2ef6b00b5fef36/examples/contexts/src/msg_ctx.rs#L5
 // #[derive(Clone, Debug, PartialEq)]
@@ -33,13 +46,48 @@ pub(crate) fn home_page() -> Html {
     // MAYBE see https://yew.rs/docs/next/concepts/suspense ?
     // and maybe https://github.com/yewstack/yew/issues/1526
     // This is way more contrived than it should be...
-    let (store, _dispatch) = use_store::<PersistentStore>();
+    let (store, dispatch) = use_store::<PersistentStore>();
     let auth_user = store.auth_user.clone();
 
+    // on load(the access token may have expired since the last visit); and then periodically
+    let refresh = Callback::from(move |()| {
+        let dispatch = dispatch.clone();
+        let Some(refresh_token) = dispatch.get().refresh_token.clone() else {
+            return;
+        };
+        spawn_local(async move {
+            match api_refresh_token(&refresh_token).await {
+                Ok(res) => set_tokens(res.access_token, res.refresh_token, &dispatch),
+                Err(e) => {
+                    // eg logged out from another device, or expired: the user MUST login again
+                    console::warn_1(&format!("api_refresh_token error: {e:?}",).into());
+                    set_auth_user(None, None, None, &dispatch);
+                }
+            }
+        });
+    });
+    {
+        let refresh = refresh.clone();
+        use_effect_with((), move |()| refresh.emit(()));
+    }
+    use_interval(move || refresh.emit(()), TOKEN_REFRESH_INTERVAL_MS);
+
     if auth_user.is_none() {
         return html! {<LoginPage />};
     }
 
+    // the websockets are per room: so we MUST pick one first
+    if store.room_id.is_none() {
+        return html! {
+        <div class="flex flex-col min-h-screen flex-grow">
+            <header class="bg-blue-500 p-4 text-black">
+                <Header />
+            </header>
+            <RoomsComponent />
+        </div>
+        };
+    }
+
     html! {
     <div class="flex flex-col min-h-screen flex-grow">
         <header class="bg-blue-500 p-4 text-black">
@@ -47,11 +95,17 @@ pub(crate) fn home_page() -> Html {
         </header>
 
         <div class="flex flex-row flex-grow">
-            <div id="map-container" class="basis-3/4 bg-gray-200 p-4">
-                <MapComponent />
+            <div class="basis-3/4 flex flex-col bg-gray-200">
+                <div id="map-container" class="flex-grow p-4">
+                    <MapComponent />
+                </div>
+                <ProfileComponent />
             </div>
 
             <div class="basis-1/4 bg-gray-200 p-4">
+                <PrivacyComponent />
+                <LeaderboardComponent />
+                <TrackComponent />
                 <WebSocketChatComponent />
             </div>
 
@@ -0,0 +1,148 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_store;
+
+use crate::api::room_api::api_set_lead;
+use crate::api::types::User;
+use crate::store::{set_show_alert, PersistentStore, Store};
+
+/// The ETA as a local time of day, eg "14:05"
+fn format_eta(eta: i64) -> String {
+    #[allow(clippy::cast_precision_loss)]
+    let date = js_sys::Date::new(&(eta as f64).into());
+    format!("{:02}:{:02}", date.get_hours(), date.get_minutes())
+}
+
+/// The riders of the room ranked by their progress along the active route, and how far behind the leader they are
+/// Live: the progress comes with each position(cf `Position::progress`, computed by `server/src/progress.rs`);
+/// the same as `GET /api/rooms/{room_id}/progress`
+/// The organisers also designate the leader and the sweeper of the group here, cf `server/src/lead.rs`
+#[function_component(LeaderboardComponent)]
+pub(crate) fn leaderboard_component() -> Html {
+    let (persistent_store, _persistent_dispatch) = use_store::<PersistentStore>();
+    let (store, dispatch) = use_store::<Store>();
+
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let room_id = persistent_store.room_id.unwrap_or_default();
+    // NOTE: the server checks that they are an organiser of THIS room
+    let is_organiser = persistent_store
+        .auth_user
+        .as_ref()
+        .is_some_and(User::is_organiser);
+
+    let on_set_lead = {
+        let dispatch = dispatch.clone();
+        Callback::from(move |(leader, sweeper): (Option<String>, Option<String>)| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            // the store is updated with `WsMessage::Lead`
+            spawn_local(async move {
+                if let Err(e) =
+                    api_set_lead(&token, room_id, leader.as_deref(), sweeper.as_deref()).await
+                {
+                    set_show_alert(e, &dispatch);
+                }
+            });
+        })
+    };
+
+    let mut riders: Vec<_> = store
+        .locations
+        .values()
+        .filter_map(|position| Some((position.username.as_str(), position.progress?)))
+        .collect();
+    // no active route, or nobody on it yet
+    if riders.is_empty() {
+        return html! {};
+    }
+    riders.sort_by(|(a_username, a), (b_username, b)| {
+        b.distance_done
+            .total_cmp(&a.distance_done)
+            .then_with(|| a_username.cmp(b_username))
+    });
+    let leader_distance = riders[0].1.distance_done;
+    let lead = &store.lead;
+
+    html! {
+        <div>
+            {
+                match lead.gap {
+                    Some(gap) => html! {
+                        <p class={if lead.is_spread { "text-red-600" } else { "" }}>
+                            {format!("Leader to sweeper: {:.1} km", gap / 1000.0)}
+                        </p>
+                    },
+                    None => html! {},
+                }
+            }
+            <table class="table-auto">
+                <thead>
+                    <tr>
+                        <th>{"#"}</th>
+                        <th>{"Rider"}</th>
+                        <th>{"Done"}</th>
+                        <th>{"Remaining (km)"}</th>
+                        <th>{"ETA"}</th>
+                        <th>{"Gap (km)"}</th>
+                        if is_organiser {
+                            <th></th>
+                        }
+                    </tr>
+                </thead>
+                <tbody>
+                    {
+                        riders.iter().enumerate().map(|(index, (username, progress))| {
+                            let username = (*username).to_string();
+                            let is_leader = lead.leader.as_ref() == Some(&username);
+                            let is_sweeper = lead.sweeper.as_ref() == Some(&username);
+                            let on_leader = {
+                                let on_set_lead = on_set_lead.clone();
+                                let sweeper = lead.sweeper.clone().filter(|sweeper| *sweeper != username);
+                                let leader = Some(username.clone());
+                                move |_| on_set_lead.emit((leader.clone(), sweeper.clone()))
+                            };
+                            let on_sweeper = {
+                                let on_set_lead = on_set_lead.clone();
+                                let leader = lead.leader.clone().filter(|leader| *leader != username);
+                                let sweeper = Some(username.clone());
+                                move |_| on_set_lead.emit((leader.clone(), sweeper.clone()))
+                            };
+                            html! {
+                                <tr>
+                                    <td>{index + 1}</td>
+                                    <td>
+                                        {&username}
+                                        if is_leader {
+                                            {" (leader)"}
+                                        }
+                                        if is_sweeper {
+                                            {" (sweeper)"}
+                                        }
+                                    </td>
+                                    <td>{format!("{:.0}%", progress.percent)}</td>
+                                    <td>{format!("{:.1}", progress.distance_remaining / 1000.0)}</td>
+                                    <td>{progress.eta.map_or_else(|| "-".to_string(), format_eta)}</td>
+                                    <td>
+                                        {
+                                            if index == 0 {
+                                                "-".to_string()
+                                            } else {
+                                                format!("{:.1}", (leader_distance - progress.distance_done) / 1000.0)
+                                            }
+                                        }
+                                    </td>
+                                    if is_organiser {
+                                        <td>
+                                            <button disabled={is_leader} onclick={on_leader}>{"Leader"}</button>
+                                            <button disabled={is_sweeper} onclick={on_sweeper}>{"Sweeper"}</button>
+                                        </td>
+                                    }
+                                </tr>
+                            }
+                        }).collect::<Html>()
+                    }
+                </tbody>
+            </table>
+        </div>
+    }
+}
@@ -4,7 +4,7 @@ use std::ops::Deref;
 use std::rc::Rc;
 
 use crate::api::types::User;
-use crate::api::user_api::api_login_user;
+use crate::api::user_api::{api_login_user, api_user_info};
 use crate::components::form_input::FormInput;
 use crate::components::loading_button::LoadingButton;
 use crate::router::{self};
@@ -152,13 +152,22 @@ pub fn login_page() -> Html {
                         let res = api_login_user(&form_json).await;
                         match res {
                             Ok(res) => {
+                                // to get the actual role; cf `User::is_organiser`
+                                let user = match api_user_info(&res.access_token).await {
+                                    Ok(user) => user,
+                                    Err(err) => {
+                                        console::warn_1(&format!("api_user_info: {err}").into());
+                                        User {
+                                            username: form_data.email,
+                                            role: "member".to_string(),
+                                        }
+                                    }
+                                };
                                 set_page_loading(false, &dispatch);
                                 set_auth_user(
-                                    Some(User {
-                                        username: form_data.email,
-                                        is_super_user: false,
-                                    }),
+                                    Some(user),
                                     Some(res.access_token),
+                                    Some(res.refresh_token),
                                     &dispatch2,
                                 );
                                 navigator.push(&router::Route::HomePage);
@@ -207,11 +216,10 @@ pub fn login_page() -> Html {
               {"Login"}
             </LoadingButton>
 
-            // TODO
-            // <span class="block">
-            //   {"Need an account?"} {" "}
-            //   <Link<Route> to={Route::RegisterPage} classes="text-ct-blue-600">{ "Sign Up Here" }</Link<Route>>
-            // </span>
+            <span class="block">
+              {"Need an account?"} {" "}
+              <Link<router::Route> to={router::Route::RegisterPage} classes="text-ct-blue-600">{ "Sign Up Here" }</Link<router::Route>>
+            </span>
           </form>
       </div>
     </section>
@@ -5,19 +5,41 @@ use std::rc::Rc;
 /// `https://github.com/slowtec/leaflet-rs/blob/master/examples/yew-component/src/components/map_component.rs`
 use gloo_utils::document;
 use js_sys::Array;
-use leaflet::{Circle, LatLng, Map, MapOptions, Polyline, PolylineOptions, TileLayer};
+use leaflet::{
+    Circle, LatLng, Map, MapOptions, Marker, MarkerOptions, PathOptions, Polygon, Polyline,
+    PolylineOptions, TileLayer,
+};
 use leaflet::{Tooltip, TooltipOptions};
-use serde_json::Value;
+use protocol::{PresenceStatus, SharingMode};
 use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::spawn_local;
 use web_sys::{console, HtmlElement};
 use yew::prelude::*;
-use yewdux::use_store;
+use yew_hooks::{use_interval, use_update};
+use yewdux::{use_store, Dispatch};
 
-use crate::store::Store;
+use crate::api::geofence_api::api_get_room_geofences;
+use crate::api::gpx_api::api_get_room_route;
+use crate::api::poi_api::api_get_room_pois;
+use crate::api::types::{Geofence, GeofenceShape, Poi};
+use crate::store::{set_geofences, set_pois, set_route, set_show_alert, PersistentStore, Store};
 
 const PARIS_LAT: f64 = 48.866_667;
 const PARIS_LNG: f64 = 2.333_333;
 
+/// A marker whose position is older than that is greyed out, eg a phone that lost signal
+const STALE_POSITION_MS: f64 = 5.0 * 60.0 * 1000.0;
+/// How often to re-render, so that the markers turn grey even if nothing else happens
+const STALE_CHECK_INTERVAL_MS: u32 = 30 * 1000;
+/// Leaflet's default
+const MARKER_COLOR: &str = "#3388ff";
+const STALE_MARKER_COLOR: &str = "grey";
+/// cf `PresenceStatus::Offline`: the marker is kept, but faded
+const OFFLINE_MARKER_COLOR: &str = "lightgrey";
+const GEOFENCE_COLOR: &str = "orange";
+/// cf `WsMessage::OffRoute`; it wins over the other colors: the organisers MUST notice them
+const OFF_ROUTE_MARKER_COLOR: &str = "red";
+
 /// We MUST NOT modify both the Store and the State in `MapComponent` because that would trigger massive redraws!
 /// So to avoid this we add a custom struct and use `use_state_eq` with our custom `PartialEq` implementation
 #[derive(Clone)]
@@ -35,7 +57,10 @@ impl PartialEq for MyCircleWrapper {
 
 #[function_component(MapComponent)]
 pub(crate) fn map_component() -> Html {
-    let (store, _dispatch) = use_store::<Store>();
+    let (store, dispatch) = use_store::<Store>();
+    let (persistent_store, _persistent_dispatch) = use_store::<PersistentStore>();
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let room_id = persistent_store.room_id.unwrap_or_default();
     let leaflet_map_state = use_state(|| None);
     // let mut map_location_markers: UseStateHandle<HashMap<String, Circle>> =
     //     use_state(|| HashMap::new());
@@ -49,6 +74,9 @@ pub(crate) fn map_component() -> Html {
     let map_location_markers: Rc<RefCell<HashMap<String, MyCircleWrapper>>> =
         use_mut_ref(HashMap::new);
 
+    let update = use_update();
+    use_interval(move || update(), STALE_CHECK_INTERVAL_MS);
+
     // "Provide a empty tuple `()` as dependencies when you need to do something only on the first render of a component."
     // let container_clone = container.clone();
     let leaflet_map_state_clone = leaflet_map_state.clone();
@@ -70,11 +98,91 @@ pub(crate) fn map_component() -> Html {
         leaflet_map.set_view(&LatLng::new(PARIS_LAT, PARIS_LNG), 11.0);
         add_tile_layer(&leaflet_map);
 
-        add_geojson_trace(&leaflet_map);
+        refresh_route(token.clone(), room_id, dispatch.clone());
+        refresh_geofences(token.clone(), room_id, dispatch.clone());
+        refresh_pois(token, room_id, dispatch);
 
         leaflet_map_state_clone.set(Some(leaflet_map));
     });
 
+    // Draw the route(cf `refresh_route`); replacing the previous one if any
+    let route_polylines: Rc<RefCell<Vec<Polyline>>> = use_mut_ref(Vec::new);
+    {
+        let leaflet_map_state = leaflet_map_state.clone();
+        let route = store.route.clone();
+        use_effect_with(
+            (route, leaflet_map_state.is_some()),
+            move |(route, _is_map_ready)| {
+                if let Some(leaflet_map) = leaflet_map_state.as_ref() {
+                    console::log_1(&"MapComponent: drawing the route".into());
+                    replace_polylines(
+                        leaflet_map,
+                        &mut route_polylines.borrow_mut(),
+                        route.as_deref().unwrap_or_default(),
+                        &PolylineOptions::default(),
+                    );
+                }
+            },
+        );
+    }
+
+    // Draw the geofences(cf `refresh_geofences`); replacing the previous ones if any
+    let geofence_layers: Rc<RefCell<GeofenceLayers>> = use_mut_ref(GeofenceLayers::default);
+    {
+        let leaflet_map_state = leaflet_map_state.clone();
+        let geofences = store.geofences.clone();
+        use_effect_with(
+            (geofences, leaflet_map_state.is_some()),
+            move |(geofences, _is_map_ready)| {
+                if let Some(leaflet_map) = leaflet_map_state.as_ref() {
+                    console::log_1(&"MapComponent: drawing the geofences".into());
+                    geofence_layers.borrow_mut().replace(leaflet_map, geofences);
+                }
+            },
+        );
+    }
+
+    // Draw the points of interest(cf `refresh_pois`); replacing the previous ones if any
+    let poi_markers: Rc<RefCell<Vec<Marker>>> = use_mut_ref(Vec::new);
+    {
+        let leaflet_map_state = leaflet_map_state.clone();
+        let pois = store.pois.clone();
+        use_effect_with(
+            (pois, leaflet_map_state.is_some()),
+            move |(pois, _is_map_ready)| {
+                if let Some(leaflet_map) = leaflet_map_state.as_ref() {
+                    console::log_1(&"MapComponent: drawing the points of interest".into());
+                    replace_poi_markers(leaflet_map, &mut poi_markers.borrow_mut(), pois);
+                }
+            },
+        );
+    }
+
+    // Draw the history of the selected user(cf `TrackComponent`); replacing the previous one if any
+    let track_polylines: Rc<RefCell<Vec<Polyline>>> = use_mut_ref(Vec::new);
+    {
+        let leaflet_map_state = leaflet_map_state.clone();
+        let track = store.track.clone();
+        use_effect_with(
+            (track, leaflet_map_state.is_some()),
+            move |(track, _is_map_ready)| {
+                if let Some(leaflet_map) = leaflet_map_state.as_ref() {
+                    let options = PolylineOptions::default();
+                    options.set_color("red".to_string());
+                    replace_polylines(
+                        leaflet_map,
+                        &mut track_polylines.borrow_mut(),
+                        track
+                            .as_ref()
+                            .map(|(_username, lines)| lines.as_slice())
+                            .unwrap_or_default(),
+                        &options,
+                    );
+                }
+            },
+        );
+    }
+
     // NOT the the first render: retrieve the existing map from the document
     // FAIL: get_element_by_id works but no way to cast it into "Map"???
     // match document().get_element_by_id("map-container") {
@@ -104,7 +212,24 @@ pub(crate) fn map_component() -> Html {
             console::log_1(&"MapComponent: leaflet_map_state ready".into());
 
             // For example, update the circles on the map
-            for (username, (lat, lng)) in &store.locations {
+            let now = js_sys::Date::now();
+            for (username, position) in &store.locations {
+                let (lat, lng) = (&position.lat, &position.lng);
+                #[allow(clippy::cast_precision_loss)]
+                let is_stale = now - position.timestamp as f64 > STALE_POSITION_MS;
+                // NOTE: the presence may NOT be known yet, cf `refresh_presence`
+                let is_paused = store.sharing.get(username) == Some(&SharingMode::Paused);
+                let color = match store.presence.get(username) {
+                    _ if store.off_route.contains_key(username) => OFF_ROUTE_MARKER_COLOR,
+                    Some(PresenceStatus::Offline) => OFFLINE_MARKER_COLOR,
+                    Some(PresenceStatus::Idle) => STALE_MARKER_COLOR,
+                    // their position will NOT move until they resume
+                    _ if is_stale || is_paused => STALE_MARKER_COLOR,
+                    _ => MARKER_COLOR,
+                };
+                let style = PathOptions::default();
+                style.set_color(color.to_string());
+
                 // if there is an entry matching username; update it
                 // else insert a new circle in the map
                 // That returns what we could call "should_insert_new_circle"
@@ -117,11 +242,13 @@ pub(crate) fn map_component() -> Html {
                         &"MapComponent: leaflet_map_state update the existing circle".into(),
                     );
                     circle_wrapper.circle.set_lat_lng(&LatLng::new(*lat, *lng));
+                    circle_wrapper.circle.set_style(&style);
                     None
                 } else {
                     // create a new circle
                     console::log_1(&"MapComponent: leaflet_map_state create a new circle".into());
                     let circle = new_circle_with_options(*lat, *lng, username);
+                    circle.set_style(&style);
                     Some(MyCircleWrapper {
                         tag: username.to_string(),
                         circle,
@@ -138,6 +265,17 @@ pub(crate) fn map_component() -> Html {
                     console::log_1(&"MapComponent: leaflet_map_state NOT inserting!".into());
                 }
             }
+
+            // and remove the users that are gone, eg after a `WsMessage::Snapshot`
+            map_location_markers
+                .borrow_mut()
+                .retain(|username, circle_wrapper| {
+                    let is_kept = store.locations.contains_key(username);
+                    if !is_kept {
+                        circle_wrapper.circle.remove();
+                    }
+                    is_kept
+                });
         }
         None => {
             console::log_1(&"MapComponent: leaflet_map_state NOT ready".into());
@@ -182,57 +320,144 @@ fn new_circle_with_options(lat: f64, lng: f64, username: &str) -> Circle {
     circle
 }
 
-/// Add a .gpx (`GeoJSON`) track on the map
-fn add_geojson_trace(map: &Map) {
-    // Parse the GeoJSON string into a serde_json::Value
-    let geojson_string: Value = serde_json::from_str(include_str!(
-        "../../../server/tests/data/2024-02-19_1444960792_MJ 19_02.geojson"
-    ))
-    .unwrap();
-
-    let lines = &geojson_string["geometries"][0]["coordinates"][0];
-    // console::log_1(&format!("MapComponent: add_geojson_trace: lines: {}", lines,).into());
-
-    let latlngs = lines
-        .as_array()
-        .unwrap()
-        .iter()
-        .map(|line| {
-            let arr = line.as_array().unwrap();
-            let lng = arr[0].as_f64().expect("arr[0].as_f64");
-            let lat = arr[1].as_f64().expect("arr[1].as_f64");
-
-            LatLng::new(lat, lng)
-        })
-        .collect::<Array>();
-    // console::log_1(&format!("MapComponent: add_geojson_trace: latlngs: {:?}", latlngs,).into());
-
-    let options = PolylineOptions::default();
-    Polyline::new_with_options(
-        &latlngs.iter().map(JsValue::from).collect::<Array>(),
-        &options,
-    )
-    .add_to(map);
-
-    // // Create a GeoJson layer from the parsed GeoJSON value
-    // let geojson_value = JsValue::from_str(&geojson_string);
-    // // let geojson_layer = GeoJson::geo_json(&geojson_value);
-
-    // let geo = GeoJson::add_data(&geojson_value);
-
-    // // Parse the GeoJSON string into a JavaScript object
-    // let window = window();
-    // let geojson_value = JsValue::from_str(&geojson_string);
-    // let geojson_object = window
-    //     .eval("JSON.parse")
-    //     .call1(&JsValue::NULL, &geojson_value)
-    //     .unwrap();
-
-    // Create a GeoJson layer from the parsed GeoJSON object
-    // let geojson_layer = GeoJSON::new(&geojson_value);
-
-    // Add the GeoJson layer to the map
-    // map.add_layer(geojson_layer);
+/// Remove the `previous` polylines from the map; and draw `lines` instead
+fn replace_polylines(
+    leaflet_map: &Map,
+    previous: &mut Vec<Polyline>,
+    lines: &[Vec<(f64, f64)>],
+    options: &PolylineOptions,
+) {
+    for polyline in previous.drain(..) {
+        polyline.remove();
+    }
+
+    for line in lines {
+        let latlngs = line
+            .iter()
+            .map(|(lat, lng)| JsValue::from(LatLng::new(*lat, *lng)))
+            .collect::<Array>();
+        let polyline = Polyline::new_with_options(&latlngs, options);
+        polyline.add_to(leaflet_map);
+        previous.push(polyline);
+    }
+}
+
+/// The shapes drawn for the geofences; cf `GeofenceLayers::replace`
+#[derive(Default)]
+struct GeofenceLayers {
+    circles: Vec<Circle>,
+    polygons: Vec<Polygon>,
+}
+
+impl GeofenceLayers {
+    /// Remove the previous geofences from the map; and draw `geofences` instead, with their name as tooltip
+    fn replace(&mut self, leaflet_map: &Map, geofences: &[Geofence]) {
+        for circle in self.circles.drain(..) {
+            circle.remove();
+        }
+        for polygon in self.polygons.drain(..) {
+            polygon.remove();
+        }
+
+        for geofence in geofences {
+            let new_tooltip = |lat: f64, lng: f64| {
+                let tooltip =
+                    Tooltip::new_with_lat_lng(&LatLng::new(lat, lng), &TooltipOptions::new());
+                tooltip.set_content(&JsValue::from_str(&geofence.name));
+                tooltip
+            };
+            match &geofence.shape {
+                GeofenceShape::Circle { lat, lng, radius } => {
+                    let options = leaflet::CircleOptions::default();
+                    options.set_radius(*radius);
+                    options.set_color(GEOFENCE_COLOR.to_string());
+                    let circle = Circle::new_with_options(&LatLng::new(*lat, *lng), &options);
+                    circle.bind_tooltip(&new_tooltip(*lat, *lng));
+                    circle.add_to(leaflet_map);
+                    self.circles.push(circle);
+                }
+                GeofenceShape::Polygon { points } => {
+                    let latlngs = points
+                        .iter()
+                        .map(|(lat, lng)| JsValue::from(LatLng::new(*lat, *lng)))
+                        .collect::<Array>();
+                    let options = PolylineOptions::default();
+                    options.set_color(GEOFENCE_COLOR.to_string());
+                    let polygon = Polygon::new_with_options(&latlngs, &options);
+                    if let Some((lat, lng)) = points.first() {
+                        polygon.bind_tooltip(&new_tooltip(*lat, *lng));
+                    }
+                    polygon.add_to(leaflet_map);
+                    self.polygons.push(polygon);
+                }
+            }
+        }
+    }
+}
+
+/// Remove the `previous` markers from the map; and draw `pois` instead, labelled with their name
+/// The description(and symbol) are shown when hovering over the marker
+fn replace_poi_markers(leaflet_map: &Map, previous: &mut Vec<Marker>, pois: &[Poi]) {
+    for marker in previous.drain(..) {
+        marker.remove();
+    }
+
+    for poi in pois {
+        let lat_lng = LatLng::new(poi.lat, poi.lng);
+        let options = MarkerOptions::default();
+        let title = [poi.symbol.as_deref(), poi.description.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(": ");
+        options.set_title(title);
+        let marker = Marker::new_with_options(&lat_lng, &options);
+
+        // NOTE: the name comes from the uploaded file: as text, NOT as HTML
+        let label = document().create_text_node(&poi.name);
+        let tooltip_options = TooltipOptions::new();
+        tooltip_options.set_permanent(true);
+        let tooltip = Tooltip::new_with_lat_lng(&lat_lng, &tooltip_options);
+        tooltip.set_content(&label.into());
+        marker.bind_tooltip(&tooltip);
+
+        marker.add_to(leaflet_map);
+        previous.push(marker);
+    }
+}
+
+/// Fetch the geofences of the room(cf `server/src/api_geofence.rs`) and put them in the Store; they will then be drawn by `MapComponent`
+/// Called on the first render, and every time the server sends `WsMessage::GeofencesUpdated` or `WsMessage::RouteUpdated`
+pub(crate) fn refresh_geofences(token: String, room_id: i64, dispatch: Dispatch<Store>) {
+    spawn_local(async move {
+        match api_get_room_geofences(&token, room_id).await {
+            Ok(geofences) => set_geofences(geofences, &dispatch),
+            Err(e) => set_show_alert(e, &dispatch),
+        }
+    });
+}
+
+/// Fetch the points of interest of the route of the room(cf `server/src/api_poi.rs`) and put them in the Store;
+/// they will then be drawn by `MapComponent`
+/// Called on the first render, and every time the server sends `WsMessage::PoisUpdated` or `WsMessage::RouteUpdated`
+pub(crate) fn refresh_pois(token: String, room_id: i64, dispatch: Dispatch<Store>) {
+    spawn_local(async move {
+        match api_get_room_pois(&token, room_id).await {
+            Ok(pois) => set_pois(pois, &dispatch),
+            Err(e) => set_show_alert(e, &dispatch),
+        }
+    });
+}
+
+/// Fetch the active route of the room(cf `server/src/api_route.rs`) and put it in the Store; it will then be drawn by `MapComponent`
+/// Called on the first render, and every time the server sends `WsMessage::RouteUpdated`
+pub(crate) fn refresh_route(token: String, room_id: i64, dispatch: Dispatch<Store>) {
+    spawn_local(async move {
+        match api_get_room_route(&token, room_id).await {
+            Ok(route) => set_route(route, &dispatch),
+            Err(e) => set_show_alert(e, &dispatch),
+        }
+    });
 }
 
 // fn render_map(container: &HtmlElement) -> Html {
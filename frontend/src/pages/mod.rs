@@ -1,6 +1,13 @@
 pub(crate) mod home_page;
+pub(crate) mod leaderboard_component;
 pub(crate) mod login_page;
 pub(crate) mod map_component;
+pub(crate) mod privacy_component;
+pub(crate) mod profile_component;
+pub(crate) mod register_page;
+pub(crate) mod rooms_component;
+pub(crate) mod routes_component;
+pub(crate) mod track_component;
 pub(crate) mod users_component;
 pub(crate) mod websocket_chat_component;
 pub(crate) mod websocket_geoloc_component;
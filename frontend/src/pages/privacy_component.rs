@@ -0,0 +1,95 @@
+use protocol::SharingMode;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_store;
+
+use crate::api::room_api::{api_get_privacy, api_set_privacy};
+use crate::api::types::Privacy;
+use crate::store::{set_show_alert, PersistentStore, Store};
+
+/// The modes that can be picked, and their label
+const MODES: [(SharingMode, &str); 3] = [
+    (SharingMode::Sharing, "Share"),
+    (SharingMode::Paused, "Pause"),
+    (SharingMode::Hidden, "Hide"),
+];
+/// The precisions that can be picked(in meters, cf `Privacy::precision`), and their label
+const PRECISIONS: [(Option<u32>, &str); 3] =
+    [(None, "Exact"), (Some(100), "100 m"), (Some(1000), "1 km")];
+
+/// Choose what we share with the room: pause the sharing of our position, or hide it completely("ghost mode");
+/// and how precise it is
+/// cf `server/src/privacy.rs`; `WebSocketGeoLocComponent` stops sending our positions when NOT sharing
+#[function_component(PrivacyComponent)]
+pub(crate) fn privacy_component() -> Html {
+    let (persistent_store, _persistent_dispatch) = use_store::<PersistentStore>();
+    let (store, dispatch) = use_store::<Store>();
+
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let room_id = persistent_store.room_id.unwrap_or_default();
+
+    {
+        let token = token.clone();
+        let dispatch = dispatch.clone();
+        use_effect_with(room_id, move |room_id| {
+            let room_id = *room_id;
+            spawn_local(async move {
+                match api_get_privacy(&token, room_id).await {
+                    Ok(privacy) => dispatch.reduce_mut(|store| store.privacy = Some(privacy)),
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+            });
+        });
+    }
+
+    let on_change = {
+        let dispatch = dispatch.clone();
+        Callback::from(move |privacy: Privacy| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            spawn_local(async move {
+                match api_set_privacy(&token, room_id, privacy).await {
+                    Ok(()) => dispatch.reduce_mut(|store| store.privacy = Some(privacy)),
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+            });
+        })
+    };
+
+    let Some(privacy) = store.privacy else {
+        return html! {};
+    };
+
+    html! {
+        <div>
+            <p>
+                {"My position: "}
+                {
+                    MODES.into_iter().map(|(mode, label)| {
+                        let on_change = on_change.clone();
+                        html! {
+                            <button disabled={privacy.mode == mode}
+                                onclick={move |_| on_change.emit(Privacy { mode, ..privacy })}>
+                                {label}
+                            </button>
+                        }
+                    }).collect::<Html>()
+                }
+            </p>
+            <p>
+                {"Precision: "}
+                {
+                    PRECISIONS.into_iter().map(|(precision, label)| {
+                        let on_change = on_change.clone();
+                        html! {
+                            <button disabled={privacy.precision == precision}
+                                onclick={move |_| on_change.emit(Privacy { precision, ..privacy })}>
+                                {label}
+                            </button>
+                        }
+                    }).collect::<Html>()
+                }
+            </p>
+        </div>
+    }
+}
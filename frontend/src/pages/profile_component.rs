@@ -0,0 +1,158 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::{use_store, Dispatch};
+
+use crate::api::room_api::api_list_rooms;
+use crate::api::route_api::api_get_route_profile;
+use crate::api::types::ProfilePoint;
+use crate::store::{set_profile, set_show_alert, PersistentStore, Store};
+
+/// The size of the chart, in SVG units; it is then scaled to the width of the page
+const CHART_WIDTH: f64 = 1000.0;
+const CHART_HEIGHT: f64 = 150.0;
+/// About one point per SVG unit; cf `server/src/route_profile.rs`
+const PROFILE_MAX_POINTS: usize = 1000;
+const PROFILE_COLOR: &str = "#3388ff";
+const RIDER_COLOR: &str = "red";
+
+/// The elevation at a given distance along the route, between the two closest points of the profile
+fn elevation_at(points: &[ProfilePoint], distance: f64) -> Option<f64> {
+    let i = points.partition_point(|point| point.distance < distance);
+    match (i.checked_sub(1).map(|j| points[j]), points.get(i)) {
+        (Some(a), Some(b)) if b.distance > a.distance => Some(
+            a.elevation
+                + (b.elevation - a.elevation) * (distance - a.distance) / (b.distance - a.distance),
+        ),
+        (_, Some(b)) => Some(b.elevation),
+        (Some(a), None) => Some(a.elevation),
+        (None, None) => None,
+    }
+}
+
+/// Fetch the elevation profile of the active route of the room(cf `server/src/api_route.rs`) and put it in the Store
+/// NOTE: the route id comes from the room, cf `api_list_rooms`
+pub(crate) fn refresh_profile(token: String, room_id: i64, dispatch: Dispatch<Store>) {
+    spawn_local(async move {
+        let route_id = match api_list_rooms(&token).await {
+            Ok(rooms) => rooms
+                .rooms
+                .into_iter()
+                .find(|room| room.id == room_id)
+                .and_then(|room| room.active_route_id),
+            Err(e) => {
+                set_show_alert(e, &dispatch);
+                return;
+            }
+        };
+        let profile = match route_id {
+            Some(route_id) => {
+                match api_get_route_profile(&token, route_id, PROFILE_MAX_POINTS).await {
+                    Ok(profile) => Some(profile),
+                    Err(e) => {
+                        set_show_alert(e, &dispatch);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        set_profile(profile, &dispatch);
+    });
+}
+
+/// The elevation profile of the active route of the room, under the map; with the position of each rider on it
+/// The riders are placed with their progress along the route(cf `Position::progress`, computed by `server/src/progress.rs`)
+/// NOTE: as a percentage: the progress is along the simplified route, the profile along the original one
+#[function_component(ProfileComponent)]
+pub(crate) fn profile_component() -> Html {
+    let (persistent_store, _persistent_dispatch) = use_store::<PersistentStore>();
+    let (store, dispatch) = use_store::<Store>();
+
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let room_id = persistent_store.room_id.unwrap_or_default();
+
+    // fetched again every time the route changes, cf `refresh_route`
+    {
+        let route = store.route.clone();
+        use_effect_with(route, move |_route| {
+            refresh_profile(token, room_id, dispatch);
+        });
+    }
+
+    // no active route, or no elevation
+    let Some(profile) = store
+        .profile
+        .as_ref()
+        .filter(|profile| profile.points.len() >= 2)
+    else {
+        return html! {};
+    };
+    let points = &profile.points;
+
+    let min_elevation = points
+        .iter()
+        .map(|point| point.elevation)
+        .fold(f64::INFINITY, f64::min);
+    let max_elevation = points
+        .iter()
+        .map(|point| point.elevation)
+        .fold(f64::NEG_INFINITY, f64::max);
+    // a flat route is drawn at the bottom
+    let elevation_range = (max_elevation - min_elevation).max(1.0);
+    let length = profile.length.max(1.0);
+    let x = |distance: f64| distance / length * CHART_WIDTH;
+    let y = |elevation: f64| {
+        CHART_HEIGHT - (elevation - min_elevation) / elevation_range * CHART_HEIGHT
+    };
+
+    let polyline = points
+        .iter()
+        .map(|point| format!("{:.1},{:.1}", x(point.distance), y(point.elevation)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut riders: Vec<_> = store
+        .locations
+        .values()
+        .filter_map(|position| {
+            let distance = position.progress?.percent / 100.0 * profile.length;
+            Some((
+                position.username.as_str(),
+                distance,
+                elevation_at(points, distance)?,
+            ))
+        })
+        .collect();
+    riders.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+    html! {
+        <div class="p-4">
+            <div class="flex justify-between text-sm">
+                <span>{format!("{min_elevation:.0}-{max_elevation:.0} m")}</span>
+                <span>{format!("{:.1} km", profile.length / 1000.0)}</span>
+            </div>
+            <svg
+                xmlns="http://www.w3.org/2000/svg"
+                viewBox={format!("0 0 {CHART_WIDTH} {CHART_HEIGHT}")}
+                preserveAspectRatio="none"
+                class="w-full h-32 bg-white"
+            >
+                <polyline points={polyline} fill="none" stroke={PROFILE_COLOR} stroke-width="2" />
+                {
+                    riders.into_iter().map(|(username, distance, elevation)| {
+                        html! {
+                            <g>
+                                <circle cx={format!("{:.1}", x(distance))} cy={format!("{:.1}", y(elevation))} r="4" fill={RIDER_COLOR}>
+                                    <title>{format!("{username}: {:.1} km, {elevation:.0} m", distance / 1000.0)}</title>
+                                </circle>
+                                <text x={format!("{:.1}", x(distance))} y={format!("{:.1}", (y(elevation) - 6.0).max(10.0))} font-size="10" text-anchor="middle">
+                                    {username}
+                                </text>
+                            </g>
+                        }
+                    }).collect::<Html>()
+                }
+            </svg>
+        </div>
+    }
+}
@@ -0,0 +1,236 @@
+/// `https://github.com/wpcodevo/rust-yew-signup-signin/blob/62e9186ba1ede01b6d13eeeac036bbd56a131e1e/src/pages/register_page.rs`
+/// Same as `LoginPage`, but creates the user first; cf `register` in `server/src/api_authorize_jwt.rs`
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use crate::api::types::User;
+use crate::api::user_api::{api_register_user, api_user_info};
+use crate::components::form_input::FormInput;
+use crate::components::loading_button::LoadingButton;
+use crate::router::{self};
+use crate::store::{set_auth_user, set_page_loading, set_show_alert, PersistentStore, Store};
+
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{console, HtmlInputElement};
+use yew::prelude::*;
+use yew_router::prelude::*;
+use yewdux::prelude::*;
+
+#[derive(Validate, Debug, Default, Clone, Deserialize, Serialize)]
+struct RegisterUserSchema {
+    #[validate(
+        length(min = 1, message = "Username is required"),
+        // email(message = "Email is invalid")
+    )]
+    email: String,
+    // NOTE: MUST match `MIN_PASSWORD_LENGTH` in `server/src/api_authorize_jwt.rs`
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    password: String,
+}
+
+fn get_input_callback(
+    name: &'static str,
+    cloned_form: UseStateHandle<RegisterUserSchema>,
+) -> Callback<String> {
+    Callback::from(move |value| {
+        let mut data = cloned_form.deref().clone();
+        match name {
+            "email" => data.email = value,
+            "password" => data.password = value,
+            _ => {
+                console::error_1(
+                    &format!("get_input_callback: unknown field {name:?}, cf RegisterUserSchema",)
+                        .into(),
+                );
+                return;
+            }
+        }
+        cloned_form.set(data);
+        console::debug_1(&format!("get_input_callback: cloned_form: {cloned_form:?}",).into());
+    })
+}
+
+#[function_component(RegisterPage)]
+pub fn register_page() -> Html {
+    let (store, dispatch) = use_store::<Store>();
+    let (_persistent_store, dispatch2) = use_store::<PersistentStore>();
+    let form = use_state(RegisterUserSchema::default);
+    let validation_errors = use_state(|| Rc::new(RefCell::new(ValidationErrors::new())));
+    let navigator = use_navigator().unwrap();
+
+    let email_input_ref = NodeRef::default();
+    let password_input_ref = NodeRef::default();
+
+    let validate_input_on_blur = {
+        let cloned_form = form.clone();
+        console::debug_1(
+            &format!("register_page: validate_input_on_blur: cloned_form: {cloned_form:?}",).into(),
+        );
+        let cloned_validation_errors = validation_errors.clone();
+        Callback::from(move |(name, value): (String, String)| {
+            let mut data = cloned_form.deref().clone();
+            match name.as_str() {
+                "email" => data.email = value,
+                "password" => data.password = value,
+                _ => {
+                    console::error_1(
+                        &format!(
+                            "register_page: validate_input_on_blur: unknown field {name:?}, cf RegisterUserSchema",
+                        )
+                        .into(),
+                    );
+                    return;
+                }
+            }
+            cloned_form.set(data);
+
+            console::debug_1(
+                &format!(
+                    "register_page: validate_input_on_blur: Callback: cloned_form: {cloned_form:?}",
+                )
+                .into(),
+            );
+
+            match cloned_form.validate() {
+                Ok(()) => {
+                    cloned_validation_errors
+                        .borrow_mut()
+                        .errors_mut()
+                        .remove(name.as_str());
+                }
+                Err(errors) => {
+                    cloned_validation_errors
+                        .borrow_mut()
+                        .errors_mut()
+                        .retain(|key, _| key != &name);
+                    for (field_name, error) in errors.errors() {
+                        if field_name == &name {
+                            cloned_validation_errors
+                                .borrow_mut()
+                                .errors_mut()
+                                .insert(field_name, error.clone());
+                        }
+                    }
+                }
+            }
+        })
+    };
+
+    let handle_email_input = get_input_callback("email", form.clone());
+    let handle_password_input = get_input_callback("password", form.clone());
+
+    let on_submit = {
+        console::debug_1(&"register_page: on_submit".into());
+        let cloned_form = form.clone();
+        console::debug_1(
+            &format!("register_page: on_submit: cloned_form: {cloned_form:?}",).into(),
+        );
+        let cloned_validation_errors = validation_errors.clone();
+        let store_dispatch = dispatch.clone();
+        let store_dispatch2 = dispatch2.clone();
+        let cloned_navigator = navigator.clone();
+
+        let cloned_email_input_ref = email_input_ref.clone();
+        let cloned_password_input_ref = password_input_ref.clone();
+
+        Callback::from(move |event: SubmitEvent| {
+            console::debug_1(&"register_page: on_submit Callback".into());
+            event.prevent_default();
+
+            let dispatch = store_dispatch.clone();
+            let dispatch2 = store_dispatch2.clone();
+            let form = cloned_form.clone();
+            let validation_errors = cloned_validation_errors.clone();
+            let navigator = cloned_navigator.clone();
+
+            let email_input_ref = cloned_email_input_ref.clone();
+            let password_input_ref = cloned_password_input_ref.clone();
+
+            spawn_local(async move {
+                console::debug_1(&"register_page: on_submit Callback spawn_local".into());
+                match form.validate() {
+                    Ok(()) => {
+                        let form_data = form.deref().clone();
+                        set_page_loading(true, &dispatch);
+
+                        let email_input = email_input_ref.cast::<HtmlInputElement>().unwrap();
+                        let password_input = password_input_ref.cast::<HtmlInputElement>().unwrap();
+
+                        email_input.set_value("");
+                        password_input.set_value("");
+
+                        let form_json = serde_json::to_string(&form_data).unwrap();
+                        console::debug_1(&"register_page: on_submit Callback form_json".into());
+                        let res = api_register_user(&form_json).await;
+                        match res {
+                            Ok(res) => {
+                                // to get the actual role; cf `User::is_organiser`
+                                let user = match api_user_info(&res.access_token).await {
+                                    Ok(user) => user,
+                                    Err(err) => {
+                                        console::warn_1(&format!("api_user_info: {err}").into());
+                                        User {
+                                            username: form_data.email,
+                                            role: "member".to_string(),
+                                        }
+                                    }
+                                };
+                                set_page_loading(false, &dispatch);
+                                set_auth_user(
+                                    Some(user),
+                                    Some(res.access_token),
+                                    Some(res.refresh_token),
+                                    &dispatch2,
+                                );
+                                navigator.push(&router::Route::HomePage);
+                            }
+                            Err(e) => {
+                                set_page_loading(false, &dispatch);
+                                set_show_alert(e.to_string(), &dispatch);
+                            }
+                        };
+                    }
+                    Err(e) => {
+                        validation_errors.set(Rc::new(RefCell::new(e)));
+                    }
+                }
+            });
+        })
+    };
+
+    html! {
+    <section class="bg-ct-blue-600 min-h-screen grid place-items-center">
+      <div class="w-full">
+        <h1 class="text-4xl xl:text-6xl text-center font-[600] text-ct-yellow-600 mb-4">
+          {"Welcome"}
+        </h1>
+        <h2 class="text-lg text-center mb-4 text-ct-dark-200">
+          {"Create an account"}
+        </h2>
+          <form
+            // TODO onsubmit? why is this not called?
+            onsubmit={on_submit}
+            class="max-w-md w-full mx-auto overflow-hidden shadow-lg bg-ct-dark-200 rounded-2xl p-8 space-y-5"
+          >
+            <FormInput label="Email" name="email" input_type="text" input_ref={email_input_ref} handle_onchange={handle_email_input} errors={&*validation_errors} handle_on_input_blur={validate_input_on_blur.clone()} />
+            <FormInput label="Password" name="password" input_type="password" input_ref={password_input_ref} handle_onchange={handle_password_input} errors={&*validation_errors} handle_on_input_blur={validate_input_on_blur.clone()}/>
+
+            <LoadingButton
+              loading={store.page_loading}
+              text_color={Some("text-ct-blue-600".to_string())}
+            >
+              {"Sign Up"}
+            </LoadingButton>
+
+            <span class="block">
+              {"Already have an account?"} {" "}
+              <Link<router::Route> to={router::Route::LoginPage} classes="text-ct-blue-600">{ "Login Here" }</Link<router::Route>>
+            </span>
+          </form>
+      </div>
+    </section>
+    }
+}
@@ -0,0 +1,178 @@
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{console, HtmlInputElement};
+use yew::prelude::*;
+use yew_hooks::{use_async_with_options, UseAsyncOptions};
+use yew_router::prelude::*;
+use yewdux::use_store;
+
+use crate::api::room_api::{api_create_room, api_join_room, api_leave_room, api_list_rooms};
+use crate::api::types::RoomLead;
+use crate::router::Route;
+use crate::store::{set_page_loading, set_room_id, set_show_alert, PersistentStore, Store};
+
+/// List the rooms; and allow to join/leave/create one
+/// cf `server/src/api_room.rs`
+#[function_component(RoomsComponent)]
+pub(crate) fn rooms_component() -> Html {
+    let (persistent_store, persistent_dispatch) = use_store::<PersistentStore>();
+    let (_store, dispatch) = use_store::<Store>();
+    let navigator = use_navigator().unwrap();
+    let new_room_input_ref = use_node_ref();
+
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let current_room_id = persistent_store.room_id;
+
+    let rooms_response_state = {
+        let token = token.clone();
+        use_async_with_options(
+            async move { api_list_rooms(&token).await },
+            UseAsyncOptions::enable_auto(),
+        )
+    };
+
+    let on_join = {
+        let token = token.clone();
+        let dispatch = dispatch.clone();
+        let persistent_dispatch = persistent_dispatch.clone();
+        let navigator = navigator.clone();
+        Callback::from(move |room_id: i64| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            let persistent_dispatch = persistent_dispatch.clone();
+            let navigator = navigator.clone();
+            spawn_local(async move {
+                set_page_loading(true, &dispatch);
+                match api_join_room(&token, room_id).await {
+                    Ok(()) => {
+                        // the positions are per room: DO NOT keep the ones from the previous room
+                        dispatch.reduce_mut(|store| {
+                            store.locations.clear();
+                            store.presence.clear();
+                            store.sharing.clear();
+                            store.off_route.clear();
+                            store.lead = RoomLead::default();
+                            store.privacy = None;
+                            store.track = None;
+                            store.geofences.clear();
+                        });
+                        set_room_id(Some(room_id), &persistent_dispatch);
+                        navigator.push(&Route::HomePage);
+                    }
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+                set_page_loading(false, &dispatch);
+            });
+        })
+    };
+
+    let on_leave = {
+        let token = token.clone();
+        let dispatch = dispatch.clone();
+        let persistent_dispatch = persistent_dispatch.clone();
+        Callback::from(move |room_id: i64| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            let persistent_dispatch = persistent_dispatch.clone();
+            spawn_local(async move {
+                match api_leave_room(&token, room_id).await {
+                    Ok(()) => {
+                        dispatch.reduce_mut(|store| {
+                            store.locations.clear();
+                            store.presence.clear();
+                            store.sharing.clear();
+                            store.off_route.clear();
+                            store.lead = RoomLead::default();
+                            store.privacy = None;
+                            store.track = None;
+                            store.geofences.clear();
+                        });
+                        set_room_id(None, &persistent_dispatch);
+                    }
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+            });
+        })
+    };
+
+    let on_create = {
+        let token = token.clone();
+        let dispatch = dispatch.clone();
+        let new_room_input_ref = new_room_input_ref.clone();
+        let rooms_response_state = rooms_response_state.clone();
+        Callback::from(move |event: SubmitEvent| {
+            event.prevent_default();
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            let rooms_response_state = rooms_response_state.clone();
+            let Some(input) = new_room_input_ref.cast::<HtmlInputElement>() else {
+                return;
+            };
+            let name = input.value();
+            input.set_value("");
+            spawn_local(async move {
+                match api_create_room(&token, &name).await {
+                    Ok(room) => {
+                        console::log_1(&format!("RoomsComponent: created room: {room:?}").into());
+                        rooms_response_state.run();
+                    }
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="flex flex-col min-h-screen flex-grow items-center space-x-4">
+        <table class="table-auto">
+            <thead>
+                <tr>
+                    <th>{"Room"}</th>
+                    <th>{"Created by"}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {
+                    if let Some(rooms) = &rooms_response_state.data {
+                        rooms.rooms.iter().map(|room| {
+                            let room_id = room.id;
+                            let on_join = on_join.clone();
+                            let on_leave = on_leave.clone();
+                            html! {
+                                <tr>
+                                    <td>{&room.name}</td>
+                                    <td>{&room.created_by}</td>
+                                    <td>
+                                    if current_room_id == Some(room_id) {
+                                        <button onclick={move |_| on_leave.emit(room_id)}>{"Leave"}</button>
+                                    } else {
+                                        <button onclick={move |_| on_join.emit(room_id)}>{"Join"}</button>
+                                    }
+                                    </td>
+                                </tr>
+                            }
+                        }).collect::<Html>()
+                    }
+                    else if let Some(error) = &rooms_response_state.error {
+                        console::error_1(&format!("api_list_rooms error: {error:?}",).into());
+                        set_page_loading(false, &dispatch);
+                        set_show_alert(error.to_string(), &dispatch);
+                        html! { format!("Error: {}", error) }
+                    }
+                    else if rooms_response_state.loading {
+                        html! { "Loading..." }
+                    }
+                    else {
+                        html! {}
+                    }
+                }
+            </tbody>
+        </table>
+
+        <form onsubmit={on_create} class="mt-4">
+            <input type="text" placeholder="New room name" ref={new_room_input_ref} />
+            <button type="submit">{"Create"}</button>
+        </form>
+        </div>
+    }
+}
@@ -0,0 +1,133 @@
+use wasm_bindgen_futures::spawn_local;
+use web_sys::console;
+use yew::prelude::*;
+use yew_hooks::{use_async_with_options, UseAsyncOptions};
+use yewdux::use_store;
+
+use crate::api::route_api::{api_delete_route, api_list_routes, api_set_room_route};
+use crate::api::types::Route;
+use crate::store::{set_page_loading, set_show_alert, PersistentStore, Store};
+
+/// A summary of the statistics of a route, eg "+350 m / -340 m, 120-480 m, 2h05, 1234 points"
+/// cf `server/src/route_stats.rs`
+fn route_summary(route: &Route) -> String {
+    let mut summary = vec![];
+    if let (Some(gain), Some(loss)) = (route.elevation_gain, route.elevation_loss) {
+        summary.push(format!("+{gain:.0} m / -{loss:.0} m"));
+    }
+    if let (Some(min), Some(max)) = (route.min_elevation, route.max_elevation) {
+        summary.push(format!("{min:.0}-{max:.0} m"));
+    }
+    if let Some(duration) = route.duration {
+        let minutes = duration / 60_000;
+        summary.push(format!("{}h{:02}", minutes / 60, minutes % 60));
+    }
+    summary.push(format!("{} points", route.point_count));
+    summary.join(", ")
+}
+
+/// The route library; organisers only
+/// Allow to display a route in the current room, or to delete it
+/// Each route comes with a summary of its statistics, computed on upload
+/// cf `server/src/api_route.rs`
+#[function_component(RoutesComponent)]
+pub(crate) fn routes_component() -> Html {
+    let (persistent_store, _persistent_dispatch) = use_store::<PersistentStore>();
+    let (_store, dispatch) = use_store::<Store>();
+
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let current_room_id = persistent_store.room_id;
+
+    let routes_response_state = {
+        let token = token.clone();
+        use_async_with_options(
+            async move { api_list_routes(&token).await },
+            UseAsyncOptions::enable_auto(),
+        )
+    };
+
+    let on_activate = {
+        let token = token.clone();
+        let dispatch = dispatch.clone();
+        Callback::from(move |route_id: i64| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            let Some(room_id) = current_room_id else {
+                set_show_alert("Join a room first".to_string(), &dispatch);
+                return;
+            };
+            spawn_local(async move {
+                // the members of the room(including us) are notified by the server
+                if let Err(e) = api_set_room_route(&token, room_id, Some(route_id)).await {
+                    set_show_alert(e, &dispatch);
+                }
+            });
+        })
+    };
+
+    let on_delete = {
+        let dispatch = dispatch.clone();
+        let routes_response_state = routes_response_state.clone();
+        Callback::from(move |route_id: i64| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            let routes_response_state = routes_response_state.clone();
+            spawn_local(async move {
+                match api_delete_route(&token, route_id).await {
+                    Ok(()) => routes_response_state.run(),
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="flex flex-col min-h-screen flex-grow items-center space-x-4">
+        <table class="table-auto">
+            <thead>
+                <tr>
+                    <th>{"Route"}</th>
+                    <th>{"Uploaded by"}</th>
+                    <th>{"Distance (km)"}</th>
+                    <th>{"Summary"}</th>
+                    <th></th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {
+                    if let Some(routes) = &routes_response_state.data {
+                        routes.routes.iter().map(|route| {
+                            let route_id = route.id;
+                            let on_activate = on_activate.clone();
+                            let on_delete = on_delete.clone();
+                            html! {
+                                <tr>
+                                    <td>{&route.name}</td>
+                                    <td>{&route.uploaded_by}</td>
+                                    <td>{format!("{:.1}", route.distance / 1000.0)}</td>
+                                    <td>{route_summary(route)}</td>
+                                    <td><button onclick={move |_| on_activate.emit(route_id)}>{"Display in room"}</button></td>
+                                    <td><button onclick={move |_| on_delete.emit(route_id)}>{"Delete"}</button></td>
+                                </tr>
+                            }
+                        }).collect::<Html>()
+                    }
+                    else if let Some(error) = &routes_response_state.error {
+                        console::error_1(&format!("api_list_routes error: {error:?}",).into());
+                        set_page_loading(false, &dispatch);
+                        set_show_alert(error.to_string(), &dispatch);
+                        html! { format!("Error: {}", error) }
+                    }
+                    else if routes_response_state.loading {
+                        html! { "Loading..." }
+                    }
+                    else {
+                        html! {}
+                    }
+                }
+            </tbody>
+        </table>
+        </div>
+    }
+}
@@ -0,0 +1,62 @@
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yewdux::use_store;
+
+use crate::api::track_api::api_get_user_track;
+use crate::store::{set_show_alert, set_track, PersistentStore, Store};
+
+/// List the users currently seen in the room; and allow to display the history of one of them
+/// The track itself is drawn by `MapComponent`
+#[function_component(TrackComponent)]
+pub(crate) fn track_component() -> Html {
+    let (persistent_store, _persistent_dispatch) = use_store::<PersistentStore>();
+    let (store, dispatch) = use_store::<Store>();
+
+    let token = persistent_store.token.clone().unwrap_or_default();
+    let current_track_username = store.track.as_ref().map(|(username, _)| username.clone());
+
+    let on_show = {
+        let dispatch = dispatch.clone();
+        Callback::from(move |username: String| {
+            let token = token.clone();
+            let dispatch = dispatch.clone();
+            spawn_local(async move {
+                match api_get_user_track(&token, &username, None, None).await {
+                    Ok(lines) => set_track(Some((username, lines)), &dispatch),
+                    Err(e) => set_show_alert(e, &dispatch),
+                }
+            });
+        })
+    };
+
+    let on_hide = {
+        let dispatch = dispatch.clone();
+        Callback::from(move |()| set_track(None, &dispatch))
+    };
+
+    let mut usernames: Vec<&String> = store.locations.keys().collect();
+    usernames.sort();
+
+    html! {
+        <ul>
+            {
+                usernames.into_iter().map(|username| {
+                    let username = username.clone();
+                    let is_shown = current_track_username.as_ref() == Some(&username);
+                    let on_show = on_show.clone();
+                    let on_hide = on_hide.clone();
+                    html! {
+                        <li>
+                            {&username}
+                            if is_shown {
+                                <button onclick={move |_| on_hide.emit(())}>{"Hide track"}</button>
+                            } else {
+                                <button onclick={move |_| on_show.emit(username.clone())}>{"Show track"}</button>
+                            }
+                        </li>
+                    }
+                }).collect::<Html>()
+            }
+        </ul>
+    }
+}
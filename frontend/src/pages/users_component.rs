@@ -29,7 +29,7 @@ pub(crate) fn users_component() -> Html {
             <thead>
                 <tr>
                     <th>{"Username"}</th>
-                    <th>{"Superuser?"}</th>
+                    <th>{"Role"}</th>
                 </tr>
             </thead>
             <tbody>
@@ -38,7 +38,7 @@ pub(crate) fn users_component() -> Html {
                         users.users.iter().map(|user| html! {
                             <tr>
                                 <td>{&user.username}</td>
-                                <td>{&user.is_super_user}</td>
+                                <td>{&user.role}</td>
                             </tr>
                         }).collect::<Html>()
                     }
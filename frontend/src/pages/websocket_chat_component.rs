@@ -3,6 +3,7 @@
 // TODO maybe switch to tungstenite cf https://github.com/tokio-rs/axum/blob/main/examples/websockets/src/client.rs
 // b/c the whole "initial delay" sucks...
 // https://github.com/snapview/tokio-tungstenite/issues/278 related ?
+use protocol::WsMessage;
 use yew::prelude::*;
 use yew_hooks::prelude::*;
 use yewdux::use_store;
@@ -14,6 +15,7 @@ pub(crate) fn websocket_chat_component() -> Html {
     let history = use_list(vec![]);
     let (store, _dispatch) = use_store::<PersistentStore>();
     let token = store.token.clone().unwrap_or_default();
+    let room_id = store.room_id.unwrap_or_default();
 
     // TODO?
     // if auth_user.is_none() {
@@ -23,11 +25,19 @@ pub(crate) fn websocket_chat_component() -> Html {
     let ws = {
         let history = history.clone();
         let ws_handle: UseWebSocketHandle = use_websocket_with_options(
-            format!("{WS_ROOT}?token={token}",),
+            format!("{WS_ROOT}?token={token}&room={room_id}",),
             UseWebSocketOptions {
                 // Receive message by callback `onmessage`.
                 onmessage: Some(Box::new(move |message| {
-                    history.push(format!("[recv]: {message}",));
+                    let line = match WsMessage::decode(&message) {
+                        Ok(WsMessage::Chat { username, text }) => format!("{username}: {text}"),
+                        Ok(WsMessage::Join { username }) => format!("{username} joined."),
+                        Ok(WsMessage::Leave { username }) => format!("{username} left."),
+                        Ok(WsMessage::Error { message }) => format!("[error]: {message}"),
+                        Ok(other) => format!("[recv]: {other:?}"),
+                        Err(err) => format!("[error]: {err}"),
+                    };
+                    history.push(line);
                 })),
                 manual: Some(false),
                 protocols: Some(vec!["chat".to_string()]),
@@ -52,8 +62,13 @@ pub(crate) fn websocket_chat_component() -> Html {
         let ws = ws.clone();
         // let history = history.clone();
         Callback::from(move |_| {
-            let message = "Hello, world!".to_string();
-            ws.send(message.clone());
+            let message = WsMessage::Chat {
+                username: String::new(),
+                text: "Hello, world!".to_string(),
+            };
+            if let Ok(message) = message.encode() {
+                ws.send(message);
+            }
             // history.push(format!("[send]: {}", message));
         })
     };
@@ -1,4 +1,6 @@
 use leaflet::LatLng;
+use protocol::{DecodeError, GeofenceTransition, Position, SharingMode, WsMessage};
+use wasm_bindgen_futures::spawn_local;
 use web_sys::console;
 /// `https://chat.openai.com`
 /// See also `https://github.com/jetli/yew-hooks/blob/e31debde4ce3c8c524c56303255baa833a0f0b79/crates/yew-hooks/src/hooks/use_websocket.rs#L163`
@@ -7,19 +9,26 @@ use web_sys::console;
 // https://github.com/snapview/tokio-tungstenite/issues/278 related ?
 use yew::prelude::*;
 use yew_hooks::prelude::*;
-use yewdux::use_store;
+use yewdux::{use_store, Dispatch};
 
 use crate::{
+    api::room_api::{api_get_lead, api_get_room_presence},
     app::WS_ROOT,
-    store::{PersistentStore, Store},
+    pages::map_component::{refresh_geofences, refresh_pois, refresh_route},
+    store::{set_show_alert, PersistentStore, Store},
 };
 
 #[function_component(WebSocketGeoLocComponent)]
 pub(crate) fn websocket_geolocation_component() -> Html {
     let (store, _dispatch) = use_store::<PersistentStore>();
     let token = store.token.clone().unwrap_or_default();
+    let room_id = store.room_id.unwrap_or_default();
 
-    let (_store, dispatch) = use_store::<Store>();
+    let (store, dispatch) = use_store::<Store>();
+    // cf `PrivacyComponent`; NOTE: the server enforces it anyway, so this only saves some traffic
+    let is_sharing = store
+        .privacy
+        .map_or(true, |privacy| privacy.mode == SharingMode::Sharing);
 
     // Create a state for the geolocation status
     let geolocation_state = use_state(|| None);
@@ -41,39 +50,153 @@ pub(crate) fn websocket_geolocation_component() -> Html {
 
     let ws = {
         // let history = history.clone();
+        let token_copy = token.clone();
         use_websocket_with_options(
-            format!("{WS_ROOT}?token={token}",),
+            format!("{WS_ROOT}?token={token}&room={room_id}",),
             UseWebSocketOptions {
                 // Receive message by callback `onmessage`.
                 onmessage: Some(Box::new(move |message| {
-                    // Parse the message eg: "aaaaaaaaaaaaa: 48.8354,2.3203"
-                    let username_and_lat_lng_str = message.split(": ").collect::<Vec<_>>();
-                    // NOTE: can only be "aaaaaaa joined" message, in which case we do nothing
-                    if username_and_lat_lng_str.len() == 2 {
-                        let lat_lng_str = username_and_lat_lng_str.last().unwrap();
-                        let username = *username_and_lat_lng_str.first().unwrap();
-                        // we SHOULD have eg "48.8354,2.3203"
-                        let lat_lng_str = lat_lng_str.split(',').collect::<Vec<_>>();
-                        // let lat_str = lat_lng_str.first().unwrap();
-                        let (lat_str, lng_str) = (lat_lng_str[0], lat_lng_str[1]);
-                        let lat: f64 = lat_str.parse().unwrap();
-                        let lng: f64 = lng_str.parse().unwrap();
-                        console::log_1(
-                            &format!(
-                                "WebSocketGeoLocComponent: [recv]: username: {username} at ({lat_str},{lng_str})",
-                            )
-                            .into(),
-                        );
+                    match WsMessage::decode(&message) {
+                        Ok(WsMessage::Positions { positions }) => {
+                            console::log_1(
+                                &format!(
+                                    "WebSocketGeoLocComponent: [recv]: {} positions",
+                                    positions.len()
+                                )
+                                .into(),
+                            );
 
-                        // update the location for this user
-                        // it will be used in frontend/src/pages/map_component.rs
-                        dispatch.reduce_mut(|store| {
-                            store.locations.insert(username.to_string(), (lat, lng));
-                        });
-                    } else {
-                        console::log_1(
-                            &format!("WebSocketGeoLocComponent: [recv]: {message}",).into(),
-                        );
+                            // update the location of these users
+                            // it will be used in frontend/src/pages/map_component.rs
+                            dispatch.reduce_mut(|store| {
+                                for position in positions {
+                                    // NOTE: the snapshot may be more recent than a position received in between
+                                    let is_more_recent = store
+                                        .locations
+                                        .get(&position.username)
+                                        .map_or(true, |last| last.timestamp <= position.timestamp);
+                                    if is_more_recent {
+                                        store.locations.insert(position.username.clone(), position);
+                                    }
+                                }
+                            });
+                        }
+                        Ok(WsMessage::Snapshot { positions }) => {
+                            // the first frame: replace whatever we had eg before a reconnection
+                            // NOTE: also sent again when we were too slow, ie we may have missed some messages
+                            dispatch.reduce_mut(|store| {
+                                store.locations = positions
+                                    .into_iter()
+                                    .map(|position| (position.username.clone(), position))
+                                    .collect();
+                            });
+                            // and the presence of everyone; the changes are then pushed, cf `WsMessage::Presence`
+                            refresh_presence(token_copy.clone(), room_id, dispatch.clone());
+                            refresh_lead(token_copy.clone(), room_id, dispatch.clone());
+                            // eg a `WsMessage::RouteUpdated` that was missed
+                            refresh_route(token_copy.clone(), room_id, dispatch.clone());
+                            refresh_geofences(token_copy.clone(), room_id, dispatch.clone());
+                            refresh_pois(token_copy.clone(), room_id, dispatch.clone());
+                        }
+                        Ok(WsMessage::Presence {
+                            username, status, ..
+                        }) => {
+                            // it will be used in frontend/src/pages/map_component.rs
+                            dispatch.reduce_mut(|store| {
+                                store.presence.insert(username, status);
+                            });
+                        }
+                        Ok(WsMessage::Sharing { username, mode }) => {
+                            dispatch.reduce_mut(|store| {
+                                // a hidden user MUST disappear from the map
+                                if mode == SharingMode::Hidden {
+                                    store.locations.remove(&username);
+                                }
+                                store.sharing.insert(username, mode);
+                            });
+                        }
+                        Ok(WsMessage::RouteUpdated) => {
+                            // the organiser uploaded a new route; fetch it
+                            // it will be drawn in frontend/src/pages/map_component.rs
+                            // NOTE: the server also resets who is off route
+                            dispatch.reduce_mut(|store| store.off_route.clear());
+                            refresh_route(token_copy.clone(), room_id, dispatch.clone());
+                            // and with it, its geofences and points of interest
+                            refresh_geofences(token_copy.clone(), room_id, dispatch.clone());
+                            refresh_pois(token_copy.clone(), room_id, dispatch.clone());
+                        }
+                        Ok(WsMessage::OffRoute {
+                            username,
+                            is_off_route,
+                            distance,
+                            ..
+                        }) => {
+                            // it will be used in frontend/src/pages/map_component.rs
+                            dispatch.reduce_mut(|store| {
+                                if is_off_route {
+                                    store.off_route.insert(username, distance);
+                                } else {
+                                    store.off_route.remove(&username);
+                                }
+                            });
+                        }
+                        Ok(WsMessage::Lead { leader, sweeper }) => {
+                            // NOTE: the server starts over with the gap
+                            dispatch.reduce_mut(|store| {
+                                store.lead.leader = leader;
+                                store.lead.sweeper = sweeper;
+                                store.lead.gap = None;
+                                store.lead.is_spread = false;
+                            });
+                        }
+                        Ok(WsMessage::GroupSpread { gap, is_spread, .. }) => {
+                            dispatch.reduce_mut(|store| {
+                                store.lead.gap = Some(gap);
+                                store.lead.is_spread = is_spread;
+                            });
+                            let message = if is_spread {
+                                format!(
+                                    "The group is spread out: the leader is {:.1} km ahead of the sweeper",
+                                    gap / 1000.0
+                                )
+                            } else {
+                                "The group is back together".to_string()
+                            };
+                            set_show_alert(message, &dispatch);
+                        }
+                        Ok(WsMessage::GeofencesUpdated) => {
+                            refresh_geofences(token_copy.clone(), room_id, dispatch.clone());
+                        }
+                        Ok(WsMessage::PoisUpdated) => {
+                            refresh_pois(token_copy.clone(), room_id, dispatch.clone());
+                        }
+                        Ok(WsMessage::Geofence {
+                            username,
+                            name,
+                            transition,
+                            ..
+                        }) => {
+                            let action = match transition {
+                                GeofenceTransition::Enter => "entered",
+                                GeofenceTransition::Exit => "left",
+                            };
+                            set_show_alert(format!("{username} {action} {name}"), &dispatch);
+                        }
+                        Ok(other) => {
+                            console::log_1(
+                                &format!("WebSocketGeoLocComponent: [recv]: {other:?}",).into(),
+                            );
+                        }
+                        // cf `PROTOCOL_VERSION`: this page is older(or newer) than the server
+                        Err(err @ DecodeError::UnsupportedVersion(_)) => {
+                            set_show_alert(format!("{err}: please reload the page"), &dispatch);
+                        }
+                        Err(err) => {
+                            console::error_1(
+                                &format!("WebSocketGeoLocComponent: [recv]: {err}: {message}",)
+                                    .into(),
+                            );
+                        }
                     }
                 })),
                 manual: Some(false),
@@ -101,6 +224,14 @@ pub(crate) fn websocket_geolocation_component() -> Html {
 
     // Use the effect hook to perform side effects when the geolocation state changes
     use_effect_with((geolocation.clone(),), move |(geolocation,)| {
+        // NOTE: the first render has no position yet; DO NOT send a bogus (0,0)
+        if geolocation.loading || geolocation.error.is_some() {
+            return;
+        }
+        if !is_sharing {
+            return;
+        }
+
         // Perform side effects when the position changes
         geolocation_state_clone.set(Some(LatLng::new(
             geolocation.latitude,
@@ -114,10 +245,25 @@ pub(crate) fn websocket_geolocation_component() -> Html {
             .into(),
         );
 
-        let message = format!("{},{}", geolocation.latitude, geolocation.longitude);
-        ws.send(message.clone());
-        // Return an effect cleanup function if needed
-        || {}
+        // NOTE: the server sets the username from the token
+        #[allow(clippy::cast_possible_truncation)]
+        let message = WsMessage::Position(Position {
+            username: String::new(),
+            lat: geolocation.latitude,
+            lng: geolocation.longitude,
+            accuracy: Some(geolocation.accuracy),
+            altitude: geolocation.altitude,
+            heading: geolocation.heading,
+            speed: geolocation.speed,
+            timestamp: geolocation.timestamp as i64,
+            progress: None,
+        });
+        match message.encode() {
+            Ok(message) => ws.send(message),
+            Err(err) => console::error_1(
+                &format!("websocket_geolocation_component: encode error: {err}").into(),
+            ),
+        }
     });
 
     html! {
@@ -136,6 +282,48 @@ pub(crate) fn websocket_geolocation_component() -> Html {
     }
 }
 
+/// Fetch the presence of every member of the room(cf `api_get_room_presence`), and replace the one in the store;
+/// along with what they share, and who is off route
+/// NOTE: NOT critical; the markers are simply NOT faded until the next `WsMessage::Presence`
+fn refresh_presence(token: String, room_id: i64, dispatch: Dispatch<Store>) {
+    spawn_local(async move {
+        match api_get_room_presence(&token, room_id).await {
+            Ok(room_presence) => dispatch.reduce_mut(|store| {
+                store.sharing = room_presence
+                    .users
+                    .iter()
+                    .map(|member| (member.username.clone(), member.sharing))
+                    .collect();
+                store.off_route = room_presence
+                    .users
+                    .iter()
+                    .filter_map(|member| Some((member.username.clone(), member.off_route?)))
+                    .collect();
+                store.presence = room_presence
+                    .users
+                    .into_iter()
+                    .map(|member| (member.username, member.status))
+                    .collect();
+            }),
+            Err(err) => console::warn_1(
+                &format!("WebSocketGeoLocComponent: could not get the presence: {err}").into(),
+            ),
+        }
+    });
+}
+
+/// Fetch the leader and the sweeper of the room; the changes are then pushed, cf `WsMessage::Lead`
+fn refresh_lead(token: String, room_id: i64, dispatch: Dispatch<Store>) {
+    spawn_local(async move {
+        match api_get_lead(&token, room_id).await {
+            Ok(lead) => dispatch.reduce_mut(|store| store.lead = lead),
+            Err(err) => console::warn_1(
+                &format!("WebSocketGeoLocComponent: could not get the lead: {err}").into(),
+            ),
+        }
+    });
+}
+
 // pub struct WebSocketGeoLocComponent {
 //     // link: ComponentLink<Self>,
 //     ws: WebSocket,
@@ -9,8 +9,9 @@ use crate::pages::{
     login_page::LoginPage,
     // TODO
     // profile_page::ProfilePage,
-    // TODO
-    // register_page::RegisterPage,
+    register_page::RegisterPage,
+    rooms_component::RoomsComponent,
+    routes_component::RoutesComponent,
     users_component::UsersComponent,
 };
 
@@ -18,9 +19,8 @@ use crate::pages::{
 pub enum Route {
     #[at("/")]
     HomePage,
-    // TODO
-    // #[at("/register")]
-    // RegisterPage,
+    #[at("/register")]
+    RegisterPage,
     #[at("/login")]
     LoginPage,
     // TODO
@@ -31,13 +31,17 @@ pub enum Route {
     NotFound,
     #[at("/users")]
     UsersComponent,
+    #[at("/rooms")]
+    RoomsComponent,
+    #[at("/routes")]
+    RoutesComponent,
 }
 
 #[allow(clippy::needless_pass_by_value)]
 pub fn switch(routes: Route) -> Html {
     match routes {
         Route::HomePage => html! {<HomePage/> },
-        // Route::RegisterPage => html! {<RegisterPage/> },
+        Route::RegisterPage => html! {<RegisterPage/> },
         Route::LoginPage => html! {<LoginPage/> },
         // Route::ProfilePage => html! {<ProfilePage/> },
         Route::NotFound => {
@@ -45,6 +49,8 @@ pub fn switch(routes: Route) -> Html {
             html! { <My404Page /> }
         }
         Route::UsersComponent => html! {<UsersComponent/> },
+        Route::RoomsComponent => html! {<RoomsComponent/> },
+        Route::RoutesComponent => html! {<RoutesComponent/> },
     }
 }
 
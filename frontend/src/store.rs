@@ -2,10 +2,11 @@ use std::collections::HashMap;
 
 /// `https://github.com/wpcodevo/rust-yew-signup-signin/blob/62e9186ba1ede01b6d13eeeac036bbd56a131e1e/src/store.rs`
 ///
+use protocol::{Position, PresenceStatus, SharingMode};
 use serde::{Deserialize, Serialize};
 use yewdux::prelude::*;
 
-use crate::api::types::User;
+use crate::api::types::{Geofence, Poi, Privacy, RoomLead, RouteProfile, User};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone)]
 pub struct AlertInput {
@@ -17,7 +18,29 @@ pub struct AlertInput {
 pub struct Store {
     pub page_loading: bool,
     pub alert_input: AlertInput,
-    pub locations: HashMap<String, (f64, f64)>,
+    /// The last known position of each user of the room; cf `WsMessage::Snapshot` and `WsMessage::Position`
+    pub locations: HashMap<String, Position>,
+    /// The presence of each member of the room; cf `api_get_room_presence` and `WsMessage::Presence`
+    pub presence: HashMap<String, PresenceStatus>,
+    /// What each member of the room shares; cf `api_get_room_presence` and `WsMessage::Sharing`
+    pub sharing: HashMap<String, SharingMode>,
+    /// The members that are off route, and their distance to it in meters; cf `api_get_room_presence` and `WsMessage::OffRoute`
+    pub off_route: HashMap<String, f64>,
+    /// The leader and the sweeper of the room, and the gap between them;
+    /// cf `api_get_lead`, `WsMessage::Lead` and `WsMessage::GroupSpread`
+    pub lead: RoomLead,
+    /// Our own privacy settings in the room; None until fetched, cf `PrivacyComponent`
+    pub privacy: Option<Privacy>,
+    /// The position history of a given user, one line of (lat, lng) per room; cf `api_get_user_track`
+    pub track: Option<(String, Vec<Vec<(f64, f64)>>)>,
+    /// The route uploaded by the organiser, one line of (lat, lng) per segment; cf `api_get_gpx`
+    pub route: Option<Vec<Vec<(f64, f64)>>>,
+    /// The geofences of the room and of its route; cf `api_get_room_geofences` and `WsMessage::GeofencesUpdated`
+    pub geofences: Vec<Geofence>,
+    /// The points of interest of the route; cf `api_get_room_pois` and `WsMessage::PoisUpdated`
+    pub pois: Vec<Poi>,
+    /// The elevation profile of the route; None if there is no active route; cf `ProfileComponent`
+    pub profile: Option<RouteProfile>,
 }
 
 /// We split the "Store" in two: a part that is in memory only; and this: that is persisted with local storage (cookies)
@@ -27,6 +50,10 @@ pub struct Store {
 pub struct PersistentStore {
     pub auth_user: Option<User>,
     pub token: Option<String>,
+    /// Used to renew `token` before it expires; cf `api_refresh_token`
+    pub refresh_token: Option<String>,
+    /// The room currently joined; cf `server/src/api_room.rs`
+    pub room_id: Option<i64>,
 }
 
 pub fn set_page_loading(loading: bool, dispatch: &Dispatch<Store>) {
@@ -38,11 +65,57 @@ pub fn set_page_loading(loading: bool, dispatch: &Dispatch<Store>) {
 pub fn set_auth_user(
     user: Option<User>,
     token: Option<String>,
+    refresh_token: Option<String>,
     dispatch: &Dispatch<PersistentStore>,
 ) {
     dispatch.reduce_mut(move |store| {
         store.auth_user = user;
         store.token = token;
+        store.refresh_token = refresh_token;
+    });
+}
+
+/// After a `api_refresh_token`: the user does NOT change
+pub fn set_tokens(token: String, refresh_token: String, dispatch: &Dispatch<PersistentStore>) {
+    dispatch.reduce_mut(move |store| {
+        store.token = Some(token);
+        store.refresh_token = Some(refresh_token);
+    });
+}
+
+pub fn set_room_id(room_id: Option<i64>, dispatch: &Dispatch<PersistentStore>) {
+    dispatch.reduce_mut(move |store| {
+        store.room_id = room_id;
+    });
+}
+
+pub fn set_track(track: Option<(String, Vec<Vec<(f64, f64)>>)>, dispatch: &Dispatch<Store>) {
+    dispatch.reduce_mut(move |store| {
+        store.track = track;
+    });
+}
+
+pub fn set_route(route: Option<Vec<Vec<(f64, f64)>>>, dispatch: &Dispatch<Store>) {
+    dispatch.reduce_mut(move |store| {
+        store.route = route;
+    });
+}
+
+pub fn set_geofences(geofences: Vec<Geofence>, dispatch: &Dispatch<Store>) {
+    dispatch.reduce_mut(move |store| {
+        store.geofences = geofences;
+    });
+}
+
+pub fn set_pois(pois: Vec<Poi>, dispatch: &Dispatch<Store>) {
+    dispatch.reduce_mut(move |store| {
+        store.pois = pois;
+    });
+}
+
+pub fn set_profile(profile: Option<RouteProfile>, dispatch: &Dispatch<Store>) {
+    dispatch.reduce_mut(move |store| {
+        store.profile = profile;
     });
 }
 
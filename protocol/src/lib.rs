@@ -0,0 +1,474 @@
+//! The WebSocket wire protocol, shared by `server/src/ws_handler.rs` and the frontend.
+//!
+//! Every frame is a JSON object tagged with its "type", and carrying the protocol version "v"
+//! eg `{"v":2,"type":"position","username":"aaa","lat":48.8354,"lng":2.3203,"timestamp":1708363750199}`
+#![deny(elided_lifetimes_in_paths)]
+#![warn(clippy::suspicious)]
+#![warn(clippy::complexity)]
+#![warn(clippy::perf)]
+#![warn(clippy::style)]
+#![warn(clippy::pedantic)]
+#![warn(clippy::expect_used)]
+#![warn(clippy::panic)]
+#![warn(clippy::unwrap_used)]
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// MUST be bumped on every breaking change of `WsMessage`
+/// - 2: the server broadcasts the positions in batches, cf `WsMessage::Positions`
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Max length(in bytes) of a chat message
+pub const MAX_CHAT_LENGTH: usize = 1000;
+
+/// A position, as sent by the browser Geolocation API
+/// cf `https://developer.mozilla.org/en-US/docs/Web/API/GeolocationCoordinates`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    /// NOTE: ignored when sent by a client; the server ALWAYS sets it from the auth token
+    #[serde(default)]
+    pub username: String,
+    /// in degrees
+    pub lat: f64,
+    /// in degrees
+    pub lng: f64,
+    /// in meters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy: Option<f64>,
+    /// in meters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    /// in degrees, clockwise from true north
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    /// in meters per second
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    /// milliseconds since UNIX epoch
+    pub timestamp: i64,
+    /// NOTE: ignored when sent by a client; set by the server when the room has an active route
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<RouteProgress>,
+}
+
+/// How far along the active route of the room a rider is; cf `Position::progress`
+/// and `GET /api/rooms/{room_id}/progress`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RouteProgress {
+    /// in meters, from the start of the route
+    pub distance_done: f64,
+    /// in meters, to the end of the route
+    pub distance_remaining: f64,
+    /// from 0 to 100
+    pub percent: f64,
+    /// in meters per second, along the route: over the last few minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    /// milliseconds since UNIX epoch: the estimated time of arrival, at `speed`; None if unknown, eg stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta: Option<i64>,
+}
+
+impl Position {
+    /// Check that all the fields are in their valid range
+    ///
+    /// # Errors
+    ///
+    /// A human readable message describing the first invalid field
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.lat.is_finite() || !(-90.0..=90.0).contains(&self.lat) {
+            return Err(format!("invalid lat: {}", self.lat));
+        }
+        if !self.lng.is_finite() || !(-180.0..=180.0).contains(&self.lng) {
+            return Err(format!("invalid lng: {}", self.lng));
+        }
+        if let Some(accuracy) = self.accuracy {
+            if !accuracy.is_finite() || accuracy < 0.0 {
+                return Err(format!("invalid accuracy: {accuracy}"));
+            }
+        }
+        if let Some(altitude) = self.altitude {
+            if !altitude.is_finite() {
+                return Err(format!("invalid altitude: {altitude}"));
+            }
+        }
+        if let Some(heading) = self.heading {
+            if !heading.is_finite() || !(0.0..=360.0).contains(&heading) {
+                return Err(format!("invalid heading: {heading}"));
+            }
+        }
+        if let Some(speed) = self.speed {
+            if !speed.is_finite() || speed < 0.0 {
+                return Err(format!("invalid speed: {speed}"));
+            }
+        }
+        if self.timestamp < 0 {
+            return Err(format!("invalid timestamp: {}", self.timestamp));
+        }
+
+        Ok(())
+    }
+}
+
+/// The presence of a user in a room; cf `WsMessage::Presence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    /// connected to the "geolocation" socket, and sending positions
+    Online,
+    /// still connected, but NOT sending positions anymore(eg the app is in the background)
+    Idle,
+    /// disconnected; or the connection was lost(no answer to the heartbeats)
+    Offline,
+}
+
+/// What a user shares with the others in a room; cf `WsMessage::Sharing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SharingMode {
+    /// the default: their positions are sent to the room
+    #[default]
+    Sharing,
+    /// their positions are NOT sent anymore; the others still see their last one
+    Paused,
+    /// "ghost mode": their positions are NOT sent anymore, and their last one is removed from the map of the others
+    Hidden,
+}
+
+/// A user entered or left a geofence; cf `WsMessage::Geofence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeofenceTransition {
+    Enter,
+    Exit,
+}
+
+/// All the messages that can go through the "chat" and "geolocation" websockets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsMessage {
+    /// server -> client: a user has connected to the room
+    Join { username: String },
+    /// server -> client: a user has disconnected from the room
+    Leave { username: String },
+    /// client -> server on the "geolocation" socket; then broadcast to the room, cf `Positions`
+    Position(Position),
+    /// server -> client on the "geolocation" socket: the positions received since the previous frame;
+    /// only the last one of each user, ordered by username
+    /// NOTE: they are batched so that each client receives about one frame per second, whatever the size of the room
+    Positions { positions: Vec<Position> },
+    /// client -> server on the "chat" socket; then broadcast to the room
+    Chat {
+        /// NOTE: ignored when sent by a client; the server ALWAYS sets it from the auth token
+        #[serde(default)]
+        username: String,
+        text: String,
+    },
+    /// server -> client: the last frame sent by this client was rejected
+    Error { message: String },
+    /// server -> client on the "geolocation" socket: the route of the room changed; fetch it with `GET /api/rooms/{room_id}/route`
+    RouteUpdated,
+    /// server -> client on the "geolocation" socket: ALWAYS the first frame; the last known position
+    /// of each user of the room, so that the map is NOT empty until everyone moves again
+    /// It is sent again when the client was too slow and some messages were dropped("resync");
+    /// so the client SHOULD also refresh what it may have missed, eg the route.
+    /// NOTE: some may be old; use `Position::timestamp` to tell
+    Snapshot { positions: Vec<Position> },
+    /// server -> client on the "geolocation" socket: the presence of a user changed
+    /// cf `GET /api/rooms/{room_id}/presence` for the presence of everyone
+    Presence {
+        username: String,
+        status: PresenceStatus,
+        /// milliseconds since UNIX epoch: the last time the server heard from this user
+        last_seen: i64,
+    },
+    /// server -> client on the "geolocation" socket: a user changed what they share(cf `PUT /api/rooms/{room_id}/privacy`)
+    /// When `SharingMode::Hidden`, the client MUST remove their marker.
+    Sharing { username: String, mode: SharingMode },
+    /// server -> client on the "geolocation" socket: a user entered or left a geofence of the room,
+    /// eg reached a checkpoint; cf `GET /api/rooms/{room_id}/geofences/events` for the history
+    Geofence {
+        username: String,
+        geofence_id: i64,
+        name: String,
+        transition: GeofenceTransition,
+        /// milliseconds since UNIX epoch: the timestamp of the position that crossed it
+        timestamp: i64,
+    },
+    /// server -> client on the "geolocation" socket: the geofences of the room changed;
+    /// fetch them with `GET /api/rooms/{room_id}/geofences`
+    /// NOTE: NOT sent when the route of the room changes, cf `RouteUpdated`: they SHOULD be fetched again too
+    GeofencesUpdated,
+    /// server -> client on the "geolocation" socket: a user went further than the threshold from the active route
+    /// of the room(`is_off_route`); or came back to it. Only sent when it changes.
+    OffRoute {
+        username: String,
+        is_off_route: bool,
+        /// in meters: their distance to the route
+        distance: f64,
+        /// milliseconds since UNIX epoch: the timestamp of the position
+        timestamp: i64,
+    },
+    /// server -> client on the "geolocation" socket: an organiser designated the leader and the sweeper(last rider)
+    /// of the room; None if none. cf `GET /api/rooms/{room_id}/lead`
+    Lead {
+        leader: Option<String>,
+        sweeper: Option<String>,
+    },
+    /// server -> client on the "geolocation" socket: the gap along the route between the leader and the sweeper
+    /// went beyond the threshold(`is_spread`), ie the group SHOULD regroup; or it is regrouped. Only sent when it changes.
+    GroupSpread {
+        /// in meters: how far ahead of the sweeper the leader is
+        gap: f64,
+        is_spread: bool,
+        /// milliseconds since UNIX epoch: the timestamp of the position that changed it
+        timestamp: i64,
+    },
+    /// server -> client on the "geolocation" socket: the points of interest of the route of the room changed;
+    /// fetch them with `GET /api/rooms/{room_id}/pois`
+    /// NOTE: NOT sent when the route of the room changes, cf `RouteUpdated`: they SHOULD be fetched again too
+    PoisUpdated,
+}
+
+impl WsMessage {
+    /// Serialize into a versioned frame, ready to be sent
+    ///
+    /// # Errors
+    ///
+    /// cf `serde_json::to_string`; in practice this can not fail for this enum
+    pub fn encode(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&Frame {
+            v: PROTOCOL_VERSION,
+            message: self.clone(),
+        })
+    }
+
+    /// Parse a frame; and check its version
+    ///
+    /// # Errors
+    ///
+    /// - `DecodeError::Json` if this is not a valid frame
+    /// - `DecodeError::UnsupportedVersion` if the frame was sent using another version of the protocol
+    pub fn decode(text: &str) -> Result<WsMessage, DecodeError> {
+        let frame: Frame =
+            serde_json::from_str(text).map_err(|err| DecodeError::Json(err.to_string()))?;
+        if frame.v != PROTOCOL_VERSION {
+            return Err(DecodeError::UnsupportedVersion(frame.v));
+        }
+
+        Ok(frame.message)
+    }
+}
+
+/// What is actually sent on the wire
+#[derive(Debug, Serialize, Deserialize)]
+struct Frame {
+    v: u32,
+    #[serde(flatten)]
+    message: WsMessage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    Json(String),
+    UnsupportedVersion(u32),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Json(err) => write!(f, "malformed message: {err}"),
+            DecodeError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "unsupported protocol version: {v}, expected {PROTOCOL_VERSION}"
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::panic)]
+mod tests {
+    use super::*;
+
+    fn new_position() -> Position {
+        Position {
+            username: "aaa".to_string(),
+            lat: 48.8354,
+            lng: 2.3203,
+            accuracy: Some(12.5),
+            altitude: None,
+            heading: None,
+            speed: Some(3.2),
+            timestamp: 1_708_363_750_199,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let messages = vec![
+            WsMessage::Join {
+                username: "aaa".to_string(),
+            },
+            WsMessage::Leave {
+                username: "aaa".to_string(),
+            },
+            WsMessage::Position(new_position()),
+            WsMessage::Position(Position {
+                progress: Some(RouteProgress {
+                    distance_done: 1200.0,
+                    distance_remaining: 800.0,
+                    percent: 60.0,
+                    speed: None,
+                    eta: None,
+                }),
+                ..new_position()
+            }),
+            WsMessage::Chat {
+                username: "aaa".to_string(),
+                text: "hello world".to_string(),
+            },
+            WsMessage::Error {
+                message: "nope".to_string(),
+            },
+            WsMessage::RouteUpdated,
+            WsMessage::Snapshot {
+                positions: vec![new_position()],
+            },
+            WsMessage::Snapshot { positions: vec![] },
+            WsMessage::Positions {
+                positions: vec![new_position(), new_position()],
+            },
+            WsMessage::Presence {
+                username: "aaa".to_string(),
+                status: PresenceStatus::Idle,
+                last_seen: 1_708_363_750_199,
+            },
+            WsMessage::Sharing {
+                username: "aaa".to_string(),
+                mode: SharingMode::Hidden,
+            },
+            WsMessage::Geofence {
+                username: "aaa".to_string(),
+                geofence_id: 1,
+                name: "checkpoint 1".to_string(),
+                transition: GeofenceTransition::Enter,
+                timestamp: 1_708_363_750_199,
+            },
+            WsMessage::GeofencesUpdated,
+            WsMessage::OffRoute {
+                username: "aaa".to_string(),
+                is_off_route: true,
+                distance: 123.4,
+                timestamp: 1_708_363_750_199,
+            },
+            WsMessage::Lead {
+                leader: Some("aaa".to_string()),
+                sweeper: None,
+            },
+            WsMessage::GroupSpread {
+                gap: 2345.6,
+                is_spread: true,
+                timestamp: 1_708_363_750_199,
+            },
+            WsMessage::PoisUpdated,
+        ];
+
+        for message in messages {
+            let text = message.encode().unwrap();
+            assert_eq!(WsMessage::decode(&text).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_encode_position_format() {
+        let text = WsMessage::Position(new_position()).encode().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "v": 2,
+                "type": "position",
+                "username": "aaa",
+                "lat": 48.8354,
+                "lng": 2.3203,
+                "accuracy": 12.5,
+                "speed": 3.2,
+                "timestamp": 1_708_363_750_199_i64,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_client_position_without_username_ok() {
+        let res = WsMessage::decode(
+            r#"{"v":2,"type":"position","lat":48.8354,"lng":2.3203,"timestamp":1}"#,
+        )
+        .unwrap();
+
+        match res {
+            WsMessage::Position(position) => assert_eq!(position.username, ""),
+            other => panic!("expected a position but got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_malformed_should_fail() {
+        assert!(matches!(
+            WsMessage::decode("aaa: 48.8354,2.3203"),
+            Err(DecodeError::Json(_))
+        ));
+        assert!(matches!(
+            WsMessage::decode(r#"{"v":2,"type":"position","lat":"abc"}"#),
+            Err(DecodeError::Json(_))
+        ));
+        assert!(matches!(
+            WsMessage::decode(r#"{"v":2,"type":"unknown"}"#),
+            Err(DecodeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_wrong_version_should_fail() {
+        assert_eq!(
+            WsMessage::decode(r#"{"v":42,"type":"join","username":"aaa"}"#),
+            Err(DecodeError::UnsupportedVersion(42))
+        );
+        // before the batched positions
+        assert_eq!(
+            WsMessage::decode(r#"{"v":1,"type":"join","username":"aaa"}"#),
+            Err(DecodeError::UnsupportedVersion(1))
+        );
+    }
+
+    #[test]
+    fn test_position_validate() {
+        assert!(new_position().validate().is_ok());
+
+        let mut position = new_position();
+        position.lat = 91.0;
+        assert!(position.validate().is_err());
+
+        let mut position = new_position();
+        position.lng = -180.5;
+        assert!(position.validate().is_err());
+
+        let mut position = new_position();
+        position.lat = f64::NAN;
+        assert!(position.validate().is_err());
+
+        let mut position = new_position();
+        position.accuracy = Some(-1.0);
+        assert!(position.validate().is_err());
+
+        let mut position = new_position();
+        position.heading = Some(400.0);
+        assert!(position.validate().is_err());
+    }
+}
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS room (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    created_by TEXT NOT NULL
+);
+
+-- NOTE: `username` is NOT a foreign key on purpose: "anonymous" users(ie not in `user`) can also join a room
+CREATE TABLE IF NOT EXISTS room_member (
+    room_id INTEGER NOT NULL REFERENCES room(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    PRIMARY KEY (room_id, username)
+);
@@ -0,0 +1,16 @@
+-- cf `protocol::Position`
+CREATE TABLE IF NOT EXISTS position (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room_id INTEGER NOT NULL REFERENCES room(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    lat REAL NOT NULL,
+    lng REAL NOT NULL,
+    accuracy REAL,
+    altitude REAL,
+    heading REAL,
+    speed REAL,
+    -- milliseconds since UNIX epoch
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS position_username_timestamp ON position (username, timestamp);
@@ -0,0 +1,21 @@
+-- The routes uploaded by the organisers, cf `server/src/route_gpx.rs`
+CREATE TABLE IF NOT EXISTS route (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    uploaded_by TEXT NOT NULL,
+    -- milliseconds since UNIX epoch
+    uploaded_at INTEGER NOT NULL,
+    -- the original file, as uploaded
+    gpx BLOB NOT NULL,
+    geojson TEXT NOT NULL,
+    -- in meters
+    distance REAL NOT NULL,
+    -- bounding box, in degrees
+    min_lat REAL NOT NULL,
+    min_lng REAL NOT NULL,
+    max_lat REAL NOT NULL,
+    max_lng REAL NOT NULL
+);
+
+-- The route currently displayed to the members of a room
+ALTER TABLE room ADD COLUMN active_route_id INTEGER REFERENCES route(id) ON DELETE SET NULL;
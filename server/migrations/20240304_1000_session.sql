@@ -0,0 +1,17 @@
+-- One row per login; the access tokens carry the session id, cf `server/src/api_authorize_jwt.rs`
+-- A session is active if NOT revoked and NOT expired
+CREATE TABLE IF NOT EXISTS session (
+    -- random, cf `Claims::sid`
+    id TEXT PRIMARY KEY NOT NULL,
+    username TEXT NOT NULL,
+    -- the refresh token is only stored hashed; it is rotated on each `POST /refresh`
+    refresh_token_hash TEXT NOT NULL,
+    -- milliseconds since UNIX epoch
+    created_at INTEGER NOT NULL,
+    -- milliseconds since UNIX epoch; extended on each refresh
+    expires_at INTEGER NOT NULL,
+    -- milliseconds since UNIX epoch; set by `POST /logout`
+    revoked_at INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS session_username ON session (username);
@@ -0,0 +1,13 @@
+-- cf `server/src/role.rs`: "viewer", "member", "organiser" or "admin"
+-- The global role of a user; replaces `is_super_user`
+ALTER TABLE user ADD COLUMN role TEXT NOT NULL DEFAULT 'member';
+UPDATE user SET role = 'admin' WHERE is_super_user;
+ALTER TABLE user DROP COLUMN is_super_user;
+
+-- The role of a user inside a given room; the creator of a room is its organiser
+ALTER TABLE room_member ADD COLUMN role TEXT NOT NULL DEFAULT 'member';
+UPDATE room_member SET role = 'organiser'
+WHERE EXISTS (
+    SELECT 1 FROM room
+    WHERE room.id = room_member.room_id AND room.created_by = room_member.username
+);
@@ -0,0 +1,5 @@
+-- cf `server/src/privacy.rs`
+-- What a member shares with the others in a room: "sharing", "paused" or "hidden"
+ALTER TABLE room_member ADD COLUMN sharing TEXT NOT NULL DEFAULT 'sharing';
+-- in meters: the positions are rounded to a grid of that size; NULL for the exact positions
+ALTER TABLE room_member ADD COLUMN precision_meters INTEGER;
@@ -0,0 +1,26 @@
+-- cf `server/src/geofence.rs`
+-- A geofence belongs to a room; or to a route, ie to every room where it is active
+CREATE TABLE IF NOT EXISTS geofence (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    room_id INTEGER REFERENCES room(id) ON DELETE CASCADE,
+    route_id INTEGER REFERENCES route(id) ON DELETE CASCADE,
+    -- JSON: cf `GeofenceShape`
+    shape TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    CHECK ((room_id IS NULL) != (route_id IS NULL))
+);
+
+-- The history of the enter/exit events, cf `protocol::WsMessage::Geofence`
+CREATE TABLE IF NOT EXISTS geofence_event (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room_id INTEGER NOT NULL REFERENCES room(id) ON DELETE CASCADE,
+    geofence_id INTEGER NOT NULL REFERENCES geofence(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    -- "enter" or "exit"
+    transition TEXT NOT NULL,
+    -- milliseconds since UNIX epoch
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS geofence_event_room_timestamp ON geofence_event (room_id, timestamp);
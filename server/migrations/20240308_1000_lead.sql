@@ -0,0 +1,5 @@
+-- cf `server/src/lead.rs`
+-- The leader and the sweeper(last rider) of the group, designated by an organiser; NULL if none
+-- They MUST be members of the room: they are cleared when they leave it
+ALTER TABLE room ADD COLUMN leader TEXT;
+ALTER TABLE room ADD COLUMN sweeper TEXT;
@@ -0,0 +1,10 @@
+-- cf `server/src/route_stats.rs`
+-- Computed on upload; the routes uploaded before have NO elevation, duration nor point count(0)
+-- in meters; NULL if the file has no elevation
+ALTER TABLE route ADD COLUMN elevation_gain REAL;
+ALTER TABLE route ADD COLUMN elevation_loss REAL;
+ALTER TABLE route ADD COLUMN min_elevation REAL;
+ALTER TABLE route ADD COLUMN max_elevation REAL;
+ALTER TABLE route ADD COLUMN point_count INTEGER NOT NULL DEFAULT 0;
+-- in milliseconds, from the first to the last timestamp; NULL if the file has no timestamps
+ALTER TABLE route ADD COLUMN duration INTEGER;
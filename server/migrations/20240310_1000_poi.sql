@@ -0,0 +1,14 @@
+-- cf `server/src/poi.rs`
+-- The points of interest of a route, eg water, food or a meeting point; from the waypoints of the GPX files
+CREATE TABLE IF NOT EXISTS poi (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    route_id INTEGER NOT NULL REFERENCES route(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    -- eg "Drinking Water": the `<sym>` of the GPX waypoints
+    symbol TEXT,
+    lat REAL NOT NULL,
+    lng REAL NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS poi_route ON poi (route_id);
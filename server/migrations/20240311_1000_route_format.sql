@@ -0,0 +1,3 @@
+-- The format of the original file, as its extension, cf `RouteFormat::extension` in `server/src/route_formats.rs`
+-- NULL for the routes uploaded before: their format is sniffed from their content
+ALTER TABLE route ADD COLUMN format TEXT;
@@ -0,0 +1,3 @@
+-- cf `server/migrations/20240302_1000_position.sql`: for the queries of a whole room,
+-- eg the snapshots, the room tracks and their GPX export
+CREATE INDEX IF NOT EXISTS position_room_id_timestamp ON position (room_id, timestamp);
@@ -1,5 +1,6 @@
 /// `https://github.com/tokio-rs/axum/blob/d703e6f97a0156177466b6741be0beac0c83d8c7/examples/jwt/src/main.rs#L1`
 // TODO use a turnkey JWT crate/lib from https://github.com/tokio-rs/axum/blob/d703e6f97a0156177466b6741be0beac0c83d8c7/ECOSYSTEM.md?plain=1#L13 ?
+use argon2::password_hash::{rand_core::OsRng, SaltString};
 use axum::{
     async_trait,
     extract::FromRequestParts,
@@ -11,15 +12,23 @@ use axum_extra::{
     headers::{authorization::Bearer, Authorization},
     TypedHeader,
 };
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation,
+};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sqlx::SqlitePool;
 use std::fmt::Display;
+use std::time::Duration;
 
 use crate::{
-    db::{get_user_from_db, user_check_password},
-    state::SharedState,
+    db::{
+        check_password_hash, generate_new_password_hash, get_session_from_db, get_user_from_db,
+        insert_session, insert_user, now_timestamp, revoke_session, revoke_user_sessions,
+        update_session_refresh_token, user_check_password,
+    },
+    state::{Config, SharedState},
 };
 
 // Quick instructions
@@ -48,6 +57,9 @@ use crate::{
 //     -H 'Authorization: Bearer blahblahblah' \
 //     http://localhost:3000/protected
 
+/// cf `register`
+pub(crate) const MIN_PASSWORD_LENGTH: usize = 8;
+
 #[allow(clippy::expect_used)]
 pub(crate) static KEYS: Lazy<Keys> = Lazy::new(|| {
     let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
@@ -93,13 +105,7 @@ pub(crate) async fn authorize(
     }
 
     // check the user credentials from a database
-    let db_pool = match state.read() {
-        Ok(state) => state.db_pool.clone(),
-        Err(err) => {
-            tracing::error!("authorize: state read lock error: {:?}", err,);
-            return Err(AuthError::DbError);
-        }
-    };
+    let (db_pool, config) = (state.db_pool.clone(), state.config.clone());
     match get_user_from_db(&db_pool, &payload.email).await {
         Ok(Some(user)) => {
             // Handle the case when the user is found in the database
@@ -114,7 +120,12 @@ pub(crate) async fn authorize(
         }
         Ok(None) => {
             // Handle the case when the user is not found in the database
-            // in this case we DO NOT check the password field
+            // in "open" mode we DO NOT check the password field
+            // else they MUST register first
+            if !config.open_login {
+                tracing::warn!("authorize: unknown user and open login is disabled");
+                return Err(AuthError::WrongCredentials);
+            }
         }
         Err(err) => {
             // Handle the case when an error occurs during the database query
@@ -122,31 +133,234 @@ pub(crate) async fn authorize(
             return Err(AuthError::DbError);
         }
     }
+
+    // Send the authorized token
+    Ok(Json(new_session(&db_pool, &config, payload.email).await?))
+}
+
+/// Create a new user with a password; and log them in
+/// Disabled with `--disable-registration`
+#[axum::debug_handler]
+pub(crate) async fn register(
+    Extension(state): Extension<SharedState>,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<AuthBody>, AuthError> {
+    let (db_pool, config) = (state.db_pool.clone(), state.config.clone());
+    if !config.registration_enabled {
+        return Err(AuthError::RegistrationDisabled);
+    }
+
+    let password = payload.password.unwrap_or_default();
+    if payload.email.trim().is_empty() || password.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AuthError::WeakPassword);
+    }
+
+    match get_user_from_db(&db_pool, &payload.email).await {
+        Ok(None) => {}
+        Ok(Some(_user)) => return Err(AuthError::UserAlreadyExists),
+        Err(err) => {
+            tracing::error!("register: db error: {:?}", err,);
+            return Err(AuthError::DbError);
+        }
+    }
+
+    // NOTE: a concurrent registration MAY have taken the username since the check above
+    insert_user(&db_pool, &payload.email, &password)
+        .await
+        .map_err(|err| {
+            if err.kind() == std::io::ErrorKind::AlreadyExists {
+                return AuthError::UserAlreadyExists;
+            }
+            tracing::error!("register: db error: {:?}", err,);
+            AuthError::DbError
+        })?;
+    // with open login, anyone MAY have logged in with this name before it was registered:
+    // those sessions(and their refresh tokens) MUST NOT outlive the registration
+    revoke_user_sessions(&db_pool, &payload.email, None)
+        .await
+        .map_err(|err| {
+            tracing::error!("register: db error: {:?}", err,);
+            AuthError::DbError
+        })?;
+
+    Ok(Json(new_session(&db_pool, &config, payload.email).await?))
+}
+
+/// Exchange a refresh token for a new access token
+/// The refresh token is rotated: the one that was sent can NOT be used again.
+#[axum::debug_handler]
+pub(crate) async fn refresh(
+    Extension(state): Extension<SharedState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<AuthBody>, AuthError> {
+    let (db_pool, config) = (state.db_pool.clone(), state.config.clone());
+
+    let (session_id, secret) = payload
+        .refresh_token
+        .split_once('.')
+        .ok_or(AuthError::InvalidToken)?;
+    let session = get_session_from_db(&db_pool, session_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("refresh: db error: {:?}", err,);
+            AuthError::DbError
+        })?
+        .filter(|session| session.is_active(now_timestamp()))
+        .ok_or(AuthError::SessionExpired)?;
+    check_password_hash(&session.refresh_token_hash, secret).map_err(|_err| {
+        tracing::warn!("refresh: wrong refresh token for session {session_id}");
+        AuthError::SessionExpired
+    })?;
+
+    let (refresh_token, refresh_token_hash) = new_refresh_token(session_id)?;
+    let rotated = update_session_refresh_token(
+        &db_pool,
+        session_id,
+        &session.refresh_token_hash,
+        &refresh_token_hash,
+        expires_at(config.refresh_token_ttl),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("refresh: db error: {:?}", err,);
+        AuthError::DbError
+    })?;
+    // eg logged out in the meantime; or rotated by a concurrent request with the same refresh token
+    if !rotated {
+        return Err(AuthError::SessionExpired);
+    }
+
+    let access_token = new_token(session.username, session.id, config.access_token_ttl)?;
+    Ok(Json(AuthBody::new(
+        access_token,
+        refresh_token,
+        config.access_token_ttl,
+    )))
+}
+
+/// Revoke the current session: its access token(s) and refresh token are rejected from now on,
+/// including by the websocket, cf `ws_handler`
+#[axum::debug_handler]
+pub(crate) async fn logout(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+) -> Result<(), AuthError> {
+    let db_pool = state.db_pool.clone();
+
+    revoke_session(&db_pool, &claims.sid).await.map_err(|err| {
+        tracing::error!("logout: db error: {:?}", err,);
+        AuthError::DbError
+    })?;
+
+    Ok(())
+}
+
+/// Create a new session for a given user, ie a refresh token; and its first access token
+async fn new_session(
+    db_pool: &SqlitePool,
+    config: &Config,
+    username: String,
+) -> Result<AuthBody, AuthError> {
+    let session_id = random_string();
+    let (refresh_token, refresh_token_hash) = new_refresh_token(&session_id)?;
+    insert_session(
+        db_pool,
+        &session_id,
+        &username,
+        &refresh_token_hash,
+        expires_at(config.refresh_token_ttl),
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("new_session: db error: {:?}", err,);
+        AuthError::DbError
+    })?;
+
+    let access_token = new_token(username, session_id, config.access_token_ttl)?;
+    Ok(AuthBody::new(
+        access_token,
+        refresh_token,
+        config.access_token_ttl,
+    ))
+}
+
+/// A random, url-safe string
+fn random_string() -> String {
+    SaltString::generate(&mut OsRng).as_str().to_owned()
+}
+
+/// returns: the refresh token to send to the client: "<session id>.<secret>"; and the hash to store in the DB
+fn new_refresh_token(session_id: &str) -> Result<(String, String), AuthError> {
+    let secret = random_string();
+    let hash = generate_new_password_hash(&secret).map_err(|_| AuthError::TokenCreation)?;
+
+    Ok((format!("{session_id}.{secret}"), hash))
+}
+
+/// milliseconds since UNIX epoch, cf the `session` table
+fn expires_at(ttl: Duration) -> i64 {
+    now_timestamp().saturating_add(i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX))
+}
+
+/// Create the authorization token for a given session
+fn new_token(sub: String, sid: String, ttl: Duration) -> Result<String, AuthError> {
+    let iat = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|_| AuthError::TokenCreation)?
+        .as_secs();
     let claims = Claims {
-        sub: payload.email,
-        company: "ACME".to_owned(),
+        sub,
+        sid,
+        iat,
         // Mandatory expiry time as UTC timestamp
-        exp: 2_000_000_000, // May 2033
+        exp: iat.saturating_add(ttl.as_secs()),
     };
-    // Create the authorization token
-    let token = encode(&Header::default(), &claims, &KEYS.encoding)
-        .map_err(|_| AuthError::TokenCreation)?;
 
-    // Send the authorized token
-    Ok(Json(AuthBody::new(token)))
+    encode(&Header::default(), &claims, &KEYS.encoding).map_err(|_| AuthError::TokenCreation)
+}
+
+/// Decode an access token, and check that its session is still active(ie NOT revoked by a logout, NOT expired)
+/// Used by the `Claims` extractor and by `ws_handler`
+pub(crate) async fn validate_token(token: &str, db_pool: &SqlitePool) -> Result<Claims, AuthError> {
+    let token_data =
+        decode::<Claims>(token, &KEYS.decoding, &Validation::default()).map_err(|err| match err
+            .kind()
+        {
+            ErrorKind::ExpiredSignature => AuthError::SessionExpired,
+            _ => AuthError::InvalidToken,
+        })?;
+    let claims = token_data.claims;
+
+    let session = get_session_from_db(db_pool, &claims.sid)
+        .await
+        .map_err(|err| {
+            tracing::error!("validate_token: db error: {:?}", err,);
+            AuthError::DbError
+        })?;
+    match session {
+        Some(session) if session.is_active(now_timestamp()) && session.username == claims.sub => {
+            Ok(claims)
+        }
+        _ => Err(AuthError::SessionExpired),
+    }
 }
 
 impl Display for Claims {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Email: {}\nCompany: {}", self.sub, self.company)
+        write!(f, "Email: {}\nSession: {}", self.sub, self.sid)
     }
 }
 
 impl AuthBody {
-    fn new(access_token: String) -> Self {
+    fn new(access_token: String, refresh_token: String, expires_in: Duration) -> Self {
         Self {
             access_token,
             token_type: "Bearer".to_string(),
+            refresh_token,
+            expires_in: expires_in.as_secs(),
         }
     }
 }
@@ -164,11 +378,12 @@ where
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| AuthError::InvalidToken)?;
-        // Decode the user data
-        let token_data = decode::<Claims>(bearer.token(), &KEYS.decoding, &Validation::default())
-            .map_err(|_| AuthError::InvalidToken)?;
-
-        Ok(token_data.claims)
+        // the session is checked against the DB
+        let Extension(state) = parts
+            .extract::<Extension<SharedState>>()
+            .await
+            .map_err(|_| AuthError::DbError)?;
+        validate_token(bearer.token(), &state.db_pool).await
     }
 }
 
@@ -179,7 +394,11 @@ impl IntoResponse for AuthError {
             AuthError::MissingCredentials => (StatusCode::BAD_REQUEST, "Missing credentials"),
             AuthError::TokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, "Token creation error"),
             AuthError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token"),
+            AuthError::SessionExpired => (StatusCode::UNAUTHORIZED, "Session expired"),
             AuthError::DbError => (StatusCode::INTERNAL_SERVER_ERROR, "DB error"),
+            AuthError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
+            AuthError::WeakPassword => (StatusCode::BAD_REQUEST, "Password too short"),
+            AuthError::RegistrationDisabled => (StatusCode::NOT_FOUND, "Registration is disabled"),
         };
         let body = Json(json!({
             "error": error_message,
@@ -205,14 +424,22 @@ impl Keys {
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Claims {
     pub(crate) sub: String,
-    company: String,
-    exp: usize,
+    /// session id, cf the `session` table; used to revoke the token
+    pub(crate) sid: String,
+    /// issued at, seconds since UNIX epoch
+    iat: u64,
+    /// expiry, seconds since UNIX epoch
+    exp: u64,
 }
 
 #[derive(Debug, Serialize)]
 pub(crate) struct AuthBody {
     access_token: String,
     token_type: String,
+    /// to send to `POST /refresh` before `access_token` expires
+    refresh_token: String,
+    /// lifetime of `access_token`, in seconds
+    expires_in: u64,
 }
 
 // #[derive(Debug, Deserialize)]
@@ -227,13 +454,23 @@ pub(crate) struct LoginRequest {
     pub(crate) password: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub(crate) struct RefreshRequest {
+    pub(crate) refresh_token: String,
+}
+
 #[derive(Debug)]
 pub(crate) enum AuthError {
     WrongCredentials,
     MissingCredentials,
     TokenCreation,
     InvalidToken,
+    /// expired access token; or revoked/expired session
+    SessionExpired,
     DbError,
+    UserAlreadyExists,
+    WeakPassword,
+    RegistrationDisabled,
 }
 
 #[cfg(test)]
@@ -248,7 +485,6 @@ pub(crate) mod tests {
     use axum::Router;
     use http_body_util::BodyExt;
     use serde_json::Value;
-    use sqlx::SqlitePool;
     use tower::util::ServiceExt;
 
     async fn init() -> (Router, SqlitePool) {
@@ -262,17 +498,13 @@ pub(crate) mod tests {
     }
 
     /// Generate a Auth token that can be used in the various "#[tokio::test]"
-    pub(crate) fn generate_token(email: &str) -> String {
-        let claims = Claims {
-            sub: email.to_owned(),
-            company: "ACME".to_owned(),
-            // Mandatory expiry time as UTC timestamp
-            exp: 2000000000, // May 2033
-        };
-        // Create the authorization token
-        let token = encode(&Header::default(), &claims, &KEYS.encoding).unwrap();
+    /// NOTE: this creates a session in the DB, like a real login
+    pub(crate) async fn generate_token(db_pool: &SqlitePool, email: &str) -> String {
+        let auth_body = new_session(db_pool, &Config::default(), email.to_owned())
+            .await
+            .unwrap();
 
-        token
+        auth_body.access_token
     }
 
     /// We WANT a random user to be able to "login"
@@ -307,7 +539,9 @@ pub(crate) mod tests {
         assert_eq!(response_status, StatusCode::OK);
         let body: Value = serde_json::from_slice(&response_body).unwrap();
         assert_eq!(body["token_type"], "Bearer");
-        assert_eq!(body["access_token"].to_string().len(), 146);
+        assert_eq!(body["access_token"].to_string().len(), 187);
+        assert_eq!(body["expires_in"], 15 * 60);
+        assert!(body["refresh_token"].as_str().unwrap().contains('.'));
     }
 
     /// test authorize: a user that exists in the DB must login with a username and password
@@ -426,6 +660,331 @@ pub(crate) mod tests {
         assert_eq!(response_status, StatusCode::OK);
         let body: Value = serde_json::from_slice(&response_body).unwrap();
         assert_eq!(body["token_type"], "Bearer");
-        assert_eq!(body["access_token"].to_string().len(), 146);
+        assert_eq!(body["access_token"].to_string().len(), 187);
+        assert_eq!(body["expires_in"], 15 * 60);
+        assert!(body["refresh_token"].as_str().unwrap().contains('.'));
+    }
+
+    /// POST `uri` with a JSON body, on an app with the given `Config`
+    async fn post_json(
+        config: Config,
+        db_pool: &SqlitePool,
+        uri: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let app = crate::new_app_with_config(db_pool.clone(), config).unwrap();
+
+        let f = async {
+            app.oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(http::Method::POST)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        let response_status = response.status();
+        let response_body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (
+            response_status,
+            serde_json::from_slice(&response_body).unwrap(),
+        )
+    }
+
+    /// When open login is disabled, an unknown user MUST register first
+    #[tokio::test]
+    async fn test_authorize_without_user_in_db_and_open_login_disabled_should_fail() {
+        let (_app, db_pool) = init().await;
+        let config = Config {
+            open_login: false,
+            ..Default::default()
+        };
+
+        let (status, body) = post_json(
+            config.clone(),
+            &db_pool,
+            "/authorize",
+            json!({ "email": "aaa", "password": "my_password" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "Wrong credentials");
+
+        let (status, body) = post_json(
+            config.clone(),
+            &db_pool,
+            "/register",
+            json!({ "email": "aaa", "password": "my_password" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["token_type"], "Bearer");
+
+        let (status, _body) = post_json(
+            config,
+            &db_pool,
+            "/authorize",
+            json!({ "email": "aaa", "password": "my_password" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_register_ok() {
+        let (_app, db_pool) = init().await;
+
+        let (status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/register",
+            json!({ "email": "aaa", "password": "my_password" }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["token_type"], "Bearer");
+        let user = get_user_from_db(&db_pool, "aaa").await.unwrap().unwrap();
+        assert!(user_check_password(&user, "my_password").await.is_ok());
+        assert_eq!(user.role, crate::role::Role::Member);
+    }
+
+    #[tokio::test]
+    async fn test_register_invalid_should_fail() {
+        let (_app, db_pool) = init().await;
+        insert_user(&db_pool, "bbb", "my_password").await.unwrap();
+
+        for (payload, expected_status, expected_error) in [
+            (
+                json!({ "email": "aaa" }),
+                StatusCode::BAD_REQUEST,
+                "Missing credentials",
+            ),
+            (
+                json!({ "email": "aaa", "password": "short" }),
+                StatusCode::BAD_REQUEST,
+                "Password too short",
+            ),
+            (
+                json!({ "email": "bbb", "password": "another_password" }),
+                StatusCode::CONFLICT,
+                "User already exists",
+            ),
+        ] {
+            let (status, body) = post_json(Config::default(), &db_pool, "/register", payload).await;
+            assert_eq!(status, expected_status);
+            assert_eq!(body["error"], expected_error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_disabled_should_fail() {
+        let (_app, db_pool) = init().await;
+        let config = Config {
+            registration_enabled: false,
+            ..Default::default()
+        };
+
+        let (status, _body) = post_json(
+            config,
+            &db_pool,
+            "/register",
+            json!({ "email": "aaa", "password": "my_password" }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(get_user_from_db(&db_pool, "aaa").await.unwrap().is_none());
+    }
+
+    /// `uri` with a Bearer token, on an app with the default `Config`
+    async fn send_with_token(
+        db_pool: &SqlitePool,
+        method: http::Method,
+        uri: &str,
+        token: &str,
+    ) -> StatusCode {
+        let app = crate::new_app(db_pool.clone()).unwrap();
+
+        let f = async {
+            app.oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(method)
+                    .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        response.status()
+    }
+
+    /// The refresh token is rotated: the new one works, the old one does NOT
+    #[tokio::test]
+    async fn test_refresh_ok_and_rotates_the_refresh_token() {
+        let (_app, db_pool) = init().await;
+
+        let (_status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/authorize",
+            json!({ "email": "aaa" }),
+        )
+        .await;
+        let refresh_token = body["refresh_token"].as_str().unwrap().to_string();
+
+        let (status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/refresh",
+            json!({ "refresh_token": refresh_token }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let new_refresh_token = body["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(new_refresh_token, refresh_token);
+        let access_token = body["access_token"].as_str().unwrap();
+        assert_eq!(
+            send_with_token(&db_pool, http::Method::GET, "/api/rooms", access_token).await,
+            StatusCode::OK
+        );
+
+        let (status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/refresh",
+            json!({ "refresh_token": refresh_token }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "Session expired");
+
+        let (status, _body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/refresh",
+            json!({ "refresh_token": "garbage" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    /// The sessions opened with open login before a name is registered are revoked
+    #[tokio::test]
+    async fn test_register_revokes_the_open_login_sessions() {
+        let (_app, db_pool) = init().await;
+
+        let (_status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/authorize",
+            json!({ "email": "aaa" }),
+        )
+        .await;
+        let refresh_token = body["refresh_token"].as_str().unwrap().to_string();
+
+        let (status, _body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/register",
+            json!({ "email": "aaa", "password": "my_password" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/refresh",
+            json!({ "refresh_token": refresh_token }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "Session expired");
+    }
+
+    /// After a logout, both the access token and the refresh token MUST be rejected
+    #[tokio::test]
+    async fn test_logout_revokes_the_session() {
+        let (_app, db_pool) = init().await;
+
+        let (_status, body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/authorize",
+            json!({ "email": "aaa" }),
+        )
+        .await;
+        let access_token = body["access_token"].as_str().unwrap();
+        let refresh_token = body["refresh_token"].as_str().unwrap();
+        // another session of the same user is NOT affected
+        let other_token = generate_token(&db_pool, "aaa").await;
+
+        assert_eq!(
+            send_with_token(&db_pool, http::Method::GET, "/api/rooms", access_token).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send_with_token(&db_pool, http::Method::POST, "/logout", access_token).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send_with_token(&db_pool, http::Method::GET, "/api/rooms", access_token).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let (status, _body) = post_json(
+            Config::default(),
+            &db_pool,
+            "/refresh",
+            json!({ "refresh_token": refresh_token }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        assert_eq!(
+            send_with_token(&db_pool, http::Method::GET, "/api/rooms", &other_token).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_access_token_should_fail() {
+        let (_app, db_pool) = init().await;
+
+        let f = async {
+            let session = new_session(&db_pool, &Config::default(), "aaa".to_string())
+                .await
+                .unwrap();
+            let claims: Claims = decode(
+                &session.access_token,
+                &KEYS.decoding,
+                &Validation::default(),
+            )
+            .unwrap()
+            .claims;
+            // NOTE: more than the default leeway of `Validation`
+            let expired = Claims {
+                iat: claims.iat - 3600,
+                exp: claims.iat - 600,
+                ..claims
+            };
+            encode(&Header::default(), &expired, &KEYS.encoding).unwrap()
+        };
+        let token = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            send_with_token(&db_pool, http::Method::GET, "/api/rooms", &token).await,
+            StatusCode::UNAUTHORIZED
+        );
     }
 }
@@ -0,0 +1,487 @@
+//! REST API of the geofences, cf `geofence.rs`
+//!
+//! - the organisers manage them: `/api/geofences` and `/api/geofences/:geofence_id`
+//! - the members of a room get the ones that apply to it, and the history of the enter/exit events:
+//!   `/api/rooms/:room_id/geofences` and `/api/rooms/:room_id/geofences/events`
+
+use axum::{extract::Path, Extension, Json};
+use protocol::WsMessage;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    api_route::check_can_edit_route,
+    db::{
+        delete_geofence, get_geofence_from_db, get_room_from_db, get_room_member_role,
+        insert_geofence, list_geofence_events_from_db, list_geofences_from_db, list_room_geofences,
+        list_rooms_with_active_route, update_geofence,
+    },
+    errors_and_responses::AppError,
+    geofence::{Geofence, GeofenceEvent, GeofenceShape},
+    role::{Organiser, RequireRole, RequireRoomRole, Role, Viewer},
+    state::SharedState,
+    ws_handler::broadcast_message,
+};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ListGeofences {
+    geofences: Vec<Geofence>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ListGeofenceEvents {
+    events: Vec<GeofenceEvent>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateGeofenceRequest {
+    pub(crate) name: String,
+    /// exactly one of `room_id` and `route_id`
+    pub(crate) room_id: Option<i64>,
+    pub(crate) route_id: Option<i64>,
+    pub(crate) shape: GeofenceShape,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PatchGeofenceRequest {
+    pub(crate) name: Option<String>,
+    pub(crate) shape: Option<GeofenceShape>,
+}
+
+fn validate(name: &str, shape: &GeofenceShape, caller: &str) -> Result<(), AppError> {
+    if name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+    shape.validate().map_err(|err| {
+        tracing::warn!("{caller}: invalid shape: {err}");
+        AppError::BadRequest
+    })
+}
+
+/// A geofence can only be attached to a room by one of its organisers(or an admin);
+/// and to a route by the organiser who uploaded it(or an admin); else `AppError::NotFound`
+async fn check_can_attach(
+    db_pool: &SqlitePool,
+    organiser: &RequireRole<Organiser>,
+    payload: &CreateGeofenceRequest,
+) -> Result<(), AppError> {
+    match (payload.room_id, payload.route_id) {
+        (Some(room_id), None) => {
+            match get_room_from_db(db_pool, room_id).await {
+                Ok(Some(_room)) => {}
+                Ok(None) => return Err(AppError::NotFound),
+                Err(err) => {
+                    tracing::error!("create_geofence: db error: {:?}", err);
+                    return Err(AppError::InternalError);
+                }
+            }
+            if organiser.role == Role::Admin {
+                return Ok(());
+            }
+            match get_room_member_role(db_pool, room_id, &organiser.claims.sub).await {
+                Ok(Some(Role::Organiser | Role::Admin)) => Ok(()),
+                Ok(_) => {
+                    tracing::error!(
+                        "create_geofence: {:?} is NOT an organiser of room {room_id}",
+                        organiser.claims.sub
+                    );
+                    Err(AppError::NotFound)
+                }
+                Err(err) => {
+                    tracing::error!("create_geofence: db error: {:?}", err);
+                    Err(AppError::InternalError)
+                }
+            }
+        }
+        (None, Some(route_id)) => {
+            check_can_edit_route(db_pool, organiser, route_id, "create_geofence").await
+        }
+        _ => Err(AppError::BadRequest),
+    }
+}
+
+/// A geofence can only be modified by the organiser who created it, or by an admin; else `AppError::NotFound`
+async fn get_editable_geofence(
+    db_pool: &SqlitePool,
+    organiser: &RequireRole<Organiser>,
+    geofence_id: i64,
+    caller: &str,
+) -> Result<Geofence, AppError> {
+    match get_geofence_from_db(db_pool, geofence_id).await {
+        Ok(Some(geofence))
+            if organiser.role == Role::Admin || geofence.created_by == organiser.claims.sub =>
+        {
+            Ok(geofence)
+        }
+        Ok(Some(_geofence)) => {
+            tracing::error!(
+                "{caller}: {:?} is NOT the creator of geofence {geofence_id}",
+                organiser.claims.sub
+            );
+            Err(AppError::NotFound)
+        }
+        Ok(None) => Err(AppError::NotFound),
+        Err(err) => {
+            tracing::error!("{caller}: db error: {:?}", err);
+            Err(AppError::InternalError)
+        }
+    }
+}
+
+/// A geofence was created, modified or deleted: the cache is reset, and the clients connected to the rooms
+/// where it applies are told to fetch them again; cf `WsMessage::GeofencesUpdated`
+async fn notify_geofences_updated(state: &SharedState, geofence: &Geofence, caller: &str) {
+    state.geofences.forget_all();
+
+    let room_ids = match (geofence.room_id, geofence.route_id) {
+        (Some(room_id), _) => vec![room_id],
+        (None, Some(route_id)) => list_rooms_with_active_route(&state.db_pool, route_id)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::error!("{caller}: db error: {:?}", err);
+                vec![]
+            }),
+        (None, None) => vec![],
+    };
+    for room_id in room_ids {
+        if let Some(room_channels) = state.existing_room_channels(room_id) {
+            broadcast_message(
+                &room_channels.location_broadcast_sender,
+                &WsMessage::GeofencesUpdated,
+            );
+        }
+    }
+}
+
+/// List all the geofences
+/// MUST be called by an organiser
+#[axum::debug_handler]
+pub(crate) async fn list_geofences(
+    Extension(state): Extension<SharedState>,
+    _organiser: RequireRole<Organiser>,
+) -> Result<Json<ListGeofences>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let geofences = list_geofences_from_db(&db_pool).await.map_err(|err| {
+        tracing::error!("list_geofences: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(ListGeofences { geofences }))
+}
+
+/// Create a geofence, attached to a room or to a route
+/// MUST be called by an organiser of this room, or by the uploader of this route(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn create_geofence(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Json(payload): Json<CreateGeofenceRequest>,
+) -> Result<Json<Geofence>, AppError> {
+    let db_pool = state.db_pool.clone();
+    validate(&payload.name, &payload.shape, "create_geofence")?;
+    check_can_attach(&db_pool, &organiser, &payload).await?;
+
+    let geofence = insert_geofence(
+        &db_pool,
+        &payload.name,
+        payload.room_id,
+        payload.route_id,
+        &payload.shape,
+        &organiser.claims.sub,
+    )
+    .await
+    .map_err(|err| {
+        tracing::error!("create_geofence: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+    notify_geofences_updated(&state, &geofence, "create_geofence").await;
+
+    Ok(Json(geofence))
+}
+
+/// Get a given geofence
+/// MUST be called by an organiser
+#[axum::debug_handler]
+pub(crate) async fn get_geofence(
+    Extension(state): Extension<SharedState>,
+    _organiser: RequireRole<Organiser>,
+    Path(geofence_id): Path<i64>,
+) -> Result<Json<Geofence>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let geofence = get_geofence_from_db(&db_pool, geofence_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_geofence: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(Json(geofence))
+}
+
+/// Rename and/or reshape a given geofence
+/// MUST be called by the organiser who created it(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn patch_geofence(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(geofence_id): Path<i64>,
+    Json(payload): Json<PatchGeofenceRequest>,
+) -> Result<Json<Geofence>, AppError> {
+    let db_pool = state.db_pool.clone();
+    let mut geofence =
+        get_editable_geofence(&db_pool, &organiser, geofence_id, "patch_geofence").await?;
+
+    if let Some(name) = payload.name {
+        geofence.name = name;
+    }
+    if let Some(shape) = payload.shape {
+        geofence.shape = shape;
+    }
+    validate(&geofence.name, &geofence.shape, "patch_geofence")?;
+
+    let is_found = update_geofence(&db_pool, geofence_id, &geofence.name, &geofence.shape)
+        .await
+        .map_err(|err| {
+            tracing::error!("patch_geofence: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    notify_geofences_updated(&state, &geofence, "patch_geofence").await;
+
+    Ok(Json(geofence))
+}
+
+/// Delete a given geofence; and its history
+/// MUST be called by the organiser who created it(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn delete_geofence_handler(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(geofence_id): Path<i64>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+    let geofence =
+        get_editable_geofence(&db_pool, &organiser, geofence_id, "delete_geofence").await?;
+
+    let is_found = delete_geofence(&db_pool, geofence_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("delete_geofence: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    notify_geofences_updated(&state, &geofence, "delete_geofence").await;
+
+    Ok(())
+}
+
+/// List the geofences that apply to a given room: its own, and the ones of its active route
+/// MUST be called by a member of the room, with any role(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn get_room_geofences(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<Json<ListGeofences>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let geofences = list_room_geofences(&db_pool, room_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_room_geofences: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+
+    Ok(Json(ListGeofences { geofences }))
+}
+
+/// The history of the enter/exit events of a given room, ordered by timestamp
+/// MUST be called by a member of the room, with any role(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn get_room_geofence_events(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<Json<ListGeofenceEvents>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let events = list_geofence_events_from_db(&db_pool, room_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_room_geofence_events: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+
+    Ok(Json(ListGeofenceEvents { events }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_route::tests::{init, send};
+    use crate::db::{insert_room, insert_user, set_room_active_route, update_user_role};
+
+    use super::*;
+
+    use axum::http::{self, StatusCode};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_geofences_crud_ok() {
+        let (app, db_pool, room_id, route) = init().await;
+        set_room_active_route(&db_pool, room_id, Some(route.id))
+            .await
+            .unwrap();
+        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::POST,
+            "/api/geofences",
+            "root",
+            Some(json!({
+                "name": "checkpoint",
+                "route_id": route.id,
+                "shape": { "type": "circle", "lat": 48.05, "lng": 2.05, "radius": 200.0 },
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let geofence_id = body["id"].as_i64().unwrap();
+        assert_eq!(body["created_by"], "root");
+
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::PATCH,
+            &format!("/api/geofences/{geofence_id}"),
+            "root",
+            Some(json!({ "name": "finish" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["name"], "finish");
+        assert_eq!(body["shape"]["radius"], 200.0);
+
+        // the members of the room see the geofences of its route; but NOT the others
+        let uri = format!("/api/rooms/{room_id}/geofences");
+        let (status, body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["geofences"][0]["id"], geofence_id);
+        let (status, _body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "aaa", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            &format!("/api/rooms/{room_id}/geofences/events"),
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "events": [] }));
+
+        let uri = format!("/api/geofences/{geofence_id}");
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::DELETE,
+            &uri,
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _body) = send(app, &db_pool, http::Method::GET, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_geofences_invalid_or_forbidden() {
+        let (app, db_pool, room_id, route) = init().await;
+        insert_user(&db_pool, "orga", "bbb").await.unwrap();
+        update_user_role(&db_pool, "orga", Role::Organiser)
+            .await
+            .unwrap();
+        let other_room = insert_room(&db_pool, "room2", "orga").await.unwrap();
+        let circle = json!({ "type": "circle", "lat": 48.05, "lng": 2.05, "radius": 200.0 });
+
+        for (body, expected_status) in [
+            // neither a room nor a route; or both
+            (
+                json!({ "name": "x", "shape": circle }),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                json!({ "name": "x", "room_id": other_room.id, "route_id": route.id, "shape": circle }),
+                StatusCode::BAD_REQUEST,
+            ),
+            // an invalid shape
+            (
+                json!({ "name": "x", "room_id": other_room.id, "shape": { "type": "circle", "lat": 48.0, "lng": 2.0, "radius": -1.0 } }),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                json!({ "name": "x", "room_id": other_room.id, "shape": { "type": "polygon", "points": [[48.0, 2.0], [48.1, 2.0]] } }),
+                StatusCode::BAD_REQUEST,
+            ),
+            // NOT their room, NOT their route
+            (
+                json!({ "name": "x", "room_id": room_id, "shape": circle }),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                json!({ "name": "x", "route_id": route.id, "shape": circle }),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                json!({ "name": "x", "room_id": other_room.id, "shape": circle }),
+                StatusCode::OK,
+            ),
+        ] {
+            let (status, _body) = send(
+                app.clone(),
+                &db_pool,
+                http::Method::POST,
+                "/api/geofences",
+                "orga",
+                Some(body.clone()),
+            )
+            .await;
+            assert_eq!(status, expected_status, "{body}");
+        }
+
+        // a geofence created by "root" can NOT be modified by "orga"
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::POST,
+            "/api/geofences",
+            "root",
+            Some(json!({ "name": "x", "room_id": room_id, "shape": circle })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let uri = format!("/api/geofences/{}", body["id"]);
+        for method in [http::Method::PATCH, http::Method::DELETE] {
+            let (status, _body) = send(
+                app.clone(),
+                &db_pool,
+                method.clone(),
+                &uri,
+                "orga",
+                Some(json!({ "name": "renamed" })),
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{method}");
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! REST API of the points of interest of the routes, cf `poi.rs`
+//!
+//! - the organisers manage the ones of their routes: `/api/routes/:route_id/pois` and `/api/pois/:poi_id`
+//! - the members of a room get the ones of its active route: `/api/rooms/:room_id/pois`
+
+use axum::{extract::Path, Extension, Json};
+use protocol::WsMessage;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    api_route::check_can_edit_route,
+    db::{
+        delete_poi, get_poi_from_db, get_route_from_db, insert_poi, list_room_pois,
+        list_rooms_with_active_route, list_route_pois, update_poi,
+    },
+    errors_and_responses::AppError,
+    poi::{Poi, Waypoint},
+    role::{Organiser, RequireRole, RequireRoomRole, Viewer},
+    state::SharedState,
+    ws_handler::broadcast_message,
+};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ListPois {
+    pois: Vec<Poi>,
+}
+
+/// eg only `lat` and `lng` to move a POI
+/// An empty `description` or `symbol` clears it
+#[derive(Deserialize)]
+pub(crate) struct PatchPoiRequest {
+    pub(crate) name: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) symbol: Option<String>,
+    pub(crate) lat: Option<f64>,
+    pub(crate) lng: Option<f64>,
+}
+
+fn validate(waypoint: &Waypoint, caller: &str) -> Result<(), AppError> {
+    waypoint.validate().map_err(|err| {
+        tracing::warn!("{caller}: invalid POI: {err}");
+        AppError::BadRequest
+    })
+}
+
+/// A POI can only be modified by the organiser who uploaded its route, or by an admin; else `AppError::NotFound`
+async fn get_editable_poi(
+    db_pool: &SqlitePool,
+    organiser: &RequireRole<Organiser>,
+    poi_id: i64,
+    caller: &str,
+) -> Result<Poi, AppError> {
+    let poi = get_poi_from_db(db_pool, poi_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("{caller}: db error: {:?}", err);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+    check_can_edit_route(db_pool, organiser, poi.route_id, caller).await?;
+
+    Ok(poi)
+}
+
+/// The POIs of a route were created, modified or deleted: the clients connected to the rooms
+/// where it is active are told to fetch them again; cf `WsMessage::PoisUpdated`
+pub(crate) async fn notify_pois_updated(state: &SharedState, route_id: i64, caller: &str) {
+    let room_ids = list_rooms_with_active_route(&state.db_pool, route_id)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!("{caller}: db error: {:?}", err);
+            vec![]
+        });
+    for room_id in room_ids {
+        if let Some(room_channels) = state.existing_room_channels(room_id) {
+            broadcast_message(
+                &room_channels.location_broadcast_sender,
+                &WsMessage::PoisUpdated,
+            );
+        }
+    }
+}
+
+/// List the POIs of a given route
+/// MUST be called by an organiser
+#[axum::debug_handler]
+pub(crate) async fn get_route_pois(
+    Extension(state): Extension<SharedState>,
+    _organiser: RequireRole<Organiser>,
+    Path(route_id): Path<i64>,
+) -> Result<Json<ListPois>, AppError> {
+    let db_pool = state.db_pool.clone();
+    let db_error = |err| {
+        tracing::error!("get_route_pois: db error: {:?}", err,);
+        AppError::InternalError
+    };
+
+    get_route_from_db(&db_pool, route_id)
+        .await
+        .map_err(db_error)?
+        .ok_or(AppError::NotFound)?;
+    let pois = list_route_pois(&db_pool, route_id)
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(ListPois { pois }))
+}
+
+/// Add a POI to a given route
+/// MUST be called by the organiser who uploaded it(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn create_poi(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(route_id): Path<i64>,
+    Json(payload): Json<Waypoint>,
+) -> Result<Json<Poi>, AppError> {
+    let db_pool = state.db_pool.clone();
+    validate(&payload, "create_poi")?;
+    check_can_edit_route(&db_pool, &organiser, route_id, "create_poi").await?;
+
+    let poi = insert_poi(&db_pool, route_id, &payload)
+        .await
+        .map_err(|err| {
+            tracing::error!("create_poi: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    notify_pois_updated(&state, route_id, "create_poi").await;
+
+    Ok(Json(poi))
+}
+
+/// Rename, describe and/or move a given POI
+/// MUST be called by the organiser who uploaded its route(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn patch_poi(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(poi_id): Path<i64>,
+    Json(payload): Json<PatchPoiRequest>,
+) -> Result<Json<Poi>, AppError> {
+    let db_pool = state.db_pool.clone();
+    let mut poi = get_editable_poi(&db_pool, &organiser, poi_id, "patch_poi").await?;
+
+    let waypoint = &mut poi.waypoint;
+    if let Some(name) = payload.name {
+        waypoint.name = name;
+    }
+    if let Some(description) = payload.description {
+        waypoint.description = Some(description).filter(|text| !text.is_empty());
+    }
+    if let Some(symbol) = payload.symbol {
+        waypoint.symbol = Some(symbol).filter(|text| !text.is_empty());
+    }
+    if let Some(lat) = payload.lat {
+        waypoint.lat = lat;
+    }
+    if let Some(lng) = payload.lng {
+        waypoint.lng = lng;
+    }
+    validate(waypoint, "patch_poi")?;
+
+    let is_found = update_poi(&db_pool, poi_id, waypoint)
+        .await
+        .map_err(|err| {
+            tracing::error!("patch_poi: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    notify_pois_updated(&state, poi.route_id, "patch_poi").await;
+
+    Ok(Json(poi))
+}
+
+/// Delete a given POI
+/// MUST be called by the organiser who uploaded its route(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn delete_poi_handler(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(poi_id): Path<i64>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+    let poi = get_editable_poi(&db_pool, &organiser, poi_id, "delete_poi").await?;
+
+    let is_found = delete_poi(&db_pool, poi_id).await.map_err(|err| {
+        tracing::error!("delete_poi: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    notify_pois_updated(&state, poi.route_id, "delete_poi").await;
+
+    Ok(())
+}
+
+/// List the POIs of the active route of a given room; empty if none
+/// MUST be called by a member of the room, with any role(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn get_room_pois(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<Json<ListPois>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let pois = list_room_pois(&db_pool, room_id).await.map_err(|err| {
+        tracing::error!("get_room_pois: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(ListPois { pois }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api_route::tests::{init, send};
+    use crate::db::{insert_user, set_room_active_route, update_user_role};
+    use crate::role::Role;
+
+    use axum::http::{self, StatusCode};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_pois_crud_ok() {
+        let (app, db_pool, room_id, route) = init().await;
+        set_room_active_route(&db_pool, room_id, Some(route.id))
+            .await
+            .unwrap();
+
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::POST,
+            &format!("/api/routes/{}/pois", route.id),
+            "root",
+            Some(json!({
+                "name": "water",
+                "description": "fountain",
+                "symbol": "Drinking Water",
+                "lat": 48.05,
+                "lng": 2.05,
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let poi_id = body["id"].as_i64().unwrap();
+        assert_eq!(body["route_id"], route.id);
+        assert_eq!(body["symbol"], "Drinking Water");
+
+        // move it
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::PATCH,
+            &format!("/api/pois/{poi_id}"),
+            "root",
+            Some(json!({ "lat": 48.06, "lng": 2.06, "description": "" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "id": poi_id,
+                "route_id": route.id,
+                "name": "water",
+                "description": null,
+                "symbol": "Drinking Water",
+                "lat": 48.06,
+                "lng": 2.06,
+            })
+        );
+
+        // the members of the room see the POIs of its route
+        let (status, room_pois) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            &format!("/api/rooms/{room_id}/pois"),
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(room_pois, json!({ "pois": [body] }));
+        let (status, route_pois) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            &format!("/api/routes/{}/pois", route.id),
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(route_pois, room_pois);
+
+        let uri = format!("/api/pois/{poi_id}");
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::DELETE,
+            &uri,
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _body) = send(app, &db_pool, http::Method::DELETE, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_pois_invalid_or_forbidden() {
+        let (app, db_pool, room_id, route) = init().await;
+        insert_user(&db_pool, "orga", "bbb").await.unwrap();
+        update_user_role(&db_pool, "orga", Role::Organiser)
+            .await
+            .unwrap();
+        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+        let water = json!({ "name": "water", "lat": 48.05, "lng": 2.05 });
+
+        let uri = format!("/api/routes/{}/pois", route.id);
+        for (username, body, expected_status) in [
+            (
+                "root",
+                json!({ "name": "", "lat": 48.05, "lng": 2.05 }),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "root",
+                json!({ "name": "x", "lat": 100.0, "lng": 2.05 }),
+                StatusCode::BAD_REQUEST,
+            ),
+            // NOT the uploader of the route
+            ("orga", water.clone(), StatusCode::NOT_FOUND),
+            // NOT an organiser
+            ("aaa", water.clone(), StatusCode::NOT_FOUND),
+        ] {
+            let (status, _body) = send(
+                app.clone(),
+                &db_pool,
+                http::Method::POST,
+                &uri,
+                username,
+                Some(body.clone()),
+            )
+            .await;
+            assert_eq!(status, expected_status, "{username}: {body}");
+        }
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::POST,
+            "/api/routes/12345/pois",
+            "root",
+            Some(water.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::POST,
+            &uri,
+            "root",
+            Some(water),
+        )
+        .await;
+        let poi_uri = format!("/api/pois/{}", body["id"]);
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::PATCH,
+            &poi_uri,
+            "orga",
+            Some(json!({ "lat": 48.06 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::DELETE,
+            &poi_uri,
+            "orga",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // NOT a member of the room
+        let (status, _body) = send(
+            app,
+            &db_pool,
+            http::Method::GET,
+            &format!("/api/rooms/{room_id}/pois"),
+            "aaa",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,860 @@
+use axum::{extract::Path, Extension, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api_authorize_jwt::Claims,
+    db::{
+        add_room_member, get_room_by_name_from_db, get_room_from_db, get_room_member_role,
+        get_user_role, insert_room, list_room_members, list_rooms_from_db, remove_room_member,
+        set_room_lead, set_room_member_privacy, set_room_member_role,
+    },
+    errors_and_responses::AppError,
+    lead::Lead,
+    presence::MemberPresence,
+    privacy::Privacy,
+    role::{Organiser, RequireRole, RequireRoomRole, Role, Viewer},
+    room::Room,
+    state::SharedState,
+};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ListRooms {
+    rooms: Vec<Room>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateRoomRequest {
+    pub(crate) name: String,
+}
+
+/// Create a new room; the creator automatically joins it, as its organiser
+/// MUST be called by an organiser
+#[axum::debug_handler]
+pub(crate) async fn create_room(
+    Extension(state): Extension<SharedState>,
+    RequireRole { claims, .. }: RequireRole<Organiser>,
+    Json(payload): Json<CreateRoomRequest>,
+) -> Result<Json<Room>, AppError> {
+    let db_pool = state.db_pool.clone();
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+    match get_room_by_name_from_db(&db_pool, &payload.name).await {
+        Ok(None) => {}
+        Ok(Some(_room)) => {
+            tracing::warn!("create_room: room already exists: {:?}", payload.name);
+            return Err(AppError::BadRequest);
+        }
+        Err(err) => {
+            tracing::error!("create_room: db error: {:?}", err,);
+            return Err(AppError::InternalError);
+        }
+    }
+
+    let room = insert_room(&db_pool, &payload.name, &claims.sub)
+        .await
+        .map_err(|err| {
+            tracing::error!("create_room: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+
+    Ok(Json(room))
+}
+
+/// List all rooms
+#[axum::debug_handler]
+pub(crate) async fn list_rooms(
+    Extension(state): Extension<SharedState>,
+    _claims: Claims,
+) -> Result<Json<ListRooms>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let rooms = list_rooms_from_db(&db_pool).await.map_err(|err| {
+        tracing::error!("list_rooms: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(ListRooms { rooms }))
+}
+
+/// Join a given room; this is REQUIRED before connecting to its websockets
+#[axum::debug_handler]
+pub(crate) async fn join_room(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+    Path(room_id): Path<i64>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    match get_room_from_db(&db_pool, room_id).await {
+        Ok(Some(_room)) => {}
+        Ok(None) => {
+            tracing::warn!("join_room: room not found: {room_id}");
+            return Err(AppError::NotFound);
+        }
+        Err(err) => {
+            tracing::error!("join_room: db error: {:?}", err,);
+            return Err(AppError::InternalError);
+        }
+    }
+
+    // a viewer stays a viewer in every room they join
+    let role = match get_user_role(&db_pool, &claims.sub).await {
+        Ok(Role::Viewer) => Role::Viewer,
+        Ok(_role) => Role::Member,
+        Err(err) => {
+            tracing::error!("join_room: db error: {:?}", err,);
+            return Err(AppError::InternalError);
+        }
+    };
+    add_room_member(&db_pool, room_id, &claims.sub, role)
+        .await
+        .map_err(|err| {
+            tracing::error!("join_room: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+
+    Ok(())
+}
+
+/// Leave a given room
+#[axum::debug_handler]
+pub(crate) async fn leave_room(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+    Path(room_id): Path<i64>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    remove_room_member(&db_pool, room_id, &claims.sub)
+        .await
+        .map_err(|err| {
+            tracing::error!("leave_room: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    state.forget_member(room_id, &claims.sub);
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SetMemberRoleRequest {
+    pub(crate) role: Role,
+}
+
+/// Set the role of a given member of a given room; eg make them a co-organiser, or a viewer
+/// MUST be called by an organiser of this room(or an admin); 404 if the user has NOT joined the room
+#[axum::debug_handler]
+pub(crate) async fn set_member_role(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRoomRole<Organiser>,
+    Path((_room_id, username)): Path<(i64, String)>,
+    Json(payload): Json<SetMemberRoleRequest>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    // admin is a global role only, cf `api_user::set_role`
+    if payload.role == Role::Admin {
+        return Err(AppError::BadRequest);
+    }
+
+    let is_found = set_room_member_role(&db_pool, organiser.room_id, &username, payload.role)
+        .await
+        .map_err(|err| {
+            tracing::error!("set_member_role: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    // the role of a socket is checked when it is opened: the client reconnects with the new one
+    state.disconnect_member(organiser.room_id, &username);
+    tracing::info!(
+        "set_member_role: {} set {username} as {} in room {}",
+        organiser.claims.sub,
+        payload.role,
+        organiser.room_id
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RoomPresence {
+    users: Vec<MemberPresence>,
+}
+
+/// The presence(online/idle/offline) of every member of a given room, ordered by username
+/// The changes are then pushed on the "geolocation" socket, cf `WsMessage::Presence`
+/// MUST be called by a member of this room(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn get_room_presence(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<Json<RoomPresence>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let members = list_room_members(&db_pool, room_id).await.map_err(|err| {
+        tracing::error!("get_room_presence: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    let mut users = state.presences.room_presence(room_id, members);
+    let off_route = state.off_route.room_off_route(room_id);
+    for user in &mut users {
+        user.off_route = off_route.get(&user.username).copied();
+    }
+
+    Ok(Json(RoomPresence { users }))
+}
+
+/// The privacy settings of the caller in a given room; cf `privacy.rs`
+/// MUST be called by a member of this room; 404 for an admin that has NOT joined it
+#[axum::debug_handler]
+pub(crate) async fn get_privacy(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole {
+        claims, room_id, ..
+    }: RequireRoomRole<Viewer>,
+) -> Result<Json<Privacy>, AppError> {
+    let privacy = state
+        .privacy(room_id, &claims.sub)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_privacy: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        // eg an admin that has NOT joined it
+        .ok_or(AppError::NotFound)?;
+
+    Ok(Json(privacy))
+}
+
+/// Change the privacy settings of the caller in a given room; eg pause the sharing of their position
+/// It applies immediately to their "geolocation" sockets; and the room is notified, cf `WsMessage::Sharing`
+/// MUST be called by a member of this room; 404 for an admin that has NOT joined it
+#[axum::debug_handler]
+pub(crate) async fn set_privacy(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole {
+        claims, room_id, ..
+    }: RequireRoomRole<Viewer>,
+    Json(payload): Json<Privacy>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    if let Err(err) = payload.validate() {
+        tracing::warn!("set_privacy: {err}");
+        return Err(AppError::BadRequest);
+    }
+
+    let is_found = set_room_member_privacy(&db_pool, room_id, &claims.sub, payload)
+        .await
+        .map_err(|err| {
+            tracing::error!("set_privacy: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    state.set_privacy(room_id, &claims.sub, payload);
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RoomLead {
+    #[serde(flatten)]
+    lead: Lead,
+    /// in meters: how far ahead of the sweeper the leader is along the route; None if unknown, cf `Lead::gap`
+    gap: Option<f64>,
+    /// cf `WsMessage::GroupSpread`
+    is_spread: bool,
+}
+
+/// The leader and the sweeper of a given room, and the gap between them; cf `lead.rs`
+/// The changes are then pushed on the "geolocation" socket, cf `WsMessage::Lead` and `WsMessage::GroupSpread`
+/// MUST be called by a member of this room(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn get_lead(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<Json<RoomLead>, AppError> {
+    let lead = state.lead(room_id).await.map_err(|err| {
+        tracing::error!("get_lead: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(RoomLead {
+        gap: state.room_gap(room_id, &lead),
+        is_spread: state.group_spreads.is_spread(room_id),
+        lead,
+    }))
+}
+
+/// Designate the leader and the sweeper(last rider) of a given room; None to clear them
+/// They MUST be members of the room, and two different riders
+/// MUST be called by an organiser of this room(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn set_lead(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRoomRole<Organiser>,
+    Json(payload): Json<Lead>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+    let room_id = organiser.room_id;
+
+    if payload.leader.is_some() && payload.leader == payload.sweeper {
+        return Err(AppError::BadRequest);
+    }
+    for username in [&payload.leader, &payload.sweeper].into_iter().flatten() {
+        let role = get_room_member_role(&db_pool, room_id, username)
+            .await
+            .map_err(|err| {
+                tracing::error!("set_lead: db error: {:?}", err,);
+                AppError::InternalError
+            })?;
+        if role.is_none() {
+            tracing::warn!("set_lead: {username} is NOT a member of room {room_id}");
+            return Err(AppError::BadRequest);
+        }
+    }
+
+    let is_found = set_room_lead(&db_pool, room_id, &payload)
+        .await
+        .map_err(|err| {
+            tracing::error!("set_lead: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    tracing::info!(
+        "set_lead: {} set {payload:?} in room {room_id}",
+        organiser.claims.sub
+    );
+    state.set_lead(room_id, payload);
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::db::{
+        add_room_member, get_room_member_role, insert_user, is_room_member, setup_db,
+        update_user_to_superuser,
+    };
+
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{self};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use serde_json::Value;
+    use sqlx::SqlitePool;
+    use tower::util::ServiceExt;
+
+    async fn init(username: Option<&str>, should_set_superuser: bool) -> (Router, SqlitePool) {
+        // https://docs.rs/crate/env_logger/latest
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+
+        // INSERT a user if asked
+        if let Some(username) = username {
+            insert_user(&db_pool, username, "bbb").await.unwrap();
+
+            if should_set_superuser {
+                update_user_to_superuser(&db_pool, username).await.unwrap();
+            }
+        }
+
+        (app, db_pool)
+    }
+
+    #[tokio::test]
+    async fn test_create_room_superuser_ok() {
+        let username = "aaa";
+        let (app, db_pool) = init(Some(username), true).await;
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
+
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/rooms")
+                        .method(http::Method::POST)
+                        .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .body(Body::from(json!({ "name": "room1" }).to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            response
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        let response_status = response.status();
+        let response_body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(response_status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&response_body).unwrap();
+        assert_eq!(body["name"], "room1");
+        assert_eq!(body["created_by"], username);
+        // the creator MUST automatically join the room
+        let room_id = body["id"].as_i64().unwrap();
+        assert!(is_room_member(&db_pool, room_id, username).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_create_room_must_be_superuser_else_404() {
+        let username = "aaa";
+        let (app, db_pool) = init(Some(username), false).await;
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
+
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/rooms")
+                        .method(http::Method::POST)
+                        .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .body(Body::from(json!({ "name": "room1" }).to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            response
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_join_then_leave_room_ok() {
+        let (app, db_pool) = init(None, false).await;
+        let room = insert_room(&db_pool, "room1", "root").await.unwrap();
+        // NOTE: "anonymous" users(ie not in the DB) CAN join a room
+        let username = "aaa";
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
+
+            let response_join = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/api/rooms/{}/join", room.id))
+                        .method(http::Method::POST)
+                        .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let is_member_after_join = is_room_member(&db_pool, room.id, username).await.unwrap();
+
+            let response_leave = app
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/api/rooms/{}/leave", room.id))
+                        .method(http::Method::POST)
+                        .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let is_member_after_leave = is_room_member(&db_pool, room.id, username).await.unwrap();
+
+            (
+                response_join,
+                is_member_after_join,
+                response_leave,
+                is_member_after_leave,
+            )
+        };
+
+        let (response_join, is_member_after_join, response_leave, is_member_after_leave) =
+            temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(response_join.status(), StatusCode::OK);
+        assert!(is_member_after_join);
+        assert_eq!(response_leave.status(), StatusCode::OK);
+        assert!(!is_member_after_leave);
+    }
+
+    #[tokio::test]
+    async fn test_join_non_existent_room_404() {
+        let (app, db_pool) = init(None, false).await;
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/rooms/42/join")
+                        .method(http::Method::POST)
+                        .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            response
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_list_rooms_ok() {
+        let (app, db_pool) = init(None, false).await;
+        insert_room(&db_pool, "room1", "root").await.unwrap();
+        insert_room(&db_pool, "room2", "root").await.unwrap();
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/rooms")
+                        .method(http::Method::GET)
+                        .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            response
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        let response_status = response.status();
+        let response_body = response.into_body().collect().await.unwrap().to_bytes();
+
+        assert_eq!(response_status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&response_body).unwrap();
+        let resp_rooms: Vec<Value> = body["rooms"].as_array().unwrap().to_vec();
+        assert_eq!(resp_rooms.len(), 2);
+        assert_eq!(resp_rooms[0]["name"], "room1");
+        assert_eq!(resp_rooms[1]["name"], "room2");
+    }
+
+    /// Only an organiser of the room can change the roles of its members
+    #[tokio::test]
+    async fn test_set_member_role() {
+        let (app, db_pool) = init(None, false).await;
+        let room = insert_room(&db_pool, "room1", "orga").await.unwrap();
+        let other_room = insert_room(&db_pool, "room2", "other_orga").await.unwrap();
+        add_room_member(&db_pool, room.id, "aaa", Role::Member)
+            .await
+            .unwrap();
+
+        let f = async {
+            let mut statuses = vec![];
+            for (caller, room_id, username, role) in [
+                ("orga", room.id, "aaa", "viewer"),
+                ("orga", room.id, "unknown", "viewer"),
+                ("orga", room.id, "aaa", "admin"),
+                // NOT an organiser of this room
+                ("aaa", room.id, "orga", "viewer"),
+                ("other_orga", room.id, "aaa", "organiser"),
+                ("orga", other_room.id, "other_orga", "viewer"),
+            ] {
+                let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, caller).await;
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/api/rooms/{room_id}/members/{username}"))
+                            .method(http::Method::PUT)
+                            .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                            .body(Body::from(json!({ "role": role }).to_string()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                statuses.push(response.status());
+            }
+
+            statuses
+        };
+
+        let statuses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+                StatusCode::BAD_REQUEST,
+                StatusCode::NOT_FOUND,
+                StatusCode::NOT_FOUND,
+                StatusCode::NOT_FOUND,
+            ]
+        );
+        assert_eq!(
+            get_room_member_role(&db_pool, room.id, "aaa")
+                .await
+                .unwrap(),
+            Some(Role::Viewer)
+        );
+        assert_eq!(
+            get_room_member_role(&db_pool, room.id, "orga")
+                .await
+                .unwrap(),
+            Some(Role::Organiser)
+        );
+    }
+
+    /// Every member is listed, even the ones that never connected; and only the members can see it
+    #[tokio::test]
+    async fn test_get_room_presence() {
+        let (_app, db_pool) = init(None, false).await;
+        let room = insert_room(&db_pool, "room1", "orga").await.unwrap();
+        add_room_member(&db_pool, room.id, "aaa", Role::Member)
+            .await
+            .unwrap();
+        let app_state = crate::state::new_state(db_pool.clone(), crate::state::Config::default());
+        let app = crate::new_app_with_state(app_state.clone());
+        app_state.presences.connect(room.id, "aaa", 1_000);
+        // NOT a member of this room
+        app_state.presences.connect(room.id + 1, "orga", 2_000);
+
+        let f = async {
+            let mut responses = vec![];
+            for caller in ["aaa", "bbb"] {
+                let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, caller).await;
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/api/rooms/{}/presence", room.id))
+                            .method(http::Method::GET)
+                            .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                responses.push((status, body));
+            }
+
+            responses
+        };
+
+        let responses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(responses[0].0, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&responses[0].1).unwrap();
+        assert_eq!(
+            body,
+            json!({ "users": [
+                { "username": "aaa", "status": "online", "last_seen": 1_000, "sharing": "sharing", "off_route": null },
+                { "username": "orga", "status": "offline", "last_seen": null, "sharing": "sharing", "off_route": null },
+            ]})
+        );
+        assert_eq!(responses[1].0, StatusCode::NOT_FOUND);
+    }
+
+    /// Each member sets their own privacy settings; and only in the rooms they joined
+    #[tokio::test]
+    async fn test_get_and_set_privacy() {
+        let (app, db_pool) = init(None, false).await;
+        let room = insert_room(&db_pool, "room1", "orga").await.unwrap();
+        add_room_member(&db_pool, room.id, "aaa", Role::Member)
+            .await
+            .unwrap();
+
+        let f = async {
+            let mut responses = vec![];
+            for (caller, method, body) in [
+                ("aaa", http::Method::GET, None),
+                (
+                    "aaa",
+                    http::Method::PUT,
+                    Some(json!({ "mode": "paused", "precision": 100 })),
+                ),
+                (
+                    "aaa",
+                    http::Method::PUT,
+                    Some(json!({ "mode": "paused", "precision": 0 })),
+                ),
+                ("aaa", http::Method::PUT, Some(json!({ "mode": "unknown" }))),
+                ("aaa", http::Method::GET, None),
+                // NOT a member of this room
+                ("bbb", http::Method::PUT, Some(json!({ "mode": "hidden" }))),
+                // the others are NOT affected
+                ("orga", http::Method::GET, None),
+            ] {
+                let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, caller).await;
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/api/rooms/{}/privacy", room.id))
+                            .method(method)
+                            .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                            .body(
+                                body.map_or_else(Body::empty, |body| Body::from(body.to_string())),
+                            )
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                responses.push((status, body));
+            }
+
+            responses
+        };
+
+        let responses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        let statuses: Vec<StatusCode> = responses.iter().map(|(status, _body)| *status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::BAD_REQUEST,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+                StatusCode::OK,
+            ]
+        );
+        let bodies: Vec<Value> = [0, 4, 6]
+            .into_iter()
+            .map(|i| serde_json::from_slice(&responses[i].1).unwrap())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                json!({ "mode": "sharing", "precision": null }),
+                json!({ "mode": "paused", "precision": 100 }),
+                json!({ "mode": "sharing", "precision": null }),
+            ]
+        );
+    }
+
+    /// Only an organiser designates the leader and the sweeper, among the members; and they are cleared when they leave
+    #[tokio::test]
+    async fn test_get_and_set_lead() {
+        let (app, db_pool) = init(None, false).await;
+        let room = insert_room(&db_pool, "room1", "orga").await.unwrap();
+        add_room_member(&db_pool, room.id, "aaa", Role::Member)
+            .await
+            .unwrap();
+
+        let f = async {
+            let mut responses = vec![];
+            for (caller, method, path, body) in [
+                (
+                    "orga",
+                    http::Method::PUT,
+                    "lead",
+                    Some(json!({ "leader": "aaa", "sweeper": "orga" })),
+                ),
+                (
+                    "orga",
+                    http::Method::PUT,
+                    "lead",
+                    Some(json!({ "leader": "aaa", "sweeper": "aaa" })),
+                ),
+                // NOT a member of this room
+                (
+                    "orga",
+                    http::Method::PUT,
+                    "lead",
+                    Some(json!({ "leader": "bbb" })),
+                ),
+                // NOT an organiser of this room
+                (
+                    "aaa",
+                    http::Method::PUT,
+                    "lead",
+                    Some(json!({ "leader": "aaa" })),
+                ),
+                ("aaa", http::Method::GET, "lead", None),
+                ("bbb", http::Method::GET, "lead", None),
+                ("aaa", http::Method::POST, "leave", None),
+                ("orga", http::Method::GET, "lead", None),
+            ] {
+                let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, caller).await;
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/api/rooms/{}/{path}", room.id))
+                            .method(method)
+                            .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                            .body(
+                                body.map_or_else(Body::empty, |body| Body::from(body.to_string())),
+                            )
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                responses.push((status, body));
+            }
+
+            responses
+        };
+
+        let responses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        let statuses: Vec<StatusCode> = responses.iter().map(|(status, _body)| *status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::BAD_REQUEST,
+                StatusCode::BAD_REQUEST,
+                StatusCode::NOT_FOUND,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+                StatusCode::OK,
+                StatusCode::OK,
+            ]
+        );
+        let bodies: Vec<Value> = [4, 7]
+            .into_iter()
+            .map(|i| serde_json::from_slice(&responses[i].1).unwrap())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                json!({ "leader": "aaa", "sweeper": "orga", "gap": null, "is_spread": false }),
+                json!({ "leader": null, "sweeper": "orga", "gap": null, "is_spread": false }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,941 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension, Json,
+};
+use protocol::WsMessage;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    api_authorize_jwt::Claims,
+    api_track::safe_file_name,
+    db::{
+        delete_route, get_room_from_db, get_room_member_role, get_route_from_db,
+        get_route_original_from_db, get_user_role, list_rooms_from_db,
+        list_rooms_with_active_route, list_routes_from_db, rename_route, set_room_active_route,
+    },
+    errors_and_responses::AppError,
+    progress::{leaderboard, RiderProgress},
+    role::{Organiser, RequireRole, RequireRoomRole, Role, Viewer},
+    route::Route,
+    route_formats::RouteFormat,
+    route_profile::Profile,
+    state::SharedState,
+    ws_handler::broadcast_message,
+};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ListRoutes {
+    routes: Vec<Route>,
+}
+
+/// cf `get_room_progress`
+#[derive(Debug, Serialize)]
+pub(crate) struct RoomProgress {
+    /// in meters
+    route_length: f64,
+    /// the furthest along the route first
+    riders: Vec<RiderProgress>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RenameRouteRequest {
+    pub(crate) name: String,
+}
+
+/// cf `get_route_profile`
+#[derive(Deserialize)]
+pub(crate) struct ProfileQuery {
+    /// downsample to at most that many points; cf `Profile::downsample`
+    max_points: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SetRoomRouteRequest {
+    /// None to clear the active route of the room
+    pub(crate) route_id: Option<i64>,
+}
+
+/// A route can only be modified by the organiser who uploaded it, or by an admin; else `AppError::NotFound`
+pub(crate) async fn check_can_edit_route(
+    db_pool: &SqlitePool,
+    organiser: &RequireRole<Organiser>,
+    route_id: i64,
+    caller: &str,
+) -> Result<(), AppError> {
+    match get_route_from_db(db_pool, route_id).await {
+        Ok(Some(route))
+            if organiser.role == Role::Admin || route.uploaded_by == organiser.claims.sub =>
+        {
+            Ok(())
+        }
+        Ok(Some(_route)) => {
+            tracing::error!(
+                "{caller}: {:?} is NOT the uploader of route {route_id}",
+                organiser.claims.sub
+            );
+            Err(AppError::NotFound)
+        }
+        Ok(None) => Err(AppError::NotFound),
+        Err(err) => {
+            tracing::error!("{caller}: db error: {:?}", err);
+            Err(AppError::InternalError)
+        }
+    }
+}
+
+/// A route can be viewed by the organisers; and by the members of the rooms where it is active, with any role;
+/// else `AppError::NotFound`
+async fn check_can_view_route(
+    db_pool: &SqlitePool,
+    username: &str,
+    route_id: i64,
+    caller: &str,
+) -> Result<(), AppError> {
+    let db_error = |err| {
+        tracing::error!("{caller}: db error: {:?}", err);
+        AppError::InternalError
+    };
+
+    if get_user_role(db_pool, username).await.map_err(db_error)? >= Role::Organiser {
+        return Ok(());
+    }
+    for room_id in list_rooms_with_active_route(db_pool, route_id)
+        .await
+        .map_err(db_error)?
+    {
+        if get_room_member_role(db_pool, room_id, username)
+            .await
+            .map_err(db_error)?
+            .is_some()
+        {
+            return Ok(());
+        }
+    }
+
+    tracing::warn!("{caller}: {username:?} can NOT view route {route_id}");
+    Err(AppError::NotFound)
+}
+
+/// Tell the clients connected to a given room to fetch its route again; cf `get_room_route`
+/// NOOP if nobody is connected to this room
+pub(crate) fn notify_route_updated(state: &SharedState, room_id: i64) {
+    if let Some(room_channels) = state.existing_room_channels(room_id) {
+        broadcast_message(
+            &room_channels.location_broadcast_sender,
+            &WsMessage::RouteUpdated,
+        );
+    }
+}
+
+/// The `GeoJSON` string is already serialized; no need to go through `Json`
+fn geojson_response(geojson: Bytes) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], geojson)
+}
+
+/// List all the uploaded routes
+/// MUST be called by an organiser
+#[axum::debug_handler]
+pub(crate) async fn list_routes(
+    Extension(state): Extension<SharedState>,
+    _organiser: RequireRole<Organiser>,
+) -> Result<Json<ListRoutes>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let routes = list_routes_from_db(&db_pool).await.map_err(|err| {
+        tracing::error!("list_routes: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(ListRoutes { routes }))
+}
+
+/// Get the `GeoJSON` of a given route
+/// MUST be called by an organiser; the members of a room SHOULD use `get_room_route` instead
+#[axum::debug_handler]
+pub(crate) async fn get_route(
+    Extension(state): Extension<SharedState>,
+    _organiser: RequireRole<Organiser>,
+    Path(route_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let geojson = state
+        .route_geojson(route_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(geojson_response(geojson))
+}
+
+/// Download the original file of a given route, as uploaded(GPX, KML, etc): at full resolution
+/// NOTE: the `GeoJSON` of `get_route` is simplified, cf `route_simplify.rs`
+/// MUST be called by an organiser
+#[axum::debug_handler]
+pub(crate) async fn get_route_original(
+    Extension(state): Extension<SharedState>,
+    _organiser: RequireRole<Organiser>,
+    Path(route_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let db_error = |err| {
+        tracing::error!("get_route_original: db error: {:?}", err,);
+        AppError::InternalError
+    };
+    let route = get_route_from_db(&state.db_pool, route_id)
+        .await
+        .map_err(db_error)?
+        .ok_or(AppError::NotFound)?;
+    let (original, format) = get_route_original_from_db(&state.db_pool, route_id)
+        .await
+        .map_err(db_error)?
+        .ok_or(AppError::NotFound)?;
+
+    // NOTE: the routes uploaded before the format was stored are sniffed again
+    let (content_type, extension) = format
+        .or_else(|| RouteFormat::detect(None, None, &original).ok())
+        .map_or(("application/octet-stream", "bin"), |format| {
+            (format.content_type(), format.extension())
+        });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}.{extension}\"",
+                    safe_file_name(&route.name)
+                ),
+            ),
+        ],
+        original,
+    ))
+}
+
+/// Rename a given route
+/// MUST be called by the organiser who uploaded it(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn patch_route(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(route_id): Path<i64>,
+    Json(payload): Json<RenameRouteRequest>,
+) -> Result<Json<Route>, AppError> {
+    let db_pool = state.db_pool.clone();
+    check_can_edit_route(&db_pool, &organiser, route_id, "patch_route").await?;
+
+    if payload.name.trim().is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    let is_found = rename_route(&db_pool, route_id, &payload.name)
+        .await
+        .map_err(|err| {
+            tracing::error!("patch_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+
+    let route = get_route_from_db(&db_pool, route_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("patch_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(Json(route))
+}
+
+/// Delete a given route; the rooms where it was active are notified
+/// MUST be called by the organiser who uploaded it(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn delete_route_handler(
+    Extension(state): Extension<SharedState>,
+    organiser: RequireRole<Organiser>,
+    Path(route_id): Path<i64>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+    check_can_edit_route(&db_pool, &organiser, route_id, "delete_route").await?;
+
+    let room_ids = list_rooms_with_active_route(&db_pool, route_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("delete_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+
+    let is_found = delete_route(&db_pool, route_id).await.map_err(|err| {
+        tracing::error!("delete_route: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    state.forget_route(route_id).await;
+
+    for room_id in room_ids {
+        state.forget_room_route(room_id);
+        notify_route_updated(&state, room_id);
+    }
+
+    Ok(())
+}
+
+/// Set(or clear) the route displayed to the members of a given room; they are notified
+/// MUST be called by an organiser of this room(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn set_room_route(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Organiser>,
+    Json(payload): Json<SetRoomRouteRequest>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    match get_room_from_db(&db_pool, room_id).await {
+        Ok(Some(_room)) => {}
+        Ok(None) => return Err(AppError::NotFound),
+        Err(err) => {
+            tracing::error!("set_room_route: db error: {:?}", err,);
+            return Err(AppError::InternalError);
+        }
+    }
+    if let Some(route_id) = payload.route_id {
+        match get_route_from_db(&db_pool, route_id).await {
+            Ok(Some(_route)) => {}
+            Ok(None) => return Err(AppError::NotFound),
+            Err(err) => {
+                tracing::error!("set_room_route: db error: {:?}", err,);
+                return Err(AppError::InternalError);
+            }
+        }
+    }
+
+    set_room_active_route(&db_pool, room_id, payload.route_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("set_room_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+
+    state.forget_room_route(room_id);
+    notify_route_updated(&state, room_id);
+
+    Ok(())
+}
+
+/// Get the `GeoJSON` of the active route of a given room
+/// MUST be called by a member of the room, with any role(or an admin); 404 if there is no active route
+#[axum::debug_handler]
+pub(crate) async fn get_room_route(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<impl IntoResponse, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let room = get_room_from_db(&db_pool, room_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_room_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+    let route_id = room.active_route_id.ok_or(AppError::NotFound)?;
+
+    let geojson = state
+        .route_geojson(route_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_room_route: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(geojson_response(geojson))
+}
+
+/// Get the `GeoJSON` of the most recently uploaded route that the caller can view, cf `check_can_view_route`;
+/// 404 if there is none
+/// NOTE: `GET /api/gpx` from before the route library, when only one route could be uploaded; the clients
+/// SHOULD use `get_room_route` instead
+#[axum::debug_handler]
+pub(crate) async fn get_latest_route(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+) -> Result<impl IntoResponse, AppError> {
+    let db_pool = state.db_pool.clone();
+    let db_error = |err| {
+        tracing::error!("get_latest_route: db error: {:?}", err);
+        AppError::InternalError
+    };
+
+    // most recent first
+    let routes = list_routes_from_db(&db_pool).await.map_err(db_error)?;
+    let route_id = if get_user_role(&db_pool, &claims.sub)
+        .await
+        .map_err(db_error)?
+        >= Role::Organiser
+    {
+        routes.first().map(|route| route.id)
+    } else {
+        let mut active_route_ids = Vec::new();
+        for room in list_rooms_from_db(&db_pool).await.map_err(db_error)? {
+            let Some(route_id) = room.active_route_id else {
+                continue;
+            };
+            if get_room_member_role(&db_pool, room.id, &claims.sub)
+                .await
+                .map_err(db_error)?
+                .is_some()
+            {
+                active_route_ids.push(route_id);
+            }
+        }
+        routes
+            .iter()
+            .map(|route| route.id)
+            .find(|route_id| active_route_ids.contains(route_id))
+    }
+    .ok_or(AppError::NotFound)?;
+
+    let geojson = state
+        .route_geojson(route_id)
+        .await
+        .map_err(db_error)?
+        .ok_or(AppError::NotFound)?;
+
+    Ok(geojson_response(geojson))
+}
+
+/// The progress of the riders of a given room along its active route, ie the leaderboard; cf `progress.rs`
+/// MUST be called by a member of the room, with any role(or an admin); 404 if there is no active route
+#[axum::debug_handler]
+pub(crate) async fn get_room_progress(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+) -> Result<Json<RoomProgress>, AppError> {
+    let route_line = state.room_route_line(room_id).await.map_err(|err| {
+        tracing::error!("get_room_progress: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+    if route_line.is_empty() {
+        return Err(AppError::NotFound);
+    }
+
+    let positions = state.last_positions(room_id).await.map_err(|err| {
+        tracing::error!("get_room_progress: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(RoomProgress {
+        route_length: route_line.length(),
+        riders: leaderboard(positions),
+    }))
+}
+
+/// The elevation profile of a given route, from its original file(cf `route_profile.rs`);
+/// downsampled with `?max_points=N`, eg for a chart
+/// MUST be called by an organiser; or by a member of a room where this route is active
+#[axum::debug_handler]
+pub(crate) async fn get_route_profile(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+    Path(route_id): Path<i64>,
+    Query(query): Query<ProfileQuery>,
+) -> Result<Json<Profile>, AppError> {
+    check_can_view_route(&state.db_pool, &claims.sub, route_id, "get_route_profile").await?;
+
+    let profile = state
+        .route_profile(route_id)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_route_profile: error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?
+        .map_err(|err| {
+            tracing::warn!("get_route_profile: route {route_id}: {err}");
+            AppError::from(err)
+        })?;
+
+    Ok(Json(match query.max_points {
+        Some(max_points) => profile.downsample(max_points),
+        None => Profile::clone(&profile),
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::db::{
+        add_room_member, insert_room, insert_route, insert_user, set_room_active_route, setup_db,
+        update_user_role, update_user_to_superuser,
+    };
+    use crate::route_formats::RouteFormat;
+    use crate::route_stats::RouteStats;
+
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{self};
+    use axum::http::{Request, StatusCode};
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use serde_json::Value;
+    use tower::util::ServiceExt;
+
+    /// INSERT a superuser "root", a room and a route
+    pub(crate) async fn init() -> (Router, SqlitePool, i64, Route) {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+
+        insert_user(&db_pool, "root", "bbb").await.unwrap();
+        update_user_to_superuser(&db_pool, "root").await.unwrap();
+        let room = insert_room(&db_pool, "room1", "root").await.unwrap();
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "root",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            r#"{"type":"GeometryCollection","geometries":[]}"#,
+            &RouteStats {
+                distance: 42.0,
+                bbox: [2.0, 48.0, 2.1, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        (app, db_pool, room.id, route)
+    }
+
+    pub(crate) async fn send(
+        app: Router,
+        db_pool: &SqlitePool,
+        method: http::Method,
+        uri: &str,
+        username: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(db_pool, username).await;
+
+            let request = Request::builder()
+                .uri(uri)
+                .method(method)
+                .header(http::header::AUTHORIZATION, format!("Bearer {}", token));
+            let request = match body {
+                Some(body) => request
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(body.to_string())),
+                None => request.body(Body::empty()),
+            };
+
+            app.oneshot(request.unwrap()).await.unwrap()
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_list_and_rename_routes_ok() {
+        let (app, db_pool, _room_id, route) = init().await;
+
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::PATCH,
+            &format!("/api/routes/{}", route.id),
+            "root",
+            Some(json!({ "name": "renamed" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["name"], "renamed");
+
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            "/api/routes",
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["routes"][0]["name"], "renamed");
+        assert_eq!(body["routes"][0]["distance"], 42.0);
+        assert_eq!(body["routes"][0]["bbox"], json!([2.0, 48.0, 2.1, 48.1]));
+
+        // not JSON
+        let (status, _body) = send(
+            app,
+            &db_pool,
+            http::Method::GET,
+            &format!("/api/routes/{}/original", route.id),
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_routes_must_be_superuser_else_404() {
+        let (app, db_pool, room_id, route) = init().await;
+        add_room_member(&db_pool, room_id, "aaa", Role::Member)
+            .await
+            .unwrap();
+
+        for (method, uri, body) in [
+            (http::Method::GET, "/api/routes".to_string(), None),
+            (http::Method::GET, format!("/api/routes/{}", route.id), None),
+            (
+                http::Method::GET,
+                format!("/api/routes/{}/original", route.id),
+                None,
+            ),
+            (
+                http::Method::PATCH,
+                format!("/api/routes/{}", route.id),
+                Some(json!({ "name": "renamed" })),
+            ),
+            (
+                http::Method::DELETE,
+                format!("/api/routes/{}", route.id),
+                None,
+            ),
+            (
+                http::Method::POST,
+                format!("/api/rooms/{room_id}/route"),
+                Some(json!({ "route_id": route.id })),
+            ),
+        ] {
+            let (status, _body) =
+                send(app.clone(), &db_pool, method.clone(), &uri, "aaa", body).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{method} {uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_and_get_room_route_ok() {
+        let (app, db_pool, room_id, route) = init().await;
+        add_room_member(&db_pool, room_id, "aaa", Role::Member)
+            .await
+            .unwrap();
+
+        // no active route yet
+        let uri = format!("/api/rooms/{room_id}/route");
+        let (status, _body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "aaa", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::POST,
+            &uri,
+            "root",
+            Some(json!({ "route_id": route.id })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "aaa", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["type"], "GeometryCollection");
+
+        // NOT a member of the room
+        let (status, _body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "bbb", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // deleting the route MUST also clear it from the room
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::DELETE,
+            &format!("/api/routes/{}", route.id),
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _body) = send(app, &db_pool, http::Method::GET, &uri, "aaa", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// `GET /api/gpx`: the last uploaded route, as long as the caller can view it
+    #[tokio::test]
+    async fn test_get_latest_route() {
+        let (app, db_pool, room_id, route) = init().await;
+        add_room_member(&db_pool, room_id, "aaa", Role::Member)
+            .await
+            .unwrap();
+
+        let (status, body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            "/api/gpx",
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["type"], "GeometryCollection");
+
+        // NOT active in any of their rooms
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            "/api/gpx",
+            "aaa",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        set_room_active_route(&db_pool, room_id, Some(route.id))
+            .await
+            .unwrap();
+        let (status, body) = send(app, &db_pool, http::Method::GET, "/api/gpx", "aaa", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["type"], "GeometryCollection");
+    }
+
+    #[tokio::test]
+    async fn test_get_room_progress() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = crate::state::new_state(db_pool.clone(), crate::state::Config::default());
+        let app = crate::new_app_with_state(state.clone());
+        insert_user(&db_pool, "root", "bbb").await.unwrap();
+        update_user_to_superuser(&db_pool, "root").await.unwrap();
+        let room = insert_room(&db_pool, "room1", "root").await.unwrap();
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "root",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            r#"{"type":"LineString","coordinates":[[2.0,48.0],[2.0,48.1]]}"#,
+            &RouteStats {
+                distance: 11_000.0,
+                bbox: [2.0, 48.0, 2.0, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+        let uri = format!("/api/rooms/{}/progress", room.id);
+
+        // no active route
+        let (status, _body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        set_room_active_route(&db_pool, room.id, Some(route.id))
+            .await
+            .unwrap();
+        state.forget_room_route(room.id);
+        let route_line = state.room_route_line(room.id).await.unwrap();
+        for (username, lat) in [("aaa", 48.02), ("bbb", 48.05)] {
+            let mut position = protocol::Position {
+                username: username.to_string(),
+                lat,
+                lng: 2.0,
+                accuracy: None,
+                altitude: None,
+                heading: None,
+                speed: None,
+                timestamp: crate::db::now_timestamp(),
+                progress: None,
+            };
+            let projection = route_line.project((lat, 2.0)).unwrap();
+            position.progress = Some(state.progresses.update(
+                room.id,
+                &position,
+                &route_line,
+                projection,
+            ));
+            state.update_last_position(room.id, position);
+        }
+
+        let (status, body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!((body["route_length"].as_f64().unwrap() - 11_119.5).abs() < 1.0);
+        assert_eq!(body["riders"][0]["username"], "bbb");
+        assert_eq!(body["riders"][0]["gap_to_leader"], 0.0);
+        assert_eq!(body["riders"][1]["username"], "aaa");
+        assert!((body["riders"][1]["percent"].as_f64().unwrap() - 20.0).abs() < 0.1);
+
+        // a new route: nobody has made any progress on it yet
+        state.forget_room_route(room.id);
+        let (status, body) = send(app, &db_pool, http::Method::GET, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["riders"], json!([]));
+    }
+
+    /// An organiser of one room can NOT act on another room; nor on the routes of another organiser
+    #[tokio::test]
+    async fn test_organiser_is_limited_to_their_rooms_and_routes() {
+        let (app, db_pool, room_id, route) = init().await;
+        insert_user(&db_pool, "orga", "bbb").await.unwrap();
+        update_user_role(&db_pool, "orga", Role::Organiser)
+            .await
+            .unwrap();
+        let other_room = insert_room(&db_pool, "room2", "orga").await.unwrap();
+
+        // the library is shared
+        let (status, _body) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            "/api/routes",
+            "orga",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        for (method, uri, body, expected_status) in [
+            (
+                http::Method::POST,
+                format!("/api/rooms/{}/route", other_room.id),
+                Some(json!({ "route_id": route.id })),
+                StatusCode::OK,
+            ),
+            (
+                http::Method::POST,
+                format!("/api/rooms/{room_id}/route"),
+                Some(json!({ "route_id": route.id })),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                http::Method::PATCH,
+                format!("/api/routes/{}", route.id),
+                Some(json!({ "name": "renamed" })),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                http::Method::DELETE,
+                format!("/api/routes/{}", route.id),
+                None,
+                StatusCode::NOT_FOUND,
+            ),
+        ] {
+            let (status, _body) =
+                send(app.clone(), &db_pool, method.clone(), &uri, "orga", body).await;
+            assert_eq!(status, expected_status, "{method} {uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_route_profile() {
+        let (app, db_pool, room_id, _route) = init().await;
+        let gpx = include_bytes!("../tests/data/2024-02-19_1444960792_MJ 19_02.gpx");
+        let route = insert_route(
+            &db_pool,
+            "route2",
+            "root",
+            gpx,
+            RouteFormat::Gpx,
+            "{}",
+            &RouteStats::default(),
+        )
+        .await
+        .unwrap();
+        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+        add_room_member(&db_pool, room_id, "aaa", Role::Viewer)
+            .await
+            .unwrap();
+        let uri = format!("/api/routes/{}/profile", route.id);
+
+        let (status, profile) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "root", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let length = profile["length"].as_f64().unwrap();
+        let points = profile["points"].as_array().unwrap();
+        assert!(points.len() > 100, "{}", points.len());
+        assert_eq!(points[0]["distance"], 0.0);
+        assert!(points[points.len() - 1]["distance"].as_f64().unwrap() <= length);
+
+        let (status, downsampled) = send(
+            app.clone(),
+            &db_pool,
+            http::Method::GET,
+            &format!("{uri}?max_points=100"),
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(downsampled["points"].as_array().unwrap().len(), 100);
+        assert_eq!(downsampled["length"], profile["length"]);
+
+        // a member of the room: only once the route is active in it
+        let (status, _body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "aaa", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        set_room_active_route(&db_pool, room_id, Some(route.id))
+            .await
+            .unwrap();
+        let (status, body) =
+            send(app.clone(), &db_pool, http::Method::GET, &uri, "aaa", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, profile);
+
+        let (status, _body) = send(
+            app,
+            &db_pool,
+            http::Method::GET,
+            "/api/routes/12345/profile",
+            "root",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// eg a corrupted DB: NOT an internal error; and the next requests do NOT parse it again
+    #[tokio::test]
+    async fn test_get_route_profile_corrupt_422() {
+        let (app, db_pool, _room_id, _route) = init().await;
+        let route = insert_route(
+            &db_pool,
+            "corrupt",
+            "root",
+            b"<gpx><trk><trkseg><trkpt",
+            RouteFormat::Gpx,
+            "{}",
+            &RouteStats::default(),
+        )
+        .await
+        .unwrap();
+        let uri = format!("/api/routes/{}/profile", route.id);
+
+        for _ in 0..2 {
+            let (status, _body) =
+                send(app.clone(), &db_pool, http::Method::GET, &uri, "root", None).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+}
@@ -0,0 +1,572 @@
+use axum::{
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+    Extension, Json,
+};
+use protocol::Position;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+
+use crate::{
+    api_authorize_jwt::Claims,
+    db::{get_user_role, list_positions_from_db, list_room_positions_from_db, list_visible_rooms},
+    errors_and_responses::AppError,
+    role::{RequireRoomRole, Role, Viewer},
+    state::SharedState,
+    track_gpx::{write_gpx, Track, GPX_CONTENT_TYPE},
+};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TrackQuery {
+    /// milliseconds since UNIX epoch; inclusive
+    from: Option<i64>,
+    /// milliseconds since UNIX epoch; inclusive
+    to: Option<i64>,
+}
+
+/// Get the position history of a given user, as a `GeoJSON` `FeatureCollection`
+/// There is one `Feature` per room: a `LineString`, or a `Point` if only one position was recorded.
+/// The properties contain the timestamps of each point, in the same order as the coordinates.
+/// Only the rooms the caller can see, cf `list_user_positions`
+#[axum::debug_handler]
+pub(crate) async fn get_user_track(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+    Path(username): Path<String>,
+    Query(query): Query<TrackQuery>,
+) -> Result<Json<Value>, AppError> {
+    let positions = list_user_positions(
+        &state.db_pool,
+        &claims.sub,
+        &username,
+        &query,
+        "get_user_track",
+    )
+    .await?;
+
+    Ok(Json(positions_to_feature_collection(&username, positions)))
+}
+
+/// Same as `get_user_track`, but as a GPX 1.1 file: one `<trkseg>` per room; cf `track_gpx.rs`
+/// Only the rooms the caller can see, cf `list_user_positions`
+#[axum::debug_handler]
+pub(crate) async fn get_user_track_gpx(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+    Path(username): Path<String>,
+    Query(query): Query<TrackQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let positions = list_user_positions(
+        &state.db_pool,
+        &claims.sub,
+        &username,
+        &query,
+        "get_user_track_gpx",
+    )
+    .await?;
+    let file_name = format!("{}.gpx", safe_file_name(&username));
+    let track = Track {
+        segments: group_by_room(positions)
+            .into_iter()
+            .map(|(_room_id, positions)| positions)
+            .collect(),
+        name: username,
+    };
+
+    Ok(gpx_response(write_gpx(&[track]), &file_name))
+}
+
+/// The position history of everyone in a given room, as a GPX 1.1 file: one `<trk>` per user; cf `track_gpx.rs`
+/// The hidden members are NOT exported.
+/// MUST be called by a member of the room, with any role(or an admin)
+#[axum::debug_handler]
+pub(crate) async fn get_room_track_gpx(
+    Extension(state): Extension<SharedState>,
+    RequireRoomRole { room_id, .. }: RequireRoomRole<Viewer>,
+    Query(query): Query<TrackQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let positions = list_room_positions_from_db(&db_pool, room_id, query.from, query.to)
+        .await
+        .map_err(|err| {
+            tracing::error!("get_room_track_gpx: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    // NOTE: sorted by username
+    let mut tracks: Vec<Track> = vec![];
+    for position in positions {
+        match tracks.last_mut() {
+            Some(track) if track.name == position.username => track.segments[0].push(position),
+            _ => tracks.push(Track {
+                name: position.username.clone(),
+                segments: vec![vec![position]],
+            }),
+        }
+    }
+
+    Ok(gpx_response(
+        write_gpx(&tracks),
+        &format!("room_{room_id}.gpx"),
+    ))
+}
+
+/// The position history of a given user, as seen by the caller:
+/// - all of it for themselves, or for an admin
+/// - otherwise, only the rooms both have joined, and where that user is NOT hidden(cf `list_visible_rooms`);
+///   `AppError::NotFound` if there is none, ie the same as an unknown user
+async fn list_user_positions(
+    db_pool: &SqlitePool,
+    caller: &str,
+    username: &str,
+    query: &TrackQuery,
+    fn_name: &str,
+) -> Result<Vec<(i64, Position)>, AppError> {
+    let db_error = |err| {
+        tracing::error!("{fn_name}: db error: {:?}", err);
+        AppError::InternalError
+    };
+
+    let visible_rooms = if caller == username
+        || get_user_role(db_pool, caller).await.map_err(db_error)? == Role::Admin
+    {
+        None
+    } else {
+        let rooms = list_visible_rooms(db_pool, caller, username)
+            .await
+            .map_err(db_error)?;
+        if rooms.is_empty() {
+            tracing::warn!("{fn_name}: {caller:?} can NOT view the track of {username:?}");
+            return Err(AppError::NotFound);
+        }
+        Some(rooms)
+    };
+    let positions = list_positions_from_db(db_pool, username, query.from, query.to)
+        .await
+        .map_err(db_error)?;
+
+    Ok(match visible_rooms {
+        Some(rooms) => positions
+            .into_iter()
+            .filter(|(room_id, _)| rooms.contains(room_id))
+            .collect(),
+        None => positions,
+    })
+}
+
+/// The usernames are free text: keep only what is safe in a `Content-Disposition` header
+pub(crate) fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn gpx_response(gpx: String, file_name: &str) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, GPX_CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        gpx,
+    )
+}
+
+/// NOTE: `positions` MUST be sorted by timestamp, cf `list_positions_from_db`
+/// returns: the positions of each room, in the order of first appearance
+fn group_by_room(positions: Vec<(i64, Position)>) -> Vec<(i64, Vec<Position>)> {
+    let mut rooms: Vec<(i64, Vec<Position>)> = vec![];
+    for (room_id, position) in positions {
+        match rooms.iter_mut().find(|(id, _)| *id == room_id) {
+            Some((_, room_positions)) => room_positions.push(position),
+            None => rooms.push((room_id, vec![position])),
+        }
+    }
+
+    rooms
+}
+
+/// NOTE: `positions` MUST be sorted by timestamp, cf `list_positions_from_db`
+fn positions_to_feature_collection(username: &str, positions: Vec<(i64, Position)>) -> Value {
+    let rooms = group_by_room(positions);
+
+    let features: Vec<Value> = rooms
+        .into_iter()
+        .map(|(room_id, positions)| {
+            // NOTE: GeoJSON is [lng, lat]
+            let coordinates: Vec<[f64; 2]> = positions.iter().map(|p| [p.lng, p.lat]).collect();
+            let timestamps: Vec<i64> = positions.iter().map(|p| p.timestamp).collect();
+            let geometry = if let [coordinate] = coordinates.as_slice() {
+                json!({ "type": "Point", "coordinates": coordinate })
+            } else {
+                json!({ "type": "LineString", "coordinates": coordinates })
+            };
+
+            json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "username": username,
+                    "room_id": room_id,
+                    "timestamps": timestamps,
+                },
+            })
+        })
+        .collect();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use protocol::SharingMode;
+
+    use crate::db::{
+        add_room_member, insert_positions, insert_room, set_room_member_privacy, setup_db,
+    };
+    use crate::privacy::Privacy;
+    use crate::role::Role;
+
+    use super::*;
+
+    use axum::body::Body;
+    use axum::http::{self};
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::util::ServiceExt;
+
+    fn new_position(lat: f64, lng: f64, timestamp: i64) -> Position {
+        Position {
+            username: "aaa".to_string(),
+            lat,
+            lng,
+            accuracy: None,
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    async fn get_json(
+        app: axum::Router,
+        db_pool: &sqlx::SqlitePool,
+        uri: &str,
+        username: &str,
+    ) -> (StatusCode, Value) {
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(db_pool, username).await;
+
+            app.oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(http::Method::GET)
+                    .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_get_user_track_ok() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        insert_positions(
+            &db_pool,
+            room1.id,
+            &[
+                new_position(48.0, 2.0, 1),
+                new_position(48.1, 2.1, 2),
+                new_position(48.2, 2.2, 3),
+            ],
+        )
+        .await
+        .unwrap();
+        insert_positions(&db_pool, room2.id, &[new_position(45.0, 5.0, 4)])
+            .await
+            .unwrap();
+
+        let (status, body) = get_json(app, &db_pool, "/api/users/aaa/track?from=2", "aaa").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "type": "FeatureCollection",
+                "features": [
+                    {
+                        "type": "Feature",
+                        "geometry": { "type": "LineString", "coordinates": [[2.1, 48.1], [2.2, 48.2]] },
+                        "properties": { "username": "aaa", "room_id": room1.id, "timestamps": [2, 3] },
+                    },
+                    {
+                        "type": "Feature",
+                        "geometry": { "type": "Point", "coordinates": [5.0, 45.0] },
+                        "properties": { "username": "aaa", "room_id": room2.id, "timestamps": [4] },
+                    },
+                ],
+            })
+        );
+    }
+
+    /// The others only see the rooms they share with that user; NOT those where that user is hidden
+    #[tokio::test]
+    async fn test_get_user_track_other_user() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        let room3 = insert_room(&db_pool, "room3", "aaa").await.unwrap();
+        for room_id in [room1.id, room2.id] {
+            add_room_member(&db_pool, room_id, "bbb", Role::Member)
+                .await
+                .unwrap();
+        }
+        set_room_member_privacy(
+            &db_pool,
+            room2.id,
+            "aaa",
+            Privacy {
+                mode: SharingMode::Hidden,
+                precision: None,
+            },
+        )
+        .await
+        .unwrap();
+        for (room_id, lat) in [(room1.id, 48.0), (room2.id, 45.0), (room3.id, 43.0)] {
+            insert_positions(&db_pool, room_id, &[new_position(lat, 2.0, 1)])
+                .await
+                .unwrap();
+        }
+
+        let (status, body) = get_json(app.clone(), &db_pool, "/api/users/aaa/track", "bbb").await;
+        assert_eq!(status, StatusCode::OK);
+        let room_ids: Vec<&Value> = body["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|feature| &feature["properties"]["room_id"])
+            .collect();
+        assert_eq!(room_ids, vec![&json!(room1.id)]);
+
+        // NOT in any of the rooms of "aaa"
+        let (status, _body) = get_json(app, &db_pool, "/api/users/aaa/track", "ccc").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn get_text(
+        app: axum::Router,
+        db_pool: &sqlx::SqlitePool,
+        uri: &str,
+        username: &str,
+    ) -> (StatusCode, String) {
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(db_pool, username).await;
+
+            app.oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(http::Method::GET)
+                    .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        };
+
+        let response = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+        let status = response.status();
+        if status == StatusCode::OK {
+            assert_eq!(
+                response.headers()[http::header::CONTENT_TYPE],
+                "application/gpx+xml"
+            );
+        }
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_user_track_gpx_ok() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        let mut with_altitude = new_position(48.0, 2.0, 1_708_363_750_199);
+        with_altitude.altitude = Some(35.5);
+        insert_positions(
+            &db_pool,
+            room1.id,
+            &[with_altitude, new_position(48.1, 2.1, 1_708_363_760_199)],
+        )
+        .await
+        .unwrap();
+        insert_positions(
+            &db_pool,
+            room2.id,
+            &[new_position(45.0, 5.0, 1_708_363_770_199)],
+        )
+        .await
+        .unwrap();
+
+        let (status, gpx) = get_text(app, &db_pool, "/api/users/aaa/track.gpx", "aaa").await;
+
+        assert_eq!(status, StatusCode::OK);
+        // one <trk> for the user; with one <trkseg> per room
+        assert_eq!(gpx.matches("<trk>").count(), 1);
+        assert_eq!(gpx.matches("<trkseg>").count(), 2);
+        assert!(gpx.contains(r#"<trkpt lat="48.1" lon="2.1">"#), "{gpx}");
+        assert!(gpx.contains("<ele>35.5</ele>"), "{gpx}");
+        assert!(
+            gpx.contains("<time>2024-02-19T17:29:10.199Z</time>"),
+            "{gpx}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user_track_gpx_other_user() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        add_room_member(&db_pool, room1.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+        insert_positions(&db_pool, room1.id, &[new_position(48.0, 2.0, 1)])
+            .await
+            .unwrap();
+        insert_positions(&db_pool, room2.id, &[new_position(45.0, 5.0, 2)])
+            .await
+            .unwrap();
+
+        // only the room they share
+        let (status, gpx) =
+            get_text(app.clone(), &db_pool, "/api/users/aaa/track.gpx", "bbb").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(gpx.matches("<trkseg>").count(), 1);
+        assert!(gpx.contains(r#"<trkpt lat="48" lon="2">"#), "{gpx}");
+
+        // NOT in any of the rooms of "aaa"
+        let (status, _gpx) = get_text(app, &db_pool, "/api/users/aaa/track.gpx", "ccc").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    /// One <trk> per user, in the given time range; NOT the hidden members, nor the other rooms
+    #[tokio::test]
+    async fn test_get_room_track_gpx_ok() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool.clone()).unwrap();
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        for username in ["bbb", "ccc"] {
+            add_room_member(&db_pool, room1.id, username, Role::Member)
+                .await
+                .unwrap();
+        }
+        set_room_member_privacy(
+            &db_pool,
+            room1.id,
+            "ccc",
+            Privacy {
+                mode: SharingMode::Hidden,
+                precision: None,
+            },
+        )
+        .await
+        .unwrap();
+        let new_user_position = |username: &str, lat: f64, timestamp: i64| Position {
+            username: username.to_string(),
+            ..new_position(lat, 2.0, timestamp)
+        };
+        insert_positions(
+            &db_pool,
+            room1.id,
+            &[
+                new_user_position("bbb", 48.0, 1),
+                new_user_position("aaa", 48.1, 2),
+                new_user_position("bbb", 48.2, 3),
+                new_user_position("ccc", 48.3, 4),
+                new_user_position("aaa", 48.4, 5),
+            ],
+        )
+        .await
+        .unwrap();
+        insert_positions(&db_pool, room2.id, &[new_user_position("aaa", 45.0, 2)])
+            .await
+            .unwrap();
+
+        let (status, gpx) = get_text(
+            app.clone(),
+            &db_pool,
+            &format!("/api/rooms/{}/track.gpx?to=4", room1.id),
+            "aaa",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let names: Vec<&str> = gpx
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("<name>")?.strip_suffix("</name>"))
+            .collect();
+        assert_eq!(names, vec!["aaa", "bbb"]);
+        assert_eq!(gpx.matches("<trkpt ").count(), 3);
+        assert!(!gpx.contains(r#"lat="48.4""#), "{gpx}");
+        assert!(!gpx.contains(r#"lat="45""#), "{gpx}");
+
+        // NOT a member of this room
+        let (status, _gpx) = get_text(
+            app,
+            &db_pool,
+            &format!("/api/rooms/{}/track.gpx", room2.id + 1),
+            "aaa",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_track_without_token_should_fail() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let app = crate::new_app(db_pool).unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/users/aaa/track")
+                    .method(http::Method::GET)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
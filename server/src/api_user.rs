@@ -1,10 +1,15 @@
-use axum::{Extension, Json};
+use axum::{extract::Path, Extension, Json};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    api_authorize_jwt::Claims,
-    db::{get_user_from_db, list_users_from_db, update_user_to_superuser},
+    api_authorize_jwt::{AuthError, Claims, MIN_PASSWORD_LENGTH},
+    db::{
+        delete_user_and_data, get_user_from_db, get_user_role, list_users_from_db,
+        revoke_user_sessions, update_user_password, update_user_role, update_user_to_superuser,
+        user_check_password,
+    },
     errors_and_responses::AppError,
+    role::{Admin, RequireRole, Role},
     state::SharedState,
     user::User,
 };
@@ -15,44 +20,13 @@ pub(crate) struct ListUsers {
 }
 
 /// List all users
-/// MUST be called by a superuser
+/// MUST be called by an admin
 #[axum::debug_handler]
 pub(crate) async fn list_users(
     Extension(state): Extension<SharedState>,
-    claims: Claims,
+    _admin: RequireRole<Admin>,
 ) -> Result<Json<ListUsers>, AppError> {
-    // check the user credentials from a database
-    let db_pool = match state.read() {
-        Ok(state) => state.db_pool.clone(),
-        Err(err) => {
-            tracing::error!("list_users: state read lock error: {:?}", err,);
-            return Err(AppError::InternalError);
-        }
-    };
-    let _user = match get_user_from_db(&db_pool, &claims.sub).await {
-        Ok(Some(user)) => {
-            // Handle the case when the user is found in the database
-            if !user.is_super_user {
-                tracing::error!(
-                    "list_users: user found but NOT a superuser: {:?}",
-                    claims.sub,
-                );
-                return Err(AppError::NotFound);
-            }
-
-            user
-        }
-        Ok(None) => {
-            // Handle the case when the user is not found in the database
-            tracing::error!("list_users: user not found: {:?}", claims.sub,);
-            return Err(AppError::NotFound);
-        }
-        Err(err) => {
-            // Handle the case when an error occurs during the database query
-            tracing::error!("list_users: db error: {:?}", err,);
-            return Err(AppError::InternalError);
-        }
-    };
+    let db_pool = state.db_pool.clone();
 
     let all_users = match list_users_from_db(&db_pool).await {
         Ok(users) => users,
@@ -70,46 +44,15 @@ pub(crate) struct SetSuperuserRequest {
     pub(crate) username: String,
 }
 
-/// Mark a given user as superuser
-/// MUST be called by a superuser
+/// Mark a given user as superuser, ie `Role::Admin`
+/// MUST be called by an admin
 #[axum::debug_handler]
 pub(crate) async fn set_superuser(
     Extension(state): Extension<SharedState>,
-    claims: Claims,
+    _admin: RequireRole<Admin>,
     Json(payload): Json<SetSuperuserRequest>,
 ) -> Result<(), AppError> {
-    // check the user credentials from a database
-    let db_pool = match state.read() {
-        Ok(state) => state.db_pool.clone(),
-        Err(err) => {
-            tracing::error!("list_users: state read lock error: {:?}", err,);
-            return Err(AppError::InternalError);
-        }
-    };
-    let _user = match get_user_from_db(&db_pool, &claims.sub).await {
-        Ok(Some(user)) => {
-            // Handle the case when the user is found in the database
-            if !user.is_super_user {
-                tracing::error!(
-                    "set_superuser: user found but NOT a superuser: {:?}",
-                    claims.sub,
-                );
-                return Err(AppError::NotFound);
-            }
-
-            user
-        }
-        Ok(None) => {
-            // Handle the case when the user is not found in the database
-            tracing::error!("set_superuser: user not found: {:?}", claims.sub,);
-            return Err(AppError::NotFound);
-        }
-        Err(err) => {
-            // Handle the case when an error occurs during the database query
-            tracing::error!("set_superuser: db error: {:?}", err,);
-            return Err(AppError::InternalError);
-        }
-    };
+    let db_pool = state.db_pool.clone();
 
     match update_user_to_superuser(&db_pool, &payload.username).await {
         Ok(()) => {}
@@ -122,9 +65,244 @@ pub(crate) async fn set_superuser(
     Ok(())
 }
 
+#[derive(Deserialize)]
+pub(crate) struct SetRoleRequest {
+    pub(crate) username: String,
+    pub(crate) role: Role,
+}
+
+/// Set the global role of a given user; cf `api_room::set_member_role` for the roles inside a room
+/// MUST be called by an admin; 404 if the user is NOT in the DB
+/// Like `unset_superuser`, an admin can NOT change their own role, so that there is always one left
+#[axum::debug_handler]
+pub(crate) async fn set_role(
+    Extension(state): Extension<SharedState>,
+    RequireRole { claims, .. }: RequireRole<Admin>,
+    Json(payload): Json<SetRoleRequest>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    if payload.username == claims.sub {
+        tracing::warn!("set_role: {} tried to change their own role", claims.sub);
+        return Err(AppError::BadRequest);
+    }
+
+    let is_found = update_user_role(&db_pool, &payload.username, payload.role)
+        .await
+        .map_err(|err| {
+            tracing::error!("set_role: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+
+    Ok(())
+}
+
+/// Revoke the superuser flag of a given user, ie back to `Role::Member`; NOOP if they were NOT an admin
+/// MUST be called by an admin; an admin can NOT demote themselves, so that there is always one left
+#[axum::debug_handler]
+pub(crate) async fn unset_superuser(
+    Extension(state): Extension<SharedState>,
+    RequireRole { claims, .. }: RequireRole<Admin>,
+    Json(payload): Json<SetSuperuserRequest>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    if payload.username == claims.sub {
+        tracing::warn!("unset_superuser: {} tried to demote themselves", claims.sub);
+        return Err(AppError::BadRequest);
+    }
+
+    let user = get_user_from_db(&db_pool, &payload.username)
+        .await
+        .map_err(|err| {
+            tracing::error!("unset_superuser: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+    if user.role == Role::Admin {
+        update_user_role(&db_pool, &user.username, Role::Member)
+            .await
+            .map_err(|err| {
+                tracing::error!("unset_superuser: db error: {:?}", err,);
+                AppError::InternalError
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Delete a given user and their data, cf `delete_user_and_data`; their sessions are deleted
+/// so they are logged out everywhere
+/// MUST be called by an admin; NOT on themselves; 404 if there was nothing to delete
+#[axum::debug_handler]
+pub(crate) async fn delete_user(
+    Extension(state): Extension<SharedState>,
+    RequireRole { claims, .. }: RequireRole<Admin>,
+    Path(username): Path<String>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    if username == claims.sub {
+        tracing::warn!("delete_user: {} tried to delete themselves", claims.sub);
+        return Err(AppError::BadRequest);
+    }
+
+    let is_found = delete_user_and_data(&db_pool, &username)
+        .await
+        .map_err(|err| {
+            tracing::error!("delete_user: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    if !is_found {
+        return Err(AppError::NotFound);
+    }
+    state.forget_user(&username);
+    tracing::info!("delete_user: {} deleted {username}", claims.sub);
+
+    Ok(())
+}
+
+/// SHOULD match `UserResponse` in `frontend/src/api/types.rs`
+#[derive(Debug, Serialize)]
+pub(crate) struct MeResponse {
+    status: &'static str,
+    data: MeData,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MeData {
+    user: Me,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Me {
+    username: String,
+    role: Role,
+}
+
+/// The current user; NOTE: the users that are NOT in the DB("anonymous") are `Role::Member`
+#[axum::debug_handler]
+pub(crate) async fn me(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+) -> Result<Json<MeResponse>, AppError> {
+    let db_pool = state.db_pool.clone();
+
+    let role = get_user_role(&db_pool, &claims.sub).await.map_err(|err| {
+        tracing::error!("me: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    Ok(Json(MeResponse {
+        status: "success",
+        data: MeData {
+            user: Me {
+                username: claims.sub,
+                role,
+            },
+        },
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChangePasswordRequest {
+    pub(crate) current_password: String,
+    pub(crate) new_password: String,
+}
+
+/// Change the password of the current user; their other sessions are revoked
+/// NOTE: the users that are NOT in the DB("anonymous") have no password: `AuthError::WrongCredentials`
+#[axum::debug_handler]
+pub(crate) async fn change_password(
+    Extension(state): Extension<SharedState>,
+    claims: Claims,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<(), AuthError> {
+    let db_pool = state.db_pool.clone();
+
+    let user = get_user_from_db(&db_pool, &claims.sub)
+        .await
+        .map_err(|err| {
+            tracing::error!("change_password: db error: {:?}", err,);
+            AuthError::DbError
+        })?
+        .ok_or(AuthError::WrongCredentials)?;
+    user_check_password(&user, &payload.current_password)
+        .await
+        .map_err(|_err| AuthError::WrongCredentials)?;
+    if payload.new_password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AuthError::WeakPassword);
+    }
+
+    update_user_password(&db_pool, &user.username, &payload.new_password)
+        .await
+        .map_err(|err| {
+            tracing::error!("change_password: db error: {:?}", err,);
+            AuthError::DbError
+        })?;
+    revoke_user_sessions(&db_pool, &user.username, Some(&claims.sid))
+        .await
+        .map_err(|err| {
+            tracing::error!("change_password: db error: {:?}", err,);
+            AuthError::DbError
+        })?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ResetPasswordRequest {
+    pub(crate) password: String,
+}
+
+/// Reset the password of a given user, eg when they forgot it; all their sessions are revoked
+/// MUST be called by an admin; 404 if the user is NOT in the DB
+#[axum::debug_handler]
+pub(crate) async fn reset_password(
+    Extension(state): Extension<SharedState>,
+    RequireRole { claims, .. }: RequireRole<Admin>,
+    Path(username): Path<String>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<(), AppError> {
+    let db_pool = state.db_pool.clone();
+
+    if payload.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest);
+    }
+    let user = get_user_from_db(&db_pool, &username)
+        .await
+        .map_err(|err| {
+            tracing::error!("reset_password: db error: {:?}", err,);
+            AppError::InternalError
+        })?
+        .ok_or(AppError::NotFound)?;
+
+    update_user_password(&db_pool, &user.username, &payload.password)
+        .await
+        .map_err(|err| {
+            tracing::error!("reset_password: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    revoke_user_sessions(&db_pool, &user.username, None)
+        .await
+        .map_err(|err| {
+            tracing::error!("reset_password: db error: {:?}", err,);
+            AppError::InternalError
+        })?;
+    tracing::info!(
+        "reset_password: {} reset the password of {username}",
+        claims.sub
+    );
+
+    Ok(())
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
-    use crate::db::{insert_user, setup_db, update_user_to_superuser};
+    use crate::db::{insert_user, setup_db};
 
     use super::*;
 
@@ -157,13 +335,39 @@ pub(crate) mod tests {
         (app, db_pool)
     }
 
+    async fn send(
+        app: &Router,
+        token: &str,
+        method: http::Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(method)
+                    .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
     #[tokio::test]
     async fn test_list_users_non_existent_user_404() {
         let username = "aaa";
-        let (app, _db_pool) = init(None, false).await;
+        let (app, db_pool) = init(None, false).await;
 
         let f = async {
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // `Router` implements `tower::Service<Request<Body>>` so we can
             // call it like any tower service, no need to run an HTTP server.
@@ -194,10 +398,10 @@ pub(crate) mod tests {
     #[tokio::test]
     async fn test_list_users_must_be_superuser_else_404() {
         let username = "aaa";
-        let (app, _db_pool) = init(Some(username), false).await;
+        let (app, db_pool) = init(Some(username), false).await;
 
         let f = async {
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // `Router` implements `tower::Service<Request<Body>>` so we can
             // call it like any tower service, no need to run an HTTP server.
@@ -228,10 +432,10 @@ pub(crate) mod tests {
     #[tokio::test]
     async fn test_list_users_superuser_ok() {
         let username = "aaa";
-        let (app, _db_pool) = init(Some(username), true).await;
+        let (app, db_pool) = init(Some(username), true).await;
 
         let f = async {
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // `Router` implements `tower::Service<Request<Body>>` so we can
             // call it like any tower service, no need to run an HTTP server.
@@ -273,7 +477,7 @@ pub(crate) mod tests {
             .unwrap();
 
         let f = async {
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // `Router` implements `tower::Service<Request<Body>>` so we can
             // call it like any tower service, no need to run an HTTP server.
@@ -305,6 +509,276 @@ pub(crate) mod tests {
             .await
             .unwrap()
             .unwrap();
-        assert!(updated_user.is_super_user);
+        assert_eq!(updated_user.role, Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn test_set_role() {
+        let username = "aaa";
+        let (app, db_pool) = init(Some(username), true).await;
+        insert_user(&db_pool, "bbb", "bbb").await.unwrap();
+
+        let f = async {
+            let mut statuses = vec![];
+            for (caller, body) in [
+                ("aaa", json!({ "username": "bbb", "role": "organiser" })),
+                ("aaa", json!({ "username": "unknown", "role": "organiser" })),
+                // NOT themselves, eg the last admin
+                ("aaa", json!({ "username": "aaa", "role": "member" })),
+                // an organiser is NOT an admin
+                ("bbb", json!({ "username": "bbb", "role": "admin" })),
+            ] {
+                let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, caller).await;
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .uri("/user/set_role")
+                            .method(http::Method::POST)
+                            .header(http::header::AUTHORIZATION, format!("Bearer {}", token))
+                            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                            .body(Body::from(body.to_string()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                statuses.push(response.status());
+            }
+
+            statuses
+        };
+
+        let statuses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+                StatusCode::BAD_REQUEST,
+                StatusCode::NOT_FOUND
+            ]
+        );
+        let user = get_user_from_db(&db_pool, "bbb").await.unwrap().unwrap();
+        assert_eq!(user.role, Role::Organiser);
+        let user = get_user_from_db(&db_pool, "aaa").await.unwrap().unwrap();
+        assert_eq!(user.role, Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_does_not_leak_password_hash() {
+        let (app, db_pool) = init(Some("aaa"), true).await;
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+            send(&app, &token, http::Method::GET, "/users", None).await
+        };
+        let (status, body) =
+            temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["users"],
+            json!([{ "username": "aaa", "role": "admin" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unset_superuser() {
+        let (app, db_pool) = init(Some("aaa"), true).await;
+        insert_user(&db_pool, "bbb", "bbb").await.unwrap();
+        update_user_to_superuser(&db_pool, "bbb").await.unwrap();
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+            let mut statuses = vec![];
+            for username in ["bbb", "aaa", "unknown"] {
+                let (status, _body) = send(
+                    &app,
+                    &token,
+                    http::Method::POST,
+                    "/user/unset_superuser",
+                    Some(json!({ "username": username })),
+                )
+                .await;
+                statuses.push(status);
+            }
+            statuses
+        };
+        let statuses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::BAD_REQUEST,
+                StatusCode::NOT_FOUND
+            ]
+        );
+        let user = get_user_from_db(&db_pool, "bbb").await.unwrap().unwrap();
+        assert_eq!(user.role, Role::Member);
+        let user = get_user_from_db(&db_pool, "aaa").await.unwrap().unwrap();
+        assert_eq!(user.role, Role::Admin);
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let (app, db_pool) = init(Some("aaa"), true).await;
+        insert_user(&db_pool, "bbb", "bbb").await.unwrap();
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+            let token_bbb = crate::api_authorize_jwt::tests::generate_token(&db_pool, "bbb").await;
+            let mut statuses = vec![];
+            for (token, uri) in [
+                // NOT an admin
+                (&token_bbb, "/api/users/aaa"),
+                (&token, "/api/users/aaa"),
+                (&token, "/api/users/bbb"),
+                (&token, "/api/users/bbb"),
+            ] {
+                let (status, _body) = send(&app, token, http::Method::DELETE, uri, None).await;
+                statuses.push(status);
+            }
+            // bbb is logged out
+            let (status, _body) = send(&app, &token_bbb, http::Method::GET, "/api/me", None).await;
+            statuses.push(status);
+            statuses
+        };
+        let statuses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::NOT_FOUND,
+                StatusCode::BAD_REQUEST,
+                StatusCode::OK,
+                StatusCode::NOT_FOUND,
+                StatusCode::UNAUTHORIZED,
+            ]
+        );
+        assert_eq!(get_user_from_db(&db_pool, "bbb").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_me() {
+        let (app, db_pool) = init(Some("aaa"), true).await;
+
+        let f = async {
+            let mut bodies = vec![];
+            // "ccc" is NOT in the DB
+            for username in ["aaa", "ccc"] {
+                let token =
+                    crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
+                let (status, body) = send(&app, &token, http::Method::GET, "/api/me", None).await;
+                assert_eq!(status, StatusCode::OK);
+                bodies.push(body);
+            }
+            bodies
+        };
+        let bodies = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            bodies,
+            vec![
+                json!({ "status": "success", "data": { "user": { "username": "aaa", "role": "admin" } } }),
+                json!({ "status": "success", "data": { "user": { "username": "ccc", "role": "member" } } }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_password() {
+        let (app, db_pool) = init(Some("aaa"), false).await;
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+            let other_token =
+                crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+            let mut statuses = vec![];
+            for body in [
+                json!({ "current_password": "wrong", "new_password": "NewPassword123" }),
+                json!({ "current_password": "bbb", "new_password": "short" }),
+                json!({ "current_password": "bbb", "new_password": "NewPassword123" }),
+            ] {
+                let (status, _body) = send(
+                    &app,
+                    &token,
+                    http::Method::POST,
+                    "/api/me/password",
+                    Some(body),
+                )
+                .await;
+                statuses.push(status);
+            }
+            // the current session is kept; the other one is revoked
+            for token in [&token, &other_token] {
+                let (status, _body) = send(&app, token, http::Method::GET, "/api/me", None).await;
+                statuses.push(status);
+            }
+            statuses
+        };
+        let statuses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::UNAUTHORIZED,
+                StatusCode::BAD_REQUEST,
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::UNAUTHORIZED,
+            ]
+        );
+        let user = get_user_from_db(&db_pool, "aaa").await.unwrap().unwrap();
+        assert!(user_check_password(&user, "NewPassword123").await.is_ok());
+        assert!(user_check_password(&user, "bbb").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_password() {
+        let (app, db_pool) = init(Some("aaa"), true).await;
+        insert_user(&db_pool, "bbb", "bbb").await.unwrap();
+
+        let f = async {
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "aaa").await;
+            let token_bbb = crate::api_authorize_jwt::tests::generate_token(&db_pool, "bbb").await;
+            let mut statuses = vec![];
+            for (token, uri, password) in [
+                // NOT an admin
+                (&token_bbb, "/api/users/bbb/password", "NewPassword123"),
+                (&token, "/api/users/bbb/password", "short"),
+                (&token, "/api/users/unknown/password", "NewPassword123"),
+                (&token, "/api/users/bbb/password", "NewPassword123"),
+            ] {
+                let (status, _body) = send(
+                    &app,
+                    token,
+                    http::Method::POST,
+                    uri,
+                    Some(json!({ "password": password })),
+                )
+                .await;
+                statuses.push(status);
+            }
+            // bbb is logged out
+            let (status, _body) = send(&app, &token_bbb, http::Method::GET, "/api/me", None).await;
+            statuses.push(status);
+            statuses
+        };
+        let statuses = temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::NOT_FOUND,
+                StatusCode::BAD_REQUEST,
+                StatusCode::NOT_FOUND,
+                StatusCode::OK,
+                StatusCode::UNAUTHORIZED,
+            ]
+        );
+        let user = get_user_from_db(&db_pool, "bbb").await.unwrap().unwrap();
+        assert!(user_check_password(&user, "NewPassword123").await.is_ok());
     }
 }
@@ -3,9 +3,22 @@ use argon2::{
     Argon2,
 };
 use futures::TryFutureExt;
+use protocol::{Position, SharingMode};
 use sqlx::sqlite::SqlitePoolOptions;
 use sqlx::{Row, SqlitePool};
 
+use crate::geofence::{
+    parse_transition, transition_as_str, Geofence, GeofenceEvent, GeofenceShape,
+};
+use crate::lead::Lead;
+use crate::poi::{Poi, Waypoint};
+use crate::privacy::{parse_sharing_mode, sharing_mode_as_str, Privacy};
+use crate::role::Role;
+use crate::room::Room;
+use crate::route::Route;
+use crate::route_formats::RouteFormat;
+use crate::route_stats::RouteStats;
+use crate::session::Session;
 use crate::user::User;
 
 /// Prepare a DB connection pool AND run migrations(eg CREATE TABLE etc)
@@ -71,9 +84,9 @@ pub(crate) async fn setup_db(
 /// INSERT a new user, with a random "salt"
 /// `https://gemini.google.com`
 ///
-/// returns: the `password_hash`; mostly for tests
-// TODO add corresponding route
-#[allow(dead_code)]
+/// returns: the `password_hash`; mostly for tests.
+/// An error of kind `std::io::ErrorKind::AlreadyExists` if the username is already taken,
+/// eg by a concurrent registration
 pub(crate) async fn insert_user(
     pool: &SqlitePool,
     username: &str,
@@ -88,17 +101,23 @@ pub(crate) async fn insert_user(
         .execute(pool)
         .map_err(|err| {
             tracing::error!("sqlite query error: {err:?}",);
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("sqlite query error: {err:?}",),
-            )
+            let kind = if err
+                .as_database_error()
+                .is_some_and(sqlx::error::DatabaseError::is_unique_violation)
+            {
+                std::io::ErrorKind::AlreadyExists
+            } else {
+                std::io::ErrorKind::Other
+            };
+            std::io::Error::new(kind, format!("sqlite query error: {err:?}",))
         })
         .await?;
 
     Ok(password_hash)
 }
 
-fn generate_new_password_hash(password: &str) -> Result<String, std::io::Error> {
+/// NOTE: also used to hash the refresh tokens, cf `insert_session`
+pub(crate) fn generate_new_password_hash(password: &str) -> Result<String, std::io::Error> {
     let salt = SaltString::generate(&mut OsRng);
 
     // Argon2 with default params (Argon2id v19)
@@ -125,6 +144,14 @@ fn generate_new_password_hash(password: &str) -> Result<String, std::io::Error>
 pub(crate) async fn user_check_password(
     user: &User,
     password_to_check: &str,
+) -> Result<(), std::io::Error> {
+    check_password_hash(&user.password_hash, password_to_check)
+}
+
+/// verify a secret against a hash from `generate_new_password_hash`
+pub(crate) fn check_password_hash(
+    password_hash: &str,
+    password_to_check: &str,
 ) -> Result<(), std::io::Error> {
     // Argon2 with default params (Argon2id v19)
     let argon2 = Argon2::default();
@@ -133,7 +160,7 @@ pub(crate) async fn user_check_password(
     //
     // NOTE: hash params from `parsed_hash` are used instead of what is configured in the
     // `Argon2` instance.
-    let parsed_hash_from_db = PasswordHash::new(&user.password_hash).map_err(|err| {
+    let parsed_hash_from_db = PasswordHash::new(password_hash).map_err(|err| {
         tracing::error!("select_user_and_check_password: PasswordHash  error: {err:?}",);
         std::io::Error::new(
             std::io::ErrorKind::Other,
@@ -161,7 +188,7 @@ pub(crate) async fn get_user_from_db(
     username: &str,
 ) -> Result<Option<User>, std::io::Error> {
     let query = r"
-        SELECT username, password_hash, is_super_user FROM user
+        SELECT username, password_hash, role FROM user
         WHERE username = $1
     ";
     let row = match sqlx::query(query).bind(username).fetch_one(pool).await {
@@ -183,7 +210,7 @@ pub(crate) async fn get_user_from_db(
     let user = User {
         username: row.get("username"),
         password_hash: row.get("password_hash"),
-        is_super_user: row.get("is_super_user"),
+        role: parse_role(row.get("role")),
     };
 
     Ok(Some(user))
@@ -193,7 +220,7 @@ pub(crate) async fn get_user_from_db(
 /// reminder: we want "anynomous" users to be able to access the app
 pub(crate) async fn list_users_from_db(pool: &SqlitePool) -> Result<Vec<User>, std::io::Error> {
     let query = r"
-        SELECT username, password_hash, is_super_user FROM user
+        SELECT username, password_hash, role FROM user
     ";
     let rows = match sqlx::query(query).fetch_all(pool).await {
         Ok(row) => row,
@@ -211,7 +238,7 @@ pub(crate) async fn list_users_from_db(pool: &SqlitePool) -> Result<Vec<User>, s
         let user = User {
             username: row.get("username"),
             password_hash: row.get("password_hash"),
-            is_super_user: row.get("is_super_user"),
+            role: parse_role(row.get("role")),
         };
 
         all_users.push(user);
@@ -220,29 +247,55 @@ pub(crate) async fn list_users_from_db(pool: &SqlitePool) -> Result<Vec<User>, s
     Ok(all_users)
 }
 
-/// UPDATE a given user to be a super user
+/// UPDATE a given user to be a super user, ie an admin
 pub(crate) async fn update_user_to_superuser(
     pool: &SqlitePool,
     username: &str,
 ) -> Result<(), std::io::Error> {
-    let query = r"
-        UPDATE user
-        SET is_super_user = 1
-        WHERE username = $1
-    ";
-    sqlx::query(query)
+    update_user_role(pool, username, Role::Admin)
+        .await
+        .map(|_is_found| ())
+}
+
+/// UPDATE the global role of a given user
+///
+/// returns: false if there is no such user
+pub(crate) async fn update_user_role(
+    pool: &SqlitePool,
+    username: &str,
+    role: Role,
+) -> Result<bool, std::io::Error> {
+    let query = r"UPDATE user SET role = $1 WHERE username = $2";
+    let res = sqlx::query(query)
+        .bind(role.as_str())
         .bind(username)
         .execute(pool)
         .map_err(|err| {
-            tracing::error!("sqlite query error: {err:?}",);
-            std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("sqlite query error: {err:?}",),
-            )
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
         })
         .await?;
 
-    Ok(())
+    Ok(res.rows_affected() > 0)
+}
+
+/// Get the global role of a given user
+/// NOTE: the users that are NOT in the DB("anonymous") are `Role::Member`
+pub(crate) async fn get_user_role(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<Role, std::io::Error> {
+    Ok(get_user_from_db(pool, username)
+        .await?
+        .map_or(Role::Member, |user| user.role))
+}
+
+/// cf the `role` columns; an unknown role(ie a corrupted DB) is treated as the default one
+fn parse_role(role: &str) -> Role {
+    role.parse().unwrap_or_else(|err| {
+        tracing::error!("parse_role: {err}");
+        Role::default()
+    })
 }
 
 /// UPDATE a given user password
@@ -274,156 +327,2139 @@ pub(crate) async fn update_user_password(
     Ok(())
 }
 
-#[cfg(test)]
-pub(crate) mod tests {
-    use super::*;
+/// INSERT a new room; the creator is automatically added as its organiser
+///
+/// returns: the new `Room`
+pub(crate) async fn insert_room(
+    pool: &SqlitePool,
+    name: &str,
+    created_by: &str,
+) -> Result<Room, std::io::Error> {
+    let query = r"INSERT INTO room (name, created_by) VALUES (?, ?)";
+    let res = sqlx::query(query)
+        .bind(name)
+        .bind(created_by)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-    async fn setup() -> SqlitePool {
-        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+    let room = Room {
+        id: res.last_insert_rowid(),
+        name: name.to_owned(),
+        created_by: created_by.to_owned(),
+        active_route_id: None,
+    };
+    add_room_member(pool, room.id, created_by, Role::Organiser).await?;
 
-        db_pool
-    }
+    Ok(room)
+}
 
-    #[sqlx::test]
-    async fn test_can_not_have_two_users_with_same_username() {
-        let db_pool = setup().await;
+/// Get a room by its id
+pub(crate) async fn get_room_from_db(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> Result<Option<Room>, std::io::Error> {
+    let query = r"
+        SELECT id, name, created_by, active_route_id FROM room
+        WHERE id = $1
+    ";
+    let row = sqlx::query(query)
+        .bind(room_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+    Ok(row.map(|row| Room {
+        id: row.get("id"),
+        name: row.get("name"),
+        created_by: row.get("created_by"),
+        active_route_id: row.get("active_route_id"),
+    }))
+}
 
-        assert!(insert_user(&db_pool, "aaa", "ccc").await.is_err());
-    }
+/// Get a room by its name; mostly used to check for duplicates before INSERT
+pub(crate) async fn get_room_by_name_from_db(
+    pool: &SqlitePool,
+    name: &str,
+) -> Result<Option<Room>, std::io::Error> {
+    let query = r"
+        SELECT id, name, created_by, active_route_id FROM room
+        WHERE name = $1
+    ";
+    let row = sqlx::query(query)
+        .bind(name)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-    #[sqlx::test]
-    async fn test_can_have_two_users_with_different_usernames() {
-        let db_pool = setup().await;
+    Ok(row.map(|row| Room {
+        id: row.get("id"),
+        name: row.get("name"),
+        created_by: row.get("created_by"),
+        active_route_id: row.get("active_route_id"),
+    }))
+}
 
-        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+/// List all the rooms
+pub(crate) async fn list_rooms_from_db(pool: &SqlitePool) -> Result<Vec<Room>, std::io::Error> {
+    let query = r"
+        SELECT id, name, created_by, active_route_id FROM room
+        ORDER BY id
+    ";
+    let rows = sqlx::query(query)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-        assert!(insert_user(&db_pool, "bbb", "ccc").await.is_ok());
-    }
+    Ok(rows
+        .into_iter()
+        .map(|row| Room {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_by: row.get("created_by"),
+            active_route_id: row.get("active_route_id"),
+        })
+        .collect())
+}
 
-    #[sqlx::test]
-    async fn test_user_good_password_ok() {
-        let db_pool = setup().await;
+/// Add a user to a room, with a given role; NOOP if they are already a member(ie their role is NOT changed)
+pub(crate) async fn add_room_member(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+    role: Role,
+) -> Result<(), std::io::Error> {
+    let query = r"INSERT OR IGNORE INTO room_member (room_id, username, role) VALUES (?, ?, ?)";
+    sqlx::query(query)
+        .bind(room_id)
+        .bind(username)
+        .bind(role.as_str())
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-        let username = "aaa";
-        let password_hash = insert_user(&db_pool, username, "bbb").await.unwrap();
-        let user = User {
-            username: username.to_string(),
-            password_hash,
-            is_super_user: false,
-        };
+    Ok(())
+}
 
-        assert!(user_check_password(&user, "bbb").await.is_ok());
+/// Remove a user from a room, with their privacy settings; NOOP if they were not a member
+/// NOTE: in a single transaction, like `delete_user_and_data`
+pub(crate) async fn remove_room_member(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+) -> Result<(), std::io::Error> {
+    let mut tx = pool
+        .begin()
+        .map_err(|err| {
+            tracing::error!("sqlite begin error: {err:?}");
+            std::io::Error::other(format!("sqlite begin error: {err:?}"))
+        })
+        .await?;
+
+    // NOTE: they are NOT the leader/sweeper of the room anymore either, cf `lead.rs`
+    for query in [
+        r"DELETE FROM room_member WHERE room_id = $1 AND username = $2",
+        r"UPDATE room SET leader = NULL WHERE id = $1 AND leader = $2",
+        r"UPDATE room SET sweeper = NULL WHERE id = $1 AND sweeper = $2",
+    ] {
+        sqlx::query(query)
+            .bind(room_id)
+            .bind(username)
+            .execute(&mut *tx)
+            .map_err(|err| {
+                tracing::error!("sqlite query error: {err:?}");
+                std::io::Error::other(format!("sqlite query error: {err:?}"))
+            })
+            .await?;
     }
 
-    #[sqlx::test]
-    async fn test_user_wrong_password_should_fail() {
-        let db_pool = setup().await;
+    tx.commit()
+        .map_err(|err| {
+            tracing::error!("sqlite commit error: {err:?}");
+            std::io::Error::other(format!("sqlite commit error: {err:?}"))
+        })
+        .await?;
 
-        let username = "aaa";
-        let password_hash = insert_user(&db_pool, username, "bbb").await.unwrap();
-        let user = User {
-            username: username.to_string(),
-            password_hash,
-            is_super_user: false,
-        };
+    Ok(())
+}
 
-        let res = user_check_password(&user, "BAD PASSWORD").await;
-        assert!(res.is_err());
-    }
+/// Get the role of a given user in a given room
+///
+/// returns: None if they have NOT joined this room
+pub(crate) async fn get_room_member_role(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+) -> Result<Option<Role>, std::io::Error> {
+    let query = r"
+        SELECT role FROM room_member
+        WHERE room_id = $1 AND username = $2
+    ";
+    let row = sqlx::query(query)
+        .bind(room_id)
+        .bind(username)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-    #[sqlx::test]
-    async fn test_is_user_in_db_existing_user_return_ok() {
-        let db_pool = setup().await;
+    Ok(row.map(|row| parse_role(row.get("role"))))
+}
 
-        let password_hash = insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+/// Check if a given user has joined a given room
+/// NOTE: the handlers use `get_room_member_role` instead, to also check the role
+#[cfg(test)]
+pub(crate) async fn is_room_member(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+) -> Result<bool, std::io::Error> {
+    Ok(get_room_member_role(pool, room_id, username)
+        .await?
+        .is_some())
+}
 
-        let res = get_user_from_db(&db_pool, "aaa").await;
-        assert!(res.is_ok());
-        let maybe_user = res.unwrap();
-        assert!(maybe_user.is_some());
-        assert_eq!(
-            maybe_user.unwrap(),
-            User {
-                username: "aaa".to_string(),
-                password_hash,
-                is_super_user: false,
-            }
-        );
-    }
+/// The usernames of the members of a given room, with what they share; ordered by username
+pub(crate) async fn list_room_members(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> Result<Vec<(String, SharingMode)>, std::io::Error> {
+    let query = r"SELECT username, sharing FROM room_member WHERE room_id = $1 ORDER BY username";
+    let rows = sqlx::query(query)
+        .bind(room_id)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-    #[sqlx::test]
-    async fn test_is_user_in_db_non_existent_user_does_not_err() {
-        let db_pool = setup().await;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("username"), parse_sharing_mode(row.get("sharing"))))
+        .collect())
+}
 
-        let res = get_user_from_db(&db_pool, "aaa").await;
-        assert!(res.is_ok());
-        let maybe_user = res.unwrap();
-        assert!(maybe_user.is_none());
-    }
+/// UPDATE the role of a given member of a given room
+///
+/// returns: false if they have NOT joined this room
+pub(crate) async fn set_room_member_role(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+    role: Role,
+) -> Result<bool, std::io::Error> {
+    let query = r"UPDATE room_member SET role = $1 WHERE room_id = $2 AND username = $3";
+    let res = sqlx::query(query)
+        .bind(role.as_str())
+        .bind(room_id)
+        .bind(username)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-    #[sqlx::test]
-    async fn test_update_user_to_superuser_ok() {
-        let db_pool = setup().await;
+    Ok(res.rows_affected() > 0)
+}
 
-        let username = "aaa";
-        let password_hash = insert_user(&db_pool, username, "bbb").await.unwrap();
+/// Get the privacy settings of a given user in a given room
+///
+/// returns: None if they have NOT joined this room
+pub(crate) async fn get_room_member_privacy(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+) -> Result<Option<Privacy>, std::io::Error> {
+    let query = r"
+        SELECT sharing, precision_meters FROM room_member
+        WHERE room_id = $1 AND username = $2
+    ";
+    let row = sqlx::query(query)
+        .bind(room_id)
+        .bind(username)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-        let res = get_user_from_db(&db_pool, username).await;
-        assert_eq!(
-            res.unwrap().unwrap(),
-            User {
-                username: username.to_string(),
-                password_hash: password_hash.clone(),
-                is_super_user: false,
-            }
-        );
+    Ok(row.map(|row| Privacy {
+        mode: parse_sharing_mode(row.get("sharing")),
+        precision: row.get("precision_meters"),
+    }))
+}
 
-        assert!(update_user_to_superuser(&db_pool, username).await.is_ok());
+/// UPDATE the privacy settings of a given member of a given room
+///
+/// returns: false if they have NOT joined this room
+pub(crate) async fn set_room_member_privacy(
+    pool: &SqlitePool,
+    room_id: i64,
+    username: &str,
+    privacy: Privacy,
+) -> Result<bool, std::io::Error> {
+    let query = r"
+        UPDATE room_member SET sharing = $1, precision_meters = $2
+        WHERE room_id = $3 AND username = $4
+    ";
+    let res = sqlx::query(query)
+        .bind(sharing_mode_as_str(privacy.mode))
+        .bind(privacy.precision)
+        .bind(room_id)
+        .bind(username)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-        let res = get_user_from_db(&db_pool, username).await;
-        assert_eq!(
-            res.unwrap().unwrap(),
-            User {
-                username: username.to_string(),
-                password_hash,
-                is_super_user: true,
-            }
-        );
-    }
+    Ok(res.rows_affected() > 0)
+}
 
-    #[sqlx::test]
-    async fn test_list_users_from_db_ok() {
-        let db_pool = setup().await;
+/// Get the leader and the sweeper of a given room
+///
+/// returns: None if the room does NOT exist
+pub(crate) async fn get_room_lead(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> Result<Option<Lead>, std::io::Error> {
+    let query = r"SELECT leader, sweeper FROM room WHERE id = $1";
+    let row = sqlx::query(query)
+        .bind(room_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
 
-        let username1 = "111";
-        let password_hash1 = insert_user(&db_pool, username1, "bbb").await.unwrap();
-        let username2 = "222";
-        let password_hash2 = insert_user(&db_pool, username2, "bbb").await.unwrap();
-        let username3 = "333";
-        let password_hash3 = insert_user(&db_pool, username3, "bbb").await.unwrap();
+    Ok(row.map(|row| Lead {
+        leader: row.get("leader"),
+        sweeper: row.get("sweeper"),
+    }))
+}
 
-        let res = list_users_from_db(&db_pool).await.unwrap();
-        assert_eq!(
-            res,
-            vec![
-                User {
-                    username: username1.to_string(),
-                    password_hash: password_hash1.clone(),
-                    is_super_user: false,
-                },
-                User {
-                    username: username2.to_string(),
-                    password_hash: password_hash2.clone(),
-                    is_super_user: false,
-                },
-                User {
-                    username: username3.to_string(),
-                    password_hash: password_hash3.clone(),
-                    is_super_user: false,
-                }
-            ]
+/// UPDATE the leader and the sweeper of a given room
+///
+/// returns: false if the room does NOT exist
+pub(crate) async fn set_room_lead(
+    pool: &SqlitePool,
+    room_id: i64,
+    lead: &Lead,
+) -> Result<bool, std::io::Error> {
+    let query = r"UPDATE room SET leader = $1, sweeper = $2 WHERE id = $3";
+    let res = sqlx::query(query)
+        .bind(&lead.leader)
+        .bind(&lead.sweeper)
+        .bind(room_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// INSERT a batch of positions, in a single transaction
+pub(crate) async fn insert_positions(
+    pool: &SqlitePool,
+    room_id: i64,
+    positions: &[Position],
+) -> Result<(), std::io::Error> {
+    let mut tx = pool
+        .begin()
+        .map_err(|err| {
+            tracing::error!("sqlite begin error: {err:?}");
+            std::io::Error::other(format!("sqlite begin error: {err:?}"))
+        })
+        .await?;
+
+    let query = r"
+        INSERT INTO position (room_id, username, lat, lng, accuracy, altitude, heading, speed, timestamp)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+    ";
+    for position in positions {
+        sqlx::query(query)
+            .bind(room_id)
+            .bind(&position.username)
+            .bind(position.lat)
+            .bind(position.lng)
+            .bind(position.accuracy)
+            .bind(position.altitude)
+            .bind(position.heading)
+            .bind(position.speed)
+            .bind(position.timestamp)
+            .execute(&mut *tx)
+            .map_err(|err| {
+                tracing::error!("sqlite query error: {err:?}");
+                std::io::Error::other(format!("sqlite query error: {err:?}"))
+            })
+            .await?;
+    }
+
+    tx.commit()
+        .map_err(|err| {
+            tracing::error!("sqlite commit error: {err:?}");
+            std::io::Error::other(format!("sqlite commit error: {err:?}"))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// SELECT the position history of a given user, ordered by timestamp
+///
+/// params:
+/// - `from`/`to`: optional bounds(inclusive), in milliseconds since UNIX epoch
+///
+/// returns: a list of `(room_id, position)`
+pub(crate) async fn list_positions_from_db(
+    pool: &SqlitePool,
+    username: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<Vec<(i64, Position)>, std::io::Error> {
+    let query = r"
+        SELECT room_id, username, lat, lng, accuracy, altitude, heading, speed, timestamp FROM position
+        WHERE username = $1 AND timestamp >= $2 AND timestamp <= $3
+        ORDER BY timestamp, id
+    ";
+    let rows = sqlx::query(query)
+        .bind(username)
+        .bind(from.unwrap_or(i64::MIN))
+        .bind(to.unwrap_or(i64::MAX))
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("room_id"), position_from_row(row)))
+        .collect())
+}
+
+/// SELECT the rooms where a given user can see the positions of another one, ordered by id:
+/// both have joined them, and the other one is NOT hidden there(cf `SharingMode::Hidden`)
+pub(crate) async fn list_visible_rooms(
+    pool: &SqlitePool,
+    viewer: &str,
+    username: &str,
+) -> Result<Vec<i64>, std::io::Error> {
+    let query = r"
+        SELECT target.room_id FROM room_member AS target
+        JOIN room_member AS viewer ON viewer.room_id = target.room_id AND viewer.username = $1
+        WHERE target.username = $2 AND target.sharing != 'hidden'
+        ORDER BY target.room_id
+    ";
+    let rows = sqlx::query(query)
+        .bind(viewer)
+        .bind(username)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get("room_id")).collect())
+}
+
+/// SELECT the positions of everyone in a given room, ordered by username then timestamp
+/// The hidden members are skipped, cf `SharingMode::Hidden`
+///
+/// params:
+/// - `from` and `to`: inclusive, in milliseconds since UNIX epoch; None for no limit
+pub(crate) async fn list_room_positions_from_db(
+    pool: &SqlitePool,
+    room_id: i64,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<Vec<Position>, std::io::Error> {
+    let query = r"
+        SELECT username, lat, lng, accuracy, altitude, heading, speed, timestamp FROM position
+        WHERE room_id = $1 AND timestamp >= $2 AND timestamp <= $3
+            AND NOT EXISTS (
+                SELECT 1 FROM room_member
+                WHERE room_member.room_id = position.room_id AND room_member.username = position.username
+                    AND room_member.sharing = 'hidden'
+            )
+        ORDER BY username, timestamp, id
+    ";
+    let rows = sqlx::query(query)
+        .bind(room_id)
+        .bind(from.unwrap_or(i64::MIN))
+        .bind(to.unwrap_or(i64::MAX))
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.iter().map(position_from_row).collect())
+}
+
+/// SELECT the last position of each user in a given room, ordered by username
+/// The hidden members are skipped, cf `SharingMode::Hidden`
+///
+/// params:
+/// - `since`: ignore the positions older than that, in milliseconds since UNIX epoch
+pub(crate) async fn list_last_positions_from_db(
+    pool: &SqlitePool,
+    room_id: i64,
+    since: i64,
+) -> Result<Vec<Position>, std::io::Error> {
+    // NOTE: with SQLite the "bare" columns come from the row where `MAX(timestamp)` is reached
+    // cf `https://www.sqlite.org/lang_select.html#bareagg`
+    let query = r"
+        SELECT username, lat, lng, accuracy, altitude, heading, speed, MAX(timestamp) AS timestamp FROM position
+        WHERE room_id = $1 AND timestamp >= $2
+            AND NOT EXISTS (
+                SELECT 1 FROM room_member
+                WHERE room_member.room_id = position.room_id AND room_member.username = position.username
+                    AND room_member.sharing = 'hidden'
+            )
+        GROUP BY username
+        ORDER BY username
+    ";
+    let rows = sqlx::query(query)
+        .bind(room_id)
+        .bind(since)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.iter().map(position_from_row).collect())
+}
+
+fn position_from_row(row: &sqlx::sqlite::SqliteRow) -> Position {
+    Position {
+        username: row.get("username"),
+        lat: row.get("lat"),
+        lng: row.get("lng"),
+        accuracy: row.get("accuracy"),
+        altitude: row.get("altitude"),
+        heading: row.get("heading"),
+        speed: row.get("speed"),
+        timestamp: row.get("timestamp"),
+        progress: None,
+    }
+}
+
+/// milliseconds since UNIX epoch
+pub(crate) fn now_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| {
+            i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+        })
+}
+
+/// INSERT a new route; `uploaded_at` is set to now
+///
+/// params:
+/// - `gpx`: the original file
+/// - `format`: the format of `gpx`, cf `get_route_original_from_db`
+/// - `geojson`: derived from `gpx`
+/// - `distance`/`bbox`: derived from `geojson` cf `crate::geo`
+///
+/// NOTE: with the POIs of the route in the same transaction, cf `route_gpx::handle_gpx_upload`
+///
+/// returns: the new `Route`
+pub(crate) async fn insert_route<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Sqlite>,
+    name: &str,
+    uploaded_by: &str,
+    gpx: &[u8],
+    format: RouteFormat,
+    geojson: &str,
+    stats: &RouteStats,
+) -> Result<Route, std::io::Error> {
+    let uploaded_at = now_timestamp();
+    let [min_lng, min_lat, max_lng, max_lat] = stats.bbox;
+
+    let query = r"
+        INSERT INTO route (name, uploaded_by, uploaded_at, gpx, format, geojson, distance, min_lat, min_lng, max_lat, max_lng,
+            elevation_gain, elevation_loss, min_elevation, max_elevation, point_count, duration)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    ";
+    let res = sqlx::query(query)
+        .bind(name)
+        .bind(uploaded_by)
+        .bind(uploaded_at)
+        .bind(gpx)
+        .bind(format.extension())
+        .bind(geojson)
+        .bind(stats.distance)
+        .bind(min_lat)
+        .bind(min_lng)
+        .bind(max_lat)
+        .bind(max_lng)
+        .bind(stats.elevation_gain)
+        .bind(stats.elevation_loss)
+        .bind(stats.min_elevation)
+        .bind(stats.max_elevation)
+        .bind(stats.point_count)
+        .bind(stats.duration)
+        .execute(executor)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(Route {
+        id: res.last_insert_rowid(),
+        name: name.to_owned(),
+        uploaded_by: uploaded_by.to_owned(),
+        uploaded_at,
+        stats: stats.clone(),
+    })
+}
+
+fn route_from_row(row: &sqlx::sqlite::SqliteRow) -> Route {
+    Route {
+        id: row.get("id"),
+        name: row.get("name"),
+        uploaded_by: row.get("uploaded_by"),
+        uploaded_at: row.get("uploaded_at"),
+        stats: RouteStats {
+            distance: row.get("distance"),
+            elevation_gain: row.get("elevation_gain"),
+            elevation_loss: row.get("elevation_loss"),
+            min_elevation: row.get("min_elevation"),
+            max_elevation: row.get("max_elevation"),
+            bbox: [
+                row.get("min_lng"),
+                row.get("min_lat"),
+                row.get("max_lng"),
+                row.get("max_lat"),
+            ],
+            point_count: row.get("point_count"),
+            duration: row.get("duration"),
+        },
+    }
+}
+
+/// Get a route by its id; WITHOUT the GPX and `GeoJSON`
+pub(crate) async fn get_route_from_db(
+    pool: &SqlitePool,
+    route_id: i64,
+) -> Result<Option<Route>, std::io::Error> {
+    let query = r"
+        SELECT id, name, uploaded_by, uploaded_at, distance, min_lat, min_lng, max_lat, max_lng,
+            elevation_gain, elevation_loss, min_elevation, max_elevation, point_count, duration
+        FROM route
+        WHERE id = $1
+    ";
+    let row = sqlx::query(query)
+        .bind(route_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(row.as_ref().map(route_from_row))
+}
+
+/// Get the `GeoJSON` of a given route
+pub(crate) async fn get_route_geojson_from_db(
+    pool: &SqlitePool,
+    route_id: i64,
+) -> Result<Option<String>, std::io::Error> {
+    let query = r"SELECT geojson FROM route WHERE id = $1";
+    let row = sqlx::query(query)
+        .bind(route_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(row.map(|row| row.get("geojson")))
+}
+
+/// Get the original file of a given route, as uploaded; cf `route_formats.rs`
+///
+/// returns: the file and its format; None for the routes uploaded before the format was stored
+pub(crate) async fn get_route_original_from_db(
+    pool: &SqlitePool,
+    route_id: i64,
+) -> Result<Option<(Vec<u8>, Option<RouteFormat>)>, std::io::Error> {
+    let query = r"SELECT gpx, format FROM route WHERE id = $1";
+    let row = sqlx::query(query)
+        .bind(route_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(row.map(|row| {
+        let format: Option<&str> = row.get("format");
+        (row.get("gpx"), format.and_then(RouteFormat::from_extension))
+    }))
+}
+
+/// List all the routes, most recent first; WITHOUT the GPX and `GeoJSON`
+pub(crate) async fn list_routes_from_db(pool: &SqlitePool) -> Result<Vec<Route>, std::io::Error> {
+    let query = r"
+        SELECT id, name, uploaded_by, uploaded_at, distance, min_lat, min_lng, max_lat, max_lng,
+            elevation_gain, elevation_loss, min_elevation, max_elevation, point_count, duration
+        FROM route
+        ORDER BY uploaded_at DESC, id DESC
+    ";
+    let rows = sqlx::query(query)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.iter().map(route_from_row).collect())
+}
+
+/// UPDATE the name of a given route
+///
+/// returns: false if there is no such route
+pub(crate) async fn rename_route(
+    pool: &SqlitePool,
+    route_id: i64,
+    name: &str,
+) -> Result<bool, std::io::Error> {
+    let query = r"UPDATE route SET name = $1 WHERE id = $2";
+    let res = sqlx::query(query)
+        .bind(name)
+        .bind(route_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// DELETE a given route; the rooms where it was active are left without a route
+///
+/// returns: false if there is no such route
+pub(crate) async fn delete_route(pool: &SqlitePool, route_id: i64) -> Result<bool, std::io::Error> {
+    let query = r"DELETE FROM route WHERE id = $1";
+    let res = sqlx::query(query)
+        .bind(route_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Set(or clear with None) the route displayed to the members of a given room
+pub(crate) async fn set_room_active_route(
+    pool: &SqlitePool,
+    room_id: i64,
+    route_id: Option<i64>,
+) -> Result<(), std::io::Error> {
+    let query = r"UPDATE room SET active_route_id = $1 WHERE id = $2";
+    sqlx::query(query)
+        .bind(route_id)
+        .bind(room_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// List the ids of the rooms where a given route is active
+pub(crate) async fn list_rooms_with_active_route(
+    pool: &SqlitePool,
+    route_id: i64,
+) -> Result<Vec<i64>, std::io::Error> {
+    let query = r"SELECT id FROM room WHERE active_route_id = $1";
+    let rows = sqlx::query(query)
+        .bind(route_id)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.into_iter().map(|row| row.get("id")).collect())
+}
+
+/// INSERT a new geofence; exactly one of `room_id` and `route_id` MUST be given(cf the CHECK constraint)
+pub(crate) async fn insert_geofence(
+    pool: &SqlitePool,
+    name: &str,
+    room_id: Option<i64>,
+    route_id: Option<i64>,
+    shape: &GeofenceShape,
+    created_by: &str,
+) -> Result<Geofence, std::io::Error> {
+    let query = r"
+        INSERT INTO geofence (name, room_id, route_id, shape, created_by)
+        VALUES (?, ?, ?, ?, ?)
+    ";
+    let res = sqlx::query(query)
+        .bind(name)
+        .bind(room_id)
+        .bind(route_id)
+        .bind(shape_to_json(shape)?)
+        .bind(created_by)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(Geofence {
+        id: res.last_insert_rowid(),
+        name: name.to_owned(),
+        room_id,
+        route_id,
+        shape: shape.clone(),
+        created_by: created_by.to_owned(),
+    })
+}
+
+fn shape_to_json(shape: &GeofenceShape) -> Result<String, std::io::Error> {
+    serde_json::to_string(shape).map_err(|err| {
+        tracing::error!("geofence shape error: {err:?}");
+        std::io::Error::other(format!("geofence shape error: {err:?}"))
+    })
+}
+
+fn geofence_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Geofence, std::io::Error> {
+    let shape: String = row.get("shape");
+    let shape = serde_json::from_str(&shape).map_err(|err| {
+        tracing::error!("geofence shape error: {err:?}");
+        std::io::Error::other(format!("geofence shape error: {err:?}"))
+    })?;
+
+    Ok(Geofence {
+        id: row.get("id"),
+        name: row.get("name"),
+        room_id: row.get("room_id"),
+        route_id: row.get("route_id"),
+        shape,
+        created_by: row.get("created_by"),
+    })
+}
+
+/// Get a geofence by its id
+pub(crate) async fn get_geofence_from_db(
+    pool: &SqlitePool,
+    geofence_id: i64,
+) -> Result<Option<Geofence>, std::io::Error> {
+    let query =
+        r"SELECT id, name, room_id, route_id, shape, created_by FROM geofence WHERE id = $1";
+    let row = sqlx::query(query)
+        .bind(geofence_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    row.as_ref().map(geofence_from_row).transpose()
+}
+
+/// SELECT all the geofences, ordered by id
+pub(crate) async fn list_geofences_from_db(
+    pool: &SqlitePool,
+) -> Result<Vec<Geofence>, std::io::Error> {
+    let query = r"SELECT id, name, room_id, route_id, shape, created_by FROM geofence ORDER BY id";
+    let rows = sqlx::query(query)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    rows.iter().map(geofence_from_row).collect()
+}
+
+/// SELECT the geofences that apply to a given room: its own; and the ones of its active route, if any
+/// Ordered by id
+pub(crate) async fn list_room_geofences(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> Result<Vec<Geofence>, std::io::Error> {
+    let query = r"
+        SELECT id, name, room_id, route_id, shape, created_by FROM geofence
+        WHERE room_id = $1 OR route_id = (SELECT active_route_id FROM room WHERE id = $1)
+        ORDER BY id
+    ";
+    let rows = sqlx::query(query)
+        .bind(room_id)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    rows.iter().map(geofence_from_row).collect()
+}
+
+/// UPDATE the name and shape of a given geofence
+///
+/// returns: false if it does NOT exist
+pub(crate) async fn update_geofence(
+    pool: &SqlitePool,
+    geofence_id: i64,
+    name: &str,
+    shape: &GeofenceShape,
+) -> Result<bool, std::io::Error> {
+    let query = r"UPDATE geofence SET name = $1, shape = $2 WHERE id = $3";
+    let res = sqlx::query(query)
+        .bind(name)
+        .bind(shape_to_json(shape)?)
+        .bind(geofence_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// DELETE a given geofence; and its history
+///
+/// returns: false if it does NOT exist
+pub(crate) async fn delete_geofence(
+    pool: &SqlitePool,
+    geofence_id: i64,
+) -> Result<bool, std::io::Error> {
+    let query = r"DELETE FROM geofence WHERE id = $1";
+    let res = sqlx::query(query)
+        .bind(geofence_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// INSERT some enter/exit events of a given room, cf `Geofences::check`
+pub(crate) async fn insert_geofence_events(
+    pool: &SqlitePool,
+    room_id: i64,
+    events: &[GeofenceEvent],
+) -> Result<(), std::io::Error> {
+    let query = r"
+        INSERT INTO geofence_event (room_id, geofence_id, username, transition, timestamp)
+        VALUES (?, ?, ?, ?, ?)
+    ";
+    for event in events {
+        sqlx::query(query)
+            .bind(room_id)
+            .bind(event.geofence_id)
+            .bind(&event.username)
+            .bind(transition_as_str(event.transition))
+            .bind(event.timestamp)
+            .execute(pool)
+            .map_err(|err| {
+                tracing::error!("sqlite query error: {err:?}");
+                std::io::Error::other(format!("sqlite query error: {err:?}"))
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// SELECT the enter/exit events of a given room, ordered by timestamp
+pub(crate) async fn list_geofence_events_from_db(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> Result<Vec<GeofenceEvent>, std::io::Error> {
+    let query = r"
+        SELECT geofence_event.geofence_id, geofence.name, geofence_event.username,
+            geofence_event.transition, geofence_event.timestamp
+        FROM geofence_event
+        JOIN geofence ON geofence.id = geofence_event.geofence_id
+        WHERE geofence_event.room_id = $1
+        ORDER BY geofence_event.timestamp, geofence_event.id
+    ";
+    let rows = sqlx::query(query)
+        .bind(room_id)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| GeofenceEvent {
+            geofence_id: row.get("geofence_id"),
+            name: row.get("name"),
+            username: row.get("username"),
+            transition: parse_transition(row.get("transition")),
+            timestamp: row.get("timestamp"),
+        })
+        .collect())
+}
+
+/// INSERT a new point of interest of a given route
+pub(crate) async fn insert_poi<'c>(
+    executor: impl sqlx::Executor<'c, Database = sqlx::Sqlite>,
+    route_id: i64,
+    waypoint: &Waypoint,
+) -> Result<Poi, std::io::Error> {
+    let query = r"
+        INSERT INTO poi (route_id, name, description, symbol, lat, lng)
+        VALUES (?, ?, ?, ?, ?, ?)
+    ";
+    let res = sqlx::query(query)
+        .bind(route_id)
+        .bind(&waypoint.name)
+        .bind(&waypoint.description)
+        .bind(&waypoint.symbol)
+        .bind(waypoint.lat)
+        .bind(waypoint.lng)
+        .execute(executor)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(Poi {
+        id: res.last_insert_rowid(),
+        route_id,
+        waypoint: waypoint.clone(),
+    })
+}
+
+fn poi_from_row(row: &sqlx::sqlite::SqliteRow) -> Poi {
+    Poi {
+        id: row.get("id"),
+        route_id: row.get("route_id"),
+        waypoint: Waypoint {
+            name: row.get("name"),
+            description: row.get("description"),
+            symbol: row.get("symbol"),
+            lat: row.get("lat"),
+            lng: row.get("lng"),
+        },
+    }
+}
+
+/// Get a point of interest by its id
+pub(crate) async fn get_poi_from_db(
+    pool: &SqlitePool,
+    poi_id: i64,
+) -> Result<Option<Poi>, std::io::Error> {
+    let query = r"SELECT id, route_id, name, description, symbol, lat, lng FROM poi WHERE id = $1";
+    let row = sqlx::query(query)
+        .bind(poi_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(row.as_ref().map(poi_from_row))
+}
+
+/// SELECT the points of interest of a given route, ordered by id
+pub(crate) async fn list_route_pois(
+    pool: &SqlitePool,
+    route_id: i64,
+) -> Result<Vec<Poi>, std::io::Error> {
+    let query = r"
+        SELECT id, route_id, name, description, symbol, lat, lng FROM poi
+        WHERE route_id = $1
+        ORDER BY id
+    ";
+    let rows = sqlx::query(query)
+        .bind(route_id)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.iter().map(poi_from_row).collect())
+}
+
+/// SELECT the points of interest of the active route of a given room, if any; ordered by id
+pub(crate) async fn list_room_pois(
+    pool: &SqlitePool,
+    room_id: i64,
+) -> Result<Vec<Poi>, std::io::Error> {
+    let query = r"
+        SELECT id, route_id, name, description, symbol, lat, lng FROM poi
+        WHERE route_id = (SELECT active_route_id FROM room WHERE id = $1)
+        ORDER BY id
+    ";
+    let rows = sqlx::query(query)
+        .bind(room_id)
+        .fetch_all(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows.iter().map(poi_from_row).collect())
+}
+
+/// UPDATE a given point of interest: its name, description, symbol and position
+///
+/// returns: false if it does NOT exist
+pub(crate) async fn update_poi(
+    pool: &SqlitePool,
+    poi_id: i64,
+    waypoint: &Waypoint,
+) -> Result<bool, std::io::Error> {
+    let query = r"
+        UPDATE poi SET name = $1, description = $2, symbol = $3, lat = $4, lng = $5
+        WHERE id = $6
+    ";
+    let res = sqlx::query(query)
+        .bind(&waypoint.name)
+        .bind(&waypoint.description)
+        .bind(&waypoint.symbol)
+        .bind(waypoint.lat)
+        .bind(waypoint.lng)
+        .bind(poi_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// DELETE a given point of interest
+///
+/// returns: false if it does NOT exist
+pub(crate) async fn delete_poi(pool: &SqlitePool, poi_id: i64) -> Result<bool, std::io::Error> {
+    let query = r"DELETE FROM poi WHERE id = $1";
+    let res = sqlx::query(query)
+        .bind(poi_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// INSERT a new session, cf `api_authorize_jwt::new_session`
+pub(crate) async fn insert_session(
+    pool: &SqlitePool,
+    session_id: &str,
+    username: &str,
+    refresh_token_hash: &str,
+    expires_at: i64,
+) -> Result<(), std::io::Error> {
+    let query = r"
+        INSERT INTO session (id, username, refresh_token_hash, created_at, expires_at)
+        VALUES (?, ?, ?, ?, ?)
+    ";
+    sqlx::query(query)
+        .bind(session_id)
+        .bind(username)
+        .bind(refresh_token_hash)
+        .bind(now_timestamp())
+        .bind(expires_at)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Get a session by its id; revoked and expired sessions are also returned, cf `Session::is_active`
+pub(crate) async fn get_session_from_db(
+    pool: &SqlitePool,
+    session_id: &str,
+) -> Result<Option<Session>, std::io::Error> {
+    let query = r"
+        SELECT id, username, refresh_token_hash, expires_at, revoked_at FROM session
+        WHERE id = $1
+    ";
+    let row = sqlx::query(query)
+        .bind(session_id)
+        .fetch_optional(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(row.map(|row| Session {
+        id: row.get("id"),
+        username: row.get("username"),
+        refresh_token_hash: row.get("refresh_token_hash"),
+        expires_at: row.get("expires_at"),
+        revoked_at: row.get("revoked_at"),
+    }))
+}
+
+/// Rotate the refresh token of a given session, and extend it
+/// Only if its refresh token is still `old_refresh_token_hash`: of two concurrent rotations, only one succeeds
+///
+/// returns: false if there is no such session, if it was revoked, or if it was already rotated
+pub(crate) async fn update_session_refresh_token(
+    pool: &SqlitePool,
+    session_id: &str,
+    old_refresh_token_hash: &str,
+    refresh_token_hash: &str,
+    expires_at: i64,
+) -> Result<bool, std::io::Error> {
+    let query = r"
+        UPDATE session SET refresh_token_hash = $1, expires_at = $2
+        WHERE id = $3 AND refresh_token_hash = $4 AND revoked_at IS NULL
+    ";
+    let res = sqlx::query(query)
+        .bind(refresh_token_hash)
+        .bind(expires_at)
+        .bind(session_id)
+        .bind(old_refresh_token_hash)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Revoke a given session: its access and refresh tokens are rejected from now on
+///
+/// returns: false if there is no such session, or if it was already revoked
+pub(crate) async fn revoke_session(
+    pool: &SqlitePool,
+    session_id: &str,
+) -> Result<bool, std::io::Error> {
+    let query = r"UPDATE session SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL";
+    let res = sqlx::query(query)
+        .bind(now_timestamp())
+        .bind(session_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Revoke all the sessions of a given user, eg after a password change
+/// `except_session_id`: keep this one, ie the session of the caller
+///
+/// returns: the number of sessions revoked
+pub(crate) async fn revoke_user_sessions(
+    pool: &SqlitePool,
+    username: &str,
+    except_session_id: Option<&str>,
+) -> Result<u64, std::io::Error> {
+    let query = r"
+        UPDATE session SET revoked_at = $1
+        WHERE username = $2 AND revoked_at IS NULL AND id IS NOT $3
+    ";
+    let res = sqlx::query(query)
+        .bind(now_timestamp())
+        .bind(username)
+        .bind(except_session_id)
+        .execute(pool)
+        .map_err(|err| {
+            tracing::error!("sqlite query error: {err:?}");
+            std::io::Error::other(format!("sqlite query error: {err:?}"))
+        })
+        .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// DELETE a user and their data: sessions, room memberships and positions
+/// NOTE: the rooms they created and the routes they uploaded are shared, so they are kept
+///
+/// returns: false if there was nothing to delete; NOTE: "anonymous" users have data but no `user` row
+pub(crate) async fn delete_user_and_data(
+    pool: &SqlitePool,
+    username: &str,
+) -> Result<bool, std::io::Error> {
+    let mut tx = pool
+        .begin()
+        .map_err(|err| {
+            tracing::error!("sqlite begin error: {err:?}");
+            std::io::Error::other(format!("sqlite begin error: {err:?}"))
+        })
+        .await?;
+
+    let mut rows_affected = 0;
+    for query in [
+        r"DELETE FROM session WHERE username = $1",
+        r"DELETE FROM room_member WHERE username = $1",
+        r"DELETE FROM position WHERE username = $1",
+        r"DELETE FROM geofence_event WHERE username = $1",
+        r"UPDATE room SET leader = NULL WHERE leader = $1",
+        r"UPDATE room SET sweeper = NULL WHERE sweeper = $1",
+        r"DELETE FROM user WHERE username = $1",
+    ] {
+        rows_affected += sqlx::query(query)
+            .bind(username)
+            .execute(&mut *tx)
+            .map_err(|err| {
+                tracing::error!("sqlite query error: {err:?}");
+                std::io::Error::other(format!("sqlite query error: {err:?}"))
+            })
+            .await?
+            .rows_affected();
+    }
+
+    tx.commit()
+        .map_err(|err| {
+            tracing::error!("sqlite commit error: {err:?}");
+            std::io::Error::other(format!("sqlite commit error: {err:?}"))
+        })
+        .await?;
+
+    Ok(rows_affected > 0)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use protocol::GeofenceTransition;
+
+    use super::*;
+
+    async fn setup() -> SqlitePool {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+
+        db_pool
+    }
+
+    #[sqlx::test]
+    async fn test_can_not_have_two_users_with_same_username() {
+        let db_pool = setup().await;
+
+        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+
+        assert_eq!(
+            insert_user(&db_pool, "aaa", "ccc")
+                .await
+                .map_err(|err| err.kind()),
+            Err(std::io::ErrorKind::AlreadyExists)
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_can_have_two_users_with_different_usernames() {
+        let db_pool = setup().await;
+
+        insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+
+        assert!(insert_user(&db_pool, "bbb", "ccc").await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_user_good_password_ok() {
+        let db_pool = setup().await;
+
+        let username = "aaa";
+        let password_hash = insert_user(&db_pool, username, "bbb").await.unwrap();
+        let user = User {
+            username: username.to_string(),
+            password_hash,
+            role: Role::Member,
+        };
+
+        assert!(user_check_password(&user, "bbb").await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_user_wrong_password_should_fail() {
+        let db_pool = setup().await;
+
+        let username = "aaa";
+        let password_hash = insert_user(&db_pool, username, "bbb").await.unwrap();
+        let user = User {
+            username: username.to_string(),
+            password_hash,
+            role: Role::Member,
+        };
+
+        let res = user_check_password(&user, "BAD PASSWORD").await;
+        assert!(res.is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_is_user_in_db_existing_user_return_ok() {
+        let db_pool = setup().await;
+
+        let password_hash = insert_user(&db_pool, "aaa", "bbb").await.unwrap();
+
+        let res = get_user_from_db(&db_pool, "aaa").await;
+        assert!(res.is_ok());
+        let maybe_user = res.unwrap();
+        assert!(maybe_user.is_some());
+        assert_eq!(
+            maybe_user.unwrap(),
+            User {
+                username: "aaa".to_string(),
+                password_hash,
+                role: Role::Member,
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_is_user_in_db_non_existent_user_does_not_err() {
+        let db_pool = setup().await;
+
+        let res = get_user_from_db(&db_pool, "aaa").await;
+        assert!(res.is_ok());
+        let maybe_user = res.unwrap();
+        assert!(maybe_user.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_update_user_to_superuser_ok() {
+        let db_pool = setup().await;
+
+        let username = "aaa";
+        let password_hash = insert_user(&db_pool, username, "bbb").await.unwrap();
+
+        let res = get_user_from_db(&db_pool, username).await;
+        assert_eq!(
+            res.unwrap().unwrap(),
+            User {
+                username: username.to_string(),
+                password_hash: password_hash.clone(),
+                role: Role::Member,
+            }
+        );
+
+        assert!(update_user_to_superuser(&db_pool, username).await.is_ok());
+
+        let res = get_user_from_db(&db_pool, username).await;
+        assert_eq!(
+            res.unwrap().unwrap(),
+            User {
+                username: username.to_string(),
+                password_hash,
+                role: Role::Admin,
+            }
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_users_from_db_ok() {
+        let db_pool = setup().await;
+
+        let username1 = "111";
+        let password_hash1 = insert_user(&db_pool, username1, "bbb").await.unwrap();
+        let username2 = "222";
+        let password_hash2 = insert_user(&db_pool, username2, "bbb").await.unwrap();
+        let username3 = "333";
+        let password_hash3 = insert_user(&db_pool, username3, "bbb").await.unwrap();
+
+        let res = list_users_from_db(&db_pool).await.unwrap();
+        assert_eq!(
+            res,
+            vec![
+                User {
+                    username: username1.to_string(),
+                    password_hash: password_hash1.clone(),
+                    role: Role::Member,
+                },
+                User {
+                    username: username2.to_string(),
+                    password_hash: password_hash2.clone(),
+                    role: Role::Member,
+                },
+                User {
+                    username: username3.to_string(),
+                    password_hash: password_hash3.clone(),
+                    role: Role::Member,
+                }
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_insert_room_creator_is_member_ok() {
+        let db_pool = setup().await;
+
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        assert!(is_room_member(&db_pool, room.id, "aaa").await.unwrap());
+        assert!(!is_room_member(&db_pool, room.id, "bbb").await.unwrap());
+        assert_eq!(
+            get_room_from_db(&db_pool, room.id).await.unwrap(),
+            Some(room)
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_can_not_have_two_rooms_with_same_name() {
+        let db_pool = setup().await;
+
+        insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        assert!(insert_room(&db_pool, "room1", "bbb").await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn test_add_and_remove_room_member_ok() {
+        let db_pool = setup().await;
+
+        let room1 = insert_room(&db_pool, "room1", "root").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "root").await.unwrap();
+
+        add_room_member(&db_pool, room1.id, "aaa", Role::Member)
+            .await
+            .unwrap();
+        // adding twice is a NOOP
+        add_room_member(&db_pool, room1.id, "aaa", Role::Member)
+            .await
+            .unwrap();
+        assert!(is_room_member(&db_pool, room1.id, "aaa").await.unwrap());
+        assert!(!is_room_member(&db_pool, room2.id, "aaa").await.unwrap());
+        assert_eq!(
+            list_room_members(&db_pool, room1.id).await.unwrap(),
+            vec![
+                ("aaa".to_string(), SharingMode::Sharing),
+                ("root".to_string(), SharingMode::Sharing)
+            ]
+        );
+
+        remove_room_member(&db_pool, room1.id, "aaa").await.unwrap();
+        assert!(!is_room_member(&db_pool, room1.id, "aaa").await.unwrap());
+        assert_eq!(
+            list_room_members(&db_pool, room1.id).await.unwrap(),
+            vec![("root".to_string(), SharingMode::Sharing)]
+        );
+
+        assert_eq!(
+            list_rooms_from_db(&db_pool).await.unwrap(),
+            vec![room1, room2]
+        );
+    }
+
+    fn new_position(username: &str, timestamp: i64) -> Position {
+        Position {
+            username: username.to_string(),
+            lat: 48.8354,
+            lng: 2.3203,
+            accuracy: Some(10.0),
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_insert_and_list_positions_ok() {
+        let db_pool = setup().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        insert_positions(
+            &db_pool,
+            room.id,
+            &[
+                new_position("aaa", 3),
+                new_position("bbb", 2),
+                new_position("aaa", 1),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let res = list_positions_from_db(&db_pool, "aaa", None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            res,
+            vec![
+                (room.id, new_position("aaa", 1)),
+                (room.id, new_position("aaa", 3))
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_positions_from_to_ok() {
+        let db_pool = setup().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        let positions: Vec<Position> = (0..10).map(|i| new_position("aaa", i)).collect();
+        insert_positions(&db_pool, room.id, &positions)
+            .await
+            .unwrap();
+
+        let res = list_positions_from_db(&db_pool, "aaa", Some(3), Some(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            res.into_iter()
+                .map(|(_room_id, position)| position.timestamp)
+                .collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_list_last_positions_ok() {
+        let db_pool = setup().await;
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        // the other columns MUST come from the last position
+        let mut previous_position = new_position("aaa", 3);
+        previous_position.lat = 0.0;
+
+        insert_positions(
+            &db_pool,
+            room1.id,
+            &[
+                new_position("bbb", 5),
+                previous_position,
+                new_position("aaa", 4),
+                new_position("ccc", 1),
+            ],
+        )
+        .await
+        .unwrap();
+        insert_positions(&db_pool, room2.id, &[new_position("aaa", 10)])
+            .await
+            .unwrap();
+
+        let res = list_last_positions_from_db(&db_pool, room1.id, 2)
+            .await
+            .unwrap();
+        assert_eq!(res, vec![new_position("aaa", 4), new_position("bbb", 5)]);
+    }
+
+    #[sqlx::test]
+    async fn test_room_lead_ok() {
+        let db_pool = setup().await;
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        add_room_member(&db_pool, room1.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_room_lead(&db_pool, room1.id).await.unwrap(),
+            Some(Lead::default())
+        );
+        assert_eq!(get_room_lead(&db_pool, room2.id + 1).await.unwrap(), None);
+
+        let lead = Lead {
+            leader: Some("aaa".to_string()),
+            sweeper: Some("bbb".to_string()),
+        };
+        assert!(set_room_lead(&db_pool, room1.id, &lead).await.unwrap());
+        assert!(set_room_lead(&db_pool, room2.id, &lead).await.unwrap());
+        assert!(!set_room_lead(&db_pool, room2.id + 1, &lead).await.unwrap());
+        assert_eq!(
+            get_room_lead(&db_pool, room1.id).await.unwrap(),
+            Some(lead.clone())
+        );
+
+        // leaving a room: only in that room
+        remove_room_member(&db_pool, room1.id, "bbb").await.unwrap();
+        assert_eq!(
+            get_room_lead(&db_pool, room1.id).await.unwrap(),
+            Some(Lead {
+                sweeper: None,
+                ..lead.clone()
+            })
+        );
+        assert_eq!(
+            get_room_lead(&db_pool, room2.id).await.unwrap(),
+            Some(lead.clone())
+        );
+
+        // deleted: in every room
+        delete_user_and_data(&db_pool, "aaa").await.unwrap();
+        assert_eq!(
+            get_room_lead(&db_pool, room2.id).await.unwrap(),
+            Some(Lead {
+                leader: None,
+                ..lead
+            })
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_room_member_privacy_ok() {
+        let db_pool = setup().await;
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room1.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+        insert_positions(
+            &db_pool,
+            room1.id,
+            &[new_position("aaa", 1), new_position("bbb", 2)],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            get_room_member_privacy(&db_pool, room1.id, "bbb")
+                .await
+                .unwrap(),
+            Some(Privacy::default())
+        );
+        assert_eq!(
+            get_room_member_privacy(&db_pool, room1.id, "ccc")
+                .await
+                .unwrap(),
+            None
+        );
+
+        let hidden = Privacy {
+            mode: SharingMode::Hidden,
+            precision: Some(100),
+        };
+        assert!(set_room_member_privacy(&db_pool, room1.id, "bbb", hidden)
+            .await
+            .unwrap());
+        assert!(!set_room_member_privacy(&db_pool, room1.id, "ccc", hidden)
+            .await
+            .unwrap());
+        assert_eq!(
+            get_room_member_privacy(&db_pool, room1.id, "bbb")
+                .await
+                .unwrap(),
+            Some(hidden)
+        );
+        assert_eq!(
+            list_room_members(&db_pool, room1.id).await.unwrap(),
+            vec![
+                ("aaa".to_string(), SharingMode::Sharing),
+                ("bbb".to_string(), SharingMode::Hidden)
+            ]
+        );
+        // the last position of a hidden member is NOT loaded for the snapshots
+        assert_eq!(
+            list_last_positions_from_db(&db_pool, room1.id, 0)
+                .await
+                .unwrap(),
+            vec![new_position("aaa", 1)]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_geofences_ok() {
+        let db_pool = setup().await;
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "aaa",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            "{}",
+            &RouteStats {
+                distance: 42.0,
+                bbox: [2.0, 48.0, 2.1, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+        set_room_active_route(&db_pool, room2.id, Some(route.id))
+            .await
+            .unwrap();
+
+        let circle = GeofenceShape::Circle {
+            lat: 48.8,
+            lng: 2.3,
+            radius: 100.0,
+        };
+        let fence1 = insert_geofence(&db_pool, "start", Some(room1.id), None, &circle, "aaa")
+            .await
+            .unwrap();
+        let fence2 = insert_geofence(&db_pool, "cp1", None, Some(route.id), &circle, "aaa")
+            .await
+            .unwrap();
+        // exactly one of room_id and route_id
+        assert!(insert_geofence(&db_pool, "bad", None, None, &circle, "aaa")
+            .await
+            .is_err());
+
+        assert_eq!(
+            get_geofence_from_db(&db_pool, fence1.id).await.unwrap(),
+            Some(fence1.clone())
+        );
+        assert_eq!(
+            list_room_geofences(&db_pool, room1.id).await.unwrap(),
+            vec![fence1.clone()]
+        );
+        assert_eq!(
+            list_room_geofences(&db_pool, room2.id).await.unwrap(),
+            vec![fence2.clone()]
+        );
+
+        let square = GeofenceShape::Polygon {
+            points: vec![(48.0, 2.0), (48.0, 2.1), (48.1, 2.1), (48.1, 2.0)],
+        };
+        assert!(update_geofence(&db_pool, fence2.id, "cp2", &square)
+            .await
+            .unwrap());
+        assert!(!update_geofence(&db_pool, 12345, "cp2", &square)
+            .await
+            .unwrap());
+        let fence2 = get_geofence_from_db(&db_pool, fence2.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((fence2.name.as_str(), &fence2.shape), ("cp2", &square));
+
+        let event = GeofenceEvent {
+            geofence_id: fence2.id,
+            name: fence2.name.clone(),
+            username: "bbb".to_string(),
+            transition: GeofenceTransition::Enter,
+            timestamp: 1,
+        };
+        insert_geofence_events(&db_pool, room2.id, &[event.clone()])
+            .await
+            .unwrap();
+        assert_eq!(
+            list_geofence_events_from_db(&db_pool, room2.id)
+                .await
+                .unwrap(),
+            vec![event]
+        );
+        assert_eq!(
+            list_geofence_events_from_db(&db_pool, room1.id)
+                .await
+                .unwrap(),
+            vec![]
+        );
+
+        // the history is deleted with the geofence
+        assert!(delete_geofence(&db_pool, fence2.id).await.unwrap());
+        assert!(!delete_geofence(&db_pool, fence2.id).await.unwrap());
+        assert_eq!(
+            list_geofence_events_from_db(&db_pool, room2.id)
+                .await
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            list_geofences_from_db(&db_pool).await.unwrap(),
+            vec![fence1]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_pois_ok() {
+        let db_pool = setup().await;
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "aaa").await.unwrap();
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "aaa",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            "{}",
+            &RouteStats {
+                distance: 42.0,
+                bbox: [2.0, 48.0, 2.1, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+        set_room_active_route(&db_pool, room2.id, Some(route.id))
+            .await
+            .unwrap();
+
+        let water = Waypoint {
+            name: "water".to_string(),
+            description: Some("fountain".to_string()),
+            symbol: Some("Drinking Water".to_string()),
+            lat: 48.01,
+            lng: 2.01,
+        };
+        let poi1 = insert_poi(&db_pool, route.id, &water).await.unwrap();
+        let poi2 = insert_poi(
+            &db_pool,
+            route.id,
+            &Waypoint {
+                name: "food".to_string(),
+                description: None,
+                symbol: None,
+                ..water.clone()
+            },
+        )
+        .await
+        .unwrap();
+        // the route MUST exist
+        assert!(insert_poi(&db_pool, 12345, &water).await.is_err());
+
+        assert_eq!(
+            get_poi_from_db(&db_pool, poi1.id).await.unwrap(),
+            Some(poi1.clone())
+        );
+        assert_eq!(
+            list_route_pois(&db_pool, route.id).await.unwrap(),
+            vec![poi1.clone(), poi2.clone()]
+        );
+        assert_eq!(
+            list_room_pois(&db_pool, room2.id).await.unwrap(),
+            vec![poi1.clone(), poi2.clone()]
+        );
+        assert_eq!(list_room_pois(&db_pool, room1.id).await.unwrap(), vec![]);
+
+        let moved = Waypoint {
+            lat: 48.02,
+            lng: 2.02,
+            ..water
+        };
+        assert!(update_poi(&db_pool, poi1.id, &moved).await.unwrap());
+        assert!(!update_poi(&db_pool, 12345, &moved).await.unwrap());
+        assert_eq!(
+            get_poi_from_db(&db_pool, poi1.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .waypoint,
+            moved
+        );
+
+        assert!(delete_poi(&db_pool, poi2.id).await.unwrap());
+        assert!(!delete_poi(&db_pool, poi2.id).await.unwrap());
+        assert_eq!(get_poi_from_db(&db_pool, poi2.id).await.unwrap(), None);
+
+        // and they are deleted with the route
+        assert!(delete_route(&db_pool, route.id).await.unwrap());
+        assert_eq!(get_poi_from_db(&db_pool, poi1.id).await.unwrap(), None);
+    }
+
+    #[sqlx::test]
+    async fn test_route_lifecycle_ok() {
+        let db_pool = setup().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "aaa",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            "{}",
+            &RouteStats {
+                distance: 42.0,
+                elevation_gain: Some(120.0),
+                elevation_loss: Some(110.0),
+                min_elevation: Some(35.0),
+                max_elevation: Some(80.0),
+                bbox: [2.0, 48.0, 2.1, 48.1],
+                point_count: 12,
+                duration: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            get_route_from_db(&db_pool, route.id).await.unwrap(),
+            Some(route.clone())
+        );
+        assert_eq!(
+            get_route_geojson_from_db(&db_pool, route.id).await.unwrap(),
+            Some("{}".to_string())
+        );
+        assert_eq!(
+            get_route_original_from_db(&db_pool, route.id)
+                .await
+                .unwrap(),
+            Some((b"<gpx></gpx>".to_vec(), Some(RouteFormat::Gpx)))
+        );
+
+        assert!(rename_route(&db_pool, route.id, "renamed").await.unwrap());
+        let routes = list_routes_from_db(&db_pool).await.unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].name, "renamed");
+
+        set_room_active_route(&db_pool, room.id, Some(route.id))
+            .await
+            .unwrap();
+        assert_eq!(
+            list_rooms_with_active_route(&db_pool, route.id)
+                .await
+                .unwrap(),
+            vec![room.id]
+        );
+
+        // deleting the route MUST also clear it from the room
+        assert!(delete_route(&db_pool, route.id).await.unwrap());
+        assert!(!delete_route(&db_pool, route.id).await.unwrap());
+        let room = get_room_from_db(&db_pool, room.id).await.unwrap().unwrap();
+        assert_eq!(room.active_route_id, None);
+    }
+
+    #[sqlx::test]
+    async fn test_session_lifecycle_ok() {
+        let db_pool = setup().await;
+        let expires_at = now_timestamp() + 60_000;
+
+        insert_session(&db_pool, "sid", "aaa", "hash", expires_at)
+            .await
+            .unwrap();
+        let session = get_session_from_db(&db_pool, "sid").await.unwrap().unwrap();
+        assert_eq!(session.username, "aaa");
+        assert!(session.is_active(now_timestamp()));
+        assert!(!session.is_active(expires_at));
+
+        assert!(
+            update_session_refresh_token(&db_pool, "sid", "hash", "hash2", expires_at + 1)
+                .await
+                .unwrap()
+        );
+        let session = get_session_from_db(&db_pool, "sid").await.unwrap().unwrap();
+        assert_eq!(session.refresh_token_hash, "hash2");
+        assert_eq!(session.expires_at, expires_at + 1);
+        // already rotated, eg by a concurrent request with the same refresh token
+        assert!(
+            !update_session_refresh_token(&db_pool, "sid", "hash", "hash3", expires_at)
+                .await
+                .unwrap()
+        );
+        let session = get_session_from_db(&db_pool, "sid").await.unwrap().unwrap();
+        assert_eq!(session.refresh_token_hash, "hash2");
+
+        assert!(revoke_session(&db_pool, "sid").await.unwrap());
+        assert!(!revoke_session(&db_pool, "sid").await.unwrap());
+        let session = get_session_from_db(&db_pool, "sid").await.unwrap().unwrap();
+        assert!(!session.is_active(now_timestamp()));
+        // a revoked session can NOT be refreshed
+        assert!(
+            !update_session_refresh_token(&db_pool, "sid", "hash2", "hash3", expires_at)
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            get_session_from_db(&db_pool, "unknown").await.unwrap(),
+            None
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_revoke_user_sessions_and_delete_user_ok() {
+        let db_pool = setup().await;
+        let expires_at = now_timestamp() + 60_000;
+        insert_user(&db_pool, "aaa", "password").await.unwrap();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        insert_positions(&db_pool, room.id, &[new_position("aaa", 1)])
+            .await
+            .unwrap();
+        for sid in ["sid1", "sid2", "sid3"] {
+            insert_session(&db_pool, sid, "aaa", "hash", expires_at)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            revoke_user_sessions(&db_pool, "aaa", Some("sid1"))
+                .await
+                .unwrap(),
+            2
+        );
+        let session = get_session_from_db(&db_pool, "sid1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_active(now_timestamp()));
+        assert_eq!(
+            revoke_user_sessions(&db_pool, "aaa", None).await.unwrap(),
+            1
+        );
+
+        assert!(delete_user_and_data(&db_pool, "aaa").await.unwrap());
+        assert!(!delete_user_and_data(&db_pool, "aaa").await.unwrap());
+        assert_eq!(get_user_from_db(&db_pool, "aaa").await.unwrap(), None);
+        assert_eq!(get_session_from_db(&db_pool, "sid1").await.unwrap(), None);
+        assert_eq!(
+            get_room_member_role(&db_pool, room.id, "aaa")
+                .await
+                .unwrap(),
+            None
         );
+        assert!(list_positions_from_db(&db_pool, "aaa", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        // the room is shared: it is kept
+        assert!(get_room_from_db(&db_pool, room.id).await.unwrap().is_some());
     }
 }
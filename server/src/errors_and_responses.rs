@@ -28,6 +28,12 @@ pub(crate) enum AppError {
     BadRequest,
     /// NOTE: for security reasons, this is ALSO used when trying to access protected routes (eg not a superuser, etc)
     NotFound,
+    /// eg an uploaded file in an unknown format; with a message for the user
+    UnsupportedMediaType(String),
+    /// eg a corrupt uploaded file; with a message for the user
+    UnprocessableEntity(String),
+    /// eg an uploaded file above the configured limits; with a message for the user
+    PayloadTooLarge(String),
     /// eg DB error, etc
     InternalError,
 }
@@ -52,6 +58,11 @@ impl IntoResponse for AppError {
             AppError::LoginError => (StatusCode::BAD_REQUEST, "login error".to_owned()),
             AppError::BadRequest => (StatusCode::BAD_REQUEST, "bad request".to_owned()),
             AppError::NotFound => (StatusCode::NOT_FOUND, "not found".to_owned()),
+            AppError::UnsupportedMediaType(message) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, message)
+            }
+            AppError::UnprocessableEntity(message) => (StatusCode::UNPROCESSABLE_ENTITY, message),
+            AppError::PayloadTooLarge(message) => (StatusCode::PAYLOAD_TOO_LARGE, message),
             AppError::InternalError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "internal error".to_owned(),
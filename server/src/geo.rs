@@ -0,0 +1,314 @@
+//! Small geographic helpers; working on (lat, lng) in degrees, like `protocol::Position`
+
+use serde_json::Value;
+
+/// Mean Earth radius, in meters
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Great-circle distance between two (lat, lng), in meters
+/// cf `https://en.wikipedia.org/wiki/Haversine_formula`
+pub(crate) fn haversine_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lng1) = (a.0.to_radians(), a.1.to_radians());
+    let (lat2, lng2) = (b.0.to_radians(), b.1.to_radians());
+
+    let h = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lng2 - lng1) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Total length of a line, in meters
+pub(crate) fn line_distance(line: &[(f64, f64)]) -> f64 {
+    line.windows(2)
+        .map(|pair| haversine_distance(pair[0], pair[1]))
+        .sum()
+}
+
+/// returns: `[min_lng, min_lat, max_lng, max_lat]` ie the `GeoJSON` "bbox" order; or None if there are no points
+pub(crate) fn bounding_box(lines: &[Vec<(f64, f64)>]) -> Option<[f64; 4]> {
+    lines
+        .iter()
+        .flatten()
+        .fold(None, |bbox, &(lat, lng)| match bbox {
+            None => Some([lng, lat, lng, lat]),
+            Some([min_lng, min_lat, max_lng, max_lat]) => Some([
+                f64::min(min_lng, lng),
+                f64::min(min_lat, lat),
+                f64::max(max_lng, lng),
+                f64::max(max_lat, lat),
+            ]),
+        })
+}
+
+/// Whether a (lat, lng) is inside a polygon, given by its vertices(closed or not)
+/// Ray casting on the raw degrees: fine for the small areas of the geofences, NOT across the antimeridian
+/// cf `https://en.wikipedia.org/wiki/Point_in_polygon#Ray_casting_algorithm`
+pub(crate) fn is_in_polygon(point: (f64, f64), polygon: &[(f64, f64)]) -> bool {
+    let (lat, lng) = point;
+    let mut is_inside = false;
+    let Some(mut previous) = polygon.last().copied() else {
+        return false;
+    };
+    for &(lat_i, lng_i) in polygon {
+        let (lat_j, lng_j) = previous;
+        if (lat_i > lat) != (lat_j > lat)
+            && lng < (lng_j - lng_i) * (lat - lat_i) / (lat_j - lat_i) + lng_i
+        {
+            is_inside = !is_inside;
+        }
+        previous = (lat_i, lng_i);
+    }
+
+    is_inside
+}
+
+/// Where a (lat, lng) is relative to a `RouteLine`; cf `RouteLine::project`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Projection {
+    /// in meters: the shortest distance to the route
+    pub(crate) distance_to: f64,
+    /// in meters: the distance from the start of the route to the closest point
+    pub(crate) distance_along: f64,
+}
+
+/// The lines of a route(cf `geojson_lines`), followed one after the other; and the distance from the start to each point
+#[derive(Debug, Default)]
+pub(crate) struct RouteLine {
+    lines: Vec<Vec<(f64, f64)>>,
+    /// same shape as `lines`; NOTE: the gaps between two lines are NOT counted
+    distances: Vec<Vec<f64>>,
+    /// in meters
+    length: f64,
+}
+
+impl RouteLine {
+    pub(crate) fn new(lines: Vec<Vec<(f64, f64)>>) -> Self {
+        let mut length = 0.0;
+        let distances = lines
+            .iter()
+            .map(|line| {
+                let mut previous = line.first().copied();
+                line.iter()
+                    .map(|&point| {
+                        length +=
+                            previous.map_or(0.0, |previous| haversine_distance(previous, point));
+                        previous = Some(point);
+                        length
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            lines,
+            distances,
+            length,
+        }
+    }
+
+    /// in meters
+    pub(crate) fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// true if there is no route at all, ie `project` always returns None
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The closest point of the route to a (lat, lng); or None if the route is empty
+    /// Each segment is projected on a plane tangent at `point`(equirectangular): accurate enough for the few
+    /// hundred meters that matter near a route; NOT across the antimeridian.
+    /// NOTE: where a route passes twice(eg a loop, or an out and back), the closest segment wins; whichever lap that is.
+    pub(crate) fn project(&self, point: (f64, f64)) -> Option<Projection> {
+        let (lat0, lng0) = point;
+        let cos_lat0 = lat0.to_radians().cos();
+        // in meters, relative to `point`
+        let project = |(lat, lng): (f64, f64)| {
+            (
+                (lng - lng0).to_radians() * cos_lat0 * EARTH_RADIUS,
+                (lat - lat0).to_radians() * EARTH_RADIUS,
+            )
+        };
+        // returns: the distance to the segment; and how far along it is the closest point, from 0 to 1
+        let project_on_segment = |a: (f64, f64), b: (f64, f64)| {
+            let ((ax, ay), (bx, by)) = (project(a), project(b));
+            let (dx, dy) = (bx - ax, by - ay);
+            let length2 = dx * dx + dy * dy;
+            let t = if length2 > 0.0 {
+                (-(ax * dx + ay * dy) / length2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            ((ax + t * dx).hypot(ay + t * dy), t)
+        };
+
+        let mut closest: Option<Projection> = None;
+        for (line, distances) in self.lines.iter().zip(&self.distances) {
+            // a single point is a segment of length 0
+            let segments: Vec<(usize, usize)> = match line.len() {
+                1 => vec![(0, 0)],
+                len => (1..len).map(|j| (j - 1, j)).collect(),
+            };
+            for (i, j) in segments {
+                let (distance_to, t) = project_on_segment(line[i], line[j]);
+                if closest.is_none_or(|closest| distance_to < closest.distance_to) {
+                    closest = Some(Projection {
+                        distance_to,
+                        distance_along: distances[i] + t * (distances[j] - distances[i]),
+                    });
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+/// Extract all the lines of a `GeoJSON` object, eg the `GeometryCollection` of `MultiLineString`
+/// produced by `geozero` from a .gpx
+/// Points are skipped; and so are malformed parts.
+/// NOTE: `GeoJSON` coordinates are [lng, lat] but this returns (lat, lng)
+pub(crate) fn geojson_lines(geojson: &Value) -> Vec<Vec<(f64, f64)>> {
+    geojson_lines_with(geojson, &|coordinate| {
+        Some((coordinate[1].as_f64()?, coordinate[0].as_f64()?))
+    })
+}
+
+/// The same as `geojson_lines`, with a given conversion of each coordinate; eg to keep the elevation
+pub(crate) fn geojson_lines_with<T>(
+    geojson: &Value,
+    to_point: &dyn Fn(&Value) -> Option<T>,
+) -> Vec<Vec<T>> {
+    let mut lines = vec![];
+    collect_lines(geojson, to_point, &mut lines);
+    lines
+}
+
+fn collect_lines<T>(
+    geojson: &Value,
+    to_point: &dyn Fn(&Value) -> Option<T>,
+    lines: &mut Vec<Vec<T>>,
+) {
+    let as_slice = |value: &Value| -> Vec<Value> { value.as_array().cloned().unwrap_or_default() };
+    let to_line = |coordinates: &Value| -> Vec<T> {
+        as_slice(coordinates).iter().filter_map(to_point).collect()
+    };
+
+    match geojson["type"].as_str() {
+        Some("FeatureCollection") => {
+            for feature in as_slice(&geojson["features"]) {
+                collect_lines(&feature, to_point, lines);
+            }
+        }
+        Some("Feature") => collect_lines(&geojson["geometry"], to_point, lines),
+        Some("GeometryCollection") => {
+            for geometry in as_slice(&geojson["geometries"]) {
+                collect_lines(&geometry, to_point, lines);
+            }
+        }
+        Some("LineString") => lines.push(to_line(&geojson["coordinates"])),
+        Some("MultiLineString") => {
+            lines.extend(as_slice(&geojson["coordinates"]).iter().map(to_line));
+        }
+        _ => {}
+    }
+
+    lines.retain(|line| !line.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_haversine_distance() {
+        // Paris -> London is ~343.5 km
+        let paris = (48.8566, 2.3522);
+        let london = (51.5074, -0.1278);
+        let distance = haversine_distance(paris, london);
+        assert!((distance - 343_500.0).abs() < 1_000.0, "{distance}");
+
+        assert!(haversine_distance(paris, paris).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_is_in_polygon() {
+        let square = [(48.0, 2.0), (48.0, 2.1), (48.1, 2.1), (48.1, 2.0)];
+        assert!(is_in_polygon((48.05, 2.05), &square));
+        assert!(!is_in_polygon((48.05, 2.15), &square));
+        assert!(!is_in_polygon((47.95, 2.05), &square));
+
+        // concave: a "U"
+        let u = [
+            (0.0, 0.0),
+            (0.0, 3.0),
+            (3.0, 3.0),
+            (3.0, 2.0),
+            (1.0, 2.0),
+            (1.0, 1.0),
+            (3.0, 1.0),
+            (3.0, 0.0),
+        ];
+        assert!(is_in_polygon((2.0, 0.5), &u));
+        assert!(!is_in_polygon((2.0, 1.5), &u));
+        assert!(!is_in_polygon((48.05, 2.05), &[]));
+    }
+
+    #[test]
+    fn test_route_line_project() {
+        // along a meridian; then a single point
+        let route = RouteLine::new(vec![vec![(48.0, 2.0), (48.01, 2.0)], vec![(49.0, 2.0)]]);
+        let segment = haversine_distance((48.0, 2.0), (48.01, 2.0));
+        assert!((route.length() - segment).abs() < 0.001);
+
+        // 0.001 degree of longitude at 48 degrees is ~74.4 meters
+        let projection = route.project((48.005, 2.001)).unwrap();
+        assert!(
+            (projection.distance_to - 74.4).abs() < 0.5,
+            "{projection:?}"
+        );
+        assert!(
+            (projection.distance_along - segment / 2.0).abs() < 1.0,
+            "{projection:?}"
+        );
+        // before the start of the segment: the distance to the start
+        let projection = route.project((47.99, 2.0)).unwrap();
+        assert!(
+            (projection.distance_to - haversine_distance((47.99, 2.0), (48.0, 2.0))).abs() < 0.5,
+            "{projection:?}"
+        );
+        assert!(projection.distance_along.abs() < 0.001);
+        // the single point: the gap between the lines is NOT counted
+        let projection = route.project((49.0, 2.0)).unwrap();
+        assert!(projection.distance_to < 0.001, "{projection:?}");
+        assert!((projection.distance_along - segment).abs() < 0.001);
+
+        assert_eq!(RouteLine::default().project((48.0, 2.0)), None);
+    }
+
+    #[test]
+    fn test_geojson_lines_and_bounding_box() {
+        let geojson = json!({
+            "type": "GeometryCollection",
+            "geometries": [
+                {
+                    "type": "MultiLineString",
+                    "coordinates": [[[2.0, 48.0], [2.1, 48.1]], [[2.2, 48.2]]],
+                },
+                { "type": "Point", "coordinates": [5.0, 45.0] },
+                { "type": "LineString", "coordinates": "malformed" },
+            ],
+        });
+
+        let lines = geojson_lines(&geojson);
+        assert_eq!(
+            lines,
+            vec![vec![(48.0, 2.0), (48.1, 2.1)], vec![(48.2, 2.2)]]
+        );
+        assert_eq!(bounding_box(&lines), Some([2.0, 48.0, 2.2, 48.2]));
+        assert_eq!(bounding_box(&[]), None);
+    }
+}
@@ -0,0 +1,372 @@
+//! Geofences: circles or polygons, eg a checkpoint or the area of an event; cf `api_geofence.rs`
+//!
+//! A geofence belongs to a room; or to a route, ie it applies to every room where that route is active.
+//! Each position shared in a room(ie after `privacy.rs`) is checked against its geofences(cf `Geofences::check`);
+//! when a user enters or exits one, a `WsMessage::Geofence` is broadcast and stored in the history.
+//! NOTE: the state(who is inside what) is only kept in memory: after a restart, the users already inside
+//! a geofence "enter" it again with their next position.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use protocol::{GeofenceTransition, Position, WsMessage};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::db::list_room_geofences;
+use crate::geo::{haversine_distance, is_in_polygon};
+
+/// The largest circle that can be created; beyond that it is NOT really a fence anymore
+pub(crate) const MAX_GEOFENCE_RADIUS: f64 = 100_000.0;
+/// The most vertices a polygon can have
+pub(crate) const MAX_GEOFENCE_POINTS: usize = 1000;
+
+/// SHOULD match the `shape` column of `server/migrations/20240307_1000_geofence.sql`
+/// eg `{"type":"circle","lat":48.8,"lng":2.3,"radius":100}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum GeofenceShape {
+    Circle {
+        /// in degrees
+        lat: f64,
+        /// in degrees
+        lng: f64,
+        /// in meters
+        radius: f64,
+    },
+    Polygon {
+        /// (lat, lng) in degrees; closed or not
+        points: Vec<(f64, f64)>,
+    },
+}
+
+impl GeofenceShape {
+    /// returns: a human readable message describing the first invalid field
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let validate_point = |lat: f64, lng: f64| {
+            if lat.is_finite()
+                && lng.is_finite()
+                && (-90.0..=90.0).contains(&lat)
+                && (-180.0..=180.0).contains(&lng)
+            {
+                Ok(())
+            } else {
+                Err(format!("invalid point: ({lat}, {lng})"))
+            }
+        };
+
+        match self {
+            GeofenceShape::Circle { lat, lng, radius } => {
+                validate_point(*lat, *lng)?;
+                if !radius.is_finite() || *radius <= 0.0 || *radius > MAX_GEOFENCE_RADIUS {
+                    return Err(format!(
+                        "radius MUST be between 0 and {MAX_GEOFENCE_RADIUS} meters"
+                    ));
+                }
+            }
+            GeofenceShape::Polygon { points } => {
+                if !(3..=MAX_GEOFENCE_POINTS).contains(&points.len()) {
+                    return Err(format!(
+                        "a polygon MUST have between 3 and {MAX_GEOFENCE_POINTS} points"
+                    ));
+                }
+                for &(lat, lng) in points {
+                    validate_point(lat, lng)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn contains(&self, lat: f64, lng: f64) -> bool {
+        match self {
+            GeofenceShape::Circle {
+                lat: center_lat,
+                lng: center_lng,
+                radius,
+            } => haversine_distance((*center_lat, *center_lng), (lat, lng)) <= *radius,
+            GeofenceShape::Polygon { points } => is_in_polygon((lat, lng), points),
+        }
+    }
+}
+
+/// SHOULD match `server/migrations/20240307_1000_geofence.sql`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Geofence {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    /// exactly one of `room_id` and `route_id` is set
+    pub(crate) room_id: Option<i64>,
+    pub(crate) route_id: Option<i64>,
+    pub(crate) shape: GeofenceShape,
+    pub(crate) created_by: String,
+}
+
+/// An entry of the history; cf `GET /api/rooms/{room_id}/geofences/events`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct GeofenceEvent {
+    pub(crate) geofence_id: i64,
+    pub(crate) name: String,
+    pub(crate) username: String,
+    pub(crate) transition: GeofenceTransition,
+    /// milliseconds since UNIX epoch
+    pub(crate) timestamp: i64,
+}
+
+impl GeofenceEvent {
+    pub(crate) fn to_message(&self) -> WsMessage {
+        WsMessage::Geofence {
+            username: self.username.clone(),
+            geofence_id: self.geofence_id,
+            name: self.name.clone(),
+            transition: self.transition,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+pub(crate) fn transition_as_str(transition: GeofenceTransition) -> &'static str {
+    match transition {
+        GeofenceTransition::Enter => "enter",
+        GeofenceTransition::Exit => "exit",
+    }
+}
+
+pub(crate) fn parse_transition(transition: &str) -> GeofenceTransition {
+    match transition {
+        "enter" => GeofenceTransition::Enter,
+        _ => GeofenceTransition::Exit,
+    }
+}
+
+/// The geofences of each room; and who is inside what
+#[derive(Debug, Default)]
+pub(crate) struct Geofences {
+    /// The geofences that apply to each room(cf `db::list_room_geofences`), indexed by room id
+    /// Loaded from the DB on the first `check`; call `forget_room` or `forget_all` when they change.
+    room_geofences: DashMap<i64, Arc<Vec<Geofence>>>,
+    /// The geofences each user is currently inside, indexed by (room id, username)
+    inside: DashMap<(i64, String), HashSet<i64>>,
+}
+
+impl Geofences {
+    /// Check a new position of a given user against the geofences of the room
+    /// returns: the geofences they entered or exited, in the order of the geofences
+    pub(crate) async fn check(
+        &self,
+        db_pool: &SqlitePool,
+        room_id: i64,
+        position: &Position,
+    ) -> Result<Vec<GeofenceEvent>, std::io::Error> {
+        let geofences = self.room_geofences(db_pool, room_id).await?;
+        if geofences.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut inside = self
+            .inside
+            .entry((room_id, position.username.clone()))
+            .or_default();
+        let mut events = vec![];
+        for geofence in geofences.iter() {
+            let is_inside = geofence.shape.contains(position.lat, position.lng);
+            let transition = match (inside.contains(&geofence.id), is_inside) {
+                (false, true) => {
+                    inside.insert(geofence.id);
+                    GeofenceTransition::Enter
+                }
+                (true, false) => {
+                    inside.remove(&geofence.id);
+                    GeofenceTransition::Exit
+                }
+                _ => continue,
+            };
+            events.push(GeofenceEvent {
+                geofence_id: geofence.id,
+                name: geofence.name.clone(),
+                username: position.username.clone(),
+                transition,
+                timestamp: position.timestamp,
+            });
+        }
+        // the geofences that were deleted meanwhile
+        inside.retain(|geofence_id| geofences.iter().any(|geofence| geofence.id == *geofence_id));
+
+        Ok(events)
+    }
+
+    /// The geofences that apply to a given room, from the cache or else from the DB
+    async fn room_geofences(
+        &self,
+        db_pool: &SqlitePool,
+        room_id: i64,
+    ) -> Result<Arc<Vec<Geofence>>, std::io::Error> {
+        if let Some(geofences) = self.room_geofences.get(&room_id) {
+            return Ok(geofences.clone());
+        }
+
+        let geofences = Arc::new(list_room_geofences(db_pool, room_id).await?);
+        self.room_geofences.insert(room_id, geofences.clone());
+
+        Ok(geofences)
+    }
+
+    /// The geofences of a given room changed, eg its route; they will be loaded again on the next `check`
+    pub(crate) fn forget_room(&self, room_id: i64) {
+        self.room_geofences.remove(&room_id);
+    }
+
+    /// Same as `forget_room`, but for every room; eg a geofence of a route changed
+    pub(crate) fn forget_all(&self) {
+        self.room_geofences.clear();
+    }
+
+    /// Remove a deleted user, in every room
+    pub(crate) fn forget_user(&self, username: &str) {
+        self.inside
+            .retain(|(_room_id, member), _geofence_ids| member != username);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{insert_geofence, insert_room, setup_db};
+
+    use super::*;
+
+    fn new_position(lat: f64, lng: f64, timestamp: i64) -> Position {
+        Position {
+            username: "aaa".to_string(),
+            lat,
+            lng,
+            accuracy: None,
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_geofence_shape() {
+        let circle = GeofenceShape::Circle {
+            lat: 48.8354,
+            lng: 2.3203,
+            radius: 100.0,
+        };
+        assert!(circle.validate().is_ok());
+        assert!(circle.contains(48.8355, 2.3204));
+        assert!(!circle.contains(48.84, 2.33));
+        assert_eq!(
+            serde_json::to_value(&circle).unwrap(),
+            serde_json::json!({ "type": "circle", "lat": 48.8354, "lng": 2.3203, "radius": 100.0 })
+        );
+
+        let square = GeofenceShape::Polygon {
+            points: vec![(48.0, 2.0), (48.0, 2.1), (48.1, 2.1), (48.1, 2.0)],
+        };
+        assert!(square.validate().is_ok());
+        assert!(square.contains(48.05, 2.05));
+        assert!(!square.contains(48.15, 2.05));
+
+        for invalid in [
+            GeofenceShape::Circle {
+                lat: 91.0,
+                lng: 2.0,
+                radius: 100.0,
+            },
+            GeofenceShape::Circle {
+                lat: 48.0,
+                lng: 2.0,
+                radius: 0.0,
+            },
+            GeofenceShape::Circle {
+                lat: 48.0,
+                lng: 2.0,
+                radius: MAX_GEOFENCE_RADIUS + 1.0,
+            },
+            GeofenceShape::Polygon {
+                points: vec![(48.0, 2.0), (48.1, 2.1)],
+            },
+            GeofenceShape::Polygon {
+                points: vec![(48.0, 2.0), (48.1, 2.1), (48.0, f64::NAN)],
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_geofences_check() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let shape = GeofenceShape::Circle {
+            lat: 48.8354,
+            lng: 2.3203,
+            radius: 100.0,
+        };
+        let geofence = insert_geofence(&db_pool, "checkpoint", Some(room.id), None, &shape, "aaa")
+            .await
+            .unwrap();
+        let geofences = Geofences::default();
+        let transitions = |events: Vec<GeofenceEvent>| -> Vec<(i64, GeofenceTransition)> {
+            events
+                .into_iter()
+                .map(|event| (event.geofence_id, event.transition))
+                .collect()
+        };
+
+        let outside = new_position(48.9, 2.4, 1);
+        let inside = new_position(48.8354, 2.3203, 2);
+        assert_eq!(
+            geofences.check(&db_pool, room.id, &outside).await.unwrap(),
+            vec![]
+        );
+        let events = geofences.check(&db_pool, room.id, &inside).await.unwrap();
+        assert_eq!(
+            events,
+            vec![GeofenceEvent {
+                geofence_id: geofence.id,
+                name: "checkpoint".to_string(),
+                username: "aaa".to_string(),
+                transition: GeofenceTransition::Enter,
+                timestamp: 2,
+            }]
+        );
+        assert_eq!(
+            geofences.check(&db_pool, room.id, &inside).await.unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            transitions(geofences.check(&db_pool, room.id, &outside).await.unwrap()),
+            vec![(geofence.id, GeofenceTransition::Exit)]
+        );
+
+        // the new geofences are only seen after `forget_room`
+        let other = insert_geofence(&db_pool, "zone", Some(room.id), None, &shape, "aaa")
+            .await
+            .unwrap();
+        assert_eq!(
+            transitions(geofences.check(&db_pool, room.id, &inside).await.unwrap()),
+            vec![(geofence.id, GeofenceTransition::Enter)]
+        );
+        geofences.forget_room(room.id);
+        assert_eq!(
+            transitions(geofences.check(&db_pool, room.id, &inside).await.unwrap()),
+            vec![(other.id, GeofenceTransition::Enter)]
+        );
+
+        // a deleted user "enters" again
+        geofences.forget_user("aaa");
+        assert_eq!(
+            transitions(geofences.check(&db_pool, room.id, &inside).await.unwrap()),
+            vec![
+                (geofence.id, GeofenceTransition::Enter),
+                (other.id, GeofenceTransition::Enter)
+            ]
+        );
+    }
+}
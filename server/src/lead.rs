@@ -0,0 +1,175 @@
+//! The leader and the sweeper(ie the last rider) of a room; and how spread out the group is between them
+//!
+//! They are designated by an organiser(cf `api_room::set_lead`) and broadcast with `WsMessage::Lead`.
+//! The gap is the distance along the active route between the leader and the sweeper(cf `progress.rs`);
+//! beyond `Config::group_spread_threshold` the group is spread out, and it is regrouped below `REGROUPED_RATIO`
+//! of it, like `off_route.rs`. Only the changes are broadcast, cf `WsMessage::GroupSpread`.
+
+use std::collections::HashMap;
+
+use dashmap::DashMap;
+use protocol::{Position, WsMessage};
+use serde::{Deserialize, Serialize};
+
+/// A spread out group is regrouped when the gap is below that fraction of the threshold
+const REGROUPED_RATIO: f64 = 0.8;
+
+/// SHOULD match `server/migrations/20240308_1000_lead.sql`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Lead {
+    pub(crate) leader: Option<String>,
+    pub(crate) sweeper: Option<String>,
+}
+
+impl Lead {
+    /// Whether a given user is the leader or the sweeper
+    pub(crate) fn contains(&self, username: &str) -> bool {
+        self.leader.as_deref() == Some(username) || self.sweeper.as_deref() == Some(username)
+    }
+
+    /// in meters: how far ahead of the sweeper the leader is along the active route
+    /// None if either is NOT designated, or has NOT been projected on the route yet
+    pub(crate) fn gap(&self, positions: &HashMap<String, Position>) -> Option<f64> {
+        let distance_done = |username: &Option<String>| {
+            positions
+                .get(username.as_deref()?)?
+                .progress
+                .map(|progress| progress.distance_done)
+        };
+
+        Some(distance_done(&self.leader)? - distance_done(&self.sweeper)?)
+    }
+}
+
+/// Which groups are spread out
+#[derive(Debug, Default)]
+pub(crate) struct GroupSpreads {
+    /// The rooms whose group is currently spread out, and their last gap(in meters)
+    spread: DashMap<i64, f64>,
+}
+
+impl GroupSpreads {
+    /// The gap of a given room changed, cf `Lead::gap`
+    /// returns: the `WsMessage::GroupSpread` to broadcast, if the group spread out or regrouped
+    pub(crate) fn check(
+        &self,
+        room_id: i64,
+        gap: f64,
+        threshold: f64,
+        timestamp: i64,
+    ) -> Option<WsMessage> {
+        let was_spread = self.spread.contains_key(&room_id);
+        let is_spread = if was_spread {
+            gap > threshold * REGROUPED_RATIO
+        } else {
+            gap > threshold
+        };
+        if is_spread {
+            self.spread.insert(room_id, gap);
+        } else {
+            self.spread.remove(&room_id);
+        }
+        if is_spread == was_spread {
+            return None;
+        }
+
+        Some(WsMessage::GroupSpread {
+            gap,
+            is_spread,
+            timestamp,
+        })
+    }
+
+    /// Whether the group of a given room is currently spread out
+    pub(crate) fn is_spread(&self, room_id: i64) -> bool {
+        self.spread.contains_key(&room_id)
+    }
+
+    /// The leader, the sweeper or the route of a given room changed: start over
+    pub(crate) fn forget_room(&self, room_id: i64) {
+        self.spread.remove(&room_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::RouteProgress;
+
+    use super::*;
+
+    fn new_position(username: &str, distance_done: Option<f64>) -> (String, Position) {
+        let position = Position {
+            username: username.to_string(),
+            lat: 48.05,
+            lng: 2.0,
+            accuracy: None,
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp: 1,
+            progress: distance_done.map(|distance_done| RouteProgress {
+                distance_done,
+                distance_remaining: 10_000.0 - distance_done,
+                percent: distance_done / 100.0,
+                speed: None,
+                eta: None,
+            }),
+        };
+        (username.to_string(), position)
+    }
+
+    #[test]
+    fn test_lead_gap() {
+        let positions = HashMap::from([
+            new_position("aaa", Some(5000.0)),
+            new_position("bbb", Some(1500.0)),
+            new_position("ccc", None),
+        ]);
+        let lead = |leader: &str, sweeper: &str| Lead {
+            leader: Some(leader.to_string()),
+            sweeper: Some(sweeper.to_string()),
+        };
+
+        assert_eq!(lead("aaa", "bbb").gap(&positions), Some(3500.0));
+        assert!(lead("aaa", "bbb").contains("bbb"));
+        assert!(!lead("aaa", "bbb").contains("ccc"));
+        // NOT on the route yet; or without any position
+        assert_eq!(lead("aaa", "ccc").gap(&positions), None);
+        assert_eq!(lead("aaa", "ddd").gap(&positions), None);
+        assert_eq!(Lead::default().gap(&positions), None);
+    }
+
+    #[test]
+    fn test_group_spreads_check() {
+        let spreads = GroupSpreads::default();
+
+        assert_eq!(spreads.check(1, 500.0, 1000.0, 1), None);
+        assert_eq!(
+            spreads.check(1, 1500.0, 1000.0, 2),
+            Some(WsMessage::GroupSpread {
+                gap: 1500.0,
+                is_spread: true,
+                timestamp: 2,
+            })
+        );
+        assert!(spreads.is_spread(1));
+        // the other rooms are NOT affected
+        assert!(!spreads.is_spread(2));
+        // still spread out; and NOT regrouped until below 80% of the threshold
+        assert_eq!(spreads.check(1, 1200.0, 1000.0, 3), None);
+        assert_eq!(spreads.check(1, 900.0, 1000.0, 4), None);
+        assert_eq!(
+            spreads.check(1, 700.0, 1000.0, 5),
+            Some(WsMessage::GroupSpread {
+                gap: 700.0,
+                is_spread: false,
+                timestamp: 5,
+            })
+        );
+        assert!(!spreads.is_spread(1));
+
+        spreads.check(1, 1500.0, 1000.0, 6);
+        spreads.forget_room(1);
+        assert!(!spreads.is_spread(1));
+    }
+}
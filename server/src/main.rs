@@ -14,9 +14,11 @@ use std::net::Ipv6Addr;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use api_authorize_jwt::Claims;
-use axum::routing::post;
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{delete, patch, post, put};
 use axum::Extension;
 use axum::{response::IntoResponse, routing::get, Router};
 use axum_server::tls_rustls::RustlsConfig;
@@ -26,15 +28,40 @@ use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 
 mod api_authorize_jwt;
+mod api_geofence;
+mod api_poi;
+mod api_room;
+mod api_route;
+mod api_track;
 mod api_user;
 mod db;
 mod errors_and_responses;
+mod geo;
+mod geofence;
+mod lead;
+mod off_route;
+mod poi;
+mod presence;
+mod privacy;
+mod progress;
+mod role;
+mod room;
+mod route;
+mod route_fit;
+mod route_formats;
 mod route_gpx;
+mod route_profile;
+mod route_simplify;
+mod route_stats;
+mod session;
 mod state;
+mod throttle;
+mod track_gpx;
 mod user;
 mod ws_handler;
 
-use crate::state::new_state;
+use crate::route_simplify::SimplifyAlgorithm;
+use crate::state::{new_state, Config, SharedState};
 use crate::ws_handler::ws_handler;
 
 // Setup the command line interface with clap.
@@ -73,6 +100,75 @@ struct Opt {
     /// eg "MyPasSwOrD1234"
     #[clap(long, requires("root_user"))]
     root_password: Option<String>,
+
+    /// if set: users that are NOT in the database can no longer login without a password;
+    /// they MUST register first(cf `POST /register`)
+    #[clap(long)]
+    disable_open_login: bool,
+
+    /// if set: `POST /register` is disabled; combined with `--disable-open-login`
+    /// only the users created by a superuser can login
+    #[clap(long)]
+    disable_registration: bool,
+
+    /// lifetime of the access tokens, in seconds; the frontend renews them with `POST /refresh`
+    #[clap(long, default_value = "900")]
+    access_token_ttl: u64,
+
+    /// lifetime of a session, in seconds: after that without any `POST /refresh` the user MUST login again
+    #[clap(long, default_value = "2592000")]
+    refresh_token_ttl: u64,
+
+    /// interval between two pings on the "geolocation" websockets, in seconds;
+    /// the clients that stop answering are disconnected, ie offline
+    #[clap(long, default_value = "15")]
+    heartbeat_interval: u64,
+
+    /// a connected user that has NOT sent any position for that long(in seconds) is idle
+    #[clap(long, default_value = "120")]
+    idle_timeout: u64,
+
+    /// the number of messages buffered for each room; the clients that fall further behind
+    /// skip to the last position of each user
+    #[clap(
+        long,
+        default_value = "100",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..=100_000)
+    )]
+    broadcast_capacity: usize,
+
+    /// the positions of a room are sent to its clients in a single frame every `fanout_interval_ms` milliseconds
+    #[clap(long, default_value = "1000", value_parser = clap::value_parser!(u64).range(1..))]
+    fanout_interval_ms: u64,
+
+    /// the maximum number of positions per second a client can send; the extra ones are dropped,
+    /// and the clients that send way more are disconnected
+    #[clap(long, default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    position_rate_limit: u32,
+
+    /// in meters: the riders further than that from the active route of their room are flagged as off route
+    #[clap(long, default_value = "100", value_parser = clap::value_parser!(u32).range(1..))]
+    off_route_threshold: u32,
+
+    /// in meters: the group is flagged as spread out when the leader is further than that ahead of the sweeper
+    #[clap(long, default_value = "1000", value_parser = clap::value_parser!(u32).range(1..))]
+    group_spread_threshold: u32,
+
+    /// how the tracks of the uploaded routes are simplified; the original files are kept as uploaded
+    #[clap(long, value_enum, default_value = "douglas-peucker")]
+    route_simplify: SimplifyAlgorithm,
+
+    /// in meters: the details of the uploaded routes smaller than that are simplified away
+    #[clap(long, default_value = "5")]
+    route_simplify_tolerance: f64,
+
+    /// in bytes: the larger uploaded routes are rejected
+    #[clap(long, default_value = "10485760", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_upload_size: usize,
+
+    /// the uploaded routes with more points are rejected
+    #[clap(long, default_value = "200000", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_route_points: usize,
 }
 
 #[tokio::main]
@@ -92,7 +188,25 @@ async fn main() -> Result<(), std::io::Error> {
         opt.root_password,
     )
     .await?;
-    let app = new_app(db_pool)?;
+    let config = Config {
+        open_login: !opt.disable_open_login,
+        registration_enabled: !opt.disable_registration,
+        access_token_ttl: Duration::from_secs(opt.access_token_ttl),
+        refresh_token_ttl: Duration::from_secs(opt.refresh_token_ttl),
+        heartbeat_interval: Duration::from_secs(opt.heartbeat_interval),
+        idle_timeout: Duration::from_secs(opt.idle_timeout),
+        broadcast_capacity: opt.broadcast_capacity,
+        fanout_interval: Duration::from_millis(opt.fanout_interval_ms),
+        position_rate_limit: opt.position_rate_limit,
+        off_route_threshold: f64::from(opt.off_route_threshold),
+        group_spread_threshold: f64::from(opt.group_spread_threshold),
+        route_simplify: opt.route_simplify,
+        route_simplify_tolerance: opt.route_simplify_tolerance,
+        max_upload_size: opt.max_upload_size,
+        max_route_points: opt.max_route_points,
+    };
+    tracing::info!("config: {config:?}");
+    let app = new_app_with_config(db_pool, config)?;
 
     let sock_addr = SocketAddr::from((
         IpAddr::from_str(opt.addr.as_str()).unwrap_or(IpAddr::V6(Ipv6Addr::LOCALHOST)),
@@ -173,9 +287,25 @@ async fn hello(claims: Claims) -> impl IntoResponse {
     "hello from server!"
 }
 
+/// Same as `new_app_with_config` with the default `Config`; mostly for tests
+#[cfg(test)]
+pub(crate) fn new_app(db_pool: SqlitePool) -> Result<Router, std::io::Error> {
+    new_app_with_config(db_pool, Config::default())
+}
+
 /// `https://github.com/tokio-rs/axum/blob/4d65ba0215b57797193ec49245d32d4dd79bb701/examples/testing/src/main.rs#L36`
 #[allow(clippy::unnecessary_wraps)]
-pub(crate) fn new_app(db_pool: SqlitePool) -> Result<Router, std::io::Error> {
+pub(crate) fn new_app_with_config(
+    db_pool: SqlitePool,
+    config: Config,
+) -> Result<Router, std::io::Error> {
+    Ok(new_app_with_state(new_state(db_pool, config)))
+}
+
+/// Same as `new_app_with_config`; but the caller keeps a handle on the `AppState`, eg for the tests
+// NOTE: the whole list of the routes; easier to read in one place
+#[allow(clippy::too_many_lines)]
+pub(crate) fn new_app_with_state(app_state: SharedState) -> Router {
     // https://github.com/tokio-rs/axum/blob/d703e6f97a0156177466b6741be0beac0c83d8c7/examples/static-file-server/src/main.rs#L44
     // `ServeDir` allows setting a fallback if an asset is not found
     // so with this `GET /assets/doesnt-exist.jpg` will return `index.html`
@@ -184,8 +314,6 @@ pub(crate) fn new_app(db_pool: SqlitePool) -> Result<Router, std::io::Error> {
     let assets_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
     let static_files_service = ServeDir::new(assets_dir).append_index_html_on_directories(true);
 
-    let app_state = new_state(db_pool);
-
     #[allow(unused_mut)]
     let mut cors_layer = CorsLayer::very_permissive();
     #[cfg(not(debug_assertions))]
@@ -193,7 +321,7 @@ pub(crate) fn new_app(db_pool: SqlitePool) -> Result<Router, std::io::Error> {
     #[cfg(not(debug_assertions))]
     let cors_layer = cors_layer.clone().allow_origin(origins);
 
-    let app = Router::new()
+    Router::new()
         .route("/api/hello", get(hello))
         .route(
             "/api/gpx",
@@ -202,16 +330,110 @@ pub(crate) fn new_app(db_pool: SqlitePool) -> Result<Router, std::io::Error> {
             //     let app_state = Arc::clone(&app_state);
             //     move |body, claims| route_gpx::handle_gpx_upload(app_state, claims, body)
             // }),
-            post(route_gpx::handle_gpx_upload),
+            // NOTE: the size is checked while reading the file, cf `Config::max_upload_size`
+            post(route_gpx::handle_gpx_upload)
+                .layer(DefaultBodyLimit::disable())
+                .get(api_route::get_latest_route),
         )
         .route("/ws", get(ws_handler))
         .route("/authorize", post(api_authorize_jwt::authorize))
+        .route("/register", post(api_authorize_jwt::register))
+        .route("/refresh", post(api_authorize_jwt::refresh))
+        .route("/logout", post(api_authorize_jwt::logout))
         .route("/users", get(api_user::list_users))
         .route("/user/set_superuser", post(api_user::set_superuser))
+        .route("/user/unset_superuser", post(api_user::unset_superuser))
+        .route("/user/set_role", post(api_user::set_role))
+        .route("/api/me", get(api_user::me))
+        .route("/api/me/password", post(api_user::change_password))
+        .route("/api/users/:username", delete(api_user::delete_user))
+        .route(
+            "/api/users/:username/password",
+            post(api_user::reset_password),
+        )
+        .route(
+            "/api/rooms",
+            get(api_room::list_rooms).post(api_room::create_room),
+        )
+        .route("/api/rooms/:room_id/join", post(api_room::join_room))
+        .route("/api/rooms/:room_id/leave", post(api_room::leave_room))
+        .route(
+            "/api/rooms/:room_id/members/:username",
+            put(api_room::set_member_role),
+        )
+        .route(
+            "/api/rooms/:room_id/presence",
+            get(api_room::get_room_presence),
+        )
+        .route(
+            "/api/rooms/:room_id/privacy",
+            get(api_room::get_privacy).put(api_room::set_privacy),
+        )
+        .route(
+            "/api/rooms/:room_id/route",
+            get(api_route::get_room_route).post(api_route::set_room_route),
+        )
+        .route(
+            "/api/rooms/:room_id/lead",
+            get(api_room::get_lead).put(api_room::set_lead),
+        )
+        .route(
+            "/api/rooms/:room_id/progress",
+            get(api_route::get_room_progress),
+        )
+        .route(
+            "/api/rooms/:room_id/geofences",
+            get(api_geofence::get_room_geofences),
+        )
+        .route(
+            "/api/rooms/:room_id/geofences/events",
+            get(api_geofence::get_room_geofence_events),
+        )
+        .route("/api/rooms/:room_id/pois", get(api_poi::get_room_pois))
+        .route("/api/routes", get(api_route::list_routes))
+        .route(
+            "/api/geofences",
+            get(api_geofence::list_geofences).post(api_geofence::create_geofence),
+        )
+        .route(
+            "/api/geofences/:geofence_id",
+            get(api_geofence::get_geofence)
+                .patch(api_geofence::patch_geofence)
+                .delete(api_geofence::delete_geofence_handler),
+        )
+        .route(
+            "/api/routes/:route_id",
+            get(api_route::get_route)
+                .patch(api_route::patch_route)
+                .delete(api_route::delete_route_handler),
+        )
+        .route(
+            "/api/routes/:route_id/original",
+            get(api_route::get_route_original),
+        )
+        .route(
+            "/api/routes/:route_id/profile",
+            get(api_route::get_route_profile),
+        )
+        .route(
+            "/api/routes/:route_id/pois",
+            get(api_poi::get_route_pois).post(api_poi::create_poi),
+        )
+        .route(
+            "/api/pois/:poi_id",
+            patch(api_poi::patch_poi).delete(api_poi::delete_poi_handler),
+        )
+        .route("/api/users/:username/track", get(api_track::get_user_track))
+        .route(
+            "/api/users/:username/track.gpx",
+            get(api_track::get_user_track_gpx),
+        )
+        .route(
+            "/api/rooms/:room_id/track.gpx",
+            get(api_track::get_room_track_gpx),
+        )
         .fallback_service(static_files_service)
         .layer(cors_layer)
         .layer(Extension(app_state.clone()))
-        .with_state(app_state);
-
-    Ok(app)
+        .with_state(app_state)
 }
@@ -0,0 +1,149 @@
+//! Off-route detection: how far each rider is from the active route of the room; cf `WsMessage::OffRoute`
+//!
+//! Each position shared in a room(ie after `privacy.rs`) is projected on its active route
+//! (cf `AppState::room_route_line`); a rider is off route beyond `Config::off_route_threshold`,
+//! and back on route below `BACK_ON_ROUTE_RATIO` of it, so that a rider riding along the threshold
+//! does NOT flood the room. Only the changes are broadcast.
+//! NOTE: the state is only kept in memory, like the geofences: after a restart, the riders that are still
+//! off route are flagged again with their next position.
+
+use std::collections::HashMap;
+
+use dashmap::DashMap;
+use protocol::{Position, WsMessage};
+
+/// An off-route rider is back on route when closer than that fraction of the threshold
+const BACK_ON_ROUTE_RATIO: f64 = 0.8;
+
+/// Who is off route in each room
+#[derive(Debug, Default)]
+pub(crate) struct OffRoute {
+    /// The riders currently off route and their last distance to the route(in meters), indexed by (room id, username)
+    off_route: DashMap<(i64, String), f64>,
+}
+
+impl OffRoute {
+    /// A new position of a given user is `distance` meters away from the active route of the room
+    /// The positions less accurate than `threshold` are ignored: they can NOT tell either way.
+    /// returns: the `WsMessage::OffRoute` to broadcast, if they went off route or came back to it
+    pub(crate) fn check(
+        &self,
+        room_id: i64,
+        position: &Position,
+        distance: f64,
+        threshold: f64,
+    ) -> Option<WsMessage> {
+        if position
+            .accuracy
+            .is_some_and(|accuracy| accuracy > threshold)
+        {
+            return None;
+        }
+
+        let key = (room_id, position.username.clone());
+        let was_off_route = self.off_route.contains_key(&key);
+        let is_off_route = if was_off_route {
+            distance > threshold * BACK_ON_ROUTE_RATIO
+        } else {
+            distance > threshold
+        };
+        if is_off_route {
+            self.off_route.insert(key, distance);
+        } else {
+            self.off_route.remove(&key);
+        }
+        if is_off_route == was_off_route {
+            return None;
+        }
+
+        Some(WsMessage::OffRoute {
+            username: position.username.clone(),
+            is_off_route,
+            distance,
+            timestamp: position.timestamp,
+        })
+    }
+
+    /// The riders currently off route in a given room, and their distance to the route
+    pub(crate) fn room_off_route(&self, room_id: i64) -> HashMap<String, f64> {
+        self.off_route
+            .iter()
+            .filter(|entry| entry.key().0 == room_id)
+            .map(|entry| (entry.key().1.clone(), *entry.value()))
+            .collect()
+    }
+
+    /// The route of a given room changed(or was deleted): nobody is off route anymore until their next position
+    pub(crate) fn forget_room(&self, room_id: i64) {
+        self.off_route
+            .retain(|(off_route_room_id, _username), _distance| *off_route_room_id != room_id);
+    }
+
+    /// Remove a deleted user, in every room
+    pub(crate) fn forget_user(&self, username: &str) {
+        self.off_route
+            .retain(|(_room_id, off_route_username), _distance| off_route_username != username);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_position(accuracy: f64) -> Position {
+        Position {
+            username: "aaa".to_string(),
+            lat: 48.05,
+            lng: 2.0,
+            accuracy: Some(accuracy),
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp: 1,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_off_route_check() {
+        let off_route = OffRoute::default();
+        let position = new_position(5.0);
+
+        assert_eq!(off_route.check(1, &position, 50.0, 100.0), None);
+        assert_eq!(
+            off_route.check(1, &position, 150.0, 100.0),
+            Some(WsMessage::OffRoute {
+                username: "aaa".to_string(),
+                is_off_route: true,
+                distance: 150.0,
+                timestamp: 1,
+            })
+        );
+        assert_eq!(
+            off_route.room_off_route(1),
+            HashMap::from([("aaa".to_string(), 150.0)])
+        );
+        // the other rooms are NOT affected
+        assert_eq!(off_route.room_off_route(2), HashMap::new());
+        // still off route; and NOT back on route until below 80% of the threshold
+        assert_eq!(off_route.check(1, &position, 150.0, 100.0), None);
+        assert_eq!(off_route.check(1, &position, 90.0, 100.0), None);
+        // too inaccurate to tell
+        assert_eq!(off_route.check(1, &new_position(500.0), 0.0, 100.0), None);
+        assert!(matches!(
+            off_route.check(1, &position, 50.0, 100.0),
+            Some(WsMessage::OffRoute {
+                is_off_route: false,
+                ..
+            })
+        ));
+        assert_eq!(off_route.room_off_route(1), HashMap::new());
+
+        off_route.check(1, &position, 150.0, 100.0);
+        off_route.forget_room(1);
+        assert_eq!(off_route.room_off_route(1), HashMap::new());
+        off_route.check(1, &position, 150.0, 100.0);
+        off_route.forget_user("aaa");
+        assert_eq!(off_route.room_off_route(1), HashMap::new());
+    }
+}
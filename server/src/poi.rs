@@ -0,0 +1,114 @@
+//! Points of interest(POI) of a route, eg water, food or a meeting point; cf `api_poi.rs`
+//!
+//! They come from the waypoints(`<wpt>`) of the uploaded GPX files, cf `route_formats.rs`;
+//! then the organisers can add, move or delete them.
+//! Like the geofences of a route, they are shown in every room where that route is active.
+
+use serde::{Deserialize, Serialize};
+
+/// The longest name, description or symbol; in chars
+pub(crate) const MAX_POI_TEXT_LENGTH: usize = 1000;
+
+/// A POI before it is stored; eg a GPX waypoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Waypoint {
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    /// eg "Drinking Water"; cf the `<sym>` of the GPX waypoints
+    pub(crate) symbol: Option<String>,
+    /// in degrees
+    pub(crate) lat: f64,
+    /// in degrees
+    pub(crate) lng: f64,
+}
+
+impl Waypoint {
+    /// returns: a human readable message describing the first invalid field
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("the name MUST NOT be empty".to_string());
+        }
+        let texts = [
+            Some(&self.name),
+            self.description.as_ref(),
+            self.symbol.as_ref(),
+        ];
+        if texts
+            .into_iter()
+            .flatten()
+            .any(|text| text.chars().count() > MAX_POI_TEXT_LENGTH)
+        {
+            return Err(format!(
+                "the name, description and symbol MUST be at most {MAX_POI_TEXT_LENGTH} characters"
+            ));
+        }
+        if !(self.lat.is_finite()
+            && self.lng.is_finite()
+            && (-90.0..=90.0).contains(&self.lat)
+            && (-180.0..=180.0).contains(&self.lng))
+        {
+            return Err(format!("invalid point: ({}, {})", self.lat, self.lng));
+        }
+
+        Ok(())
+    }
+}
+
+/// SHOULD match `server/migrations/20240310_1000_poi.sql`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Poi {
+    pub(crate) id: i64,
+    pub(crate) route_id: i64,
+    #[serde(flatten)]
+    pub(crate) waypoint: Waypoint,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waypoint_validate() {
+        let waypoint = Waypoint {
+            name: "water".to_string(),
+            description: Some("fountain, next to the church".to_string()),
+            symbol: Some("Drinking Water".to_string()),
+            lat: 48.0,
+            lng: 2.0,
+        };
+        assert_eq!(waypoint.validate(), Ok(()));
+
+        for (invalid, expected) in [
+            (
+                Waypoint {
+                    name: " ".to_string(),
+                    ..waypoint.clone()
+                },
+                "the name MUST NOT be empty",
+            ),
+            (
+                Waypoint {
+                    description: Some("a".repeat(MAX_POI_TEXT_LENGTH + 1)),
+                    ..waypoint.clone()
+                },
+                "the name, description and symbol MUST be at most 1000 characters",
+            ),
+            (
+                Waypoint {
+                    lat: 91.0,
+                    ..waypoint.clone()
+                },
+                "invalid point: (91, 2)",
+            ),
+            (
+                Waypoint {
+                    lng: f64::NAN,
+                    ..waypoint.clone()
+                },
+                "invalid point: (48, NaN)",
+            ),
+        ] {
+            assert_eq!(invalid.validate(), Err(expected.to_string()));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Who is online, idle or offline in each room; cf `WsMessage::Presence`
+//!
+//! A user is online when they are connected to the "geolocation" socket of a room, and they sent a position recently;
+//! they become idle after `Config::idle_timeout` without any position(eg the app is in the background);
+//! and offline when their last connection is closed, or lost(ie they no longer answer the heartbeats).
+//! NOTE: the presence is only kept in memory; after a restart everyone is offline until they reconnect.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use protocol::{PresenceStatus, SharingMode, WsMessage};
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UserPresence {
+    status: PresenceStatus,
+    /// the number of "geolocation" sockets of this user in this room, eg a phone AND a laptop
+    connections: usize,
+    /// milliseconds since UNIX epoch: the last frame received from this user; including the pongs
+    last_seen: i64,
+    /// milliseconds since UNIX epoch: the last position received from this user
+    last_activity: i64,
+}
+
+/// The presence of a given user; cf `GET /api/rooms/{room_id}/presence`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct MemberPresence {
+    pub(crate) username: String,
+    pub(crate) status: PresenceStatus,
+    /// None if they never connected since the server started
+    pub(crate) last_seen: Option<i64>,
+    /// cf `privacy.rs`; the changes are pushed with `WsMessage::Sharing`
+    pub(crate) sharing: SharingMode,
+    /// in meters: their distance to the route if they are off route, cf `off_route.rs`;
+    /// the changes are pushed with `WsMessage::OffRoute`
+    pub(crate) off_route: Option<f64>,
+}
+
+/// The presence of each user, indexed by room id then username
+/// Each method that changes the status of a user returns the `WsMessage::Presence` to broadcast, if any.
+#[derive(Debug, Default)]
+pub(crate) struct Presences(DashMap<i64, HashMap<String, UserPresence>>);
+
+impl Presences {
+    /// A new "geolocation" socket was opened: the user is online
+    pub(crate) fn connect(&self, room_id: i64, username: &str, now: i64) -> Option<WsMessage> {
+        let mut users = self.0.entry(room_id).or_default();
+        let user = users
+            .entry(username.to_string())
+            .or_insert_with(|| UserPresence {
+                status: PresenceStatus::Offline,
+                connections: 0,
+                last_seen: now,
+                last_activity: now,
+            });
+        user.connections += 1;
+        user.last_seen = now;
+        user.last_activity = now;
+
+        set_status(username, user, PresenceStatus::Online)
+    }
+
+    /// A "geolocation" socket was closed: the user is offline if it was their last one
+    pub(crate) fn disconnect(&self, room_id: i64, username: &str, now: i64) -> Option<WsMessage> {
+        let mut users = self.0.get_mut(&room_id)?;
+        let user = users.get_mut(username)?;
+        user.connections = user.connections.saturating_sub(1);
+        user.last_seen = now;
+        if user.connections > 0 {
+            return None;
+        }
+
+        set_status(username, user, PresenceStatus::Offline)
+    }
+
+    /// Any frame was received from this user(eg a pong): they are still connected
+    pub(crate) fn seen(&self, room_id: i64, username: &str, now: i64) {
+        if let Some(mut users) = self.0.get_mut(&room_id) {
+            if let Some(user) = users.get_mut(username) {
+                user.last_seen = now;
+            }
+        }
+    }
+
+    /// A position was received from this user: they are online again if they were idle
+    pub(crate) fn activity(&self, room_id: i64, username: &str, now: i64) -> Option<WsMessage> {
+        let mut users = self.0.get_mut(&room_id)?;
+        let user = users.get_mut(username)?;
+        user.last_seen = now;
+        user.last_activity = now;
+
+        set_status(username, user, PresenceStatus::Online)
+    }
+
+    /// Called periodically by each socket: the user is idle if they have NOT sent any position for `idle_timeout`
+    pub(crate) fn check_idle(
+        &self,
+        room_id: i64,
+        username: &str,
+        now: i64,
+        idle_timeout: Duration,
+    ) -> Option<WsMessage> {
+        let mut users = self.0.get_mut(&room_id)?;
+        let user = users.get_mut(username)?;
+        let idle_timeout = i64::try_from(idle_timeout.as_millis()).unwrap_or(i64::MAX);
+        if user.status != PresenceStatus::Online
+            || now.saturating_sub(user.last_activity) < idle_timeout
+        {
+            return None;
+        }
+
+        set_status(username, user, PresenceStatus::Idle)
+    }
+
+    /// The presence of the given members of a room(cf `db::list_room_members`), in the same order
+    /// The members that never connected since the server started are offline.
+    pub(crate) fn room_presence(
+        &self,
+        room_id: i64,
+        members: Vec<(String, SharingMode)>,
+    ) -> Vec<MemberPresence> {
+        let users = self.0.get(&room_id);
+        members
+            .into_iter()
+            .map(|(username, sharing)| {
+                let user = users.as_ref().and_then(|users| users.get(&username));
+                MemberPresence {
+                    status: user.map_or(PresenceStatus::Offline, |user| user.status),
+                    last_seen: user.map(|user| user.last_seen),
+                    username,
+                    sharing,
+                    off_route: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Remove a deleted user, in every room
+    pub(crate) fn forget_user(&self, username: &str) {
+        for mut users in self.0.iter_mut() {
+            users.remove(username);
+        }
+    }
+}
+
+/// returns: the message to broadcast, if the status actually changed
+fn set_status(
+    username: &str,
+    user: &mut UserPresence,
+    status: PresenceStatus,
+) -> Option<WsMessage> {
+    if user.status == status {
+        return None;
+    }
+    user.status = status;
+
+    Some(WsMessage::Presence {
+        username: username.to_string(),
+        status,
+        last_seen: user.last_seen,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presence(username: &str, status: PresenceStatus, last_seen: i64) -> Option<WsMessage> {
+        Some(WsMessage::Presence {
+            username: username.to_string(),
+            status,
+            last_seen,
+        })
+    }
+
+    #[test]
+    fn test_presences() {
+        let presences = Presences::default();
+        let idle_timeout = Duration::from_secs(10);
+
+        assert_eq!(
+            presences.connect(1, "aaa", 1_000),
+            presence("aaa", PresenceStatus::Online, 1_000)
+        );
+        // a second connection of the same user does NOT change anything
+        assert_eq!(presences.connect(1, "aaa", 2_000), None);
+        assert_eq!(presences.check_idle(1, "aaa", 5_000, idle_timeout), None);
+        // the pongs do NOT count as an activity
+        presences.seen(1, "aaa", 11_000);
+        assert_eq!(
+            presences.check_idle(1, "aaa", 12_000, idle_timeout),
+            presence("aaa", PresenceStatus::Idle, 11_000)
+        );
+        assert_eq!(presences.check_idle(1, "aaa", 13_000, idle_timeout), None);
+        assert_eq!(
+            presences.activity(1, "aaa", 14_000),
+            presence("aaa", PresenceStatus::Online, 14_000)
+        );
+        assert_eq!(presences.activity(1, "aaa", 15_000), None);
+
+        assert_eq!(presences.disconnect(1, "aaa", 16_000), None);
+        assert_eq!(
+            presences.disconnect(1, "aaa", 17_000),
+            presence("aaa", PresenceStatus::Offline, 17_000)
+        );
+        // the other rooms are NOT affected
+        assert_eq!(presences.disconnect(2, "aaa", 18_000), None);
+
+        assert_eq!(
+            presences.room_presence(
+                1,
+                vec![
+                    ("aaa".to_string(), SharingMode::Sharing),
+                    ("bbb".to_string(), SharingMode::Paused)
+                ]
+            ),
+            vec![
+                MemberPresence {
+                    username: "aaa".to_string(),
+                    status: PresenceStatus::Offline,
+                    last_seen: Some(17_000),
+                    sharing: SharingMode::Sharing,
+                    off_route: None,
+                },
+                MemberPresence {
+                    username: "bbb".to_string(),
+                    status: PresenceStatus::Offline,
+                    last_seen: None,
+                    sharing: SharingMode::Paused,
+                    off_route: None,
+                },
+            ]
+        );
+
+        presences.forget_user("aaa");
+        assert_eq!(
+            presences.room_presence(1, vec![("aaa".to_string(), SharingMode::Sharing)])[0]
+                .last_seen,
+            None
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! What each user shares with the others in a room; cf `GET/PUT /api/rooms/{room_id}/privacy`
+//!
+//! - `SharingMode::Sharing`: the default; their positions are broadcast as usual
+//! - `SharingMode::Paused`: their positions are dropped by the server(NOT broadcast, NOT persisted);
+//!   the others still see their last one. They still receive the positions of the others.
+//! - `SharingMode::Hidden`: same as paused; but their last position is also removed from the snapshots
+//!   and from the map of the others(cf `WsMessage::Sharing`)
+//!
+//! And independently, `Privacy::precision` reduces the precision of their positions BEFORE they are broadcast
+//! AND persisted; ie the exact position never leaves `ws_handler.rs`.
+//! NOTE: they still appear in the presence(cf `presence.rs`); only their positions are concerned.
+
+use protocol::{Position, SharingMode};
+use serde::{Deserialize, Serialize};
+
+/// The coarsest precision that can be asked for; beyond that the positions are useless anyway
+pub(crate) const MAX_PRECISION_METERS: u32 = 10_000;
+
+/// Roughly, the length of a degree of latitude; and of a degree of longitude at the equator
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// The privacy settings of a given member of a given room
+/// SHOULD match `server/migrations/20240306_1000_privacy.sql`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub(crate) struct Privacy {
+    pub(crate) mode: SharingMode,
+    /// in meters: the positions are rounded to a grid of that size; None for the exact positions
+    #[serde(default)]
+    pub(crate) precision: Option<u32>,
+}
+
+impl Privacy {
+    /// returns: a human readable message if `precision` is out of range
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self.precision {
+            Some(precision) if !(1..=MAX_PRECISION_METERS).contains(&precision) => Err(format!(
+                "precision MUST be between 1 and {MAX_PRECISION_METERS} meters"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// What is actually shared of a given position
+    /// returns: None if it MUST NOT be shared at all, ie paused or hidden
+    pub(crate) fn apply(&self, position: Position) -> Option<Position> {
+        match (self.mode, self.precision) {
+            (SharingMode::Sharing, Some(precision)) => Some(round_position(position, precision)),
+            (SharingMode::Sharing, None) => Some(position),
+            (SharingMode::Paused | SharingMode::Hidden, _) => None,
+        }
+    }
+}
+
+/// Snap a position to a grid of `meters`; and make its accuracy at least that
+/// NOTE: the positions are rounded, NOT randomly fuzzed: the average of many fuzzed positions would reveal the exact one.
+fn round_position(mut position: Position, meters: u32) -> Position {
+    let meters = f64::from(meters);
+
+    let lat_step = meters / METERS_PER_DEGREE;
+    let lat = ((position.lat / lat_step).round() * lat_step).clamp(-90.0, 90.0);
+    // the meridians get closer toward the poles; and using the rounded lat keeps the grid the same for a given row
+    let lng_step = (meters / (METERS_PER_DEGREE * lat.to_radians().cos().max(0.01))).min(360.0);
+    let lng = ((position.lng / lng_step).round() * lng_step).clamp(-180.0, 180.0);
+
+    position.lat = lat;
+    position.lng = lng;
+    position.accuracy = Some(
+        position
+            .accuracy
+            .map_or(meters, |accuracy| accuracy.max(meters)),
+    );
+
+    position
+}
+
+pub(crate) fn sharing_mode_as_str(mode: SharingMode) -> &'static str {
+    match mode {
+        SharingMode::Sharing => "sharing",
+        SharingMode::Paused => "paused",
+        SharingMode::Hidden => "hidden",
+    }
+}
+
+/// NOTE: an unknown value is treated as the most private one
+pub(crate) fn parse_sharing_mode(mode: &str) -> SharingMode {
+    match mode {
+        "sharing" => SharingMode::Sharing,
+        "paused" => SharingMode::Paused,
+        _ => SharingMode::Hidden,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geo::haversine_distance;
+
+    use super::*;
+
+    fn new_position(lat: f64, lng: f64) -> Position {
+        Position {
+            username: "aaa".to_string(),
+            lat,
+            lng,
+            accuracy: Some(5.0),
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp: 1,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_privacy_apply() {
+        let position = new_position(48.8354, 2.3203);
+
+        assert_eq!(
+            Privacy::default().apply(position.clone()),
+            Some(position.clone())
+        );
+        for mode in [SharingMode::Paused, SharingMode::Hidden] {
+            let privacy = Privacy {
+                mode,
+                precision: None,
+            };
+            assert_eq!(privacy.apply(position.clone()), None);
+        }
+
+        let privacy = Privacy {
+            mode: SharingMode::Sharing,
+            precision: Some(500),
+        };
+        let rounded = privacy.apply(position.clone()).unwrap();
+        assert_eq!(rounded.accuracy, Some(500.0));
+        assert!(
+            haversine_distance((position.lat, position.lng), (rounded.lat, rounded.lng)) < 500.0
+        );
+        // the nearby positions end up at the same place
+        let nearby = privacy.apply(new_position(48.8355, 2.3204)).unwrap();
+        assert_eq!((nearby.lat, nearby.lng), (rounded.lat, rounded.lng));
+        // still valid near the poles and the antimeridian
+        assert!(privacy
+            .apply(new_position(89.999, 179.999))
+            .unwrap()
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_privacy_validate_and_strings() {
+        assert!(Privacy::default().validate().is_ok());
+        for (precision, is_ok) in [
+            (1, true),
+            (MAX_PRECISION_METERS, true),
+            (0, false),
+            (MAX_PRECISION_METERS + 1, false),
+        ] {
+            let privacy = Privacy {
+                mode: SharingMode::Sharing,
+                precision: Some(precision),
+            };
+            assert_eq!(privacy.validate().is_ok(), is_ok);
+        }
+
+        for mode in [
+            SharingMode::Sharing,
+            SharingMode::Paused,
+            SharingMode::Hidden,
+        ] {
+            assert_eq!(parse_sharing_mode(sharing_mode_as_str(mode)), mode);
+            assert_eq!(
+                serde_json::to_string(&mode).unwrap(),
+                format!("\"{}\"", sharing_mode_as_str(mode))
+            );
+        }
+    }
+}
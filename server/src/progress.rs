@@ -0,0 +1,248 @@
+//! Progress along the active route of the room: distance done, remaining and ETA of each rider; cf `protocol::RouteProgress`
+//!
+//! Each position shared in a room(ie after `privacy.rs`) is projected on its active route(cf `AppState::room_route_line`).
+//! The speed is the progress along the route over the last `SPEED_WINDOW_MS`, NOT the speed of the device:
+//! a rider going around in circles during a break does NOT get any closer to the finish.
+//! The progress is sent along with the position(cf `Position::progress`), and kept with the last positions
+//! for the leaderboard; cf `leaderboard` and `GET /api/rooms/{room_id}/progress`.
+
+use std::collections::VecDeque;
+
+use dashmap::DashMap;
+use protocol::{Position, RouteProgress};
+use serde::Serialize;
+
+use crate::geo::{Projection, RouteLine};
+
+/// The speed is averaged over that many milliseconds
+const SPEED_WINDOW_MS: i64 = 5 * 60 * 1000;
+/// Below that many milliseconds of history the average is too noisy: the speed of the device is used instead, if any
+const MIN_SPEED_WINDOW_MS: i64 = 10 * 1000;
+
+/// The recent progress of each rider, to compute their speed
+#[derive(Debug, Default)]
+pub(crate) struct Progresses {
+    /// The (timestamp, distance done) of the positions of the last `SPEED_WINDOW_MS`, oldest first;
+    /// indexed by (room id, username)
+    samples: DashMap<(i64, String), VecDeque<(i64, f64)>>,
+}
+
+impl Progresses {
+    /// The progress of a new position of a given user, projected on the active route of the room
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub(crate) fn update(
+        &self,
+        room_id: i64,
+        position: &Position,
+        route: &RouteLine,
+        projection: Projection,
+    ) -> RouteProgress {
+        let length = route.length();
+        let distance_done = projection.distance_along.clamp(0.0, length);
+        let distance_remaining = length - distance_done;
+        let percent = if length > 0.0 {
+            distance_done / length * 100.0
+        } else {
+            100.0
+        };
+
+        let mut samples = self
+            .samples
+            .entry((room_id, position.username.clone()))
+            .or_default();
+        // NOTE: an out of order position(eg sent again after a reconnection) does NOT count for the speed
+        if samples
+            .back()
+            .is_none_or(|&(timestamp, _distance)| timestamp < position.timestamp)
+        {
+            samples.push_back((position.timestamp, distance_done));
+        }
+        while samples
+            .front()
+            .is_some_and(|&(timestamp, _distance)| position.timestamp - timestamp > SPEED_WINDOW_MS)
+        {
+            samples.pop_front();
+        }
+        let speed = match (samples.front(), samples.back()) {
+            (Some(&(first_timestamp, first_distance)), Some(&(last_timestamp, last_distance)))
+                if last_timestamp - first_timestamp >= MIN_SPEED_WINDOW_MS =>
+            {
+                Some(
+                    (last_distance - first_distance)
+                        / ((last_timestamp - first_timestamp) as f64 / 1000.0),
+                )
+            }
+            _ => position.speed,
+        }
+        // stopped, or going backward: no ETA
+        .filter(|speed| speed.is_finite() && *speed > 0.0);
+
+        let eta = if distance_remaining <= 0.0 {
+            Some(position.timestamp)
+        } else {
+            speed.map(|speed| position.timestamp + (distance_remaining / speed * 1000.0) as i64)
+        };
+
+        RouteProgress {
+            distance_done,
+            distance_remaining,
+            percent,
+            speed,
+            eta,
+        }
+    }
+
+    /// The route of a given room changed(or was deleted): the progress so far is meaningless
+    pub(crate) fn forget_room(&self, room_id: i64) {
+        self.samples
+            .retain(|(samples_room_id, _username), _samples| *samples_room_id != room_id);
+    }
+
+    /// Remove a deleted user, in every room
+    pub(crate) fn forget_user(&self, username: &str) {
+        self.samples
+            .retain(|(_room_id, samples_username), _samples| samples_username != username);
+    }
+}
+
+/// An entry of the leaderboard; cf `leaderboard`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct RiderProgress {
+    pub(crate) username: String,
+    #[serde(flatten)]
+    pub(crate) progress: RouteProgress,
+    /// in meters: how far behind the leader they are along the route; 0 for the leader
+    pub(crate) gap_to_leader: f64,
+    /// milliseconds since UNIX epoch: the timestamp of the position
+    pub(crate) timestamp: i64,
+}
+
+/// The riders that have a progress(cf `Position::progress`), the furthest along the route first
+pub(crate) fn leaderboard(positions: Vec<Position>) -> Vec<RiderProgress> {
+    let mut riders: Vec<RiderProgress> = positions
+        .into_iter()
+        .filter_map(|position| {
+            Some(RiderProgress {
+                progress: position.progress?,
+                username: position.username,
+                gap_to_leader: 0.0,
+                timestamp: position.timestamp,
+            })
+        })
+        .collect();
+    riders.sort_by(|a, b| {
+        b.progress
+            .distance_done
+            .total_cmp(&a.progress.distance_done)
+            .then_with(|| a.username.cmp(&b.username))
+    });
+
+    let leader_distance = riders
+        .first()
+        .map_or(0.0, |leader| leader.progress.distance_done);
+    for rider in &mut riders {
+        rider.gap_to_leader = leader_distance - rider.progress.distance_done;
+    }
+
+    riders
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_position(username: &str, lat: f64, timestamp: i64) -> Position {
+        Position {
+            username: username.to_string(),
+            lat,
+            lng: 2.0,
+            accuracy: None,
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_progresses_update() {
+        // ~11.1 km along a meridian
+        let route = RouteLine::new(vec![vec![(48.0, 2.0), (48.1, 2.0)]]);
+        let length = route.length();
+        let progresses = Progresses::default();
+        let update = |lat: f64, timestamp: i64| {
+            let position = new_position("aaa", lat, timestamp);
+            let projection = route.project((lat, 2.0)).unwrap();
+            progresses.update(1, &position, &route, projection)
+        };
+
+        let progress = update(48.0, 0);
+        assert!(progress.distance_done.abs() < 0.001);
+        assert!((progress.distance_remaining - length).abs() < 0.001);
+        // NOT enough history yet
+        assert_eq!((progress.speed, progress.eta), (None, None));
+
+        // halfway after 200 seconds
+        let progress = update(48.05, 200_000);
+        assert!((progress.percent - 50.0).abs() < 0.1, "{progress:?}");
+        let speed = progress.speed.unwrap();
+        assert!((speed - length / 2.0 / 200.0).abs() < 0.01, "{progress:?}");
+        let eta = progress.eta.unwrap();
+        assert!((eta - 400_000).abs() < 1000, "{progress:?}");
+
+        // stopped for the whole window: no ETA
+        update(48.05, 300_000);
+        let progress = update(48.05, 200_000 + SPEED_WINDOW_MS);
+        assert_eq!((progress.speed, progress.eta), (None, None));
+
+        // the finish
+        let progress = update(48.2, 600_000);
+        assert!(progress.distance_remaining.abs() < 0.001);
+        assert_eq!(progress.percent, 100.0);
+        assert_eq!(progress.eta, Some(600_000));
+
+        progresses.forget_user("aaa");
+        assert!(progresses.samples.is_empty());
+    }
+
+    #[test]
+    fn test_leaderboard() {
+        let route = RouteLine::new(vec![vec![(48.0, 2.0), (48.1, 2.0)]]);
+        let progresses = Progresses::default();
+        let positions: Vec<Position> = [("aaa", 48.02), ("bbb", 48.08), ("ccc", 48.05)]
+            .into_iter()
+            .map(|(username, lat)| {
+                let mut position = new_position(username, lat, 1);
+                position.progress = Some(progresses.update(
+                    1,
+                    &position,
+                    &route,
+                    route.project((lat, 2.0)).unwrap(),
+                ));
+                position
+            })
+            // without a progress: NOT in the leaderboard
+            .chain([new_position("ddd", 48.09, 1)])
+            .collect();
+
+        let riders = leaderboard(positions);
+        assert_eq!(
+            riders
+                .iter()
+                .map(|rider| rider.username.as_str())
+                .collect::<Vec<_>>(),
+            vec!["bbb", "ccc", "aaa"]
+        );
+        assert_eq!(riders[0].gap_to_leader, 0.0);
+        assert!(
+            (riders[2].gap_to_leader - crate::geo::haversine_distance((48.02, 2.0), (48.08, 2.0)))
+                .abs()
+                < 1.0
+        );
+
+        let json = serde_json::to_value(&riders[0]).unwrap();
+        assert_eq!(json["username"], "bbb");
+        assert!(json["percent"].is_f64());
+    }
+}
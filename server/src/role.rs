@@ -0,0 +1,222 @@
+//! Roles, and the extractors to check them in the handlers
+//!
+//! A user has a global role(the `role` column of `user`) and a role in each room they joined
+//! (the `role` column of `room_member`).
+//! The roles are ordered: `Viewer` < `Member` < `Organiser` < `Admin`; ie an `Admin` can do everything an `Organiser` can etc.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+    Extension, RequestPartsExt,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api_authorize_jwt::Claims,
+    db::{get_room_member_role, get_user_role},
+    errors_and_responses::AppError,
+    state::SharedState,
+};
+
+/// SHOULD match `server/migrations/20240305_1000_role.sql`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Role {
+    /// can follow a room, but NOT send positions or chat messages
+    Viewer,
+    /// the default: the users that are NOT in the DB("anonymous") are also members
+    #[default]
+    Member,
+    /// can upload routes and create rooms; and manage the rooms they organise
+    Organiser,
+    /// the old "superuser": can do everything; and in every room
+    /// NOTE: only a global role; NOT a room role
+    Admin,
+}
+
+impl Role {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Member => "member",
+            Role::Organiser => "organiser",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "member" => Ok(Role::Member),
+            "organiser" => Ok(Role::Organiser),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("unknown role: {s}")),
+        }
+    }
+}
+
+/// The minimum role required by `RequireRole` and `RequireRoomRole`
+pub(crate) trait MinRole {
+    const ROLE: Role;
+}
+
+pub(crate) struct Viewer;
+pub(crate) struct Organiser;
+pub(crate) struct Admin;
+
+impl MinRole for Viewer {
+    const ROLE: Role = Role::Viewer;
+}
+impl MinRole for Organiser {
+    const ROLE: Role = Role::Organiser;
+}
+impl MinRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// Same as `Claims`, but the user MUST have at least the global role `R`, else `AppError::NotFound`
+/// eg `RequireRole<Organiser>`
+pub(crate) struct RequireRole<R> {
+    pub(crate) claims: Claims,
+    /// the actual global role of the user; ie >= `R::ROLE`
+    pub(crate) role: Role,
+    _min_role: PhantomData<fn() -> R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    R: MinRole,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let db_pool = get_db_pool(parts).await?;
+
+        let role = get_user_role(&db_pool, &claims.sub).await.map_err(|err| {
+            tracing::error!("RequireRole: db error: {:?}", err);
+            AppError::InternalError.into_response()
+        })?;
+        if role < R::ROLE {
+            tracing::warn!("RequireRole: {} is {role}, NOT {}", claims.sub, R::ROLE);
+            return Err(AppError::NotFound.into_response());
+        }
+
+        Ok(Self {
+            claims,
+            role,
+            _min_role: PhantomData,
+        })
+    }
+}
+
+/// Same as `Claims`, but the user MUST have at least the role `R` in the room given by the `:room_id` path parameter,
+/// else `AppError::NotFound`; an `Admin` has every role in every room(even if they did NOT join it)
+/// eg `RequireRoomRole<Organiser>`
+pub(crate) struct RequireRoomRole<R> {
+    pub(crate) claims: Claims,
+    pub(crate) room_id: i64,
+    _min_role: PhantomData<fn() -> R>,
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRoomRole<R>
+where
+    S: Send + Sync,
+    R: MinRole,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Path(params) = parts
+            .extract::<Path<HashMap<String, String>>>()
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let room_id: i64 = params
+            .get("room_id")
+            .and_then(|room_id| room_id.parse().ok())
+            .ok_or_else(|| AppError::BadRequest.into_response())?;
+        let db_pool = get_db_pool(parts).await?;
+
+        let global_role = get_user_role(&db_pool, &claims.sub).await.map_err(|err| {
+            tracing::error!("RequireRoomRole: db error: {:?}", err);
+            AppError::InternalError.into_response()
+        })?;
+        let role = if global_role == Role::Admin {
+            Some(Role::Admin)
+        } else {
+            get_room_member_role(&db_pool, room_id, &claims.sub)
+                .await
+                .map_err(|err| {
+                    tracing::error!("RequireRoomRole: db error: {:?}", err);
+                    AppError::InternalError.into_response()
+                })?
+        };
+        match role {
+            Some(role) if role >= R::ROLE => Ok(Self {
+                claims,
+                room_id,
+                _min_role: PhantomData,
+            }),
+            role => {
+                tracing::warn!(
+                    "RequireRoomRole: {} is {role:?} in room {room_id}, NOT {}",
+                    claims.sub,
+                    R::ROLE
+                );
+                Err(AppError::NotFound.into_response())
+            }
+        }
+    }
+}
+
+async fn get_db_pool(parts: &mut Parts) -> Result<sqlx::SqlitePool, Response> {
+    let Extension(state) = parts
+        .extract::<Extension<SharedState>>()
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok(state.db_pool.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_order_and_strings() {
+        assert!(Role::Viewer < Role::Member);
+        assert!(Role::Member < Role::Organiser);
+        assert!(Role::Organiser < Role::Admin);
+
+        for role in [Role::Viewer, Role::Member, Role::Organiser, Role::Admin] {
+            assert_eq!(role.as_str().parse::<Role>(), Ok(role));
+            assert_eq!(serde_json::to_string(&role).unwrap(), format!("\"{role}\""));
+        }
+        assert!("superuser".parse::<Role>().is_err());
+    }
+}
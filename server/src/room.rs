@@ -0,0 +1,11 @@
+use serde::Serialize;
+
+/// SHOULD match `server/migrations/20240301_1000_room.sql`
+#[derive(PartialEq, Debug, Serialize, Clone)]
+pub(crate) struct Room {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) created_by: String,
+    /// The route currently displayed to the members, if any; cf `api_route::set_room_route`
+    pub(crate) active_route_id: Option<i64>,
+}
@@ -0,0 +1,17 @@
+use serde::Serialize;
+
+use crate::route_stats::RouteStats;
+
+/// SHOULD match `server/migrations/20240303_1000_route.sql` and `server/migrations/20240309_1000_route_stats.sql`
+/// NOTE: the GPX and `GeoJSON` are NOT included: they can be big; cf `get_route_geojson_from_db`
+#[derive(PartialEq, Debug, Serialize, Clone)]
+pub(crate) struct Route {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    pub(crate) uploaded_by: String,
+    /// milliseconds since UNIX epoch
+    pub(crate) uploaded_at: i64,
+    /// eg the distance, the elevation gain, etc
+    #[serde(flatten)]
+    pub(crate) stats: RouteStats,
+}
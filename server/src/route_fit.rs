@@ -0,0 +1,370 @@
+//! A minimal decoder of the FIT files(eg from Garmin devices): only the positions of the `record` messages
+//! cf `https://developer.garmin.com/fit/protocol/`
+//! NOTE: written by hand: there is no FIT crate available, and only a small part of the protocol is needed
+
+use std::collections::HashMap;
+
+use crate::route_formats::TrackPoint;
+
+/// The global message number of `record`, cf the FIT profile
+const RECORD_MESSAGE: u16 = 20;
+const RECORD_FIELD_LAT: u8 = 0;
+const RECORD_FIELD_LNG: u8 = 1;
+const RECORD_FIELD_ALTITUDE: u8 = 2;
+const RECORD_FIELD_ENHANCED_ALTITUDE: u8 = 78;
+/// The same in all the messages
+const FIELD_TIMESTAMP: u8 = 253;
+/// The invalid values of each base type
+const INVALID_SINT32: i32 = 0x7FFF_FFFF;
+const INVALID_UINT16: u32 = 0xFFFF;
+const INVALID_UINT32: u32 = 0xFFFF_FFFF;
+/// The FIT timestamps are in seconds since 1989-12-31T00:00:00Z
+const FIT_EPOCH: i64 = 631_065_600;
+
+const CRC_TABLE: [u16; 16] = [
+    0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401,
+    0x5000, 0x9C01, 0x8801, 0x4400,
+];
+
+/// The layout of the data messages of a local message type
+struct Definition {
+    is_big_endian: bool,
+    global_message: u16,
+    /// (field number, size)
+    fields: Vec<(u8, usize)>,
+    /// The size of the developer fields, skipped
+    developer_size: usize,
+}
+
+impl Definition {
+    /// An unsigned value of 1, 2 or 4 bytes; None for the other sizes, eg a string or an array
+    fn read_value(&self, bytes: &[u8]) -> Option<u32> {
+        let mut bytes = bytes.to_vec();
+        if !matches!(bytes.len(), 1 | 2 | 4) {
+            return None;
+        }
+        if self.is_big_endian {
+            bytes.reverse();
+        }
+        Some(
+            bytes
+                .iter()
+                .rev()
+                .fold(0, |value, &byte| (value << 8) | u32::from(byte)),
+        )
+    }
+}
+
+/// The fields of a `record` message
+#[derive(Default)]
+struct Record {
+    /// in semicircles
+    lat: Option<i32>,
+    lng: Option<i32>,
+    /// raw: scale 5, offset 500
+    altitude: Option<u32>,
+}
+
+/// cf "FIT File Structure > CRC"
+pub(crate) fn fit_crc(data: &[u8]) -> u16 {
+    data.iter().fold(0, |mut crc, &byte| {
+        for nibble in [byte & 0xF, byte >> 4] {
+            let tmp = CRC_TABLE[usize::from(crc & 0xF)];
+            crc = (crc >> 4) & 0x0FFF;
+            crc = crc ^ tmp ^ CRC_TABLE[usize::from(nibble)];
+        }
+        crc
+    })
+}
+
+/// The positions of the `record` messages, ie the track, as one line; with their altitude and timestamp
+/// The records without a(valid) position are skipped, eg indoor or before the GPS fix
+pub(crate) fn fit_tracks(data: &[u8]) -> Result<Vec<Vec<TrackPoint>>, String> {
+    let records = fit_records(data)?;
+    let mut track = vec![];
+    let mut reader = Reader {
+        data: records,
+        offset: 0,
+    };
+    let mut definitions: HashMap<u8, Definition> = HashMap::new();
+    // in FIT seconds; the compressed timestamps are relative to it
+    let mut last_timestamp: Option<u32> = None;
+
+    while reader.offset < reader.data.len() {
+        let header = reader.read_u8()?;
+        // a compressed timestamp header: ALWAYS a data message
+        let (local_message, is_definition, has_developer_fields) = if header & 0x80 != 0 {
+            let offset = u32::from(header & 0x1F);
+            last_timestamp = match last_timestamp {
+                Some(last_timestamp) if offset < last_timestamp & 0x1F => Some(
+                    ((last_timestamp & !0x1F) + offset)
+                        .checked_add(0x20)
+                        .ok_or_else(|| {
+                            format!("compressed timestamp overflow: {last_timestamp}")
+                        })?,
+                ),
+                Some(last_timestamp) => Some((last_timestamp & !0x1F) + offset),
+                None => None,
+            };
+            ((header >> 5) & 0x3, false, false)
+        } else {
+            (header & 0xF, header & 0x40 != 0, header & 0x20 != 0)
+        };
+
+        if is_definition {
+            definitions.insert(local_message, reader.read_definition(has_developer_fields)?);
+            continue;
+        }
+        let definition = definitions
+            .get(&local_message)
+            .ok_or_else(|| format!("undefined local message type: {local_message}"))?;
+        let mut record = Record::default();
+        for &(field, size) in &definition.fields {
+            let bytes = reader.read(size)?;
+            let value = || definition.read_value(bytes);
+            match (field, size) {
+                (FIELD_TIMESTAMP, 4) => {
+                    last_timestamp = value().filter(|&value| value != INVALID_UINT32);
+                }
+                _ if definition.global_message != RECORD_MESSAGE => {}
+                #[allow(clippy::cast_possible_wrap)]
+                (RECORD_FIELD_LAT, 4) => record.lat = value().map(|value| value as i32),
+                #[allow(clippy::cast_possible_wrap)]
+                (RECORD_FIELD_LNG, 4) => record.lng = value().map(|value| value as i32),
+                (RECORD_FIELD_ALTITUDE, 2) => {
+                    record.altitude = record
+                        .altitude
+                        .or(value().filter(|&value| value != INVALID_UINT16));
+                }
+                // more precise: preferred
+                (RECORD_FIELD_ENHANCED_ALTITUDE, 4) => {
+                    record.altitude = value()
+                        .filter(|&value| value != INVALID_UINT32)
+                        .or(record.altitude);
+                }
+                _ => {}
+            }
+        }
+        reader.read(definition.developer_size)?;
+
+        if let (Some(lat), Some(lng)) = (record.lat, record.lng) {
+            if lat != INVALID_SINT32 && lng != INVALID_SINT32 {
+                track.push(TrackPoint {
+                    lat: semicircles_to_degrees(lat),
+                    lng: semicircles_to_degrees(lng),
+                    // scale 5, offset 500
+                    elevation: record
+                        .altitude
+                        .map(|altitude| f64::from(altitude) / 5.0 - 500.0),
+                    timestamp: last_timestamp
+                        .map(|timestamp| (i64::from(timestamp) + FIT_EPOCH) * 1000),
+                });
+            }
+        }
+    }
+
+    Ok(vec![track])
+}
+
+/// Check the header and the CRC of the file
+/// returns: its records
+fn fit_records(data: &[u8]) -> Result<&[u8], String> {
+    let header_size = usize::from(*data.first().ok_or("empty file")?);
+    if !matches!(header_size, 12 | 14) || data.len() < header_size {
+        return Err("invalid header".to_string());
+    }
+    if &data[8..12] != b".FIT" {
+        return Err("missing .FIT signature".to_string());
+    }
+    let data_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let end = usize::try_from(data_size)
+        .ok()
+        .and_then(|data_size| header_size.checked_add(data_size))
+        .filter(|&end| end + 2 <= data.len())
+        .ok_or("truncated file")?;
+    // the CRC of the header and the records, ie the CRC of the whole file including it is 0
+    if fit_crc(&data[..end + 2]) != 0 {
+        return Err("invalid CRC".to_string());
+    }
+
+    Ok(&data[header_size..end])
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn semicircles_to_degrees(semicircles: i32) -> f64 {
+    f64::from(semicircles) * 180.0 / 2_147_483_648.0
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, size: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + size)
+            .ok_or("truncated record")?;
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read(1)?[0])
+    }
+
+    fn read_definition(&mut self, has_developer_fields: bool) -> Result<Definition, String> {
+        let _reserved = self.read_u8()?;
+        let is_big_endian = self.read_u8()? == 1;
+        let global_message = self.read(2)?;
+        let global_message = if is_big_endian {
+            u16::from_be_bytes([global_message[0], global_message[1]])
+        } else {
+            u16::from_le_bytes([global_message[0], global_message[1]])
+        };
+        let field_count = self.read_u8()?;
+        let fields = (0..field_count)
+            .map(|_| {
+                // (field number, size, base type)
+                let field = self.read(3)?;
+                Ok((field[0], usize::from(field[1])))
+            })
+            .collect::<Result<_, String>>()?;
+        let mut developer_size = 0;
+        if has_developer_fields {
+            for _ in 0..self.read_u8()? {
+                developer_size += usize::from(self.read(3)?[1]);
+            }
+        }
+
+        Ok(Definition {
+            is_big_endian,
+            global_message,
+            fields,
+            developer_size,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn degrees_to_semicircles(degrees: f64) -> i32 {
+        (degrees * 2_147_483_648.0 / 180.0).round() as i32
+    }
+
+    /// The timestamp of the first record of `new_fit`, in FIT seconds; the next ones are 1s apart
+    /// NOTE: its 5 lower bits are 30: the compressed timestamps roll over
+    pub(crate) const FIT_START: u32 = 1_000_000_030;
+
+    /// A FIT file with a `record` for each given position, at an altitude of 100m + its index;
+    /// and a `file_id` message, ignored. `None` is a record without position
+    /// The first record has a timestamp; the next ones a compressed timestamp header
+    pub(crate) fn new_fit(positions: &[Option<(f64, f64)>]) -> Vec<u8> {
+        new_fit_from(FIT_START, positions)
+    }
+
+    /// Same as `new_fit`, but the first record has a given timestamp
+    fn new_fit_from(start: u32, positions: &[Option<(f64, f64)>]) -> Vec<u8> {
+        let mut records = vec![
+            // definition of the local message 0: file_id(0), little endian, one field: type(0), 1 byte, enum
+            0x40, 0, 0, 0, 0, 1, 0, 1, 0x00, //
+            // data: type = activity
+            0x00, 4, //
+            // definition of the local message 1: record(20), big endian, timestamp(253), lat(0), long(1), altitude(2)
+            0x41, 0, 1, 0, 20, 4, 253, 4, 0x86, 0, 4, 0x85, 1, 4, 0x85, 2, 2, 0x84, //
+            // definition of the local message 2: record(20), little endian, lat(0), long(1), altitude(2)
+            0x42, 0, 0, 20, 0, 3, 0, 4, 0x85, 1, 4, 0x85, 2, 2, 0x84,
+        ];
+        for (index, position) in positions.iter().enumerate() {
+            let (lat, lng) = position.map_or((INVALID_SINT32, INVALID_SINT32), |(lat, lng)| {
+                (degrees_to_semicircles(lat), degrees_to_semicircles(lng))
+            });
+            let index = u32::try_from(index).unwrap();
+            let altitude = u16::try_from((100 + index + 500) * 5).unwrap();
+            if index == 0 {
+                records.push(0x01);
+                records.extend(start.to_be_bytes());
+                records.extend(lat.to_be_bytes());
+                records.extend(lng.to_be_bytes());
+                records.extend(altitude.to_be_bytes());
+            } else {
+                let offset = u8::try_from(start.wrapping_add(index) & 0x1F).unwrap();
+                records.push(0x80 | (2 << 5) | offset);
+                records.extend(lat.to_le_bytes());
+                records.extend(lng.to_le_bytes());
+                records.extend(altitude.to_le_bytes());
+            }
+        }
+
+        let mut fit = vec![14, 0x10, 0x08, 0x08];
+        fit.extend(u32::try_from(records.len()).unwrap().to_le_bytes());
+        fit.extend(b".FIT");
+        let header_crc = fit_crc(&fit);
+        fit.extend(header_crc.to_le_bytes());
+        fit.extend(records);
+        let crc = fit_crc(&fit);
+        fit.extend(crc.to_le_bytes());
+        fit
+    }
+
+    #[test]
+    fn test_fit_tracks() {
+        let fit = new_fit(&[
+            Some((48.0, 2.0)),
+            None,
+            Some((48.1, -2.1)),
+            Some((48.2, 2.2)),
+        ]);
+
+        let tracks = fit_tracks(&fit).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].len(), 3);
+        for (point, (index, lat, lng)) in
+            tracks[0]
+                .iter()
+                .zip([(0, 48.0, 2.0), (2, 48.1, -2.1), (3, 48.2, 2.2)])
+        {
+            assert!((point.lat - lat).abs() < 1e-6, "{point:?}");
+            assert!((point.lng - lng).abs() < 1e-6, "{point:?}");
+            assert_eq!(point.elevation, Some(100.0 + f64::from(index)));
+            // including after the roll over of the compressed timestamps
+            assert_eq!(
+                point.timestamp,
+                Some((i64::from(FIT_START + index) + FIT_EPOCH) * 1000)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fit_tracks_corrupt() {
+        let fit = new_fit(&[Some((48.0, 2.0))]);
+
+        let mut corrupt = fit.clone();
+        *corrupt.last_mut().unwrap() ^= 0xFF;
+        assert_eq!(fit_tracks(&corrupt), Err("invalid CRC".to_string()));
+        assert_eq!(
+            fit_tracks(&fit[..fit.len() - 4]),
+            Err("truncated file".to_string())
+        );
+        assert_eq!(fit_tracks(b"hello"), Err("invalid header".to_string()));
+        assert_eq!(fit_tracks(b""), Err("empty file".to_string()));
+    }
+
+    /// A compressed timestamp that rolls over past the last FIT timestamp: corrupt, NOT a panic
+    #[test]
+    fn test_fit_tracks_compressed_timestamp_overflow() {
+        let fit = new_fit_from(
+            u32::MAX - 1,
+            &[Some((48.0, 2.0)), Some((48.1, 2.1)), Some((48.2, 2.2))],
+        );
+
+        assert_eq!(
+            fit_tracks(&fit),
+            Err(format!("compressed timestamp overflow: {}", u32::MAX))
+        );
+    }
+}
@@ -0,0 +1,849 @@
+//! The formats accepted for the routes: each one is converted to the same `GeoJSON` as the GPX files,
+//! ie what `GpxReader::to_json` produces: a `GeometryCollection` of `MultiLineString`; cf `route_gpx.rs`
+//! NOTE: only the tracks matter, and the waypoints of the GPX files(cf `poi.rs`); the laps, heart rates, etc are ignored
+
+use std::fmt;
+
+use geozero::gpx::GpxReader;
+use geozero::ProcessToJson;
+use serde_json::{json, Value};
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::errors_and_responses::AppError;
+use crate::geo::geojson_lines_with;
+use crate::poi::Waypoint;
+use crate::route_fit::fit_tracks;
+use crate::route_simplify::{simplify, SimplifyAlgorithm};
+
+/// The XML formats are sniffed from their root element, in the first bytes of the file
+const SNIFF_LENGTH: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RouteFormat {
+    Gpx,
+    GeoJson,
+    Kml,
+    Tcx,
+    Fit,
+}
+
+/// Why an uploaded file was rejected; cf `From<RouteFormatError> for AppError`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RouteFormatError {
+    /// Neither the file name, the content type nor the content match a supported format
+    Unsupported(String),
+    /// The file could NOT be read as the given format
+    Corrupt(RouteFormat, String),
+    /// eg a .gpx with only waypoints
+    NoTrack(RouteFormat),
+}
+
+/// A point of a track, with what the formats MAY also have
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub(crate) struct TrackPoint {
+    pub(crate) lat: f64,
+    pub(crate) lng: f64,
+    /// in meters
+    pub(crate) elevation: Option<f64>,
+    /// milliseconds since UNIX epoch
+    pub(crate) timestamp: Option<i64>,
+}
+
+/// A route converted to `GeoJSON`
+pub(crate) struct ParsedRoute {
+    pub(crate) geojson: String,
+    /// The points of each line of the `GeoJSON`; cf `RouteStats::compute`
+    pub(crate) tracks: Vec<Vec<TrackPoint>>,
+    /// The `<wpt>` of the GPX files; empty for the other formats
+    pub(crate) waypoints: Vec<Waypoint>,
+}
+
+impl ParsedRoute {
+    /// The `GeoJSON` of the simplified tracks; cf `route_simplify.rs`
+    /// Without any simplification, the `GeoJSON` is kept as is: eg the one of the GPX files
+    pub(crate) fn simplified_geojson(
+        &self,
+        algorithm: SimplifyAlgorithm,
+        tolerance: f64,
+    ) -> String {
+        if algorithm == SimplifyAlgorithm::None || tolerance <= 0.0 {
+            return self.geojson.clone();
+        }
+        let tracks: Vec<Vec<TrackPoint>> = self
+            .tracks
+            .iter()
+            .map(|track| simplify(track, algorithm, tolerance))
+            .collect();
+
+        tracks_to_geojson(&tracks)
+    }
+}
+
+impl RouteFormat {
+    /// From the extension of the file name; else from the content type(the browsers often send a generic one);
+    /// else from its content
+    pub(crate) fn detect(
+        file_name: Option<&str>,
+        content_type: Option<&str>,
+        data: &[u8],
+    ) -> Result<Self, RouteFormatError> {
+        let extension = file_name
+            .and_then(|file_name| file_name.rsplit_once('.'))
+            .map(|(_stem, extension)| extension.to_ascii_lowercase());
+
+        extension
+            .as_deref()
+            .and_then(Self::from_extension)
+            .or_else(|| content_type.and_then(Self::from_content_type))
+            .or_else(|| Self::sniff(data))
+            .ok_or_else(|| {
+                RouteFormatError::Unsupported(match (extension, content_type) {
+                    (Some(extension), _) => format!(".{extension}"),
+                    (None, Some(content_type)) => content_type.to_string(),
+                    (None, None) => "unknown".to_string(),
+                })
+            })
+    }
+
+    /// eg to download the original file, cf `api_route::get_route_original`
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::Gpx => "gpx",
+            Self::GeoJson => "geojson",
+            Self::Kml => "kml",
+            Self::Tcx => "tcx",
+            Self::Fit => "fit",
+        }
+    }
+
+    /// The reverse of `from_content_type`
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Self::Gpx => "application/gpx+xml",
+            Self::GeoJson => "application/geo+json",
+            Self::Kml => "application/vnd.google-earth.kml+xml",
+            Self::Tcx => "application/vnd.garmin.tcx+xml",
+            Self::Fit => "application/vnd.ant.fit",
+        }
+    }
+
+    /// NOTE: also the `format` column of the `route` table, cf `db::get_route_original_from_db`
+    pub(crate) fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "gpx" => Some(Self::Gpx),
+            "geojson" | "json" => Some(Self::GeoJson),
+            "kml" => Some(Self::Kml),
+            "tcx" => Some(Self::Tcx),
+            "fit" => Some(Self::Fit),
+            _ => None,
+        }
+    }
+
+    fn from_content_type(content_type: &str) -> Option<Self> {
+        // eg "application/json; charset=utf-8"
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        match mime.to_ascii_lowercase().as_str() {
+            "application/gpx+xml" => Some(Self::Gpx),
+            "application/geo+json" | "application/json" => Some(Self::GeoJson),
+            "application/vnd.google-earth.kml+xml" => Some(Self::Kml),
+            "application/vnd.garmin.tcx+xml" => Some(Self::Tcx),
+            "application/vnd.ant.fit" => Some(Self::Fit),
+            _ => None,
+        }
+    }
+
+    fn sniff(data: &[u8]) -> Option<Self> {
+        if data.get(8..12) == Some(b".FIT".as_slice()) {
+            return Some(Self::Fit);
+        }
+        let head = String::from_utf8_lossy(&data[..data.len().min(SNIFF_LENGTH)]);
+        if head
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .starts_with('{')
+        {
+            return Some(Self::GeoJson);
+        }
+        [
+            ("<gpx", Self::Gpx),
+            ("<kml", Self::Kml),
+            ("<TrainingCenterDatabase", Self::Tcx),
+        ]
+        .into_iter()
+        .find(|(root, _format)| head.contains(root))
+        .map(|(_root, format)| format)
+    }
+
+    /// Convert a file of this format
+    /// NOTE: the tracks MAY be empty, cf `RouteFormatError::NoTrack`
+    pub(crate) fn parse(self, data: &[u8]) -> Result<ParsedRoute, RouteFormatError> {
+        let corrupt = |reason: String| RouteFormatError::Corrupt(self, reason);
+
+        let tracks = match self {
+            // NOTE: the `GeoJSON` of the GPX files is kept as is; but it has neither the elevations nor the times
+            Self::Gpx => {
+                let mut cursor = std::io::Cursor::new(data);
+                let geojson = GpxReader(&mut cursor)
+                    .to_json()
+                    .map_err(|err| corrupt(err.to_string()))?;
+                let (mut tracks, waypoints) = gpx_tracks_and_waypoints(data).map_err(corrupt)?;
+                tracks.retain(|track| !track.is_empty());
+                return Ok(ParsedRoute {
+                    geojson,
+                    tracks,
+                    waypoints,
+                });
+            }
+            Self::GeoJson => {
+                let value: Value =
+                    serde_json::from_slice(data).map_err(|err| corrupt(err.to_string()))?;
+                // [lng, lat, elevation]
+                geojson_lines_with(&value, &|coordinate| {
+                    Some(TrackPoint {
+                        lat: coordinate[1].as_f64()?,
+                        lng: coordinate[0].as_f64()?,
+                        elevation: coordinate[2].as_f64(),
+                        timestamp: None,
+                    })
+                })
+            }
+            Self::Kml => kml_tracks(data).map_err(corrupt)?,
+            Self::Tcx => tcx_tracks(data).map_err(corrupt)?,
+            Self::Fit => fit_tracks(data).map_err(corrupt)?,
+        };
+        let tracks: Vec<Vec<TrackPoint>> = tracks
+            .into_iter()
+            .filter(|track| !track.is_empty())
+            .collect();
+
+        Ok(ParsedRoute {
+            geojson: tracks_to_geojson(&tracks),
+            tracks,
+            waypoints: vec![],
+        })
+    }
+}
+
+impl fmt::Display for RouteFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gpx => "GPX",
+            Self::GeoJson => "GeoJSON",
+            Self::Kml => "KML",
+            Self::Tcx => "TCX",
+            Self::Fit => "FIT",
+        })
+    }
+}
+
+impl fmt::Display for RouteFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(format) => write!(
+                f,
+                "unsupported route format: {format}; expected GPX, GeoJSON, KML, TCX or FIT"
+            ),
+            Self::Corrupt(format, reason) => write!(f, "corrupt {format} file: {reason}"),
+            Self::NoTrack(format) => write!(f, "the {format} file has no track"),
+        }
+    }
+}
+
+impl From<RouteFormatError> for AppError {
+    fn from(err: RouteFormatError) -> Self {
+        match err {
+            RouteFormatError::Unsupported(_) => AppError::UnsupportedMediaType(err.to_string()),
+            RouteFormatError::Corrupt(..) | RouteFormatError::NoTrack(_) => {
+                AppError::UnprocessableEntity(err.to_string())
+            }
+        }
+    }
+}
+
+/// The same structure as `GpxReader::to_json`: one `MultiLineString` with all the tracks
+fn tracks_to_geojson(tracks: &[Vec<TrackPoint>]) -> String {
+    // NOTE: GeoJSON is [lng, lat]
+    let coordinates: Vec<Vec<[f64; 2]>> = tracks
+        .iter()
+        .map(|track| track.iter().map(|point| [point.lng, point.lat]).collect())
+        .collect();
+
+    json!({
+        "type": "GeometryCollection",
+        "geometries": [{ "type": "MultiLineString", "coordinates": coordinates }],
+    })
+    .to_string()
+}
+
+/// RFC 3339, eg "2024-02-19T17:29:10.199Z" or "2024-02-19T18:29:10+01:00" -> milliseconds since UNIX epoch
+/// The reverse of `track_gpx::format_timestamp`
+/// cf `http://howardhinnant.github.io/date_algorithms.html#days_from_civil`
+fn parse_timestamp(text: &str) -> Option<i64> {
+    let (date, time) = text.trim().split_once(['T', 't', ' '])?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // "Z", or eg "+01:00"
+    let (time, offset_minutes) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let (time, offset) = time.split_at(time.rfind(['+', '-'])?);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let minutes = hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?;
+        (time, sign * minutes)
+    };
+    let mut time = time.splitn(3, ':');
+    let hours = time.next()?.parse::<i64>().ok()?;
+    let minutes = time.next()?.parse::<i64>().ok()?;
+    // eg "10", or "10.199"
+    let seconds = time.next()?;
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let seconds = seconds.parse::<i64>().ok()?;
+    if !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    // the first 3 digits of the fraction, if any
+    let milliseconds = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse::<i64>()
+        .ok()?;
+
+    let shifted_year = if month <= 2 { year - 1 } else { year };
+    let era = shifted_year.div_euclid(400);
+    let year_of_era = shifted_year.rem_euclid(400);
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    Some(
+        (days * 86_400 + hours * 3600 + minutes * 60 + seconds - offset_minutes * 60) * 1000
+            + milliseconds,
+    )
+}
+
+/// The `<trkseg>` and `<rte>` of a GPX file, ie the same lines as `GpxReader::to_json`; and its `<wpt>`
+/// A waypoint without a name is named after its symbol, else its position in the file
+/// cf `https://www.topografix.com/GPX/1/1/`
+fn gpx_tracks_and_waypoints(data: &[u8]) -> Result<(Vec<Vec<TrackPoint>>, Vec<Waypoint>), String> {
+    let mut tracks = vec![];
+    let mut track: Option<Vec<TrackPoint>> = None;
+    let mut point: Option<TrackPoint> = None;
+    let mut waypoints = vec![];
+    let mut waypoint: Option<Waypoint> = None;
+    let mut text = String::new();
+
+    for event in EventReader::new(data) {
+        match event.map_err(|err| err.to_string())? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                text.clear();
+                match name.local_name.as_str() {
+                    "trkseg" | "rte" => track = Some(vec![]),
+                    "trkpt" | "rtept" | "wpt" => {
+                        let attribute =
+                            |local_name: &str| -> Result<f64, String> {
+                                let attribute = attributes
+                                    .iter()
+                                    .find(|attribute| attribute.name.local_name == local_name)
+                                    .ok_or("missing coordinate")?;
+                                attribute.value.trim().parse().map_err(|_err| {
+                                    format!("invalid coordinate: {}", attribute.value)
+                                })
+                            };
+                        let (lat, lng) = (attribute("lat")?, attribute("lon")?);
+                        if name.local_name == "wpt" {
+                            waypoint = Some(Waypoint {
+                                name: String::new(),
+                                description: None,
+                                symbol: None,
+                                lat,
+                                lng,
+                            });
+                        } else {
+                            point = Some(TrackPoint {
+                                lat,
+                                lng,
+                                ..TrackPoint::default()
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(chars) | XmlEvent::CData(chars) | XmlEvent::Whitespace(chars) => {
+                text.push_str(&chars);
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                // NOT an error: they are optional
+                "ele" => {
+                    if let Some(point) = &mut point {
+                        point.elevation = text.trim().parse().ok();
+                    }
+                }
+                "time" => {
+                    if let Some(point) = &mut point {
+                        point.timestamp = parse_timestamp(&text);
+                    }
+                }
+                "trkpt" | "rtept" => {
+                    if let (Some(track), Some(point)) = (&mut track, point.take()) {
+                        track.push(point);
+                    }
+                }
+                "trkseg" | "rte" => tracks.extend(track.take()),
+                // NOTE: the `<trk>` and `<rte>` also have a name; NOT inside a waypoint
+                "name" | "desc" | "cmt" | "sym" => {
+                    if let Some(waypoint) = &mut waypoint {
+                        read_waypoint_element(waypoint, &name.local_name, text.trim());
+                    }
+                }
+                "wpt" => {
+                    if let Some(mut waypoint) = waypoint.take() {
+                        if waypoint.name.is_empty() {
+                            waypoint.name = waypoint
+                                .symbol
+                                .clone()
+                                .unwrap_or_else(|| format!("Waypoint {}", waypoints.len() + 1));
+                        }
+                        waypoints.push(waypoint);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok((tracks, waypoints))
+}
+
+/// The `<name>`, `<desc>`(else `<cmt>`) and `<sym>` of a GPX waypoint
+fn read_waypoint_element(waypoint: &mut Waypoint, element: &str, text: &str) {
+    let value = Some(text.to_string()).filter(|text| !text.is_empty());
+    match element {
+        "name" => waypoint.name = value.unwrap_or_default(),
+        "desc" => waypoint.description = value,
+        "cmt" if waypoint.description.is_none() => waypoint.description = value,
+        "sym" => waypoint.symbol = value,
+        _ => {}
+    }
+}
+
+/// The `<LineString>`(and `<gx:Track>`) of a KML file, whatever their `<Placemark>` or `<Folder>`
+/// cf `https://developers.google.com/kml/documentation/kmlreference`
+fn kml_tracks(data: &[u8]) -> Result<Vec<Vec<TrackPoint>>, String> {
+    let mut tracks = vec![];
+    let mut is_in_line_string = false;
+    // the `<when>` and the `<gx:coord>` of a `<gx:Track>`, in the same order
+    let mut track: Option<(Vec<Option<i64>>, Vec<TrackPoint>)> = None;
+    let mut text = String::new();
+
+    for event in EventReader::new(data) {
+        match event.map_err(|err| err.to_string())? {
+            XmlEvent::StartElement { name, .. } => {
+                text.clear();
+                match name.local_name.as_str() {
+                    "LineString" => is_in_line_string = true,
+                    "Track" => track = Some((vec![], vec![])),
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(chars) | XmlEvent::CData(chars) | XmlEvent::Whitespace(chars) => {
+                text.push_str(&chars);
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "coordinates" if is_in_line_string => {
+                    // "lng,lat[,alt]" tuples, separated by whitespaces
+                    let line = text
+                        .split_whitespace()
+                        .map(|tuple| parse_kml_point(tuple.split(',')))
+                        .collect::<Result<_, _>>()?;
+                    tracks.push(line);
+                }
+                "LineString" => is_in_line_string = false,
+                "when" => {
+                    if let Some((timestamps, _points)) = &mut track {
+                        timestamps.push(parse_timestamp(&text));
+                    }
+                }
+                // `<gx:coord>`: "lng lat alt"
+                "coord" => {
+                    if let Some((_timestamps, points)) = &mut track {
+                        points.push(parse_kml_point(text.split_whitespace())?);
+                    }
+                }
+                "Track" => {
+                    if let Some((timestamps, mut points)) = track.take() {
+                        for (point, timestamp) in points.iter_mut().zip(timestamps) {
+                            point.timestamp = timestamp;
+                        }
+                        tracks.push(points);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(tracks)
+}
+
+/// lng, lat and an optional altitude
+fn parse_kml_point<'a>(mut values: impl Iterator<Item = &'a str>) -> Result<TrackPoint, String> {
+    let parse = |value: &str| -> Result<f64, String> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_err| format!("invalid coordinate: {value}"))
+    };
+    let lng = parse(values.next().ok_or("missing coordinate")?)?;
+    let lat = parse(values.next().ok_or("missing coordinate")?)?;
+    let elevation = values.next().map(parse).transpose()?;
+
+    Ok(TrackPoint {
+        lat,
+        lng,
+        elevation,
+        timestamp: None,
+    })
+}
+
+/// The `<Track>` of a TCX file, ie of its `<Activity>` or `<Course>`; the `<Trackpoint>` without a `<Position>`
+/// are skipped, eg only a heart rate
+/// cf `https://www8.garmin.com/xmlschemas/TrainingCenterDatabasev2.xsd`
+fn tcx_tracks(data: &[u8]) -> Result<Vec<Vec<TrackPoint>>, String> {
+    let mut tracks = vec![];
+    let mut track: Option<Vec<TrackPoint>> = None;
+    let (mut lat, mut lng) = (None, None);
+    let (mut elevation, mut timestamp) = (None, None);
+    let mut text = String::new();
+    let parse = |text: &str| -> Result<f64, String> {
+        text.trim()
+            .parse()
+            .map_err(|_err| format!("invalid coordinate: {text}"))
+    };
+
+    for event in EventReader::new(data) {
+        match event.map_err(|err| err.to_string())? {
+            XmlEvent::StartElement { name, .. } => {
+                text.clear();
+                match name.local_name.as_str() {
+                    "Track" => track = Some(vec![]),
+                    "Trackpoint" => {
+                        (lat, lng) = (None, None);
+                        (elevation, timestamp) = (None, None);
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(chars) | XmlEvent::CData(chars) | XmlEvent::Whitespace(chars) => {
+                text.push_str(&chars);
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "LatitudeDegrees" => lat = Some(parse(&text)?),
+                "LongitudeDegrees" => lng = Some(parse(&text)?),
+                "AltitudeMeters" => elevation = text.trim().parse().ok(),
+                "Time" => timestamp = parse_timestamp(&text),
+                "Trackpoint" => {
+                    if let (Some(track), Some(lat), Some(lng)) = (&mut track, lat, lng) {
+                        track.push(TrackPoint {
+                            lat,
+                            lng,
+                            elevation,
+                            timestamp,
+                        });
+                    }
+                }
+                "Track" => tracks.extend(track.take()),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cf `geojson_lines`
+    fn lines(route: &ParsedRoute) -> Vec<Vec<(f64, f64)>> {
+        route
+            .tracks
+            .iter()
+            .map(|track| track.iter().map(|point| (point.lat, point.lng)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_route_format_detect() {
+        let detect = |file_name, content_type, data: &[u8]| {
+            RouteFormat::detect(file_name, content_type, data)
+        };
+
+        assert_eq!(detect(Some("a.GPX"), None, b""), Ok(RouteFormat::Gpx));
+        assert_eq!(detect(Some("a.kml"), None, b""), Ok(RouteFormat::Kml));
+        assert_eq!(
+            detect(Some("a"), Some("application/geo+json"), b""),
+            Ok(RouteFormat::GeoJson)
+        );
+        // a generic content type: sniffed
+        assert_eq!(
+            detect(
+                None,
+                Some("application/octet-stream"),
+                b"\x0e\x10\x00\x00\x00\x00\x00\x00.FIT"
+            ),
+            Ok(RouteFormat::Fit)
+        );
+        assert_eq!(
+            detect(
+                None,
+                Some("text/xml"),
+                br#"<?xml version="1.0"?><TrainingCenterDatabase xmlns="">"#
+            ),
+            Ok(RouteFormat::Tcx)
+        );
+        assert_eq!(
+            detect(Some("route"), None, b"  {\"type\":\"LineString\"}"),
+            Ok(RouteFormat::GeoJson)
+        );
+        assert_eq!(
+            detect(Some("route.docx"), Some("application/msword"), b"PK"),
+            Err(RouteFormatError::Unsupported(".docx".to_string()))
+        );
+        assert_eq!(
+            detect(None, Some("text/plain"), b"hello"),
+            Err(RouteFormatError::Unsupported("text/plain".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp("2024-02-19T17:29:10.199Z"),
+            Some(1_708_363_750_199)
+        );
+        assert_eq!(
+            parse_timestamp(" 2024-02-19T18:29:10.199123+01:00 "),
+            Some(1_708_363_750_199)
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-19T12:29:10.1-05:00"),
+            Some(1_708_363_750_100)
+        );
+        // leap day
+        assert_eq!(
+            parse_timestamp("2024-02-29T23:59:59.999Z"),
+            Some(1_709_251_199_999)
+        );
+        assert_eq!(parse_timestamp("1969-12-31T23:59:59.999Z"), Some(-1));
+        assert_eq!(parse_timestamp("2024-02-19"), None);
+        assert_eq!(parse_timestamp("2024-13-19T17:29:10Z"), None);
+        assert_eq!(parse_timestamp("2024-02-19T17:29:10.1a9Z"), None);
+    }
+
+    #[test]
+    fn test_route_format_parse_gpx() {
+        let gpx = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+              <metadata><time>2024-02-19T17:00:00Z</time></metadata>
+              <wpt lat="47.0" lon="1.0"><ele>1000</ele><name>summit</name><cmt>cairn</cmt><desc>the top</desc></wpt>
+              <wpt lat="47.1" lon="1.1"><sym>Drinking Water</sym><cmt>fountain</cmt></wpt>
+              <wpt lat="47.2" lon="1.2"></wpt>
+              <rte><rtept lat="45.0" lon="5.0"/><rtept lat="45.1" lon="5.1"/></rte>
+              <trk><trkseg>
+                <trkpt lat="48.0" lon="2.0"><ele>35.5</ele><time>2024-02-19T17:29:10Z</time></trkpt>
+                <trkpt lat="48.1" lon="2.1"></trkpt>
+              </trkseg></trk>
+            </gpx>"#;
+
+        let route = RouteFormat::Gpx.parse(gpx).unwrap();
+        assert_eq!(
+            lines(&route),
+            vec![
+                vec![(45.0, 5.0), (45.1, 5.1)],
+                vec![(48.0, 2.0), (48.1, 2.1)],
+            ]
+        );
+        assert_eq!(route.tracks[1][0].elevation, Some(35.5));
+        assert_eq!(route.tracks[1][0].timestamp, Some(1_708_363_750_000));
+        assert_eq!(route.tracks[1][1].elevation, None);
+        assert_eq!(
+            route.waypoints,
+            vec![
+                Waypoint {
+                    name: "summit".to_string(),
+                    description: Some("the top".to_string()),
+                    symbol: None,
+                    lat: 47.0,
+                    lng: 1.0,
+                },
+                Waypoint {
+                    name: "Drinking Water".to_string(),
+                    description: Some("fountain".to_string()),
+                    symbol: Some("Drinking Water".to_string()),
+                    lat: 47.1,
+                    lng: 1.1,
+                },
+                Waypoint {
+                    name: "Waypoint 3".to_string(),
+                    description: None,
+                    symbol: None,
+                    lat: 47.2,
+                    lng: 1.2,
+                },
+            ]
+        );
+        // the same lines as the `GeoJSON`, whatever their order
+        let geojson: Value = serde_json::from_str(&route.geojson).unwrap();
+        let mut geojson_lines = crate::geo::geojson_lines(&geojson);
+        geojson_lines.sort_by(|a, b| a[0].0.total_cmp(&b[0].0));
+        assert_eq!(geojson_lines, lines(&route));
+    }
+
+    #[test]
+    fn test_route_format_parse_geojson() {
+        let route = RouteFormat::GeoJson
+            .parse(
+                br#"{"type":"FeatureCollection","features":[
+                    {"type":"Feature","geometry":{"type":"LineString","coordinates":[[2.0,48.0],[2.1,48.1,35.0]]},"properties":{}},
+                    {"type":"Feature","geometry":{"type":"Point","coordinates":[2.0,48.0]},"properties":{}}
+                ]}"#,
+            )
+            .unwrap();
+
+        assert_eq!(lines(&route), vec![vec![(48.0, 2.0), (48.1, 2.1)]]);
+        // the same structure as the GPX files
+        let geojson: Value = serde_json::from_str(&route.geojson).unwrap();
+        assert_eq!(
+            geojson,
+            json!({
+                "type": "GeometryCollection",
+                "geometries": [{ "type": "MultiLineString", "coordinates": [[[2.0, 48.0], [2.1, 48.1]]] }],
+            })
+        );
+        assert_eq!(crate::geo::geojson_lines(&geojson), lines(&route));
+        // [lng, lat, elevation]
+        assert_eq!(route.tracks[0][0].elevation, None);
+        assert_eq!(route.tracks[0][1].elevation, Some(35.0));
+
+        assert!(matches!(
+            RouteFormat::GeoJson.parse(b"{\"type\":"),
+            Err(RouteFormatError::Corrupt(RouteFormat::GeoJson, _))
+        ));
+    }
+
+    #[test]
+    fn test_route_format_parse_kml() {
+        let kml = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">
+              <Document>
+                <Placemark><name>start</name><Point><coordinates>2.0,48.0,0</coordinates></Point></Placemark>
+                <Placemark>
+                  <MultiGeometry>
+                    <LineString><coordinates>
+                      2.0,48.0,35 2.1,48.1,36
+                      2.2,48.2
+                    </coordinates></LineString>
+                  </MultiGeometry>
+                </Placemark>
+                <Placemark>
+                  <gx:Track>
+                    <when>2024-02-19T17:29:10Z</when>
+                    <gx:coord>5.0 45.0 250</gx:coord>
+                    <gx:coord>5.1 45.1 260</gx:coord>
+                  </gx:Track>
+                </Placemark>
+              </Document>
+            </kml>"#;
+
+        let route = RouteFormat::Kml.parse(kml).unwrap();
+        assert_eq!(
+            lines(&route),
+            vec![
+                vec![(48.0, 2.0), (48.1, 2.1), (48.2, 2.2)],
+                vec![(45.0, 5.0), (45.1, 5.1)],
+            ]
+        );
+        assert_eq!(route.tracks[0][0].elevation, Some(35.0));
+        assert_eq!(route.tracks[0][2].elevation, None);
+        // `<when>` and `<gx:coord>` are paired in order
+        assert_eq!(route.tracks[1][0].timestamp, Some(1_708_363_750_000));
+        assert_eq!(route.tracks[1][1].timestamp, None);
+        assert_eq!(route.tracks[1][1].elevation, Some(260.0));
+
+        assert_eq!(
+            RouteFormat::Kml
+                .parse(b"<kml><LineString><coordinates>2.0,abc</coordinates></LineString></kml>")
+                .err(),
+            Some(RouteFormatError::Corrupt(
+                RouteFormat::Kml,
+                "invalid coordinate: abc".to_string()
+            ))
+        );
+        assert!(matches!(
+            RouteFormat::Kml.parse(b"<kml><Document>"),
+            Err(RouteFormatError::Corrupt(RouteFormat::Kml, _))
+        ));
+    }
+
+    #[test]
+    fn test_route_format_parse_tcx() {
+        let tcx = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+              <Courses><Course><Name>MJ</Name>
+                <Track>
+                  <Trackpoint>
+                    <Time>2024-02-19T17:29:10Z</Time>
+                    <Position><LatitudeDegrees>48.0</LatitudeDegrees><LongitudeDegrees>2.0</LongitudeDegrees></Position>
+                    <AltitudeMeters>35.0</AltitudeMeters>
+                  </Trackpoint>
+                  <Trackpoint><Time>2024-02-19T17:29:20Z</Time><HeartRateBpm><Value>120</Value></HeartRateBpm></Trackpoint>
+                  <Trackpoint>
+                    <Position><LatitudeDegrees>48.1</LatitudeDegrees><LongitudeDegrees>2.1</LongitudeDegrees></Position>
+                  </Trackpoint>
+                </Track>
+              </Course></Courses>
+            </TrainingCenterDatabase>"#;
+
+        let route = RouteFormat::Tcx.parse(tcx).unwrap();
+        assert_eq!(lines(&route), vec![vec![(48.0, 2.0), (48.1, 2.1)]]);
+        assert_eq!(
+            route.tracks[0][0],
+            TrackPoint {
+                lat: 48.0,
+                lng: 2.0,
+                elevation: Some(35.0),
+                timestamp: Some(1_708_363_750_000),
+            }
+        );
+        assert_eq!(route.tracks[0][1].timestamp, None);
+
+        // NO track at all: NOT an error here, cf `RouteFormatError::NoTrack`
+        let route = RouteFormat::Tcx
+            .parse(b"<TrainingCenterDatabase><Courses/></TrainingCenterDatabase>")
+            .unwrap();
+        assert!(route.tracks.is_empty());
+    }
+
+    #[test]
+    fn test_route_format_error_to_app_error() {
+        assert!(matches!(
+            AppError::from(RouteFormatError::Unsupported(".docx".to_string())),
+            AppError::UnsupportedMediaType(message) if message.contains(".docx")
+        ));
+        assert!(matches!(
+            AppError::from(RouteFormatError::NoTrack(RouteFormat::Fit)),
+            AppError::UnprocessableEntity(message) if message == "the FIT file has no track"
+        ));
+    }
+}
@@ -1,56 +1,37 @@
+use axum::extract::multipart::Field;
 use axum::extract::Multipart;
-use axum::Extension;
-use geozero::gpx::GpxReader;
-use geozero::ProcessToJson;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
 
-use crate::api_authorize_jwt::Claims;
-use crate::db::get_user_from_db;
+use crate::db::{insert_poi, insert_route};
 use crate::errors_and_responses::AppError;
+use crate::role::{Organiser, RequireRole};
+use crate::route::Route;
+use crate::route_formats::{RouteFormat, RouteFormatError};
+use crate::route_stats::RouteStats;
 use crate::state::SharedState;
 
 /// see https://github.com/tokio-rs/axum/blob/d703e6f97a0156177466b6741be0beac0c83d8c7/examples/multipart-form/src/main.rs#L64
+/// The route is added to the library(cf `api_route.rs`); use `set_room_route` to display it in a room.
+/// Besides GPX: `GeoJSON`, KML, TCX and FIT, cf `route_formats.rs`; all are stored as the same `GeoJSON`.
+/// returns: the route with its statistics, eg the elevation gain; cf `route_stats.rs`
+/// An unsupported format is a 415, and a corrupt file or without any track a 422; both with a message naming the format.
+/// Beyond `Config::max_upload_size` or `Config::max_route_points`: a 413
+/// The tracks are simplified before being stored as `GeoJSON`, cf `Config::route_simplify`
+/// The GPX waypoints become the points of interest of the route, cf `api_poi.rs`
+/// MUST be called by an organiser
 #[axum::debug_handler]
 pub(crate) async fn handle_gpx_upload(
     Extension(state): Extension<SharedState>,
-    claims: Claims,
+    RequireRole { claims, .. }: RequireRole<Organiser>,
     mut multipart: Multipart,
-) -> Result<(), AppError> {
-    // check the user credentials from a database
-    let db_pool = &state
-        .read()
-        .map_err(|err| {
-            tracing::error!("handle_gpx_upload: state read lock error: {:?}", err,);
-            AppError::InternalError
-        })?
-        .db_pool
-        .clone();
-    match get_user_from_db(db_pool, &claims.sub).await {
-        Ok(Some(user)) => {
-            // Handle the case when the user is found in the database
-            if !user.is_super_user {
-                tracing::error!(
-                    "handle_gpx_upload: user found but NOT a superuser: {:?}",
-                    claims.sub,
-                );
-                return Err(AppError::NotFound);
-            }
-        }
-        Ok(None) => {
-            // Handle the case when the user is not found in the database
-            tracing::error!("handle_gpx_upload: user not found: {:?}", claims.sub,);
-            return Err(AppError::NotFound);
-        }
-        Err(err) => {
-            // Handle the case when an error occurs during the database query
-            tracing::error!("authorize: db error: {:?}", err,);
-            return Err(AppError::InternalError);
-        }
-    }
+) -> Result<Json<Route>, AppError> {
+    let db_pool = &state.db_pool;
 
     #[allow(clippy::never_loop)]
     while let Some(field) = multipart.next_field().await.map_err(|err| {
-        tracing::error!("handle_gpx_upload: next_field error: {:?}", err,);
-        AppError::InternalError
+        tracing::warn!("handle_gpx_upload: next_field error: {:?}", err,);
+        AppError::BadRequest
     })? {
         // let field = field.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         // TODO? check field.name() == "file" ?
@@ -66,63 +47,111 @@ pub(crate) async fn handle_gpx_upload(
         // }
 
         // let _name = field.name().unwrap().to_string();
-        // let _file_name = field.file_name().unwrap().to_string();
-        // let _content_type = field.content_type().unwrap().to_string();
-        let data = field.bytes().await.map_err(|err| {
-            tracing::error!("handle_gpx_upload: bytes error: {:?}", err,);
-            AppError::InternalError
-        })?;
-        // // let reader = BufReader::from(data);
-        let mut reader = std::io::Cursor::new(data.as_ref());
-
-        // // Read the GPX file and convert to GeoJSON
-        // let gpx: Gpx = read(reader).unwrap();
-        // info!("gpx.tracks : {}", gpx.tracks.len());
-        // let track: &Track = &gpx.tracks[0];
-        // let segment: &TrackSegment = &track.segments[0];
-        // let points = &segment.points;
-
-        // let mut features = Vec::new();
-        // for point in points {
-        //     let coordinates = vec![point.point().y(), point.point().x()];
-        //     let geometry = Geometry::new(Value::Point(coordinates));
-        //     let properties = json!({
-        //         "elevation": point.elevation,
-        //         "time": point.time,
-        //     });
-        //     let feature = Feature {
-        //         bbox: None,
-        //         geometry: Some(geometry),
-        //         id: None,
-        //         properties: Some(properties),
-        //         foreign_members: None,
-        //     };
-        //     features.push(feature);
-        // }
-        //
-        // let feature_collection = GeoJson::FeatureCollection(FeatureCollection::from(features));
+        // the route is named after the file, without its extension
+        let name = field
+            .file_name()
+            .map(|file_name| {
+                file_name
+                    .rsplit_once('.')
+                    .map_or(file_name, |(stem, _ext)| stem)
+            })
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or("route")
+            .to_string();
+        let content_type = field.content_type().map(ToString::to_string);
+        let file_name = field.file_name().map(ToString::to_string);
+        let data = read_field(field, state.config.max_upload_size).await?;
+
+        let format = RouteFormat::detect(file_name.as_deref(), content_type.as_deref(), &data)
+            .inspect_err(|err| tracing::warn!("handle_gpx_upload: {err}"))?;
+        let parsed = format
+            .parse(&data)
+            .inspect_err(|err| tracing::warn!("handle_gpx_upload: {err}"))?;
+        // eg a .gpx with only waypoints
+        // NOTE: on the full resolution tracks, NOT the simplified ones
+        let Some(route_stats) = RouteStats::compute(&parsed.tracks) else {
+            tracing::warn!("handle_gpx_upload: no track in the uploaded {format} file");
+            return Err(RouteFormatError::NoTrack(format).into());
+        };
+        let max_route_points = state.config.max_route_points;
+        if usize::try_from(route_stats.point_count).unwrap_or(usize::MAX) > max_route_points {
+            tracing::warn!(
+                "handle_gpx_upload: {} points in the uploaded {format} file",
+                route_stats.point_count
+            );
+            return Err(AppError::PayloadTooLarge(format!(
+                "the route has {} points; at most {max_route_points} are allowed",
+                route_stats.point_count
+            )));
+        }
+        let geojson = parsed.simplified_geojson(
+            state.config.route_simplify,
+            state.config.route_simplify_tolerance,
+        );
 
-        // cf https://github.com/georust/geozero/blob/52a4d2d3c11f02e734274fcb6ee4b88b94b5b53d/geozero/tests/gpx.rs#L91
-        let mut reader = GpxReader(&mut reader);
-        let geojson_str = reader.to_json().map_err(|err| {
-            tracing::error!("handle_gpx_upload: to_json error: {:?}", err,);
+        let db_error = |err: &dyn std::fmt::Debug| {
+            tracing::error!("handle_gpx_upload: db error: {:?}", err,);
             AppError::InternalError
-        })?;
-
-        state
-            .write()
-            .map_err(|err| {
-                tracing::error!("handle_gpx_upload: state write lock error: {:?}", err,);
-                AppError::InternalError
-            })?
-            .geojson = Some(geojson_str);
+        };
+        // the route and its POIs, or nothing at all; rolled back when dropped without a commit
+        let mut tx = db_pool.begin().await.map_err(|err| db_error(&err))?;
+        // NOTE: the original file is kept as is, cf `api_route::get_route_original`
+        let route = insert_route(
+            &mut *tx,
+            &name,
+            &claims.sub,
+            &data,
+            format,
+            &geojson,
+            &route_stats,
+        )
+        .await
+        .map_err(|err| db_error(&err))?;
+        // the waypoints become the POIs of the route; NOT an error if some are invalid
+        for waypoint in &parsed.waypoints {
+            if let Err(err) = waypoint.validate() {
+                tracing::warn!("handle_gpx_upload: skipping a waypoint: {err}");
+                continue;
+            }
+            insert_poi(&mut *tx, route.id, waypoint)
+                .await
+                .map_err(|err| db_error(&err))?;
+        }
+        tx.commit().await.map_err(|err| db_error(&err))?;
 
         // return Ok(Json(json!({ "status": "success" })));
-        return Ok(());
+        return Ok(Json(route));
     }
     Err(AppError::BadRequest)
 }
 
+/// Read a whole multipart field; but NOT beyond `max_size` bytes
+async fn read_field(mut field: Field<'_>, max_size: usize) -> Result<Vec<u8>, AppError> {
+    let too_large = || {
+        tracing::warn!("handle_gpx_upload: the uploaded file is larger than {max_size} bytes");
+        AppError::PayloadTooLarge(format!(
+            "the uploaded file is larger than the maximum of {max_size} bytes"
+        ))
+    };
+
+    let mut data = vec![];
+    // eg the connection was closed, or the body is NOT a valid multipart
+    while let Some(chunk) = field.chunk().await.map_err(|err| {
+        if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return too_large();
+        }
+        tracing::warn!("handle_gpx_upload: chunk error: {:?}", err,);
+        AppError::BadRequest
+    })? {
+        if data.len() + chunk.len() > max_size {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(data)
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -137,8 +166,14 @@ mod tests {
     };
     use serde_json::Value;
 
-    use crate::db::{insert_user, setup_db, update_user_to_superuser};
+    use crate::db::{
+        get_route_geojson_from_db, get_route_original_from_db, insert_user, list_route_pois,
+        list_routes_from_db, setup_db, update_user_to_superuser,
+    };
     use crate::new_state;
+    use crate::route_simplify::SimplifyAlgorithm;
+    use crate::state::{Config, SharedState};
+    use sqlx::SqlitePool;
 
     use super::*;
 
@@ -149,7 +184,12 @@ mod tests {
             let username = "aaa";
             insert_user(&db_pool, username, "password").await.unwrap();
             update_user_to_superuser(&db_pool, username).await.unwrap();
-            let app_state = new_state(db_pool);
+            // the GeoJSON of the GPX files is kept as is, cf `ParsedRoute::simplified_geojson`
+            let config = Config {
+                route_simplify: SimplifyAlgorithm::None,
+                ..Config::default()
+            };
+            let app_state = new_state(db_pool.clone(), config);
 
             let my_app = Router::new()
                 .route("/api/gpx", axum::routing::post(handle_gpx_upload))
@@ -158,7 +198,7 @@ mod tests {
             // Create a TestServer with your application
             let server = TestServer::new(my_app).unwrap();
 
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // Create a multipart form data payload
             let bytes = include_bytes!("../tests/data/2024-02-19_1444960792_MJ 19_02.gpx");
@@ -182,19 +222,26 @@ mod tests {
         let (response, app_state) =
             temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await;
 
-        let response_body = response.text();
-
         // Assert the response is as expected
         assert_eq!(response.status_code(), 200);
-        assert_eq!(response_body, "");
-        // FIXME
-        let geojson_str: String = app_state
-            .write()
-            .unwrap()
-            .geojson
-            .as_ref()
+        let route: Value = response.json();
+        assert_eq!(route["name"], "file");
+        assert_eq!(route["uploaded_by"], "aaa");
+        assert!(route["distance"].as_f64().unwrap() > 0.0);
+        // the statistics; cf `route_stats.rs`
+        assert_eq!(route["point_count"], 758);
+        assert!(route["elevation_gain"].as_f64().unwrap() > 0.0);
+        assert!(route["elevation_loss"].as_f64().unwrap() > 0.0);
+        assert!(
+            route["min_elevation"].as_f64().unwrap() <= route["max_elevation"].as_f64().unwrap()
+        );
+        assert!(route["duration"].as_i64().unwrap() > 0);
+        assert_eq!(route["bbox"].as_array().unwrap().len(), 4);
+        let db_pool = app_state.db_pool.clone();
+        let geojson_str = get_route_geojson_from_db(&db_pool, route["id"].as_i64().unwrap())
+            .await
             .unwrap()
-            .to_string();
+            .unwrap();
         assert_eq!(geojson_str.len(), 15830);
         // we use this reference file in the frontend tests
         let geojson_res: Value = serde_json::from_str(&geojson_str).unwrap();
@@ -212,7 +259,7 @@ mod tests {
             let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
             let username = "aaa";
             insert_user(&db_pool, username, "password").await.unwrap();
-            let app_state = new_state(db_pool);
+            let app_state = new_state(db_pool.clone(), Config::default());
 
             let my_app = Router::new()
                 .route("/api/gpx", axum::routing::post(handle_gpx_upload))
@@ -221,7 +268,7 @@ mod tests {
             // Create a TestServer with your application
             let server = TestServer::new(my_app).unwrap();
 
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // Create a multipart form data payload
             let bytes = include_bytes!("../tests/data/2024-02-19_1444960792_MJ 19_02.gpx");
@@ -257,7 +304,7 @@ mod tests {
         let f = async {
             let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
             let username = "aaa";
-            let app_state = new_state(db_pool);
+            let app_state = new_state(db_pool.clone(), Config::default());
 
             let my_app = Router::new()
                 .route("/api/gpx", axum::routing::post(handle_gpx_upload))
@@ -266,7 +313,7 @@ mod tests {
             // Create a TestServer with your application
             let server = TestServer::new(my_app).unwrap();
 
-            let token = crate::api_authorize_jwt::tests::generate_token(username);
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
 
             // Create a multipart form data payload
             let bytes = include_bytes!("../tests/data/2024-02-19_1444960792_MJ 19_02.gpx");
@@ -295,4 +342,305 @@ mod tests {
         // Assert the response is as expected
         assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
     }
+
+    /// Upload a file as an organiser
+    async fn upload(file_part: Part) -> (axum_test::TestResponse, SharedState) {
+        upload_with_config(file_part, Config::default()).await
+    }
+
+    async fn upload_with_config(
+        file_part: Part,
+        config: Config,
+    ) -> (axum_test::TestResponse, SharedState) {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        upload_to(db_pool, file_part, config).await
+    }
+
+    async fn upload_to(
+        db_pool: SqlitePool,
+        file_part: Part,
+        config: Config,
+    ) -> (axum_test::TestResponse, SharedState) {
+        let f = async {
+            let username = "aaa";
+            insert_user(&db_pool, username, "password").await.unwrap();
+            update_user_to_superuser(&db_pool, username).await.unwrap();
+            let app_state = new_state(db_pool.clone(), config);
+
+            let my_app = Router::new()
+                .route("/api/gpx", axum::routing::post(handle_gpx_upload))
+                .layer(Extension(app_state.clone()));
+            let server = TestServer::new(my_app).unwrap();
+            let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, username).await;
+
+            let response = server
+                .post("/api/gpx")
+                .add_header(
+                    HeaderName::from_str("Authorization").unwrap(),
+                    HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+                )
+                .multipart(MultipartForm::new().add_part("file", file_part))
+                .await;
+
+            (response, app_state)
+        };
+
+        temp_env::async_with_vars([("JWT_SECRET", Some("0123456789"))], f).await
+    }
+
+    /// The other formats are stored as the same GeoJSON as the GPX files
+    #[tokio::test]
+    async fn test_handle_gpx_upload_other_formats_ok() {
+        let kml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <kml xmlns="http://www.opengis.net/kml/2.2"><Document><Placemark>
+              <LineString><coordinates>2.0,48.0,35 2.1,48.1,36</coordinates></LineString>
+            </Placemark></Document></kml>"#;
+        let tcx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+              <Courses><Course><Track>
+                <Trackpoint><Position><LatitudeDegrees>48.0</LatitudeDegrees><LongitudeDegrees>2.0</LongitudeDegrees></Position></Trackpoint>
+                <Trackpoint><Position><LatitudeDegrees>48.1</LatitudeDegrees><LongitudeDegrees>2.1</LongitudeDegrees></Position></Trackpoint>
+              </Track></Course></Courses>
+            </TrainingCenterDatabase>"#;
+        let geojson = r#"{"type":"LineString","coordinates":[[2.0,48.0],[2.1,48.1]]}"#;
+        let fit = crate::route_fit::tests::new_fit(&[Some((48.0, 2.0)), Some((48.1, 2.1))]);
+
+        for (file_part, name) in [
+            (Part::text(kml).file_name("kml route.kml"), "kml route"),
+            // from the content type
+            (
+                Part::text(tcx)
+                    .file_name("tcx")
+                    .mime_type("application/vnd.garmin.tcx+xml"),
+                "tcx",
+            ),
+            // from the content
+            (
+                Part::text(geojson)
+                    .file_name("geojson")
+                    .mime_type("application/octet-stream"),
+                "geojson",
+            ),
+            (
+                Part::bytes(fit)
+                    .file_name("activity.fit")
+                    .mime_type("application/octet-stream"),
+                "activity",
+            ),
+        ] {
+            let (response, app_state) = upload(file_part).await;
+
+            assert_eq!(response.status_code(), StatusCode::OK, "{name}");
+            let route: Value = response.json();
+            assert_eq!(route["name"], name);
+            let distance = route["distance"].as_f64().unwrap();
+            assert!((distance - 13_400.0).abs() < 100.0, "{name}: {distance}");
+            let geojson_str =
+                get_route_geojson_from_db(&app_state.db_pool, route["id"].as_i64().unwrap())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            assert_eq!(route["point_count"], 2, "{name}");
+            let geojson: Value = serde_json::from_str(&geojson_str).unwrap();
+            assert_eq!(geojson["type"], "GeometryCollection", "{name}");
+            assert_eq!(
+                geojson["geometries"][0]["type"], "MultiLineString",
+                "{name}"
+            );
+            // cf `new_fit`
+            if name == "activity" {
+                assert_eq!(route["elevation_gain"], 1.0);
+                assert_eq!(route["duration"], 1000);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_gpx_upload_unsupported_or_corrupt_4xx() {
+        let (response, _app_state) = upload(
+            Part::bytes(b"PK\x03\x04".as_slice())
+                .file_name("route.docx")
+                .mime_type("application/octet-stream"),
+        )
+        .await;
+        assert_eq!(response.status_code(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let message = response.json::<Value>()["message"].to_string();
+        assert!(message.contains(".docx"), "{message}");
+
+        let (response, _app_state) =
+            upload(Part::text("<kml><Document>").file_name("route.kml")).await;
+        assert_eq!(response.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        let message = response.json::<Value>()["message"].to_string();
+        assert!(message.contains("corrupt KML file"), "{message}");
+
+        // only waypoints
+        let (response, _app_state) = upload(
+            Part::text(r#"<gpx version="1.1"><wpt lat="48.0" lon="2.0"></wpt></gpx>"#)
+                .file_name("route.gpx"),
+        )
+        .await;
+        assert_eq!(response.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.json::<Value>()["message"],
+            "the GPX file has no track"
+        );
+    }
+
+    /// The GeoJSON is simplified, but NOT the statistics nor the original file
+    #[tokio::test]
+    async fn test_handle_gpx_upload_simplified() {
+        let bytes = include_bytes!("../tests/data/2024-02-19_1444960792_MJ 19_02.gpx");
+
+        for algorithm in [
+            SimplifyAlgorithm::DouglasPeucker,
+            SimplifyAlgorithm::Visvalingam,
+        ] {
+            let config = Config {
+                route_simplify: algorithm,
+                route_simplify_tolerance: 5.0,
+                ..Config::default()
+            };
+            let (response, app_state) =
+                upload_with_config(Part::bytes(bytes.as_slice()).file_name("file.gpx"), config)
+                    .await;
+
+            assert_eq!(response.status_code(), StatusCode::OK, "{algorithm:?}");
+            let route: Value = response.json();
+            assert_eq!(route["point_count"], 758, "{algorithm:?}");
+            let route_id = route["id"].as_i64().unwrap();
+            let geojson_str = get_route_geojson_from_db(&app_state.db_pool, route_id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(geojson_str.len() < 15830, "{algorithm:?}");
+            let geojson: Value = serde_json::from_str(&geojson_str).unwrap();
+            let points = geojson["geometries"][0]["coordinates"][0]
+                .as_array()
+                .unwrap()
+                .len();
+            assert!(points > 2 && points < 758, "{algorithm:?}: {points}");
+
+            let (original, format) = get_route_original_from_db(&app_state.db_pool, route_id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(original, bytes, "{algorithm:?}");
+            assert_eq!(format, Some(RouteFormat::Gpx), "{algorithm:?}");
+        }
+    }
+
+    /// The format is stored: it can NOT always be sniffed again, eg from the extension only
+    #[tokio::test]
+    async fn test_handle_gpx_upload_format_stored() {
+        let kml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!-- {} -->
+            <kml xmlns="http://www.opengis.net/kml/2.2"><Document><Placemark>
+              <LineString><coordinates>2.0,48.0 2.1,48.1</coordinates></LineString>
+            </Placemark></Document></kml>"#,
+            "a".repeat(2000)
+        );
+        assert!(RouteFormat::detect(None, None, kml.as_bytes()).is_err());
+
+        let (response, app_state) = upload(Part::text(kml.clone()).file_name("route.kml")).await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let route: Value = response.json();
+        let (original, format) =
+            get_route_original_from_db(&app_state.db_pool, route["id"].as_i64().unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(original, kml.as_bytes());
+        assert_eq!(format, Some(RouteFormat::Kml));
+    }
+
+    #[tokio::test]
+    async fn test_handle_gpx_upload_too_large_413() {
+        let bytes = include_bytes!("../tests/data/2024-02-19_1444960792_MJ 19_02.gpx");
+
+        let config = Config {
+            max_upload_size: 1000,
+            ..Config::default()
+        };
+        let (response, _app_state) =
+            upload_with_config(Part::bytes(bytes.as_slice()).file_name("file.gpx"), config).await;
+        assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        let message = response.json::<Value>()["message"].to_string();
+        assert!(message.contains("1000 bytes"), "{message}");
+
+        let config = Config {
+            max_route_points: 100,
+            ..Config::default()
+        };
+        let (response, _app_state) =
+            upload_with_config(Part::bytes(bytes.as_slice()).file_name("file.gpx"), config).await;
+        assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            response.json::<Value>()["message"],
+            "the route has 758 points; at most 100 are allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_gpx_upload_waypoints_as_pois() {
+        let gpx = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+              <wpt lat="48.05" lon="2.05"><name>water</name><desc>fountain</desc><sym>Drinking Water</sym></wpt>
+              <wpt lat="48.06" lon="2.06"><name>invalid</name><desc>{}</desc></wpt>
+              <trk><name>the track</name><trkseg>
+                <trkpt lat="48.0" lon="2.0"></trkpt>
+                <trkpt lat="48.1" lon="2.1"></trkpt>
+              </trkseg></trk>
+            </gpx>"#,
+            "a".repeat(crate::poi::MAX_POI_TEXT_LENGTH + 1)
+        );
+
+        let (response, app_state) = upload(Part::text(gpx).file_name("route.gpx")).await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let route: Value = response.json();
+        let pois = list_route_pois(&app_state.db_pool, route["id"].as_i64().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(pois.len(), 1);
+        assert_eq!(pois[0].waypoint.name, "water");
+        assert_eq!(pois[0].waypoint.description.as_deref(), Some("fountain"));
+        assert_eq!(pois[0].waypoint.symbol.as_deref(), Some("Drinking Water"));
+        assert_eq!((pois[0].waypoint.lat, pois[0].waypoint.lng), (48.05, 2.05));
+    }
+
+    /// The route is NOT stored without its POIs
+    #[tokio::test]
+    async fn test_handle_gpx_upload_poi_error_rolled_back() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        sqlx::query(
+            "CREATE TRIGGER poi_error BEFORE INSERT ON poi BEGIN SELECT RAISE(ABORT, 'test'); END",
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+              <wpt lat="48.05" lon="2.05"><name>water</name></wpt>
+              <trk><trkseg>
+                <trkpt lat="48.0" lon="2.0"></trkpt>
+                <trkpt lat="48.1" lon="2.1"></trkpt>
+              </trkseg></trk>
+            </gpx>"#;
+
+        let (response, app_state) = upload_to(
+            db_pool,
+            Part::text(gpx).file_name("route.gpx"),
+            Config::default(),
+        )
+        .await;
+
+        assert_eq!(response.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            list_routes_from_db(&app_state.db_pool).await.unwrap(),
+            vec![]
+        );
+    }
 }
@@ -0,0 +1,182 @@
+//! The elevation profile of a route: its elevation versus the distance from the start; cf `api_route::get_route_profile`
+//!
+//! Derived from the original file at full resolution(cf `route_formats.rs`): the `GeoJSON` has no elevation,
+//! and it is simplified. The distances are counted like `RouteLine`: the gaps between two tracks are NOT counted.
+//! NOTE: `RouteProgress::distance_done` is along the simplified route, ie slightly shorter;
+//! use `RouteProgress::percent` to place a rider on the profile.
+
+use serde::Serialize;
+
+use crate::geo::haversine_distance;
+use crate::route_formats::TrackPoint;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct ProfilePoint {
+    /// in meters, from the start of the route
+    pub(crate) distance: f64,
+    /// in meters
+    pub(crate) elevation: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Default)]
+pub(crate) struct Profile {
+    /// in meters: the whole route, including the parts without any elevation
+    pub(crate) length: f64,
+    /// ordered by distance; empty if the route has no elevation at all, eg drawn on a map
+    pub(crate) points: Vec<ProfilePoint>,
+}
+
+impl Profile {
+    /// The points without any elevation are skipped; but NOT their distance
+    pub(crate) fn compute(tracks: &[Vec<TrackPoint>]) -> Self {
+        let mut length = 0.0;
+        let mut points = vec![];
+        for track in tracks {
+            let mut previous: Option<&TrackPoint> = None;
+            for point in track {
+                length += previous.map_or(0.0, |previous| {
+                    haversine_distance((previous.lat, previous.lng), (point.lat, point.lng))
+                });
+                previous = Some(point);
+                if let Some(elevation) = point.elevation {
+                    points.push(ProfilePoint {
+                        distance: length,
+                        elevation,
+                    });
+                }
+            }
+        }
+
+        Self { length, points }
+    }
+
+    /// At most `max_points`(but at least the first and the last ones), eg for a chart a few hundred pixels wide
+    /// With the Largest-Triangle-Three-Buckets algorithm: unlike one point every N, the peaks and the valleys are kept
+    /// cf `https://skemman.is/bitstream/1946/15343/3/SS_MSthesis.pdf`
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub(crate) fn downsample(&self, max_points: usize) -> Self {
+        let points = &self.points;
+        let count = points.len();
+        let max_points = max_points.max(2);
+        if count <= max_points {
+            return self.clone();
+        }
+
+        // the first and the last points are alone in their bucket
+        let bucket_size = (count - 2) as f64 / (max_points - 2) as f64;
+        let bucket = |i: usize| {
+            let start = (i as f64 * bucket_size) as usize + 1;
+            let end = (((i + 1) as f64 * bucket_size) as usize + 1).min(count - 1);
+            start..end.max(start + 1)
+        };
+        let mut sampled = Vec::with_capacity(max_points);
+        let mut selected = points[0];
+        sampled.push(selected);
+        for i in 0..max_points - 2 {
+            // the third vertex of the triangles: the average of the next bucket; or the last point
+            let next = if i + 1 < max_points - 2 {
+                &points[bucket(i + 1)]
+            } else {
+                &points[count - 1..]
+            };
+            let next_distance =
+                next.iter().map(|point| point.distance).sum::<f64>() / next.len() as f64;
+            let next_elevation =
+                next.iter().map(|point| point.elevation).sum::<f64>() / next.len() as f64;
+
+            let area = |point: &ProfilePoint| {
+                ((selected.distance - next_distance) * (point.elevation - selected.elevation)
+                    - (selected.distance - point.distance) * (next_elevation - selected.elevation))
+                    .abs()
+            };
+            if let Some(largest) = points[bucket(i)]
+                .iter()
+                .max_by(|a, b| area(a).total_cmp(&area(b)))
+            {
+                selected = *largest;
+                sampled.push(selected);
+            }
+        }
+        sampled.push(points[count - 1]);
+
+        Self {
+            length: self.length,
+            points: sampled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Along a meridian: ~111m between two points
+    fn new_track(elevations: &[Option<f64>]) -> Vec<TrackPoint> {
+        elevations
+            .iter()
+            .enumerate()
+            .map(|(i, &elevation)| TrackPoint {
+                #[allow(clippy::cast_precision_loss)]
+                lat: 48.0 + i as f64 * 0.001,
+                lng: 2.0,
+                elevation,
+                timestamp: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_profile_compute() {
+        let tracks = vec![
+            new_track(&[Some(100.0), None, Some(120.0)]),
+            // the gap between the two tracks is NOT counted
+            new_track(&[Some(200.0), Some(210.0)]),
+        ];
+
+        let profile = Profile::compute(&tracks);
+        assert!((profile.length - 3.0 * 111.2).abs() < 1.0, "{profile:?}");
+        #[allow(clippy::cast_possible_truncation)]
+        let points: Vec<(i64, f64)> = profile
+            .points
+            .iter()
+            .map(|point| (point.distance.round() as i64, point.elevation))
+            .collect();
+        assert_eq!(
+            points,
+            vec![(0, 100.0), (222, 120.0), (222, 200.0), (334, 210.0)]
+        );
+
+        assert_eq!(Profile::compute(&[new_track(&[None, None])]).points, vec![]);
+    }
+
+    #[test]
+    fn test_profile_downsample() {
+        // flat, with a single peak in the middle
+        let elevations: Vec<Option<f64>> = (0..1001)
+            .map(|i| Some(if i == 500 { 1000.0 } else { 100.0 }))
+            .collect();
+        let profile = Profile::compute(&[new_track(&elevations)]);
+
+        let downsampled = profile.downsample(50);
+        assert_eq!(downsampled.points.len(), 50);
+        assert_eq!(downsampled.length, profile.length);
+        assert_eq!(downsampled.points.first(), profile.points.first());
+        assert_eq!(downsampled.points.last(), profile.points.last());
+        // the peak is kept
+        assert!(downsampled
+            .points
+            .iter()
+            .any(|point| point.elevation == 1000.0));
+        assert!(downsampled
+            .points
+            .windows(2)
+            .all(|pair| pair[0].distance < pair[1].distance));
+
+        assert_eq!(profile.downsample(0).points.len(), 2);
+        assert_eq!(profile.downsample(2000), profile);
+    }
+}
@@ -0,0 +1,220 @@
+//! Simplify the tracks of the uploaded routes: their `GeoJSON` is sent to every member of a room, often on mobile data.
+//! The original file is kept at full resolution, cf `api_route::get_route_original`.
+//! cf `https://en.wikipedia.org/wiki/Ramer%E2%80%93Douglas%E2%80%93Peucker_algorithm`
+//! and `https://en.wikipedia.org/wiki/Visvalingam%E2%80%93Whyatt_algorithm`
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::route_formats::TrackPoint;
+
+/// Mean Earth radius, in meters; cf `geo.rs`
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum SimplifyAlgorithm {
+    /// Keep every point
+    None,
+    /// Drop the points closer than the tolerance to the simplified line
+    DouglasPeucker,
+    /// Drop the points whose triangle with their neighbours has an area below the tolerance squared
+    Visvalingam,
+}
+
+/// Simplify a track; its first and last points are ALWAYS kept
+/// `tolerance`: in meters
+pub(crate) fn simplify(
+    track: &[TrackPoint],
+    algorithm: SimplifyAlgorithm,
+    tolerance: f64,
+) -> Vec<TrackPoint> {
+    if track.len() <= 2 || tolerance <= 0.0 {
+        return track.to_vec();
+    }
+    let points = project(track);
+    let keep = match algorithm {
+        SimplifyAlgorithm::None => return track.to_vec(),
+        SimplifyAlgorithm::DouglasPeucker => douglas_peucker(&points, tolerance),
+        SimplifyAlgorithm::Visvalingam => visvalingam(&points, tolerance * tolerance),
+    };
+
+    track
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
+/// in meters, on a plane tangent at the first point(equirectangular): accurate enough to compare small distances;
+/// NOT across the antimeridian
+fn project(track: &[TrackPoint]) -> Vec<(f64, f64)> {
+    let (lat0, lng0) = (track[0].lat, track[0].lng);
+    let cos_lat0 = lat0.to_radians().cos();
+
+    track
+        .iter()
+        .map(|point| {
+            (
+                (point.lng - lng0).to_radians() * cos_lat0 * EARTH_RADIUS,
+                (point.lat - lat0).to_radians() * EARTH_RADIUS,
+            )
+        })
+        .collect()
+}
+
+/// The distance from `p` to the segment `[a, b]`
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2 = dx * dx + dy * dy;
+    let t = if length2 > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a.0 + t * dx - p.0).hypot(a.1 + t * dy - p.1)
+}
+
+/// returns: which points to keep
+/// NOTE: iterative, with a stack: the tracks can have hundreds of thousands of points
+fn douglas_peucker(points: &[(f64, f64)], tolerance: f64) -> Vec<bool> {
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    (keep[0], keep[last]) = (true, true);
+
+    let mut stack = vec![(0, last)];
+    while let Some((first, last)) = stack.pop() {
+        let furthest = (first + 1..last)
+            .map(|i| (i, segment_distance(points[i], points[first], points[last])))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((i, distance)) = furthest {
+            if distance > tolerance {
+                keep[i] = true;
+                stack.push((first, i));
+                stack.push((i, last));
+            }
+        }
+    }
+
+    keep
+}
+
+/// returns: which points to keep
+/// `min_area`: in square meters
+fn visvalingam(points: &[(f64, f64)], min_area: f64) -> Vec<bool> {
+    let count = points.len();
+    let area = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+    };
+    let mut keep = vec![true; count];
+    // a doubly linked list of the points that are kept
+    let mut previous: Vec<usize> = (0..count).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (1..=count).collect();
+    let mut areas = vec![f64::INFINITY; count];
+    // smallest area first; NOTE: the bits of a positive f64 are in the same order as the f64 itself
+    // The outdated entries are skipped: their area is NOT the one in `areas` anymore
+    let mut heap = BinaryHeap::new();
+    for i in 1..count - 1 {
+        areas[i] = area(points[i - 1], points[i], points[i + 1]);
+        heap.push(Reverse((areas[i].to_bits(), i)));
+    }
+
+    while let Some(Reverse((bits, i))) = heap.pop() {
+        if !keep[i] || bits != areas[i].to_bits() {
+            continue;
+        }
+        let removed_area = f64::from_bits(bits);
+        if removed_area >= min_area {
+            break;
+        }
+        keep[i] = false;
+        let (p, n) = (previous[i], next[i]);
+        next[p] = n;
+        previous[n] = p;
+        // NOT smaller than the area just removed: else the neighbours would be removed before it
+        for j in [p, n] {
+            if j > 0 && j < count - 1 {
+                areas[j] = area(points[previous[j]], points[j], points[next[j]]).max(removed_area);
+                heap.push(Reverse((areas[j].to_bits(), j)));
+            }
+        }
+    }
+
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Along a meridian, with a small zigzag of `offset` degrees of longitude on every other point
+    fn new_track(count: usize, offset: f64) -> Vec<TrackPoint> {
+        (0..count)
+            .map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let lat = 48.0 + i as f64 * 0.001;
+                TrackPoint {
+                    lat,
+                    lng: 2.0 + if i % 2 == 1 { offset } else { 0.0 },
+                    elevation: Some(35.0),
+                    timestamp: None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simplify() {
+        // ~0.7m of zigzag, every ~111m: triangles of ~80m²
+        let track = new_track(101, 0.000_01);
+
+        for algorithm in [
+            SimplifyAlgorithm::DouglasPeucker,
+            SimplifyAlgorithm::Visvalingam,
+        ] {
+            let simplified = simplify(&track, algorithm, 10.0);
+            if algorithm == SimplifyAlgorithm::DouglasPeucker {
+                assert_eq!(simplified, vec![track[0], track[100]], "a straight line");
+            } else {
+                // the triangles grow as their neighbours are removed
+                assert!(simplified.len() < 50, "{}", simplified.len());
+                assert_eq!(simplified.first(), track.first());
+                assert_eq!(simplified.last(), track.last());
+            }
+
+            // the zigzag is kept below its size
+            assert_eq!(simplify(&track, algorithm, 0.1).len(), 101, "{algorithm:?}");
+        }
+
+        assert_eq!(simplify(&track, SimplifyAlgorithm::None, 5.0), track);
+        assert_eq!(
+            simplify(&track, SimplifyAlgorithm::DouglasPeucker, 0.0),
+            track
+        );
+        assert_eq!(
+            simplify(&track[..2], SimplifyAlgorithm::Visvalingam, 5.0),
+            track[..2]
+        );
+    }
+
+    #[test]
+    fn test_simplify_keeps_the_corners() {
+        // an "L": north, then east
+        let mut track = new_track(11, 0.0);
+        let corner = track[10];
+        track.extend((1..=10).map(|i| TrackPoint {
+            lng: corner.lng + f64::from(i) * 0.001,
+            ..corner
+        }));
+
+        for algorithm in [
+            SimplifyAlgorithm::DouglasPeucker,
+            SimplifyAlgorithm::Visvalingam,
+        ] {
+            assert_eq!(
+                simplify(&track, algorithm, 5.0),
+                vec![track[0], corner, track[20]],
+                "{algorithm:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! The statistics of a route: computed on upload(cf `route_gpx.rs`) from the parsed tracks, and stored with it
+//! The elevations and the timestamps are optional: eg a route drawn on a map has neither
+
+use serde::Serialize;
+
+use crate::geo::{bounding_box, line_distance};
+use crate::route_formats::TrackPoint;
+
+/// SHOULD match `server/migrations/20240309_1000_route_stats.sql`
+#[derive(PartialEq, Debug, Serialize, Clone, Default)]
+pub(crate) struct RouteStats {
+    /// in meters
+    pub(crate) distance: f64,
+    /// in meters: the sums of the climbs and of the descents; None if there is no elevation
+    pub(crate) elevation_gain: Option<f64>,
+    pub(crate) elevation_loss: Option<f64>,
+    /// in meters
+    pub(crate) min_elevation: Option<f64>,
+    pub(crate) max_elevation: Option<f64>,
+    /// `[min_lng, min_lat, max_lng, max_lat]` ie the `GeoJSON` "bbox" order
+    pub(crate) bbox: [f64; 4],
+    pub(crate) point_count: i64,
+    /// in milliseconds: from the first to the last timestamp; None if there are none, eg a planned route
+    pub(crate) duration: Option<i64>,
+}
+
+impl RouteStats {
+    /// None if there is no point at all
+    /// NOTE: the gain and the loss are within each track; NOT between the end of a track and the start of the next one
+    pub(crate) fn compute(tracks: &[Vec<TrackPoint>]) -> Option<Self> {
+        let lines: Vec<Vec<(f64, f64)>> = tracks
+            .iter()
+            .map(|track| track.iter().map(|point| (point.lat, point.lng)).collect())
+            .collect();
+        let bbox = bounding_box(&lines)?;
+
+        let (mut elevation_gain, mut elevation_loss) = (0.0, 0.0);
+        for track in tracks {
+            let elevations: Vec<f64> = track.iter().filter_map(|point| point.elevation).collect();
+            for pair in elevations.windows(2) {
+                let delta = pair[1] - pair[0];
+                elevation_gain += delta.max(0.0);
+                elevation_loss += (-delta).max(0.0);
+            }
+        }
+        let points = || tracks.iter().flatten();
+        let elevations = || points().filter_map(|point| point.elevation);
+        let timestamps = || points().filter_map(|point| point.timestamp);
+        let has_elevation = elevations().next().is_some();
+
+        Some(Self {
+            distance: lines.iter().map(|line| line_distance(line)).sum(),
+            elevation_gain: has_elevation.then_some(elevation_gain),
+            elevation_loss: has_elevation.then_some(elevation_loss),
+            min_elevation: elevations().reduce(f64::min),
+            max_elevation: elevations().reduce(f64::max),
+            bbox,
+            point_count: i64::try_from(points().count()).unwrap_or(i64::MAX),
+            duration: timestamps()
+                .min()
+                .zip(timestamps().max())
+                .map(|(min, max)| max - min),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_point(lat: f64, elevation: Option<f64>, timestamp: Option<i64>) -> TrackPoint {
+        TrackPoint {
+            lat,
+            lng: 2.0,
+            elevation,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_route_stats_compute() {
+        let tracks = [
+            vec![
+                new_point(48.0, Some(100.0), Some(1_000)),
+                new_point(48.01, Some(150.0), Some(2_000)),
+                // a missing elevation is skipped
+                new_point(48.02, None, Some(3_000)),
+                new_point(48.03, Some(120.0), Some(4_000)),
+            ],
+            // NO climb from 120m to 300m between the tracks
+            vec![
+                new_point(48.05, Some(300.0), None),
+                new_point(48.06, Some(310.0), Some(10_000)),
+            ],
+        ];
+
+        let stats = RouteStats::compute(&tracks).unwrap();
+        assert!(
+            (stats.distance - 4.0 * 1112.0).abs() < 10.0,
+            "{}",
+            stats.distance
+        );
+        assert_eq!(stats.elevation_gain, Some(60.0));
+        assert_eq!(stats.elevation_loss, Some(30.0));
+        assert_eq!(stats.min_elevation, Some(100.0));
+        assert_eq!(stats.max_elevation, Some(310.0));
+        assert_eq!(stats.bbox, [2.0, 48.0, 2.0, 48.06]);
+        assert_eq!(stats.point_count, 6);
+        assert_eq!(stats.duration, Some(9_000));
+    }
+
+    #[test]
+    fn test_route_stats_compute_without_elevation_nor_timestamp() {
+        let tracks = [vec![
+            new_point(48.0, None, None),
+            new_point(48.01, None, None),
+        ]];
+
+        let stats = RouteStats::compute(&tracks).unwrap();
+        assert!(stats.distance > 0.0);
+        assert_eq!(stats.elevation_gain, None);
+        assert_eq!(stats.elevation_loss, None);
+        assert_eq!(stats.min_elevation, None);
+        assert_eq!(stats.max_elevation, None);
+        assert_eq!(stats.point_count, 2);
+        assert_eq!(stats.duration, None);
+
+        assert_eq!(RouteStats::compute(&[]), None);
+        assert_eq!(RouteStats::compute(&[vec![]]), None);
+    }
+}
@@ -0,0 +1,18 @@
+/// SHOULD match `server/migrations/20240304_1000_session.sql`
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct Session {
+    pub(crate) id: String,
+    pub(crate) username: String,
+    pub(crate) refresh_token_hash: String,
+    /// milliseconds since UNIX epoch
+    pub(crate) expires_at: i64,
+    /// milliseconds since UNIX epoch; None if the session is still valid
+    pub(crate) revoked_at: Option<i64>,
+}
+
+impl Session {
+    /// NOT revoked, and NOT expired
+    pub(crate) fn is_active(&self, now: i64) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
@@ -1,37 +1,642 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::sync::RwLock;
+use std::time::Duration;
 
+use axum::body::Bytes;
+use dashmap::{DashMap, DashSet};
 use sqlx::SqlitePool;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, RwLock};
+
+use protocol::{Position, SharingMode, WsMessage};
+use serde_json::Value;
+
+use crate::db::{
+    get_room_from_db, get_room_lead, get_room_member_privacy, get_route_geojson_from_db,
+    get_route_original_from_db, list_last_positions_from_db, now_timestamp,
+};
+use crate::geo::{geojson_lines, RouteLine};
+use crate::geofence::Geofences;
+use crate::lead::{GroupSpreads, Lead};
+use crate::off_route::OffRoute;
+use crate::presence::Presences;
+use crate::privacy::Privacy;
+use crate::progress::Progresses;
+use crate::route_formats::{RouteFormat, RouteFormatError};
+use crate::route_profile::Profile;
+use crate::route_simplify::SimplifyAlgorithm;
+use crate::throttle::{spawn_positions_fanout, PendingPositions};
+use crate::ws_handler::broadcast_message;
+
+/// When the last positions of a room are loaded from the DB(cf `AppState::last_positions`),
+/// the ones older than that are ignored: nobody wants to see where someone was last week
+const LAST_POSITIONS_MAX_AGE: Duration = Duration::from_hours(24);
+
+/// The broadcast channels of a given room
+/// Each room is fully isolated: a client subscribed to a room NEVER receives messages from another one.
+/// NOTE: cloning only clones the senders, ie it is cheap
+#[derive(Clone)]
+pub(crate) struct RoomChannels {
+    /// Channel used to send messages to all clients connected to this room.
+    pub(crate) chat_broadcast_sender: broadcast::Sender<String>,
+    /// Channel used to send locations to all clients connected to this room.
+    pub(crate) location_broadcast_sender: broadcast::Sender<String>,
+    /// The positions waiting to be sent on `location_broadcast_sender`; cf `throttle.rs`
+    pub(crate) pending_positions: Arc<PendingPositions>,
+    /// The usernames whose sockets MUST be closed(they left the room, were deleted or got a new role);
+    /// cf `AppState::disconnect_member`
+    pub(crate) disconnect_sender: broadcast::Sender<String>,
+}
+
+impl RoomChannels {
+    /// NOTE: this spawns the fan out task of the room; ie it MUST be called from within the runtime
+    fn new(config: &Config) -> Self {
+        let (chat_tx, _rx) = broadcast::channel(config.broadcast_capacity);
+        let (location_tx, _rx) = broadcast::channel(config.broadcast_capacity);
+        let (disconnect_tx, _rx) = broadcast::channel(config.broadcast_capacity);
+        let pending_positions = Arc::new(PendingPositions::default());
+        spawn_positions_fanout(
+            Arc::downgrade(&pending_positions),
+            location_tx.clone(),
+            config.fanout_interval,
+        );
+
+        Self {
+            chat_broadcast_sender: chat_tx,
+            location_broadcast_sender: location_tx,
+            pending_positions,
+            disconnect_sender: disconnect_tx,
+        }
+    }
+}
+
+/// The server options that change the behavior of the API; cf `Opt` in `main.rs`
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    /// If true: a user that is NOT in the DB can login without a password ("anonymous" user)
+    /// else they MUST register first, cf `api_authorize_jwt::register`
+    pub(crate) open_login: bool,
+    /// If false: `POST /register` is disabled; the only users are those created by a superuser(or `--root-user`)
+    pub(crate) registration_enabled: bool,
+    /// Lifetime of the JWT access tokens; they are renewed with `POST /refresh`
+    pub(crate) access_token_ttl: Duration,
+    /// Lifetime of a session(ie of a refresh token) without any `POST /refresh`
+    pub(crate) refresh_token_ttl: Duration,
+    /// The "geolocation" sockets are pinged at this interval; a client that does NOT answer
+    /// for `ws_handler::HEARTBEAT_MISSED_MAX` intervals is disconnected, ie offline
+    pub(crate) heartbeat_interval: Duration,
+    /// A connected user that has NOT sent any position for that long is idle; cf `presence.rs`
+    pub(crate) idle_timeout: Duration,
+    /// The number of messages buffered by each broadcast channel of a room(cf `RoomChannels`),
+    /// for the clients that are too slow; beyond that they are resynced, cf `ws_handler::spawn_send_task`
+    pub(crate) broadcast_capacity: usize,
+    /// The positions of a room are sent to its clients at most once per interval; cf `throttle.rs`
+    pub(crate) fanout_interval: Duration,
+    /// The maximum number of frames per second that a client can send on the "geolocation" socket;
+    /// the extra ones are dropped, cf `throttle::RateLimiter`
+    pub(crate) position_rate_limit: u32,
+    /// in meters: a rider further than that from the active route of the room is off route; cf `off_route.rs`
+    pub(crate) off_route_threshold: f64,
+    /// in meters: the group is spread out when the leader is further than that ahead of the sweeper along the route;
+    /// cf `lead.rs`
+    pub(crate) group_spread_threshold: f64,
+    /// How the tracks of the uploaded routes are simplified; cf `route_simplify.rs`
+    pub(crate) route_simplify: SimplifyAlgorithm,
+    /// in meters: cf `route_simplify::simplify`
+    pub(crate) route_simplify_tolerance: f64,
+    /// in bytes: the larger uploaded routes are rejected, cf `route_gpx::handle_gpx_upload`
+    pub(crate) max_upload_size: usize,
+    /// The uploaded routes with more points(before simplification) are rejected
+    pub(crate) max_route_points: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            open_login: true,
+            registration_enabled: true,
+            access_token_ttl: Duration::from_mins(15),
+            refresh_token_ttl: Duration::from_hours(30 * 24),
+            heartbeat_interval: Duration::from_secs(15),
+            idle_timeout: Duration::from_mins(2),
+            broadcast_capacity: 100,
+            fanout_interval: Duration::from_secs(1),
+            position_rate_limit: 10,
+            off_route_threshold: 100.0,
+            group_spread_threshold: 1000.0,
+            route_simplify: SimplifyAlgorithm::DouglasPeucker,
+            route_simplify_tolerance: 5.0,
+            max_upload_size: 10 * 1024 * 1024,
+            max_route_points: 200_000,
+        }
+    }
+}
 
 /// `https://github.com/tokio-rs/axum/blob/d703e6f97a0156177466b6741be0beac0c83d8c7/examples/chat/src/main.rs#L26C1-L32C2`
 /// Our shared state
+/// There is NO global lock: the immutable parts(`db_pool`, `config`; and `api_authorize_jwt::KEYS`) are used as-is,
+/// and each mutable part has its own synchronization.
 pub(crate) struct AppState {
-    /// Channel used to send messages to all connected clients.
-    pub(crate) chat_broadcast_sender: broadcast::Sender<String>,
-    /// Channel used to send locations to all connected clients.
-    pub(crate) location_broadcast_sender: broadcast::Sender<String>,
-    /// GeoJSON result of https://github.com/georust/geozero/blob/52a4d2d3c11f02e734274fcb6ee4b88b94b5b53d/geozero/src/geojson/mod.rs#L34
-    /// so this is a String
-    pub(crate) geojson: Option<String>,
     pub(crate) db_pool: SqlitePool,
+    pub(crate) config: Config,
+    /// Broadcast channels registry, indexed by room id.
+    /// The channels are created lazily, when the first client connects to a given room.
+    /// Concurrent map: the sockets of different rooms do NOT contend with each other.
+    pub(crate) rooms: DashMap<i64, RoomChannels>,
+    /// The `GeoJSON` of the routes, indexed by route id; filled on the first `route_geojson`.
+    /// Async lock: it is held across the DB query on a miss, so that when a whole room
+    /// fetches a new route(cf `WsMessage::RouteUpdated`) the DB is only hit once.
+    pub(crate) route_geojson_cache: RwLock<HashMap<i64, Bytes>>,
+    /// The elevation profile of the routes, indexed by route id; filled on the first `route_profile`
+    route_profiles: DashMap<i64, Result<Arc<Profile>, RouteFormatError>>,
+    /// The last known position of each user, indexed by room id then username; cf `WsMessage::Snapshot`
+    /// A room is loaded from the DB when its first client connects; then it is kept up to date in memory.
+    last_positions: DashMap<i64, HashMap<String, Position>>,
+    /// The rooms whose `last_positions` were loaded from the DB
+    /// NOT the keys of `last_positions`: a position can be received before, cf `update_last_position`
+    last_positions_loaded: DashSet<i64>,
+    /// Who is online/idle/offline in each room; cf `WsMessage::Presence`
+    pub(crate) presences: Presences,
+    /// The privacy settings of the members, indexed by (room id, username); cf `privacy.rs`
+    /// Loaded from the DB on the first `privacy`; then kept up to date by `set_privacy`.
+    privacies: DashMap<(i64, String), Privacy>,
+    /// The geofences of each room, and who is inside what; cf `geofence.rs`
+    pub(crate) geofences: Geofences,
+    /// The active route of each room, ready to be projected on(cf `room_route_line`), indexed by room id
+    /// Loaded on the first `room_route_line`; call `forget_room_route` when it changes.
+    room_route_lines: DashMap<i64, Arc<RouteLine>>,
+    /// Who is off route in each room; cf `off_route.rs`
+    pub(crate) off_route: OffRoute,
+    /// The recent progress of each rider along the route; cf `progress.rs`
+    pub(crate) progresses: Progresses,
+    /// The leader and the sweeper of each room, indexed by room id; cf `lead.rs`
+    /// Loaded from the DB on the first `lead`; then kept up to date by `set_lead`.
+    leads: DashMap<i64, Lead>,
+    /// Which groups are spread out; cf `lead.rs`
+    pub(crate) group_spreads: GroupSpreads,
 }
 
-/// For now just an Arc; but if needed we can add a `RwLock`
+impl AppState {
+    /// Get the channels for a given room; create them if needed
+    pub(crate) fn room_channels(&self, room_id: i64) -> RoomChannels {
+        self.rooms
+            .entry(room_id)
+            .or_insert_with(|| RoomChannels::new(&self.config))
+            .clone()
+    }
+
+    /// Same as `room_channels`, but None if nobody ever connected to this room
+    pub(crate) fn existing_room_channels(&self, room_id: i64) -> Option<RoomChannels> {
+        self.rooms
+            .get(&room_id)
+            .map(|room_channels| room_channels.clone())
+    }
+
+    /// Get the `GeoJSON` of a given route, from the cache or else from the DB
+    /// NOTE: the `GeoJSON` of a route never changes; but call `forget_route` when it is deleted
+    pub(crate) async fn route_geojson(
+        &self,
+        route_id: i64,
+    ) -> Result<Option<Bytes>, std::io::Error> {
+        if let Some(geojson) = self.route_geojson_cache.read().await.get(&route_id) {
+            return Ok(Some(geojson.clone()));
+        }
+
+        let mut cache = self.route_geojson_cache.write().await;
+        // another request may have filled it while we were waiting for the lock
+        if let Some(geojson) = cache.get(&route_id) {
+            return Ok(Some(geojson.clone()));
+        }
+        let Some(geojson) = get_route_geojson_from_db(&self.db_pool, route_id).await? else {
+            return Ok(None);
+        };
+        let geojson = Bytes::from(geojson);
+        cache.insert(route_id, geojson.clone());
+
+        Ok(Some(geojson))
+    }
+
+    /// Get the elevation profile of a given route, from the cache or else from its original file
+    /// NOTE: like its `GeoJSON`, it never changes; but call `forget_route` when it is deleted
+    ///
+    /// returns: None if the route does NOT exist; a `RouteFormatError` if its original file can NOT be parsed
+    /// (eg a corrupted DB), which is also cached
+    pub(crate) async fn route_profile(
+        &self,
+        route_id: i64,
+    ) -> Result<Option<Result<Arc<Profile>, RouteFormatError>>, std::io::Error> {
+        if let Some(profile) = self.route_profiles.get(&route_id) {
+            return Ok(Some(profile.clone()));
+        }
+
+        let Some((original, format)) = get_route_original_from_db(&self.db_pool, route_id).await?
+        else {
+            return Ok(None);
+        };
+        // NOTE: it was already parsed on upload, cf `route_gpx.rs`;
+        // only the routes uploaded before the format was stored are sniffed again
+        let profile = format
+            .map_or_else(|| RouteFormat::detect(None, None, &original), Ok)
+            .and_then(|format| format.parse(&original))
+            .map(|parsed| Arc::new(Profile::compute(&parsed.tracks)))
+            .inspect_err(|err| tracing::error!("route_profile: route {route_id}: {err}"));
+        self.route_profiles.insert(route_id, profile.clone());
+
+        Ok(Some(profile))
+    }
+
+    /// The last known position of each user of a given room, ordered by username
+    /// The first call for a given room loads it from the DB(ie the positions sent before a restart)
+    pub(crate) async fn last_positions(
+        &self,
+        room_id: i64,
+    ) -> Result<Vec<Position>, std::io::Error> {
+        if !self.last_positions_loaded.contains(&room_id) {
+            let since = now_timestamp().saturating_sub(
+                i64::try_from(LAST_POSITIONS_MAX_AGE.as_millis()).unwrap_or(i64::MAX),
+            );
+            let positions = list_last_positions_from_db(&self.db_pool, room_id, since).await?;
+            // NOTE: another client may have sent a more recent position while we were querying
+            for position in positions {
+                self.update_last_position(room_id, position);
+            }
+            self.last_positions_loaded.insert(room_id);
+        }
+
+        let mut positions: Vec<Position> = self
+            .last_positions
+            .get(&room_id)
+            .map(|positions| positions.values().cloned().collect())
+            .unwrap_or_default();
+        positions.sort_by(|a, b| a.username.cmp(&b.username));
+
+        Ok(positions)
+    }
+
+    /// The active route of a given room(empty if none), from the cache or else from `route_geojson`
+    /// NOTE: called for every position, cf `ws_handler::publish_position`
+    pub(crate) async fn room_route_line(
+        &self,
+        room_id: i64,
+    ) -> Result<Arc<RouteLine>, std::io::Error> {
+        if let Some(route_line) = self.room_route_lines.get(&room_id) {
+            return Ok(route_line.clone());
+        }
+
+        let route_id = get_room_from_db(&self.db_pool, room_id)
+            .await?
+            .and_then(|room| room.active_route_id);
+        let geojson = match route_id {
+            Some(route_id) => self.route_geojson(route_id).await?,
+            None => None,
+        };
+        let lines = geojson
+            .and_then(|geojson| serde_json::from_slice::<Value>(&geojson).ok())
+            .map(|geojson| geojson_lines(&geojson))
+            .unwrap_or_default();
+        let route_line = Arc::new(RouteLine::new(lines));
+        self.room_route_lines.insert(room_id, route_line.clone());
+
+        Ok(route_line)
+    }
+
+    /// The active route of a given room changed, or was deleted: reset everything that depends on it
+    /// ie `room_route_line`, the geofences of the route, who is off route, and the progress of everyone
+    pub(crate) fn forget_room_route(&self, room_id: i64) {
+        self.room_route_lines.remove(&room_id);
+        self.geofences.forget_room(room_id);
+        self.off_route.forget_room(room_id);
+        self.progresses.forget_room(room_id);
+        self.group_spreads.forget_room(room_id);
+        if let Some(mut positions) = self.last_positions.get_mut(&room_id) {
+            for position in positions.values_mut() {
+                position.progress = None;
+            }
+        }
+    }
+
+    /// Keep a given position if it is the most recent one for this user in this room
+    /// NOTE: the positions can arrive out of order, eg a phone that was offline
+    pub(crate) fn update_last_position(&self, room_id: i64, position: Position) {
+        let mut positions = self.last_positions.entry(room_id).or_default();
+        match positions.get(&position.username) {
+            Some(last_position) if last_position.timestamp > position.timestamp => {}
+            _ => {
+                positions.insert(position.username.clone(), position);
+            }
+        }
+    }
+
+    /// The privacy settings of a given member of a given room, from the cache or else from the DB
+    /// NOTE: checked for every position, cf `ws_handler::publish_position`; so the DB is only hit once per member
+    ///
+    /// returns: None if they are NOT a member of this room, eg they left it with a socket still open;
+    /// NOT the default settings, which would share their exact positions
+    pub(crate) async fn privacy(
+        &self,
+        room_id: i64,
+        username: &str,
+    ) -> Result<Option<Privacy>, std::io::Error> {
+        let key = (room_id, username.to_string());
+        if let Some(privacy) = self.privacies.get(&key) {
+            return Ok(Some(*privacy));
+        }
+
+        let Some(privacy) = get_room_member_privacy(&self.db_pool, room_id, username).await? else {
+            return Ok(None);
+        };
+        // a `set_privacy` may have happened while we were querying: it wins
+        Ok(Some(*self.privacies.entry(key).or_insert(privacy)))
+    }
+
+    /// Apply the new privacy settings of a given member of a given room; they MUST already be persisted
+    /// cf `db::set_room_member_privacy`
+    /// The room is notified when the mode changes(cf `WsMessage::Sharing`); and when they are hidden,
+    /// their last position is forgotten, so that it is NOT in the next snapshots.
+    pub(crate) fn set_privacy(&self, room_id: i64, username: &str, privacy: Privacy) {
+        let previous = self
+            .privacies
+            .insert((room_id, username.to_string()), privacy);
+
+        if privacy.mode == SharingMode::Hidden {
+            if let Some(mut positions) = self.last_positions.get_mut(&room_id) {
+                positions.remove(username);
+            }
+        }
+        // nobody ever connected to this room: nothing to notify
+        let Some(room_channels) = self.existing_room_channels(room_id) else {
+            return;
+        };
+        if privacy.mode == SharingMode::Hidden {
+            room_channels.pending_positions.remove(username);
+        }
+        if previous.map(|previous| previous.mode) != Some(privacy.mode) {
+            broadcast_message(
+                &room_channels.location_broadcast_sender,
+                &WsMessage::Sharing {
+                    username: username.to_string(),
+                    mode: privacy.mode,
+                },
+            );
+        }
+    }
+
+    /// The leader and the sweeper of a given room, from the cache or else from the DB
+    /// NOTE: checked for every position, cf `ws_handler::check_group_spread`
+    pub(crate) async fn lead(&self, room_id: i64) -> Result<Lead, std::io::Error> {
+        if let Some(lead) = self.leads.get(&room_id) {
+            return Ok(lead.clone());
+        }
+
+        let lead = get_room_lead(&self.db_pool, room_id)
+            .await?
+            .unwrap_or_default();
+        // a `set_lead` may have happened while we were querying: it wins
+        Ok(self.leads.entry(room_id).or_insert(lead).clone())
+    }
+
+    /// The gap between the leader and the sweeper of a given room, from their last positions; cf `Lead::gap`
+    pub(crate) fn room_gap(&self, room_id: i64, lead: &Lead) -> Option<f64> {
+        lead.gap(&*self.last_positions.get(&room_id)?)
+    }
+
+    /// Apply the new leader and sweeper of a given room; they MUST already be persisted, cf `db::set_room_lead`
+    /// The room is notified(cf `WsMessage::Lead`); and the gap starts over.
+    pub(crate) fn set_lead(&self, room_id: i64, lead: Lead) {
+        self.leads.insert(room_id, lead.clone());
+        self.group_spreads.forget_room(room_id);
+
+        if let Some(room_channels) = self.existing_room_channels(room_id) {
+            broadcast_message(
+                &room_channels.location_broadcast_sender,
+                &WsMessage::Lead {
+                    leader: lead.leader,
+                    sweeper: lead.sweeper,
+                },
+            );
+        }
+    }
+
+    /// Close the sockets of a given user in a given room; they are checked again if the client reconnects,
+    /// cf `ws_handler::ws_handler`
+    pub(crate) fn disconnect_member(&self, room_id: i64, username: &str) {
+        if let Some(room_channels) = self.existing_room_channels(room_id) {
+            // NOTE: error if no socket is open in this room
+            let _ = room_channels.disconnect_sender.send(username.to_string());
+        }
+    }
+
+    /// A user left a given room: their sockets are closed, their privacy settings are reset, and they are
+    /// NOT the leader/sweeper anymore, like in the DB
+    pub(crate) fn forget_member(&self, room_id: i64, username: &str) {
+        self.disconnect_member(room_id, username);
+        self.privacies.remove(&(room_id, username.to_string()));
+
+        let Some(mut lead) = self.leads.get(&room_id).map(|lead| lead.clone()) else {
+            return;
+        };
+        if !lead.contains(username) {
+            return;
+        }
+        for designated in [&mut lead.leader, &mut lead.sweeper] {
+            if designated.as_deref() == Some(username) {
+                *designated = None;
+            }
+        }
+        self.set_lead(room_id, lead);
+    }
+
+    /// Close the sockets of a deleted user, and remove them from `last_positions`, `presences`, `privacies`,
+    /// `geofences`, `off_route`, `progresses` and `leads`, in every room
+    pub(crate) fn forget_user(&self, username: &str) {
+        for room_channels in &self.rooms {
+            let _ = room_channels.disconnect_sender.send(username.to_string());
+        }
+        for mut positions in self.last_positions.iter_mut() {
+            positions.remove(username);
+        }
+        self.presences.forget_user(username);
+        self.privacies
+            .retain(|(_room_id, member), _privacy| member != username);
+        self.geofences.forget_user(username);
+        self.off_route.forget_user(username);
+        self.progresses.forget_user(username);
+        // NOTE: reloaded from the DB, where they were cleared too
+        self.leads.retain(|_room_id, lead| !lead.contains(username));
+    }
+
+    /// Remove a deleted route from the caches of `route_geojson` and `route_profile`
+    pub(crate) async fn forget_route(&self, route_id: i64) {
+        self.route_geojson_cache.write().await.remove(&route_id);
+        self.route_profiles.remove(&route_id);
+    }
+}
+
+/// The handlers only get a shared reference, cf `AppState` for the synchronization
 /// cf `https://github.com/tokio-rs/axum/blob/4d65ba0215b57797193ec49245d32d4dd79bb701/examples/key-value-store/src/main.rs#L83`
-pub(crate) type SharedState = Arc<RwLock<AppState>>;
+pub(crate) type SharedState = Arc<AppState>;
 
-pub(crate) fn new_state(db_pool: SqlitePool) -> SharedState {
+pub(crate) fn new_state(db_pool: SqlitePool, config: Config) -> SharedState {
     // Set up application state for use with with_state().
-    let (chat_tx, _rx) = broadcast::channel(100);
-    let (location_tx, _rx) = broadcast::channel(100);
-
     let app_state = AppState {
-        chat_broadcast_sender: chat_tx,
-        location_broadcast_sender: location_tx,
-        geojson: None,
         db_pool,
+        config,
+        rooms: DashMap::new(),
+        route_geojson_cache: RwLock::new(HashMap::new()),
+        route_profiles: DashMap::new(),
+        last_positions: DashMap::new(),
+        last_positions_loaded: DashSet::new(),
+        presences: Presences::default(),
+        privacies: DashMap::new(),
+        geofences: Geofences::default(),
+        room_route_lines: DashMap::new(),
+        off_route: OffRoute::default(),
+        progresses: Progresses::default(),
+        leads: DashMap::new(),
+        group_spreads: GroupSpreads::default(),
+    };
+
+    Arc::new(app_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{
+        delete_route, insert_positions, insert_room, insert_route, remove_room_member,
+        set_room_member_privacy, setup_db,
     };
+    use crate::route_stats::RouteStats;
+    use protocol::SharingMode;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_route_geojson_is_cached_until_forgotten() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = new_state(db_pool.clone(), Config::default());
+        let geojson = r#"{"type":"GeometryCollection","geometries":[]}"#;
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "root",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            geojson,
+            &RouteStats {
+                distance: 42.0,
+                bbox: [2.0, 48.0, 2.1, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            state.route_geojson(route.id).await.unwrap(),
+            Some(Bytes::from(geojson))
+        );
+        // served from the cache, NOT from the DB
+        delete_route(&db_pool, route.id).await.unwrap();
+        assert_eq!(
+            state.route_geojson(route.id).await.unwrap(),
+            Some(Bytes::from(geojson))
+        );
+
+        state.forget_route(route.id).await;
+        assert_eq!(state.route_geojson(route.id).await.unwrap(), None);
+        assert_eq!(state.route_geojson(route.id + 1).await.unwrap(), None);
+    }
+
+    fn new_position(username: &str, timestamp: i64) -> Position {
+        Position {
+            username: username.to_string(),
+            lat: 48.8354,
+            lng: 2.3203,
+            accuracy: None,
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_last_positions() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = new_state(db_pool.clone(), Config::default());
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let now = now_timestamp();
+        // only the recent ones are loaded from the DB
+        insert_positions(
+            &db_pool,
+            room.id,
+            &[
+                new_position("aaa", now - 1000),
+                new_position("old", now - 48 * 3600 * 1000),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            state.last_positions(room.id).await.unwrap(),
+            vec![new_position("aaa", now - 1000)]
+        );
+
+        // out of order: ignored
+        state.update_last_position(room.id, new_position("aaa", now - 2000));
+        state.update_last_position(room.id, new_position("bbb", now));
+        state.update_last_position(room.id + 1, new_position("ccc", now));
+        assert_eq!(
+            state.last_positions(room.id).await.unwrap(),
+            vec![new_position("aaa", now - 1000), new_position("bbb", now)]
+        );
+
+        state.forget_user("aaa");
+        assert_eq!(
+            state.last_positions(room.id).await.unwrap(),
+            vec![new_position("bbb", now)]
+        );
+    }
+
+    /// A position received before the first snapshot of the room MUST NOT prevent loading the others from the DB
+    #[tokio::test]
+    async fn test_last_positions_updated_before_loaded() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = new_state(db_pool.clone(), Config::default());
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let now = now_timestamp();
+        insert_positions(&db_pool, room.id, &[new_position("aaa", now - 1000)])
+            .await
+            .unwrap();
+
+        state.update_last_position(room.id, new_position("bbb", now));
+        assert_eq!(
+            state.last_positions(room.id).await.unwrap(),
+            vec![new_position("aaa", now - 1000), new_position("bbb", now)]
+        );
+    }
+
+    /// NOT the default settings once they left: they would share their exact positions
+    #[tokio::test]
+    async fn test_privacy_of_a_former_member() {
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = new_state(db_pool.clone(), Config::default());
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let hidden = Privacy {
+            mode: SharingMode::Hidden,
+            precision: None,
+        };
+        set_room_member_privacy(&db_pool, room.id, "aaa", hidden)
+            .await
+            .unwrap();
+        state.set_privacy(room.id, "aaa", hidden);
+        assert_eq!(state.privacy(room.id, "aaa").await.unwrap(), Some(hidden));
+
+        remove_room_member(&db_pool, room.id, "aaa").await.unwrap();
+        state.forget_member(room.id, "aaa");
 
-    Arc::new(RwLock::new(app_state))
+        assert_eq!(state.privacy(room.id, "aaa").await.unwrap(), None);
+        assert_eq!(state.privacy(room.id, "bbb").await.unwrap(), None);
+    }
 }
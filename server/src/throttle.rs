@@ -0,0 +1,237 @@
+//! Throttling of the "geolocation" sockets
+//!
+//! With 50-100 riders, rebroadcasting each position as soon as it is received is a lot of traffic; so:
+//! - the positions of a room are coalesced(ie only the last one of each user is kept), and fanned out
+//!   every `Config::fanout_interval` in a single `WsMessage::Positions` frame; cf `spawn_positions_fanout`
+//! - each connection is rate limited, cf `RateLimiter`
+//!
+//! NOTE: only the fan out is delayed; the positions are still persisted, and kept for the snapshots, as soon as they are received.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError, Weak};
+use std::time::Duration;
+
+use protocol::{Position, WsMessage};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::ws_handler::broadcast_message;
+
+/// A client that sends more than that many times `Config::position_rate_limit` in a given second is disconnected;
+/// below that, the extra frames are only dropped
+pub(crate) const RATE_LIMIT_DISCONNECT_FACTOR: u32 = 5;
+
+/// The positions of a room received since the last fan out; indexed by username
+#[derive(Debug, Default)]
+pub(crate) struct PendingPositions(Mutex<HashMap<String, Position>>);
+
+impl PendingPositions {
+    /// Keep a given position until the next fan out; unless a more recent one of this user is already pending
+    pub(crate) fn push(&self, position: Position) {
+        let mut positions = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        match positions.get(&position.username) {
+            Some(pending) if pending.timestamp > position.timestamp => {}
+            _ => {
+                positions.insert(position.username.clone(), position);
+            }
+        }
+    }
+
+    /// Drop the pending position of a given user, if any; eg they just hid, cf `AppState::set_privacy`
+    pub(crate) fn remove(&self, username: &str) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(username);
+    }
+
+    /// Remove all the pending positions, ordered by username
+    fn take(&self) -> Vec<Position> {
+        let positions = std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner));
+        let mut positions: Vec<Position> = positions.into_values().collect();
+        positions.sort_by(|a, b| a.username.cmp(&b.username));
+
+        positions
+    }
+}
+
+/// Spawn the task that broadcasts the pending positions of a room every `fanout_interval`, if any
+/// It stops when the `PendingPositions` is dropped, ie with its `RoomChannels`
+pub(crate) fn spawn_positions_fanout(
+    pending_positions: Weak<PendingPositions>,
+    broadcast_sender: broadcast::Sender<String>,
+    fanout_interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // NOTE: `interval` would tick immediately; there is nothing to send yet
+        let mut interval =
+            tokio::time::interval_at(Instant::now() + fanout_interval, fanout_interval);
+        loop {
+            interval.tick().await;
+            let Some(pending_positions) = pending_positions.upgrade() else {
+                break;
+            };
+            let positions = pending_positions.take();
+            if !positions.is_empty() {
+                broadcast_message(&broadcast_sender, &WsMessage::Positions { positions });
+            }
+        }
+    })
+}
+
+/// cf `RateLimiter::check`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RateLimit {
+    Allowed,
+    /// the frame MUST be dropped; `is_first` for the first one in the current window, eg to warn the client only once
+    Dropped {
+        is_first: bool,
+    },
+    /// way too fast: the client MUST be disconnected, cf `RATE_LIMIT_DISCONNECT_FACTOR`
+    Exceeded,
+}
+
+/// A fixed window rate limiter: at most `max_per_second` frames per second, for a given connection
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    max_per_second: u32,
+    window_start: Instant,
+    count: u32,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(max_per_second: u32) -> Self {
+        Self {
+            max_per_second,
+            window_start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// Count a new frame received at `now`
+    pub(crate) fn check(&mut self, now: Instant) -> RateLimit {
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(1);
+
+        if self.count <= self.max_per_second {
+            RateLimit::Allowed
+        } else if self.count
+            > self
+                .max_per_second
+                .saturating_mul(RATE_LIMIT_DISCONNECT_FACTOR)
+        {
+            RateLimit::Exceeded
+        } else {
+            RateLimit::Dropped {
+                is_first: self.count == self.max_per_second + 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn new_position(username: &str, timestamp: i64) -> Position {
+        Position {
+            username: username.to_string(),
+            lat: 48.8,
+            lng: 2.3,
+            accuracy: None,
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_pending_positions_keep_the_last_one_per_user() {
+        let pending_positions = PendingPositions::default();
+        pending_positions.push(new_position("bbb", 1));
+        pending_positions.push(new_position("aaa", 2));
+        pending_positions.push(new_position("bbb", 3));
+        // out of order: ignored
+        pending_positions.push(new_position("aaa", 1));
+        pending_positions.push(new_position("ccc", 1));
+        pending_positions.remove("ccc");
+
+        assert_eq!(
+            pending_positions.take(),
+            vec![new_position("aaa", 2), new_position("bbb", 3)]
+        );
+        assert_eq!(pending_positions.take(), vec![]);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut rate_limiter = RateLimiter::new(2);
+        let start = Instant::now();
+
+        let results: Vec<RateLimit> = (0..11).map(|_| rate_limiter.check(start)).collect();
+        assert_eq!(
+            results,
+            vec![
+                RateLimit::Allowed,
+                RateLimit::Allowed,
+                RateLimit::Dropped { is_first: true },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Dropped { is_first: false },
+                RateLimit::Exceeded,
+            ]
+        );
+
+        // a new window
+        assert_eq!(
+            rate_limiter.check(start + Duration::from_secs(1)),
+            RateLimit::Allowed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_positions_fanout() {
+        let pending_positions = Arc::new(PendingPositions::default());
+        let (broadcast_sender, mut broadcast_receiver) = broadcast::channel(10);
+        let fanout = spawn_positions_fanout(
+            Arc::downgrade(&pending_positions),
+            broadcast_sender,
+            Duration::from_millis(10),
+        );
+
+        pending_positions.push(new_position("aaa", 1));
+        pending_positions.push(new_position("aaa", 2));
+        let msg = broadcast_receiver.recv().await.unwrap();
+        assert_eq!(
+            WsMessage::decode(&msg).unwrap(),
+            WsMessage::Positions {
+                positions: vec![new_position("aaa", 2)]
+            }
+        );
+
+        // nothing pending: nothing sent
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), broadcast_receiver.recv())
+                .await
+                .is_err()
+        );
+
+        drop(pending_positions);
+        tokio::time::timeout(Duration::from_secs(1), fanout)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+//! Export the recorded positions as GPX 1.1; the reverse of the import in `route_gpx.rs`
+//! cf `https://www.topografix.com/GPX/1/1/`
+//! NOTE: written by hand: the format is simple enough, and `geozero` can only read it
+
+use std::fmt::Write;
+
+use protocol::Position;
+
+/// NOT registered with IANA, but the usual one
+pub(crate) const GPX_CONTENT_TYPE: &str = "application/gpx+xml";
+
+/// A `<trk>`: the positions of a user
+pub(crate) struct Track {
+    pub(crate) name: String,
+    /// One `<trkseg>` each; the positions MUST be sorted by timestamp
+    pub(crate) segments: Vec<Vec<Position>>,
+}
+
+/// Write a GPX 1.1 document, with one `<trk>` per track
+/// Each point has its timestamp; and its elevation when known
+pub(crate) fn write_gpx(tracks: &[Track]) -> String {
+    let mut gpx = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<gpx version="1.1" creator="group-live-tracker" xmlns="http://www.topografix.com/GPX/1/1">"#,
+        "\n",
+    ));
+
+    // NOTE: writing to a String can NOT fail
+    for track in tracks {
+        gpx.push_str("  <trk>\n");
+        let _ = writeln!(gpx, "    <name>{}</name>", escape_xml(&track.name));
+        for segment in &track.segments {
+            gpx.push_str("    <trkseg>\n");
+            for position in segment {
+                let _ = writeln!(
+                    gpx,
+                    r#"      <trkpt lat="{}" lon="{}">"#,
+                    position.lat, position.lng
+                );
+                if let Some(altitude) = position.altitude {
+                    let _ = writeln!(gpx, "        <ele>{altitude}</ele>");
+                }
+                let _ = writeln!(
+                    gpx,
+                    "        <time>{}</time>",
+                    format_timestamp(position.timestamp)
+                );
+                gpx.push_str("      </trkpt>\n");
+            }
+            gpx.push_str("    </trkseg>\n");
+        }
+        gpx.push_str("  </trk>\n");
+    }
+    gpx.push_str("</gpx>\n");
+
+    gpx
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// milliseconds since UNIX epoch -> RFC 3339 in UTC, eg "2024-02-19T17:29:10.199Z"
+/// cf `http://howardhinnant.github.io/date_algorithms.html#civil_from_days`
+fn format_timestamp(timestamp: i64) -> String {
+    let seconds = timestamp.div_euclid(1000);
+    let milliseconds = timestamp.rem_euclid(1000);
+    let days = seconds.div_euclid(86_400);
+    let seconds_of_day = seconds.rem_euclid(86_400);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{milliseconds:03}Z",
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use geozero::gpx::GpxReader;
+    use geozero::ProcessToJson;
+    use serde_json::Value;
+
+    use crate::geo::geojson_lines;
+
+    use super::*;
+
+    fn new_position(lat: f64, lng: f64, altitude: Option<f64>, timestamp: i64) -> Position {
+        Position {
+            username: "aaa".to_string(),
+            lat,
+            lng,
+            accuracy: None,
+            altitude,
+            heading: None,
+            speed: None,
+            timestamp,
+            progress: None,
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(1_708_363_750_199),
+            "2024-02-19T17:29:10.199Z"
+        );
+        // leap day
+        assert_eq!(
+            format_timestamp(1_709_251_199_999),
+            "2024-02-29T23:59:59.999Z"
+        );
+        assert_eq!(format_timestamp(-1), "1969-12-31T23:59:59.999Z");
+    }
+
+    /// What we write can be read back with the same reader as the uploaded routes, cf `route_gpx.rs`
+    #[test]
+    fn test_write_gpx_roundtrip() {
+        let tracks = [
+            Track {
+                name: "aaa <&>".to_string(),
+                segments: vec![
+                    vec![
+                        new_position(48.0, 2.0, Some(35.5), 1_708_363_750_199),
+                        new_position(48.1, 2.1, None, 1_708_363_760_199),
+                    ],
+                    vec![
+                        new_position(45.0, 5.0, Some(250.0), 1_708_363_770_199),
+                        new_position(45.1, 5.1, Some(260.0), 1_708_363_780_199),
+                    ],
+                ],
+            },
+            Track {
+                name: "bbb".to_string(),
+                segments: vec![vec![
+                    new_position(48.5, 2.5, None, 1_708_363_750_199),
+                    new_position(48.6, 2.6, None, 1_708_363_760_199),
+                ]],
+            },
+        ];
+
+        let gpx = write_gpx(&tracks);
+        assert!(gpx.contains("<name>aaa &lt;&amp;&gt;</name>"), "{gpx}");
+        assert!(gpx.contains("<ele>35.5</ele>"), "{gpx}");
+        assert!(
+            gpx.contains("<time>2024-02-19T17:29:10.199Z</time>"),
+            "{gpx}"
+        );
+
+        let mut cursor = std::io::Cursor::new(gpx.as_bytes());
+        let geojson = GpxReader(&mut cursor).to_json().unwrap();
+        let geojson: Value = serde_json::from_str(&geojson).unwrap();
+        assert_eq!(
+            geojson_lines(&geojson),
+            vec![
+                vec![(48.0, 2.0), (48.1, 2.1)],
+                vec![(45.0, 5.0), (45.1, 5.1)],
+                vec![(48.5, 2.5), (48.6, 2.6)],
+            ]
+        );
+    }
+}
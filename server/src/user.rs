@@ -1,10 +1,15 @@
 use serde::Serialize;
 
+use crate::role::Role;
+
 /// SHOULD match `server/migrations/20240226_1558_initial.sql`
 #[allow(clippy::struct_field_names)]
 #[derive(PartialEq, Debug, Serialize)]
 pub(crate) struct User {
     pub(crate) username: String,
+    /// NEVER sent to the clients, eg by `list_users`
+    #[serde(skip_serializing)]
     pub(crate) password_hash: String,
-    pub(crate) is_super_user: bool,
+    /// the global role; cf `room_member` for the roles inside a room
+    pub(crate) role: Role,
 }
@@ -3,6 +3,8 @@
 //! and `https://github.com/tokio-rs/axum/blob/d703e6f97a0156177466b6741be0beac0c83d8c7/examples/chat/src/main.rs`
 
 use std::net::SocketAddr;
+use std::ops::ControlFlow;
+use std::time::Duration;
 
 use axum::{
     extract::ws::{Message, WebSocket, WebSocketUpgrade},
@@ -16,20 +18,39 @@ use axum::extract::connect_info::ConnectInfo;
 //allows to split the websocket stream into separate TX and RX branches
 use axum::body::Body;
 use axum::extract::Query;
+use futures::stream::{SplitSink, SplitStream};
 use futures::SinkExt;
 use futures::StreamExt;
-use jsonwebtoken::{decode, Validation};
+use protocol::{Position, WsMessage, MAX_CHAT_LENGTH};
 use serde::Deserialize;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, Interval};
 
 use crate::{
-    api_authorize_jwt::{Claims, KEYS},
+    api_authorize_jwt::validate_token,
+    db::{get_room_member_role, insert_geofence_events, insert_positions, now_timestamp},
     errors_and_responses::AppError,
-    state::SharedState,
+    role::Role,
+    state::{RoomChannels, SharedState},
+    throttle::{RateLimit, RateLimiter},
 };
 
+/// The positions are written to the DB by batches of (at most) this size...
+const POSITIONS_BATCH_SIZE: usize = 20;
+/// ...or at least every `POSITIONS_FLUSH_INTERVAL`
+const POSITIONS_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// A "geolocation" socket is closed when nothing(not even a pong) was received
+/// during that many `Config::heartbeat_interval`
+pub(crate) const HEARTBEAT_MISSED_MAX: u32 = 3;
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct QueryToken {
     token: String,
+    /// The room to connect to; the user MUST have joined it first cf `api_room::join_room`
+    room: i64,
 }
 
 /// The handler for the HTTP request (this gets called when the HTTP GET lands at the start
@@ -53,11 +74,29 @@ pub(crate) async fn ws_handler(
     };
     tracing::debug!("ws_handler: `{user_agent}` at {addr} connected. [token = {query_token:?}]");
 
-    // cf "impl<S> FromRequestParts<S> for Claims"
-    // Decode the token here, similar to how you would in your FromRequestParts implementation
-    // This is just a placeholder, replace it with your actual decoding logic
-    let token_data = decode::<Claims>(&query_token.token, &KEYS.decoding, &Validation::default())
-        .map_err(|_jwt_err| AppError::LoginError)?;
+    let room_id = query_token.room;
+    let db_pool = state.db_pool.clone();
+
+    // cf "impl<S> FromRequestParts<S> for Claims": the token MUST be valid, and its session NOT revoked
+    let claims = validate_token(&query_token.token, &db_pool)
+        .await
+        .map_err(|_auth_err| AppError::LoginError)?;
+
+    // The user MUST be a member of the room they want to connect to
+    let role = match get_room_member_role(&db_pool, room_id, &claims.sub).await {
+        Ok(Some(role)) => role,
+        Ok(None) => {
+            tracing::warn!(
+                "ws_handler: {} is NOT a member of room {room_id}",
+                claims.sub
+            );
+            return Err(AppError::NotFound);
+        }
+        Err(err) => {
+            tracing::error!("ws_handler: db error: {:?}", err,);
+            return Err(AppError::InternalError);
+        }
+    };
 
     // finalize the upgrade process by returning upgrade callback.
     // we can customize the callback by sending additional info such as address.
@@ -67,7 +106,7 @@ pub(crate) async fn ws_handler(
             tracing::error!("ws_handler on_failed_upgrade: error: {error}");
         })
         .on_upgrade(move |socket| {
-            let fut = handle_socket(socket, addr, state.clone(), token_data.claims.sub);
+            let fut = handle_socket(socket, addr, state.clone(), claims.sub, room_id, role);
             async move {
                 if let Err(e) = fut.await {
                     tracing::error!("Error in handle_socket: {:?}", e);
@@ -81,6 +120,8 @@ async fn handle_socket(
     addr: SocketAddr,
     state: SharedState,
     claims_sub: String,
+    room_id: i64,
+    role: Role,
 ) -> Result<Response, AppError> {
     tracing::debug!("handle_socket: protocol: {:?}", socket.protocol());
 
@@ -88,10 +129,10 @@ async fn handle_socket(
 
     if let Some("chat") = protocol {
         tracing::info!("handle_socket: chat");
-        Ok(handle_socket_chat(socket, addr, state, claims_sub).await?)
+        Ok(handle_socket_chat(socket, addr, state, claims_sub, room_id, role).await?)
     } else if let Some("geolocation") = protocol {
         tracing::info!("handle_socket: geolocation");
-        Ok(handle_socket_geolocation(socket, addr, state, claims_sub).await?)
+        Ok(handle_socket_geolocation(socket, addr, state, claims_sub, room_id, role).await?)
     } else {
         tracing::warn!("handle_socket: unsupported protocol: {:?}", protocol);
         // todo!("handle_socket: unsupported protocol")
@@ -99,109 +140,520 @@ async fn handle_socket(
     }
 }
 
+/// Serialize and send a message to all the subscribers of a room
+/// NOTE: `send` only fails when there are no subscribers at all; which is fine
+pub(crate) fn broadcast_message(broadcast_sender: &broadcast::Sender<String>, message: &WsMessage) {
+    match message.encode() {
+        Ok(text) => {
+            let _ = broadcast_sender.send(text);
+        }
+        Err(err) => tracing::error!("broadcast_message: encode error: {:?}", err),
+    }
+}
+
+/// Send a `WsMessage::Error` ONLY to the client that sent an invalid frame
+fn send_error(direct_sender: &mpsc::Sender<String>, message: String) {
+    tracing::warn!("send_error: {message}");
+    match (WsMessage::Error { message }).encode() {
+        Ok(text) => {
+            // NOTE: if the client does not read its messages; there is no point in queuing more errors
+            let _ = direct_sender.try_send(text);
+        }
+        Err(err) => tracing::error!("send_error: encode error: {:?}", err),
+    }
+}
+
+/// "Spawn the first task that will receive broadcast messages and send text
+/// messages over the websocket to our client."
+/// It ALSO forwards the messages meant only for this client(eg `WsMessage::Error`)
+///
+/// For a "geolocation" socket(ie `geolocation_room` is given):
+/// - the client is pinged every `Config::heartbeat_interval`(the browsers answer automatically), cf `Heartbeat`
+/// - a client that is too slow(eg a bad network) misses the messages that no longer fit in the channel,
+///   cf `Config::broadcast_capacity`; it then skips ALL the pending messages, and receives a new `WsMessage::Snapshot`
+///   ie the last position of each user; instead of the whole history, that it could NOT keep up with anyway
+///
+/// A slow "chat" client simply skips the messages it missed. In both cases it is NOT disconnected.
+fn spawn_send_task(
+    mut sender: SplitSink<WebSocket, Message>,
+    mut broadcast_receiver: broadcast::Receiver<String>,
+    mut direct_receiver: mpsc::Receiver<String>,
+    geolocation_room: Option<(SharedState, i64)>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut heartbeat = geolocation_room.as_ref().map(|(state, _room_id)| {
+            let period = state.config.heartbeat_interval;
+            tokio::time::interval_at(Instant::now() + period, period)
+        });
+        loop {
+            let msg = tokio::select! {
+                res = broadcast_receiver.recv() => match res {
+                    Ok(msg) => Message::Text(msg),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!("send_task: the client is too slow, {skipped} messages skipped");
+                        let Some((state, room_id)) = &geolocation_room else {
+                            continue;
+                        };
+                        // NOTE: the last positions are updated BEFORE being broadcast; so the snapshot
+                        // has everything that is NOT in the new receiver
+                        broadcast_receiver = broadcast_receiver.resubscribe();
+                        match snapshot_message(state, *room_id).await {
+                            Ok(snapshot) => Message::Text(snapshot),
+                            Err(_err) => break,
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                Some(msg) = direct_receiver.recv() => Message::Text(msg),
+                () = tick(&mut heartbeat) => Message::Ping(vec![]),
+            };
+            // In any websocket error, break loop.
+            if sender.send(msg).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Wait for the next tick of an optional interval; ie forever if None
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Broadcast a `WsMessage::Presence` returned by `AppState::presences`, if any
+fn broadcast_presence(broadcast_sender: &broadcast::Sender<String>, presence: Option<WsMessage>) {
+    if let Some(presence) = presence {
+        broadcast_message(broadcast_sender, &presence);
+    }
+}
+
+/// Spawn a task that persists the positions of a given connection(cf table `position`)
+/// They are batched so that the DB is NOT hit on every frame.
+/// The remaining positions are flushed when all the senders are dropped, ie when the socket is closed.
+fn spawn_positions_writer(
+    db_pool: SqlitePool,
+    room_id: i64,
+) -> (mpsc::Sender<Position>, JoinHandle<()>) {
+    let (positions_sender, mut positions_receiver) = mpsc::channel::<Position>(256);
+
+    let handle = tokio::spawn(async move {
+        let mut batch = Vec::with_capacity(POSITIONS_BATCH_SIZE);
+        // NOTE: `interval` would tick immediately; there is nothing to flush yet
+        let mut interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + POSITIONS_FLUSH_INTERVAL,
+            POSITIONS_FLUSH_INTERVAL,
+        );
+        loop {
+            let is_closed = tokio::select! {
+                res = positions_receiver.recv() => match res {
+                    Some(position) => {
+                        batch.push(position);
+                        if batch.len() < POSITIONS_BATCH_SIZE {
+                            continue;
+                        }
+                        false
+                    }
+                    None => true,
+                },
+                _ = interval.tick() => false,
+            };
+
+            if !batch.is_empty() {
+                if let Err(err) = insert_positions(&db_pool, room_id, &batch).await {
+                    tracing::error!(
+                        "positions_writer: could not persist {} positions: {:?}",
+                        batch.len(),
+                        err
+                    );
+                }
+                batch.clear();
+            }
+
+            if is_closed {
+                break;
+            }
+        }
+    });
+
+    (positions_sender, handle)
+}
+
+/// The `WsMessage::Snapshot` for a new client of a given room, ie the last known position of each user
+async fn snapshot_message(state: &SharedState, room_id: i64) -> Result<String, AppError> {
+    let positions = state.last_positions(room_id).await.map_err(|err| {
+        tracing::error!("snapshot_message: db error: {:?}", err,);
+        AppError::InternalError
+    })?;
+
+    (WsMessage::Snapshot { positions }).encode().map_err(|err| {
+        tracing::error!("snapshot_message: encode error: {:?}", err,);
+        AppError::InternalError
+    })
+}
+
+/// A valid position was received: apply the privacy settings of its user(cf `privacy.rs`); then persist it
+/// (cf `spawn_positions_writer`), keep it for the next clients(cf `WsMessage::Snapshot`), and queue it
+/// for the next fan out to the room(cf `throttle.rs`)
+async fn publish_position(
+    state: &SharedState,
+    room_id: i64,
+    room_channels: &RoomChannels,
+    positions_sender: &mpsc::Sender<Position>,
+    position: Position,
+) {
+    let privacy = match state.privacy(room_id, &position.username).await {
+        Ok(Some(privacy)) => privacy,
+        // eg they left the room with this socket still open
+        Ok(None) => {
+            tracing::warn!(
+                "publish_position: {} is NOT a member of room {room_id}: position dropped",
+                position.username
+            );
+            return;
+        }
+        Err(err) => {
+            // NOTE: when in doubt, DO NOT share
+            tracing::error!("publish_position: db error: {:?}", err);
+            return;
+        }
+    };
+    let Some(mut position) = privacy.apply(position) else {
+        tracing::debug!("publish_position: {privacy:?}: position dropped");
+        return;
+    };
+    track_route(state, room_id, room_channels, &mut position).await;
+
+    if positions_sender.send(position.clone()).await.is_err() {
+        tracing::error!("publish_position: positions writer is gone");
+    }
+    let presence = state
+        .presences
+        .activity(room_id, &position.username, now_timestamp());
+    state.update_last_position(room_id, position.clone());
+    room_channels.pending_positions.push(position.clone());
+    broadcast_presence(&room_channels.location_broadcast_sender, presence);
+    check_geofences(state, room_id, room_channels, &position).await;
+    check_group_spread(state, room_id, room_channels, &position).await;
+}
+
+/// When the leader or the sweeper of the room moved: check the gap between them, cf `lead.rs`
+async fn check_group_spread(
+    state: &SharedState,
+    room_id: i64,
+    room_channels: &RoomChannels,
+    position: &Position,
+) {
+    if position.progress.is_none() {
+        return;
+    }
+    let lead = match state.lead(room_id).await {
+        Ok(lead) => lead,
+        Err(err) => {
+            tracing::error!("check_group_spread: db error: {:?}", err);
+            return;
+        }
+    };
+    if !lead.contains(&position.username) {
+        return;
+    }
+    let Some(gap) = state.room_gap(room_id, &lead) else {
+        return;
+    };
+
+    if let Some(message) = state.group_spreads.check(
+        room_id,
+        gap,
+        state.config.group_spread_threshold,
+        position.timestamp,
+    ) {
+        broadcast_message(&room_channels.location_broadcast_sender, &message);
+    }
+}
+
+/// Check a shared position against the geofences of the room; then store and broadcast the enter/exit events, if any
+/// NOTE: the events are broadcast right away, NOT coalesced like the positions
+async fn check_geofences(
+    state: &SharedState,
+    room_id: i64,
+    room_channels: &RoomChannels,
+    position: &Position,
+) {
+    let events = match state
+        .geofences
+        .check(&state.db_pool, room_id, position)
+        .await
+    {
+        Ok(events) if events.is_empty() => return,
+        Ok(events) => events,
+        Err(err) => {
+            tracing::error!("check_geofences: db error: {:?}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = insert_geofence_events(&state.db_pool, room_id, &events).await {
+        tracing::error!("check_geofences: db error: {:?}", err);
+    }
+    for event in events {
+        broadcast_message(
+            &room_channels.location_broadcast_sender,
+            &event.to_message(),
+        );
+    }
+}
+
+/// Project a shared position on the active route of the room, if any: set its progress(cf `progress.rs`);
+/// and broadcast whether they went off route, if it changed(cf `off_route.rs`)
+/// NOTE: the progress sent by the client, if any, is ALWAYS overwritten
+async fn track_route(
+    state: &SharedState,
+    room_id: i64,
+    room_channels: &RoomChannels,
+    position: &mut Position,
+) {
+    position.progress = None;
+    let route_line = match state.room_route_line(room_id).await {
+        Ok(route_line) => route_line,
+        Err(err) => {
+            tracing::error!("track_route: db error: {:?}", err);
+            return;
+        }
+    };
+    let Some(projection) = route_line.project((position.lat, position.lng)) else {
+        return;
+    };
+
+    position.progress = Some(
+        state
+            .progresses
+            .update(room_id, position, &route_line, projection),
+    );
+    if let Some(message) = state.off_route.check(
+        room_id,
+        position,
+        projection.distance_to,
+        state.config.off_route_threshold,
+    ) {
+        broadcast_message(&room_channels.location_broadcast_sender, &message);
+    }
+}
+
+/// Count a new frame, cf `RateLimiter`; and warn the client when its frames start being dropped
+/// returns: Break if the client MUST be disconnected; else whether the frame MUST be dropped
+fn rate_limit(
+    rate_limiter: &mut RateLimiter,
+    direct_sender: &mpsc::Sender<String>,
+    username: &str,
+) -> ControlFlow<(), bool> {
+    match rate_limiter.check(Instant::now()) {
+        RateLimit::Allowed => ControlFlow::Continue(false),
+        RateLimit::Dropped { is_first } => {
+            if is_first {
+                send_error(
+                    direct_sender,
+                    "too many messages: some of them are dropped".to_string(),
+                );
+            }
+            ControlFlow::Continue(true)
+        }
+        RateLimit::Exceeded => {
+            tracing::warn!("rate_limit: {username} is sending way too fast; disconnecting");
+            ControlFlow::Break(())
+        }
+    }
+}
+
+/// The receiving side of the heartbeats of a "geolocation" socket(the pings are sent by `spawn_send_task`)
+struct Heartbeat {
+    interval: Interval,
+    /// when the last frame(including the pongs) was received
+    last_frame: Instant,
+}
+
+impl Heartbeat {
+    fn new(period: Duration) -> Self {
+        Self {
+            // NOTE: `interval` would tick immediately
+            interval: tokio::time::interval_at(Instant::now() + period, period),
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Wait for the next frame; meanwhile at each heartbeat, update the presence of the user(cf `Presences::check_idle`)
+    /// returns: None if the socket was closed; or if nothing was received for `HEARTBEAT_MISSED_MAX` heartbeats,
+    /// eg a phone that lost its network(the socket would NOT be closed until the TCP timeout)
+    async fn next_frame(
+        &mut self,
+        receiver: &mut SplitStream<WebSocket>,
+        state: &SharedState,
+        room_id: i64,
+        username: &str,
+        broadcast_sender: &broadcast::Sender<String>,
+    ) -> Option<Message> {
+        loop {
+            tokio::select! {
+                msg = receiver.next() => {
+                    let msg = msg?.ok()?;
+                    self.last_frame = Instant::now();
+                    state.presences.seen(room_id, username, now_timestamp());
+                    return Some(msg);
+                }
+                _ = self.interval.tick() => {
+                    if self.last_frame.elapsed() > state.config.heartbeat_interval * HEARTBEAT_MISSED_MAX {
+                        tracing::warn!("next_frame: {username} stopped answering the heartbeats");
+                        return None;
+                    }
+                    broadcast_presence(
+                        broadcast_sender,
+                        state.presences.check_idle(room_id, username, now_timestamp(), state.config.idle_timeout),
+                    );
+                }
+            }
+        }
+    }
+}
+
 /// `https://github.com/tokio-rs/axum/blob/9ebd105d0410dcb8a4133374c32415b5a6950371/examples/chat/src/main.rs#L72C44-L72C59`
 /// Actual websocket statemachine (one will be spawned per connection)
+/// A `Role::Viewer` receives the messages, but can NOT send any.
 async fn handle_socket_chat(
     socket: WebSocket,
     who: SocketAddr,
     state: SharedState,
     claims_sub: String,
+    room_id: i64,
+    role: Role,
 ) -> Result<Response, AppError> {
     tracing::debug!(
-        "handle_socket_chat: protocol: {:?}, who: {who:?}",
+        "handle_socket_chat: protocol: {:?}, who: {who:?}, room: {room_id}",
         socket.protocol()
     );
 
     // "By splitting, we can send and receive at the same time."
-    let (mut sender, mut receiver) = socket.split();
+    let (sender, mut receiver) = socket.split();
 
     // Username is extracted from Auth header(or query param token in this case)
     let username = claims_sub;
 
+    // "Clone things we want to pass (move) to the receiving task."
+    let chat_broadcast_sender = state.room_channels(room_id).chat_broadcast_sender;
+
     // "We subscribe *before* sending the "joined" message, so that we will also
     // display it to our client."
-    let mut rx = state
-        .write()
-        .map_err(|err| {
-            tracing::error!("handle_socket_chat: state write lock error: {:?}", err,);
-            AppError::InternalError
-        })?
-        .chat_broadcast_sender
-        .subscribe();
+    let rx = chat_broadcast_sender.subscribe();
+    let disconnect_receiver = state.room_channels(room_id).disconnect_sender.subscribe();
 
     // Now send the "joined" message to all subscribers.
-    let msg = format!("{username} joined.");
-    tracing::debug!("{msg}");
-    let _ = state
-        .write()
-        .map_err(|err| {
-            tracing::error!("handle_socket_chat: state write lock error: {:?}", err,);
-            AppError::InternalError
-        })?
-        .chat_broadcast_sender
-        .send(msg);
-
-    // "Spawn the first task that will receive broadcast messages and send text
-    // messages over the websocket to our client."
-    let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            // In any websocket error, break loop.
-            if sender.send(Message::Text(msg)).await.is_err() {
-                break;
-            }
-        }
-    });
+    tracing::debug!("{username} joined room {room_id}");
+    broadcast_message(
+        &chat_broadcast_sender,
+        &WsMessage::Join {
+            username: username.clone(),
+        },
+    );
 
-    // "Clone things we want to pass (move) to the receiving task."
-    let chat_broadcast_sender = state
-        .write()
-        .map_err(|err| {
-            tracing::error!("handle_socket_chat: state write lock error: {:?}", err,);
-            AppError::InternalError
-        })?
-        .chat_broadcast_sender
-        .clone();
-
-    // "Spawn a task that takes messages from the websocket, prepends the user
+    let (direct_sender, direct_receiver) = mpsc::channel(16);
+    let send_task = spawn_send_task(sender, rx, direct_receiver, None);
+
+    // "Spawn a task that takes messages from the websocket, validates them, sets the user
     // name, and sends them to all broadcast subscribers."
     let username_copy = username.clone();
-    let mut recv_task = tokio::spawn(async move {
-        while let Some(Ok(Message::Text(text))) = receiver.next().await {
-            // Add username before message.
-            let _ = chat_broadcast_sender.send(format!("{username_copy}: {text}"));
+    let chat_broadcast_sender_copy = chat_broadcast_sender.clone();
+    let recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = receiver.next().await {
+            let text = match msg {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                // Ping/Pong are handled by axum; and we do not use Binary
+                _ => continue,
+            };
+
+            match WsMessage::decode(&text) {
+                Ok(WsMessage::Chat { .. }) if role == Role::Viewer => {
+                    send_error(&direct_sender, "viewers can NOT send messages".to_string());
+                }
+                Ok(WsMessage::Chat { text, .. }) => {
+                    if text.trim().is_empty() || text.len() > MAX_CHAT_LENGTH {
+                        send_error(
+                            &direct_sender,
+                            format!("chat messages MUST be between 1 and {MAX_CHAT_LENGTH} bytes"),
+                        );
+                        continue;
+                    }
+                    broadcast_message(
+                        &chat_broadcast_sender_copy,
+                        &WsMessage::Chat {
+                            username: username_copy.clone(),
+                            text,
+                        },
+                    );
+                }
+                Ok(other) => send_error(
+                    &direct_sender,
+                    format!("unexpected message on the chat socket: {other:?}"),
+                ),
+                Err(err) => send_error(&direct_sender, err.to_string()),
+            }
         }
     });
 
-    // "If any one of the tasks run to completion, we abort the other."
-    tokio::select! {
-        _ = (&mut send_task) => recv_task.abort(),
-        _ = (&mut recv_task) => send_task.abort(),
-    };
+    join_socket_tasks(send_task, recv_task, disconnect_receiver, &username).await;
 
     // "Send "user left" message (similar to "joined" above)."
-    let msg = format!("{username} left.");
-    tracing::debug!("{msg}");
-    let _ = state
-        .write()
-        .map_err(|err| {
-            tracing::error!("handle_socket_chat: state write lock error: {:?}", err,);
-            AppError::InternalError
-        })?
-        .chat_broadcast_sender
-        .send(msg);
+    tracing::debug!("{username} left room {room_id}");
+    broadcast_message(&chat_broadcast_sender, &WsMessage::Leave { username });
 
     Ok(Response::new(Body::empty()))
 }
 
-///
+/// "If any one of the tasks run to completion, we abort the other."
+/// And both when the sockets of the user MUST be closed, cf `AppState::disconnect_member`: dropping both halves
+/// closes the socket
+/// NOTE: if some disconnections were missed, the socket is closed anyway: the membership is checked again
+/// when the client reconnects
+async fn join_socket_tasks(
+    mut send_task: JoinHandle<()>,
+    mut recv_task: JoinHandle<()>,
+    mut disconnect_receiver: broadcast::Receiver<String>,
+    username: &str,
+) {
+    let disconnected = async {
+        loop {
+            match disconnect_receiver.recv().await {
+                Ok(disconnected) if disconnected == username => return,
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => return,
+                Err(RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+        () = disconnected => {
+            recv_task.abort();
+            send_task.abort();
+        }
+    };
+}
+
+/// Same state machine as `handle_socket_chat`, but for `WsMessage::Position`
 async fn handle_socket_geolocation(
     socket: WebSocket,
     who: SocketAddr,
     state: SharedState,
     claims_sub: String,
+    room_id: i64,
+    role: Role,
 ) -> Result<Response, AppError> {
     tracing::debug!(
-        "handle_socket_geolocation: protocol: {:?}, who: {who:?}",
+        "handle_socket_geolocation: protocol: {:?}, who: {who:?}, room: {room_id}",
         socket.protocol()
     );
 
@@ -211,89 +663,114 @@ async fn handle_socket_geolocation(
     // Username is extracted from Auth header(or query param token in this case)
     let username = claims_sub;
 
+    // "Clone things we want to pass (move) to the receiving task."
+    let room_channels = state.room_channels(room_id);
+    let location_broadcast_sender = room_channels.location_broadcast_sender.clone();
+    let db_pool = state.db_pool.clone();
+
     // "We subscribe *before* sending the "joined" message, so that we will also
     // display it to our client."
-    let mut rx = state
-        .write()
-        .map_err(|err| {
-            tracing::error!(
-                "handle_socket_geolocation: state write lock error 0: {:?}",
-                err,
-            );
-            AppError::InternalError
-        })?
-        .location_broadcast_sender
-        .subscribe();
+    let rx = location_broadcast_sender.subscribe();
+    let disconnect_receiver = room_channels.disconnect_sender.subscribe();
+
+    // The snapshot is sent *after* subscribing: a position sent in between may be received twice,
+    // but none is missed.
+    if let Err(err) = sender
+        .send(Message::Text(snapshot_message(&state, room_id).await?))
+        .await
+    {
+        tracing::warn!("handle_socket_geolocation: could not send the snapshot: {err:?}");
+        return Ok(Response::new(Body::empty()));
+    }
 
     // Now send the "joined" message to all subscribers.
-    let msg = format!("{username} joined.");
-    tracing::debug!("{msg}");
-    let _ = state
-        .write()
-        .map_err(|err| {
-            tracing::error!(
-                "handle_socket_geolocation: state write lock error: {:?}",
-                err,
-            );
-            AppError::InternalError
-        })?
-        .location_broadcast_sender
-        .send(msg);
-
-    // "Spawn the first task that will receive broadcast messages and send text
-    // messages over the websocket to our client."
-    let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            // In any websocket error, break loop.
-            if sender.send(Message::Text(msg)).await.is_err() {
-                break;
-            }
-        }
-    });
+    tracing::debug!("{username} joined room {room_id}");
+    broadcast_message(
+        &location_broadcast_sender,
+        &WsMessage::Join {
+            username: username.clone(),
+        },
+    );
+    broadcast_presence(
+        &location_broadcast_sender,
+        state.presences.connect(room_id, &username, now_timestamp()),
+    );
 
-    // "Clone things we want to pass (move) to the receiving task."
-    let location_broadcast_sender = state
-        .write()
-        .map_err(|err| {
-            tracing::error!(
-                "handle_socket_geolocation: state write lock error 1: {:?}",
-                err,
-            );
-            AppError::InternalError
-        })?
-        .location_broadcast_sender
-        .clone();
+    let (direct_sender, direct_receiver) = mpsc::channel(16);
+    let send_task = spawn_send_task(sender, rx, direct_receiver, Some((state.clone(), room_id)));
+    let (positions_sender, positions_writer) = spawn_positions_writer(db_pool, room_id);
 
-    // "Spawn a task that takes messages from the websocket, prepends the user
+    // "Spawn a task that takes messages from the websocket, validates them, sets the user
     // name, and sends them to all broadcast subscribers."
     let username_copy = username.clone();
-    let mut recv_task = tokio::spawn(async move {
-        while let Some(Ok(Message::Text(text))) = receiver.next().await {
-            // Add username before message.
-            let _ = location_broadcast_sender.send(format!("{username_copy}: {text}"));
+    let state_copy = state.clone();
+    let recv_task = tokio::spawn(async move {
+        let mut heartbeat = Heartbeat::new(state_copy.config.heartbeat_interval);
+        let mut rate_limiter = RateLimiter::new(state_copy.config.position_rate_limit);
+        while let Some(msg) = heartbeat
+            .next_frame(
+                &mut receiver,
+                &state_copy,
+                room_id,
+                &username_copy,
+                &room_channels.location_broadcast_sender,
+            )
+            .await
+        {
+            let text = match msg {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                // Ping/Pong are handled by axum; and we do not use Binary
+                _ => continue,
+            };
+            match rate_limit(&mut rate_limiter, &direct_sender, &username_copy) {
+                ControlFlow::Break(()) => break,
+                ControlFlow::Continue(true) => continue,
+                ControlFlow::Continue(false) => {}
+            }
+
+            match WsMessage::decode(&text) {
+                Ok(WsMessage::Position(_position)) if role == Role::Viewer => {
+                    send_error(&direct_sender, "viewers can NOT send positions".to_string());
+                }
+                Ok(WsMessage::Position(mut position)) => {
+                    if let Err(err) = position.validate() {
+                        send_error(&direct_sender, err);
+                        continue;
+                    }
+                    position.username.clone_from(&username_copy);
+                    publish_position(
+                        &state_copy,
+                        room_id,
+                        &room_channels,
+                        &positions_sender,
+                        position,
+                    )
+                    .await;
+                }
+                Ok(other) => send_error(
+                    &direct_sender,
+                    format!("unexpected message on the geolocation socket: {other:?}"),
+                ),
+                Err(err) => send_error(&direct_sender, err.to_string()),
+            }
         }
     });
 
-    // "If any one of the tasks run to completion, we abort the other."
-    tokio::select! {
-        _ = (&mut send_task) => recv_task.abort(),
-        _ = (&mut recv_task) => send_task.abort(),
-    };
+    join_socket_tasks(send_task, recv_task, disconnect_receiver, &username).await;
 
     // "Send "user left" message (similar to "joined" above)."
-    let msg = format!("{username} left.");
-    tracing::debug!("{msg}");
-    let _ = state
-        .write()
-        .map_err(|err| {
-            tracing::error!(
-                "handle_socket_geolocation: state write lock error 2: {:?}",
-                err,
-            );
-            AppError::InternalError
-        })?
-        .location_broadcast_sender
-        .send(msg);
+    tracing::debug!("{username} left room {room_id}");
+    let presence = state
+        .presences
+        .disconnect(room_id, &username, now_timestamp());
+    broadcast_presence(&location_broadcast_sender, presence);
+    broadcast_message(&location_broadcast_sender, &WsMessage::Leave { username });
+
+    // The positions sender was owned by `recv_task`, so this will flush the last batch
+    if let Err(err) = positions_writer.await {
+        tracing::error!("handle_socket_geolocation: positions writer error: {err:?}");
+    }
 
     Ok(Response::new(Body::empty()))
 }
@@ -335,43 +812,80 @@ async fn handle_socket_geolocation(
 /// cf https://github.com/tokio-rs/axum/blob/main/examples/testing-websockets/src/main.rs
 #[cfg(test)]
 mod tests {
-    use crate::{db::setup_db, new_app};
+    use crate::{
+        db::{
+            add_room_member, insert_geofence, insert_room, insert_route, list_positions_from_db,
+            set_room_active_route, set_room_lead, setup_db,
+        },
+        geofence::GeofenceShape,
+        lead::Lead,
+        new_app_with_state,
+        privacy::Privacy,
+        role::Role,
+        route_formats::RouteFormat,
+        route_stats::RouteStats,
+        state::{new_state, Config},
+    };
 
     use super::*;
 
     use axum_test::http::Request;
     use base64::Engine;
+    use protocol::{GeofenceTransition, SharingMode};
     use rand::Rng;
+    use sqlx::SqlitePool;
     use std::{
         future::IntoFuture,
         net::{Ipv4Addr, SocketAddr},
     };
     use tokio_tungstenite::tungstenite::{self};
 
-    async fn setup(websocket_protocol: &str, username: &str) -> Request<()> {
+    /// Start a server on a random port
+    async fn setup_server() -> (SocketAddr, SqlitePool) {
+        setup_server_with_config(Config::default()).await
+    }
+
+    async fn setup_server_with_config(config: Config) -> (SocketAddr, SqlitePool) {
+        let (addr, state) = setup_server_with_state(config).await;
+
+        (addr, state.db_pool.clone())
+    }
+
+    /// Same as `setup_server_with_config`; but keep a handle on the `AppState`
+    async fn setup_server_with_state(config: Config) -> (SocketAddr, SharedState) {
         let listener = tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))
             .await
             .unwrap();
         let addr = listener.local_addr().unwrap();
         let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = new_state(db_pool, config);
         tokio::spawn(
             axum::serve(
                 listener,
-                new_app(db_pool)
-                    .unwrap()
+                new_app_with_state(state.clone())
                     .into_make_service_with_connect_info::<SocketAddr>(),
             )
             .into_future(),
         );
 
+        (addr, state)
+    }
+
+    async fn new_ws_request(
+        addr: SocketAddr,
+        db_pool: &SqlitePool,
+        websocket_protocol: &str,
+        username: &str,
+        room_id: i64,
+    ) -> Request<()> {
         // "Generate a random 16-byte nonce"
         let nonce: [u8; 16] = rand::thread_rng().gen();
         let sec_websocket_key = base64::engine::general_purpose::STANDARD.encode(&nonce);
 
-        let token = crate::api_authorize_jwt::tests::generate_token(username);
+        let token = crate::api_authorize_jwt::tests::generate_token(db_pool, username).await;
 
         let request = Request::builder()
-            .uri(format!("ws://{addr}/ws?token={token}"))
+            .uri(format!("ws://{addr}/ws?token={token}&room={room_id}"))
             .header("Host", "127.0.0.1")
             .header("Upgrade", "websocket")
             .header("Connection", "Upgrade")
@@ -393,68 +907,1185 @@ mod tests {
         request
     }
 
-    #[tokio::test]
-    async fn test_handle_socket_chat() {
-        let username = "aaa";
+    /// Start a server, create a room and make `username` join it
+    async fn setup(websocket_protocol: &str, username: &str) -> Request<()> {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", username).await.unwrap();
 
-        let request = setup("chat", username).await;
+        new_ws_request(addr, &db_pool, websocket_protocol, username, room.id).await
+    }
+
+    type TestSocket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Read the next frame, and parse it
+    /// NOTE: the pings and the `WsMessage::Presence` are skipped; cf `test_handle_socket_geolocation_presence`
+    async fn recv_message(socket: &mut TestSocket) -> WsMessage {
+        loop {
+            match socket.next().await.unwrap().unwrap() {
+                tungstenite::Message::Text(msg) => match WsMessage::decode(&msg).unwrap() {
+                    WsMessage::Presence { .. } => {}
+                    msg => return msg,
+                },
+                tungstenite::Message::Ping(_) => {}
+                other => panic!("expected a text message but got {other:?}"),
+            }
+        }
+    }
+
+    /// Connect to the "geolocation" socket; and read the snapshot, ie the first frame
+    async fn connect_geolocation(request: Request<()>) -> (TestSocket, Vec<Position>) {
         let (mut socket, _response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        match recv_message(&mut socket).await {
+            WsMessage::Snapshot { positions } => (socket, positions),
+            other => panic!("expected a snapshot but got {other:?}"),
+        }
+    }
+
+    /// Read the next `WsMessage::Positions`, ie the next fan out(cf `throttle.rs`); skipping the joins and leaves
+    async fn recv_positions(socket: &mut TestSocket) -> Vec<Position> {
+        loop {
+            match recv_message(socket).await {
+                WsMessage::Positions { positions } => return positions,
+                WsMessage::Join { .. } | WsMessage::Leave { .. } => {}
+                other => panic!("expected positions but got {other:?}"),
+            }
+        }
+    }
 
+    async fn send_message(socket: &mut TestSocket, message: &WsMessage) {
         socket
-            .send(tungstenite::Message::Text("hello world".to_string()))
+            .send(tungstenite::Message::Text(message.encode().unwrap()))
             .await
             .expect("Failed to write WebSocket request");
+    }
 
-        let msg = match socket.next().await.unwrap().unwrap() {
-            tungstenite::Message::Text(msg) => msg,
-            other => panic!("expected a text message but got {other:?}"),
-        };
+    fn new_position(lat: f64, lng: f64) -> protocol::Position {
+        protocol::Position {
+            username: String::new(),
+            lat,
+            lng,
+            accuracy: Some(10.0),
+            altitude: None,
+            heading: None,
+            speed: None,
+            timestamp: 1_708_363_750_199,
+            progress: None,
+        }
+    }
 
-        assert_eq!(msg, format!("{username} joined."));
+    #[tokio::test]
+    async fn test_handle_socket_chat() {
+        let username = "aaa";
 
-        socket
-            .send(tungstenite::Message::Text("does not matter".to_string()))
-            .await
-            .expect("Failed to write WebSocket request");
+        let request = setup("chat", username).await;
+        let (mut socket, _response) = tokio_tungstenite::connect_async(request).await.unwrap();
 
-        let msg = match socket.next().await.unwrap().unwrap() {
-            tungstenite::Message::Text(msg) => msg,
-            other => panic!("expected a text message but got {other:?}"),
-        };
+        assert_eq!(
+            recv_message(&mut socket).await,
+            WsMessage::Join {
+                username: username.to_string()
+            }
+        );
+
+        send_message(
+            &mut socket,
+            &WsMessage::Chat {
+                // MUST be overwritten by the server
+                username: "someone else".to_string(),
+                text: "hello world".to_string(),
+            },
+        )
+        .await;
 
-        assert_eq!(msg, format!("{username}: hello world"));
+        assert_eq!(
+            recv_message(&mut socket).await,
+            WsMessage::Chat {
+                username: username.to_string(),
+                text: "hello world".to_string(),
+            }
+        );
     }
 
-    // NOTE: for now the logic is exactly the same as `handle_socket_chat`
     #[tokio::test]
     async fn test_handle_socket_geolocation() {
         let username = "aaa";
 
         let request = setup("geolocation", username).await;
-        let (mut socket, _response) = tokio_tungstenite::connect_async(request).await.unwrap();
+        let (mut socket, snapshot) = connect_geolocation(request).await;
+        assert_eq!(snapshot, vec![]);
 
-        socket
-            .send(tungstenite::Message::Text("hello world".to_string()))
+        assert_eq!(
+            recv_message(&mut socket).await,
+            WsMessage::Join {
+                username: username.to_string()
+            }
+        );
+
+        send_message(
+            &mut socket,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+
+        let mut expected = new_position(48.8354, 2.3203);
+        expected.username = username.to_string();
+        assert_eq!(recv_positions(&mut socket).await, vec![expected]);
+    }
+
+    /// Malformed or invalid frames MUST be rejected with an error frame; and NOT rebroadcast
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_malformed_messages_are_rejected() {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
             .await
-            .expect("Failed to write WebSocket request");
+            .unwrap();
+
+        let (mut socket_bbb, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+        recv_message(&mut socket_bbb).await;
+        let (mut socket, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        recv_message(&mut socket).await;
+        recv_message(&mut socket_bbb).await;
+
+        for invalid in [
+            // the old "lat,lng" format
+            "48.8354,2.3203".to_string(),
+            r#"{"v":2,"type":"position","lat":"abc"}"#.to_string(),
+            r#"{"v":42,"type":"position","lat":48.8,"lng":2.3,"timestamp":1}"#.to_string(),
+            WsMessage::Position(new_position(123.0, 2.3203))
+                .encode()
+                .unwrap(),
+            WsMessage::Chat {
+                username: String::new(),
+                text: "not on this socket".to_string(),
+            }
+            .encode()
+            .unwrap(),
+        ] {
+            socket
+                .send(tungstenite::Message::Text(invalid.clone()))
+                .await
+                .unwrap();
+
+            match recv_message(&mut socket).await {
+                WsMessage::Error { message } => assert!(!message.is_empty()),
+                other => panic!("expected an error for {invalid} but got {other:?}"),
+            }
+        }
+
+        // none of them were broadcast
+        let res = tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            recv_message(&mut socket_bbb),
+        )
+        .await;
+        assert!(res.is_err(), "bbb received a message: {res:?}");
+    }
+
+    /// A member that leaves the room MUST NOT receive its positions anymore
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_closed_on_leave() {
+        let (addr, state) = setup_server_with_state(Config::default()).await;
+        let db_pool = state.db_pool.clone();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+
+        let (mut socket_bbb, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+        let (mut socket, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
 
-        let msg = match socket.next().await.unwrap().unwrap() {
-            tungstenite::Message::Text(msg) => msg,
-            other => panic!("expected a text message but got {other:?}"),
+        let token = crate::api_authorize_jwt::tests::generate_token(&db_pool, "bbb").await;
+        let response = tower::util::ServiceExt::oneshot(
+            new_app_with_state(state.clone()),
+            Request::builder()
+                .uri(format!("/api/rooms/{}/leave", room.id))
+                .method(axum::http::Method::POST)
+                .header(axum::http::header::AUTHORIZATION, format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+
+        send_message(
+            &mut socket,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+        recv_positions(&mut socket).await;
+
+        // only the frames sent before the leave; then the socket is closed
+        let res = tokio::time::timeout(std::time::Duration::from_secs(2), async {
+            loop {
+                match socket_bbb.next().await {
+                    Some(Ok(tungstenite::Message::Text(msg))) => {
+                        if let Ok(WsMessage::Positions { .. }) = WsMessage::decode(&msg) {
+                            panic!("bbb received positions after leaving: {msg}");
+                        }
+                    }
+                    Some(Ok(tungstenite::Message::Ping(_))) => {}
+                    Some(Ok(tungstenite::Message::Close(_)) | Err(_)) | None => return,
+                    Some(Ok(other)) => panic!("unexpected frame {other:?}"),
+                }
+            }
+        })
+        .await;
+        assert!(res.is_ok(), "the socket of bbb is still open");
+    }
+
+    #[tokio::test]
+    async fn test_handle_socket_not_a_room_member_should_fail() {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", "root").await.unwrap();
+
+        let request = new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await;
+        let res = tokio_tungstenite::connect_async(request).await;
+
+        match res {
+            Err(tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), 404);
+            }
+            other => panic!("expected a HTTP 404 error but got {other:?}"),
+        }
+    }
+
+    /// A viewer receives the positions of the others; but can NOT send theirs
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_viewer_can_not_send_positions() {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "viewer", Role::Viewer)
+            .await
+            .unwrap();
+
+        let request = new_ws_request(addr, &db_pool, "geolocation", "viewer", room.id).await;
+        let (mut socket_viewer, _snapshot) = connect_geolocation(request).await;
+        recv_message(&mut socket_viewer).await;
+        let request = new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await;
+        let (mut socket, _snapshot) = connect_geolocation(request).await;
+        recv_message(&mut socket).await;
+        recv_message(&mut socket_viewer).await;
+
+        send_message(
+            &mut socket_viewer,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+        match recv_message(&mut socket_viewer).await {
+            WsMessage::Error { message } => assert!(message.contains("viewers")),
+            other => panic!("expected an error but got {other:?}"),
+        }
+
+        send_message(
+            &mut socket,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+        assert_eq!(recv_positions(&mut socket_viewer).await[0].username, "aaa");
+    }
+
+    /// A token whose session was revoked(ie logout) MUST be rejected
+    #[tokio::test]
+    async fn test_handle_socket_revoked_session_should_fail() {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        let request = new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await;
+        sqlx::query("UPDATE session SET revoked_at = 1")
+            .execute(&db_pool)
+            .await
+            .unwrap();
+        let res = tokio_tungstenite::connect_async(request).await;
+
+        match res {
+            Err(tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), 400);
+            }
+            other => panic!("expected a HTTP 400 error but got {other:?}"),
+        }
+    }
+
+    /// A user in one room MUST NEVER receive positions from another room
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_rooms_are_isolated() {
+        let (addr, db_pool) = setup_server().await;
+        let room1 = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let room2 = insert_room(&db_pool, "room2", "bbb").await.unwrap();
+        add_room_member(&db_pool, room1.id, "ccc", Role::Member)
+            .await
+            .unwrap();
+
+        // connect one by one and wait for each "joined" message: that way we know everyone is subscribed
+        let mut sockets = vec![];
+        for (username, room_id) in [("bbb", room2.id), ("aaa", room1.id), ("ccc", room1.id)] {
+            let request = new_ws_request(addr, &db_pool, "geolocation", username, room_id).await;
+            let (mut socket, _snapshot) = connect_geolocation(request).await;
+            assert_eq!(
+                recv_message(&mut socket).await,
+                WsMessage::Join {
+                    username: username.to_string()
+                }
+            );
+            sockets.push(socket);
+        }
+        let [mut socket_room2, mut socket_room1, mut socket_room1_bis]: [_; 3] =
+            sockets.try_into().unwrap();
+        // "ccc joined" was also sent to "aaa"
+        recv_message(&mut socket_room1).await;
+
+        send_message(
+            &mut socket_room1,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+
+        // the other member of room1 MUST receive it
+        assert_eq!(
+            recv_positions(&mut socket_room1_bis).await[0].username,
+            "aaa"
+        );
+
+        // but NOT the member of room2
+        let res = tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            recv_message(&mut socket_room2),
+        )
+        .await;
+        assert!(res.is_err(), "room2 received a message: {res:?}");
+    }
+
+    /// The positions MUST be persisted; at the latest when the socket is closed
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_positions_are_persisted() {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+
+        let (mut socket, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        recv_message(&mut socket).await;
+
+        for i in 0..3 {
+            let mut position = new_position(48.8354, 2.3203);
+            position.timestamp += i;
+            send_message(&mut socket, &WsMessage::Position(position)).await;
+            recv_message(&mut socket).await;
+        }
+        socket.close(None).await.unwrap();
+
+        let mut positions = vec![];
+        for _ in 0..50 {
+            positions = list_positions_from_db(&db_pool, "aaa", None, None)
+                .await
+                .unwrap();
+            if positions.len() == 3 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(positions.len(), 3);
+        for (room_id, position) in positions {
+            assert_eq!(room_id, room.id);
+            assert_eq!(position.username, "aaa");
+        }
+    }
+
+    /// Load test: many geolocation sockets, in several rooms, all sending positions at the same time.
+    /// Each room has an active route, already in `room_route_lines`; and the route cache is write-locked
+    /// during the whole test: the positions go through the route state(cf `publish_position`) but MUST NOT
+    /// need the cache; and since there is no other global lock, nothing serializes the rooms.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_handle_socket_geolocation_many_sockets_do_not_contend() {
+        const ROOMS: usize = 4;
+        const SOCKETS_PER_ROOM: usize = 8;
+        const POSITIONS_PER_SOCKET: usize = 5;
+
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let db_pool = setup_db("sqlite::memory:", None, None).await.unwrap();
+        let state = new_state(db_pool.clone(), Config::default());
+        tokio::spawn(
+            axum::serve(
+                listener,
+                new_app_with_state(state.clone())
+                    .into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .into_future(),
+        );
+
+        let mut requests = vec![];
+        for room_index in 0..ROOMS {
+            let room = insert_room(&db_pool, &format!("room{room_index}"), "root")
+                .await
+                .unwrap();
+            let route = insert_route(
+                &db_pool,
+                &format!("route{room_index}"),
+                "root",
+                b"<gpx></gpx>",
+                RouteFormat::Gpx,
+                r#"{"type":"LineString","coordinates":[[2.3203,48.83],[2.3203,48.84]]}"#,
+                &RouteStats {
+                    distance: 1_100.0,
+                    bbox: [2.3203, 48.83, 2.3203, 48.84],
+                    ..RouteStats::default()
+                },
+            )
+            .await
+            .unwrap();
+            set_room_active_route(&db_pool, room.id, Some(route.id))
+                .await
+                .unwrap();
+            assert!(!state.room_route_line(room.id).await.unwrap().is_empty());
+            for socket_index in 0..SOCKETS_PER_ROOM {
+                let username = format!("room{room_index}-user{socket_index}");
+                add_room_member(&db_pool, room.id, &username, Role::Member)
+                    .await
+                    .unwrap();
+                let request =
+                    new_ws_request(addr, &db_pool, "geolocation", &username, room.id).await;
+                requests.push((room_index, username, request));
+            }
+        }
+
+        let _route_cache_guard = state.route_geojson_cache.write().await;
+        let barrier = std::sync::Arc::new(tokio::sync::Barrier::new(requests.len()));
+        let mut tasks = vec![];
+        for (room_index, username, request) in requests {
+            let barrier = barrier.clone();
+            tasks.push(tokio::spawn(async move {
+                let (mut socket, _response) =
+                    tokio_tungstenite::connect_async(request).await.unwrap();
+                // our own "joined" means that we are subscribed
+                while recv_message(&mut socket).await
+                    != (WsMessage::Join {
+                        username: username.clone(),
+                    })
+                {}
+                barrier.wait().await;
+
+                for i in 0..POSITIONS_PER_SOCKET {
+                    let mut position = new_position(48.8354, 2.3203);
+                    position.timestamp += i as i64;
+                    send_message(&mut socket, &WsMessage::Position(position)).await;
+                }
+
+                // the positions are coalesced(cf `throttle.rs`): wait for the last one of each user
+                let last_timestamp =
+                    new_position(0.0, 0.0).timestamp + POSITIONS_PER_SOCKET as i64 - 1;
+                let mut received = std::collections::HashSet::new();
+                while received.len() < SOCKETS_PER_ROOM {
+                    for position in recv_positions(&mut socket).await {
+                        assert!(
+                            position.username.starts_with(&format!("room{room_index}-")),
+                            "{username} received a position from another room: {position:?}"
+                        );
+                        if position.timestamp == last_timestamp {
+                            assert!(position.progress.is_some(), "{position:?}");
+                            received.insert(position.username);
+                        }
+                    }
+                }
+                received.len()
+            }));
+        }
+
+        let all_received = tokio::time::timeout(
+            std::time::Duration::from_secs(30),
+            futures::future::join_all(tasks),
+        )
+        .await
+        .expect("the sockets are stuck");
+        for received in all_received {
+            assert_eq!(received.unwrap(), SOCKETS_PER_ROOM);
+        }
+        assert_eq!(state.rooms.len(), ROOMS);
+    }
+
+    /// A client that connects MUST first receive the last known position of the others; even the
+    /// ones that already left, or that sent them before a restart(ie from the DB)
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_snapshot_on_connect() {
+        let (addr, db_pool) = setup_server().await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+        add_room_member(&db_pool, room.id, "ccc", Role::Member)
+            .await
+            .unwrap();
+        let mut position_ccc = new_position(48.8, 2.3);
+        position_ccc.username = "ccc".to_string();
+        position_ccc.timestamp = crate::db::now_timestamp() - 60_000;
+        crate::db::insert_positions(&db_pool, room.id, &[position_ccc.clone()])
+            .await
+            .unwrap();
+
+        let (mut socket, snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        assert_eq!(snapshot, vec![position_ccc.clone()]);
+        recv_message(&mut socket).await;
+        for lat in [48.0, 48.1] {
+            send_message(&mut socket, &WsMessage::Position(new_position(lat, 2.3203))).await;
+            recv_message(&mut socket).await;
+        }
+        socket.close(None).await.unwrap();
+
+        let (_socket, snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+        let mut position_aaa = new_position(48.1, 2.3203);
+        position_aaa.username = "aaa".to_string();
+        assert_eq!(snapshot, vec![position_aaa, position_ccc]);
+    }
+
+    /// online on connect; idle without positions; online again on a position; offline on disconnect
+    /// NOTE: reading the socket also answers the pings(cf tungstenite), ie the client stays connected while idle
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_presence() {
+        let (addr, db_pool) = setup_server_with_config(Config {
+            heartbeat_interval: Duration::from_millis(100),
+            idle_timeout: Duration::from_millis(300),
+            ..Default::default()
+        })
+        .await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+
+        /// Skip the pings and the other messages; and return the next `WsMessage::Presence`
+        async fn recv_presence(socket: &mut TestSocket) -> (String, protocol::PresenceStatus) {
+            loop {
+                match socket.next().await.unwrap().unwrap() {
+                    tungstenite::Message::Text(msg) => {
+                        if let WsMessage::Presence {
+                            username, status, ..
+                        } = WsMessage::decode(&msg).unwrap()
+                        {
+                            return (username, status);
+                        }
+                    }
+                    tungstenite::Message::Ping(_) => {}
+                    other => panic!("expected a text message but got {other:?}"),
+                }
+            }
+        }
+
+        // NOTE: "aaa" MUST keep reading its socket to answer the pings; else it is disconnected
+        let request_bbb = new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await;
+        let (mut socket_aaa, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        let online = ("aaa".to_string(), protocol::PresenceStatus::Online);
+        assert_eq!(recv_presence(&mut socket_aaa).await, online);
+
+        let timeout = Duration::from_secs(5);
+        let idle = ("aaa".to_string(), protocol::PresenceStatus::Idle);
+        assert_eq!(
+            tokio::time::timeout(timeout, recv_presence(&mut socket_aaa))
+                .await
+                .unwrap(),
+            idle
+        );
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.8, 2.3)),
+        )
+        .await;
+        assert_eq!(
+            tokio::time::timeout(timeout, recv_presence(&mut socket_aaa))
+                .await
+                .unwrap(),
+            online
+        );
+
+        let (mut socket_bbb, _snapshot) = connect_geolocation(request_bbb).await;
+        socket_bbb.close(None).await.unwrap();
+        // NOTE: "aaa" may be idle again in the meantime
+        let presence = tokio::time::timeout(timeout, async {
+            let mut presence = vec![];
+            while presence.len() < 2 {
+                let (username, status) = recv_presence(&mut socket_aaa).await;
+                if username == "bbb" {
+                    presence.push(status);
+                }
+            }
+            presence
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            presence,
+            vec![
+                protocol::PresenceStatus::Online,
+                protocol::PresenceStatus::Offline
+            ]
+        );
+    }
+
+    /// A client that is too slow to read its messages MUST NOT be disconnected: it skips to the last
+    /// position of each user(cf `WsMessage::Snapshot`), then receives the next messages as usual
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_slow_client_is_resynced() {
+        const USERS: i32 = 10;
+        const FLOOD: i32 = 100;
+        const BROADCAST_CAPACITY: usize = 4;
+
+        let (addr, state) = setup_server_with_state(Config {
+            broadcast_capacity: BROADCAST_CAPACITY,
+            ..Default::default()
+        })
+        .await;
+        let db_pool = state.db_pool.clone();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+
+        let (mut socket_aaa, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        let (mut socket_bbb, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+        // wait until "bbb" is subscribed
+        while recv_message(&mut socket_aaa).await
+            != (WsMessage::Join {
+                username: "bbb".to_string(),
+            })
+        {}
+
+        // flood the room, without yielding: ie none of the send tasks can keep up
+        // NOTE: same as `handle_socket_geolocation` when a position is received
+        let location_broadcast_sender = state.room_channels(room.id).location_broadcast_sender;
+        let mut expected = vec![];
+        for i in 0..FLOOD {
+            let mut position = new_position(48.0 + f64::from(i) / 100.0, 2.3);
+            position.username = format!("user{}", i % USERS);
+            position.timestamp += i64::from(i);
+            state.update_last_position(room.id, position.clone());
+            broadcast_message(
+                &location_broadcast_sender,
+                &WsMessage::Positions {
+                    positions: vec![position.clone()],
+                },
+            );
+            if i >= FLOOD - USERS {
+                expected.push(position);
+            }
+        }
+        expected.sort_by(|a, b| a.username.cmp(&b.username));
+
+        for socket in [&mut socket_aaa, &mut socket_bbb] {
+            let snapshot = tokio::time::timeout(Duration::from_secs(5), async {
+                loop {
+                    match recv_message(socket).await {
+                        WsMessage::Snapshot { positions } => return positions,
+                        // eg the messages sent before the flood
+                        WsMessage::Join { .. } => {}
+                        other => panic!("expected a snapshot but got {other:?}"),
+                    }
+                }
+            })
+            .await
+            .unwrap();
+            assert_eq!(snapshot, expected);
+        }
+
+        // ...and they are still connected
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.8, 2.3)),
+        )
+        .await;
+        for socket in [&mut socket_aaa, &mut socket_bbb] {
+            assert_eq!(recv_positions(socket).await[0].username, "aaa");
+        }
+    }
+
+    /// The positions received between two fan outs are sent in a single frame; only the last one of each user
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_positions_are_coalesced() {
+        let (addr, db_pool) = setup_server_with_config(Config {
+            fanout_interval: Duration::from_millis(500),
+            ..Default::default()
+        })
+        .await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let (mut socket, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+
+        let mut last_position = new_position(48.8354, 2.3203);
+        for i in 0..5 {
+            last_position = new_position(48.8354 + f64::from(i) / 1000.0, 2.3203);
+            last_position.timestamp += i64::from(i);
+            send_message(&mut socket, &WsMessage::Position(last_position.clone())).await;
+        }
+        last_position.username = "aaa".to_string();
+
+        // NOTE: the positions may straddle two fan outs
+        let mut frames = 0;
+        while recv_positions(&mut socket).await != vec![last_position.clone()] {
+            frames += 1;
+        }
+        assert!(frames <= 1, "{frames} frames before the last position");
+    }
+
+    /// A client that sends too fast: the extra frames are dropped; and way too fast: it is disconnected
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_rate_limit() {
+        let (addr, db_pool) = setup_server_with_config(Config {
+            position_rate_limit: 2,
+            ..Default::default()
+        })
+        .await;
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let (mut socket, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+
+        recv_message(&mut socket).await;
+        for _ in 0..3 {
+            send_message(&mut socket, &WsMessage::Position(new_position(48.8, 2.3))).await;
+        }
+        loop {
+            match recv_message(&mut socket).await {
+                WsMessage::Error { message } => {
+                    assert!(message.contains("too many"));
+                    break;
+                }
+                WsMessage::Positions { .. } => {}
+                other => panic!("expected an error but got {other:?}"),
+            }
+        }
+
+        for _ in 0..(2 * crate::throttle::RATE_LIMIT_DISCONNECT_FACTOR) {
+            // NOTE: the socket may already be closed
+            let _ = socket
+                .send(tungstenite::Message::Text(
+                    WsMessage::Position(new_position(48.8, 2.3))
+                        .encode()
+                        .unwrap(),
+                ))
+                .await;
+        }
+        let is_closed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match socket.next().await {
+                    None | Some(Err(_) | Ok(tungstenite::Message::Close(_))) => return true,
+                    Some(Ok(_)) => {}
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(is_closed);
+    }
+
+    /// Paused: the positions are dropped, but the others are still received; hidden: the last position is
+    /// also removed from the snapshots; and with a reduced precision, only the rounded positions are shared
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_privacy() {
+        let (addr, state) = setup_server_with_state(Config {
+            fanout_interval: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .await;
+        let db_pool = state.db_pool.clone();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        for username in ["bbb", "ccc"] {
+            add_room_member(&db_pool, room.id, username, Role::Member)
+                .await
+                .unwrap();
+        }
+        let set_privacy = |mode, precision| {
+            let state = state.clone();
+            async move {
+                let privacy = Privacy { mode, precision };
+                crate::db::set_room_member_privacy(&state.db_pool, room.id, "aaa", privacy)
+                    .await
+                    .unwrap();
+                state.set_privacy(room.id, "aaa", privacy);
+            }
         };
+        /// Skip everything but the `WsMessage::Sharing` and `WsMessage::Positions`
+        async fn recv_sharing_or_positions(socket: &mut TestSocket) -> WsMessage {
+            loop {
+                match recv_message(socket).await {
+                    msg @ (WsMessage::Sharing { .. } | WsMessage::Positions { .. }) => return msg,
+                    _ => {}
+                }
+            }
+        }
 
-        assert_eq!(msg, format!("{username} joined."));
+        let (mut socket_aaa, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        let (mut socket_bbb, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+        let mut position_aaa = new_position(48.8354, 2.3203);
+        send_message(&mut socket_aaa, &WsMessage::Position(position_aaa.clone())).await;
+        position_aaa.username = "aaa".to_string();
+        for socket in [&mut socket_aaa, &mut socket_bbb] {
+            assert_eq!(
+                recv_sharing_or_positions(socket).await,
+                WsMessage::Positions {
+                    positions: vec![position_aaa.clone()]
+                }
+            );
+        }
 
-        socket
-            .send(tungstenite::Message::Text("does not matter".to_string()))
+        set_privacy(SharingMode::Paused, None).await;
+        assert_eq!(
+            recv_sharing_or_positions(&mut socket_bbb).await,
+            WsMessage::Sharing {
+                username: "aaa".to_string(),
+                mode: SharingMode::Paused
+            }
+        );
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.9, 2.4)),
+        )
+        .await;
+        // "aaa" still receives the others
+        send_message(
+            &mut socket_bbb,
+            &WsMessage::Position(new_position(48.7, 2.2)),
+        )
+        .await;
+        let mut position_bbb = new_position(48.7, 2.2);
+        position_bbb.username = "bbb".to_string();
+        assert_eq!(
+            recv_sharing_or_positions(&mut socket_aaa).await,
+            WsMessage::Sharing {
+                username: "aaa".to_string(),
+                mode: SharingMode::Paused
+            }
+        );
+        assert_eq!(
+            recv_sharing_or_positions(&mut socket_aaa).await,
+            WsMessage::Positions {
+                positions: vec![position_bbb.clone()]
+            }
+        );
+        // and the position of "aaa" was dropped
+        assert_eq!(
+            recv_sharing_or_positions(&mut socket_bbb).await,
+            WsMessage::Positions {
+                positions: vec![position_bbb.clone()]
+            }
+        );
+        assert_eq!(
+            state.last_positions(room.id).await.unwrap(),
+            vec![position_aaa.clone(), position_bbb.clone()]
+        );
+
+        set_privacy(SharingMode::Hidden, None).await;
+        assert_eq!(
+            recv_sharing_or_positions(&mut socket_bbb).await,
+            WsMessage::Sharing {
+                username: "aaa".to_string(),
+                mode: SharingMode::Hidden
+            }
+        );
+        let (_socket_ccc, snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "ccc", room.id).await,
+        )
+        .await;
+        assert_eq!(snapshot, vec![position_bbb]);
+
+        set_privacy(SharingMode::Sharing, Some(1000)).await;
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+        assert_eq!(
+            recv_sharing_or_positions(&mut socket_bbb).await,
+            WsMessage::Sharing {
+                username: "aaa".to_string(),
+                mode: SharingMode::Sharing
+            }
+        );
+        let WsMessage::Positions { positions } = recv_sharing_or_positions(&mut socket_bbb).await
+        else {
+            panic!("expected positions");
+        };
+        assert_eq!(positions.len(), 1);
+        assert_ne!(
+            (positions[0].lat, positions[0].lng),
+            (position_aaa.lat, position_aaa.lng)
+        );
+        assert_eq!(positions[0].accuracy, Some(1000.0));
+    }
+
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_geofence() {
+        let (addr, state) = setup_server_with_state(Config {
+            fanout_interval: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .await;
+        let db_pool = state.db_pool.clone();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
             .await
-            .expect("Failed to write WebSocket request");
+            .unwrap();
+        let geofence = insert_geofence(
+            &db_pool,
+            "checkpoint",
+            Some(room.id),
+            None,
+            &GeofenceShape::Circle {
+                lat: 48.8354,
+                lng: 2.3203,
+                radius: 100.0,
+            },
+            "aaa",
+        )
+        .await
+        .unwrap();
+        /// Skip everything but the `WsMessage::Geofence`
+        async fn recv_geofence(socket: &mut TestSocket) -> WsMessage {
+            loop {
+                if let msg @ WsMessage::Geofence { .. } = recv_message(socket).await {
+                    return msg;
+                }
+            }
+        }
+        let event = |transition| WsMessage::Geofence {
+            username: "aaa".to_string(),
+            geofence_id: geofence.id,
+            name: "checkpoint".to_string(),
+            transition,
+            timestamp: new_position(0.0, 0.0).timestamp,
+        };
+
+        let (mut socket_aaa, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        let (mut socket_bbb, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+
+        // outside: nothing
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.9, 2.4)),
+        )
+        .await;
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.8355, 2.3204)),
+        )
+        .await;
+        assert_eq!(
+            recv_geofence(&mut socket_bbb).await,
+            event(GeofenceTransition::Enter)
+        );
+        // still inside: nothing
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.8354, 2.3203)),
+        )
+        .await;
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.9, 2.4)),
+        )
+        .await;
+        assert_eq!(
+            recv_geofence(&mut socket_bbb).await,
+            event(GeofenceTransition::Exit)
+        );
+
+        let transitions: Vec<GeofenceTransition> =
+            crate::db::list_geofence_events_from_db(&db_pool, room.id)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|event| event.transition)
+                .collect();
+        assert_eq!(
+            transitions,
+            vec![GeofenceTransition::Enter, GeofenceTransition::Exit]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_off_route() {
+        let (addr, state) = setup_server_with_state(Config {
+            off_route_threshold: 100.0,
+            ..Default::default()
+        })
+        .await;
+        let db_pool = state.db_pool.clone();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "aaa",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            r#"{"type":"LineString","coordinates":[[2.0,48.0],[2.0,48.1]]}"#,
+            &RouteStats {
+                distance: 11_000.0,
+                bbox: [2.0, 48.0, 2.0, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+        set_room_active_route(&db_pool, room.id, Some(route.id))
+            .await
+            .unwrap();
+        /// Skip everything but the `WsMessage::OffRoute`
+        async fn recv_off_route(socket: &mut TestSocket) -> (String, bool) {
+            loop {
+                if let WsMessage::OffRoute {
+                    username,
+                    is_off_route,
+                    ..
+                } = recv_message(socket).await
+                {
+                    return (username, is_off_route);
+                }
+            }
+        }
+
+        let (mut socket, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        send_message(&mut socket, &WsMessage::Position(new_position(48.05, 2.0))).await;
+        // the progress along the route is sent with the positions
+        let positions = recv_positions(&mut socket).await;
+        let progress = positions[0].progress.unwrap();
+        assert!((progress.percent - 50.0).abs() < 0.1, "{progress:?}");
 
-        let msg = match socket.next().await.unwrap().unwrap() {
-            tungstenite::Message::Text(msg) => msg,
-            other => panic!("expected a text message but got {other:?}"),
+        send_message(&mut socket, &WsMessage::Position(new_position(48.05, 2.01))).await;
+        assert_eq!(recv_off_route(&mut socket).await, ("aaa".to_string(), true));
+        assert!(state.off_route.room_off_route(room.id).contains_key("aaa"));
+
+        send_message(&mut socket, &WsMessage::Position(new_position(48.06, 2.0))).await;
+        assert_eq!(
+            recv_off_route(&mut socket).await,
+            ("aaa".to_string(), false)
+        );
+    }
+
+    /// The room is alerted when the leader gets too far ahead of the sweeper; and when they regroup
+    #[tokio::test]
+    async fn test_handle_socket_geolocation_group_spread() {
+        let (addr, state) = setup_server_with_state(Config {
+            group_spread_threshold: 1000.0,
+            ..Default::default()
+        })
+        .await;
+        let db_pool = state.db_pool.clone();
+        let room = insert_room(&db_pool, "room1", "aaa").await.unwrap();
+        add_room_member(&db_pool, room.id, "bbb", Role::Member)
+            .await
+            .unwrap();
+        let route = insert_route(
+            &db_pool,
+            "route1",
+            "aaa",
+            b"<gpx></gpx>",
+            RouteFormat::Gpx,
+            r#"{"type":"LineString","coordinates":[[2.0,48.0],[2.0,48.1]]}"#,
+            &RouteStats {
+                distance: 11_000.0,
+                bbox: [2.0, 48.0, 2.0, 48.1],
+                ..RouteStats::default()
+            },
+        )
+        .await
+        .unwrap();
+        set_room_active_route(&db_pool, room.id, Some(route.id))
+            .await
+            .unwrap();
+        let lead = Lead {
+            leader: Some("aaa".to_string()),
+            sweeper: Some("bbb".to_string()),
         };
+        set_room_lead(&db_pool, room.id, &lead).await.unwrap();
+        /// Skip everything but the `WsMessage::GroupSpread`
+        async fn recv_group_spread(socket: &mut TestSocket) -> bool {
+            loop {
+                if let WsMessage::GroupSpread { is_spread, .. } = recv_message(socket).await {
+                    return is_spread;
+                }
+            }
+        }
+
+        let (mut socket_aaa, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "aaa", room.id).await,
+        )
+        .await;
+        let (mut socket_bbb, _snapshot) = connect_geolocation(
+            new_ws_request(addr, &db_pool, "geolocation", "bbb", room.id).await,
+        )
+        .await;
+        send_message(
+            &mut socket_bbb,
+            &WsMessage::Position(new_position(48.0, 2.0)),
+        )
+        .await;
+        // ~5.5 km ahead
+        send_message(
+            &mut socket_aaa,
+            &WsMessage::Position(new_position(48.05, 2.0)),
+        )
+        .await;
+        // the whole room is alerted
+        assert!(recv_group_spread(&mut socket_aaa).await);
+        assert!(recv_group_spread(&mut socket_bbb).await);
+        assert!(state.group_spreads.is_spread(room.id));
 
-        assert_eq!(msg, format!("{username}: hello world"));
+        // ~550 m behind
+        send_message(
+            &mut socket_bbb,
+            &WsMessage::Position(new_position(48.045, 2.0)),
+        )
+        .await;
+        assert!(!recv_group_spread(&mut socket_aaa).await);
+        assert!(!state.group_spreads.is_spread(room.id));
     }
 }